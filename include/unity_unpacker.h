@@ -0,0 +1,73 @@
+#ifndef UNITY_UNPACKER_H
+#define UNITY_UNPACKER_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The call succeeded.
+#define UNITY_PACKAGE_OK 0
+
+// The call failed, `unity_package_last_error` tells why.
+#define UNITY_PACKAGE_ERROR 1
+
+// The handle is null.
+#define UNITY_PACKAGE_INVALID_HANDLE 2
+
+// A package opened by `unity_package_open`. Opaque to C.
+typedef struct UnityPackageHandle UnityPackageHandle;
+
+// Open the package at path. target and tmp may be null, the library picks
+// its defaults then. Returns null if path is null, a string is no valid
+// UTF-8 or the file is not a package. The handle has to be released with
+// `unity_package_free`.
+//
+// # Safety
+// Every non-null argument has to point to a null terminated string.
+struct UnityPackageHandle *unity_package_open(const char *path,
+                                              const char *target,
+                                              const char *tmp);
+
+// Unpack the package into its target. Returns `UNITY_PACKAGE_OK` on
+// success, otherwise `unity_package_last_error` has the reason.
+//
+// # Safety
+// handle has to be null or a handle returned by `unity_package_open` that has
+// not been freed.
+int unity_package_unpack(struct UnityPackageHandle *handle, bool delete_tmp);
+
+// The error of the last failed call, null if it succeeded. Owned by the
+// handle.
+//
+// # Safety
+// handle has to be null or a handle returned by `unity_package_open` that has
+// not been freed.
+const char *unity_package_last_error(const struct UnityPackageHandle *handle);
+
+// The number of files the last unpack indexed, 0 before the first unpack.
+//
+// # Safety
+// handle has to be null or a handle returned by `unity_package_open` that has
+// not been freed.
+size_t unity_package_file_count(const struct UnityPackageHandle *handle);
+
+// The guid (32 hex digits) of the file at index, null if index is out of
+// range. Owned by the handle, valid until the next unpack.
+//
+// # Safety
+// handle has to be null or a handle returned by `unity_package_open` that has
+// not been freed.
+const char *unity_package_get_guid(const struct UnityPackageHandle *handle, size_t index);
+
+// Release a handle and every string it handed out. Null is ignored.
+//
+// # Safety
+// handle has to be null or a handle returned by `unity_package_open` that has
+// not been freed.
+void unity_package_free(struct UnityPackageHandle *handle);
+
+#endif /* UNITY_UNPACKER_H */
@@ -0,0 +1,354 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufWriter, Read},
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    atomic_file::AtomicFile,
+    checksums,
+    guid::Guid,
+    package_listing::{self, DirectoryVisitor, EntryInfo, GuidDirectory},
+    tmp_guard::TempDirGuard,
+    unity_package::{self, TMP_SUBFOLDER},
+    unpack_limits::UnpackLimits,
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+#[cfg(feature = "zip")]
+use zip::{write::SimpleFileOptions, CompressionMethod, DateTime, ZipWriter};
+
+/// The archive formats `UnityPackage::convert_to` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArchiveFormat {
+    /// A deflate compressed zip file, zip64 where the sizes need it. Needs
+    /// the `zip` feature.
+    #[cfg(feature = "zip")]
+    Zip,
+    /// An uncompressed tar file.
+    Tar,
+}
+
+/// The archive being written.
+enum Output {
+    #[cfg(feature = "zip")]
+    Zip(Box<ZipWriter<BufWriter<AtomicFile>>>),
+    Tar(tar::Builder<BufWriter<AtomicFile>>),
+}
+
+impl Output {
+    fn add_directory(&mut self, name: &str, mtime: u64) -> io::Result<()> {
+        match self {
+            #[cfg(feature = "zip")]
+            Output::Zip(zip) => {
+                check_zip_name(name)?;
+                let options = SimpleFileOptions::default()
+                    .last_modified_time(dos_date_time(mtime))
+                    .unix_permissions(0o755);
+                zip.add_directory(name, options).map_err(io::Error::from)
+            }
+            Output::Tar(builder) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_size(0);
+                header.set_mode(0o755);
+                header.set_mtime(mtime);
+                header.set_cksum();
+                builder.append_data(&mut header, format!("{}/", name), io::empty())
+            }
+        }
+    }
+
+    fn add_file(&mut self, name: &str, content: impl Read, info: EntryInfo) -> io::Result<()> {
+        match self {
+            #[cfg(feature = "zip")]
+            Output::Zip(zip) => {
+                check_zip_name(name)?;
+                let options = SimpleFileOptions::default()
+                    .compression_method(CompressionMethod::Deflated)
+                    .last_modified_time(dos_date_time(info.mtime))
+                    .unix_permissions(info.mode)
+                    .large_file(info.size >= u64::from(u32::MAX));
+                zip.start_file(name, options)?;
+                let mut content = content;
+                io::copy(&mut content, zip.as_mut()).map(|_| ())
+            }
+            Output::Tar(builder) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(info.size);
+                header.set_mode(info.mode);
+                header.set_mtime(info.mtime);
+                header.set_cksum();
+                builder.append_data(&mut header, name, content)
+            }
+        }
+    }
+
+    /// Complete the archive and move it into place.
+    fn finish(self) -> io::Result<()> {
+        let out = match self {
+            #[cfg(feature = "zip")]
+            Output::Zip(zip) => zip.finish()?,
+            Output::Tar(builder) => builder.into_inner()?,
+        };
+        out.into_inner().map_err(|e| e.into_error())?.commit()
+    }
+}
+
+/// Zip stores the length of a name in 16 bits.
+#[cfg(feature = "zip")]
+fn check_zip_name(name: &str) -> io::Result<()> {
+    if name.len() + 1 > usize::from(u16::MAX) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("a name of {} bytes is too long for a zip file", name.len()),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The MS-DOS date and time of a unix timestamp (UTC), clamped to the years
+/// 1980 to 2107 zip can store.
+#[cfg(feature = "zip")]
+fn dos_date_time(mtime: u64) -> DateTime {
+    let days = (mtime / 86_400) as i64;
+    let seconds = mtime % 86_400;
+
+    // Civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    if year < 1980 {
+        return DateTime::default();
+    }
+    if year > 2107 {
+        return DateTime::from_date_and_time(2107, 12, 31, 23, 59, 58).unwrap_or_default();
+    }
+
+    DateTime::from_date_and_time(
+        year as u16,
+        month as u8,
+        day as u8,
+        (seconds / 3600) as u8,
+        ((seconds / 60) % 60) as u8,
+        (seconds % 60) as u8,
+    )
+    .unwrap_or_default()
+}
+
+/// The payload of a guid directory, copied from the archive into the spool
+/// directory.
+struct SpooledFile {
+    path: PathBuf,
+    info: EntryInfo,
+}
+
+fn write_error(path: &Path, e: io::Error) -> UnityPackageReaderError {
+    UnityPackageReaderError::CouldNotWritePackage(ErrorInformation::new(
+        Some(format!("{:?}: {}", path, e)),
+        file!(),
+        line!(),
+    ))
+}
+
+/// Writes the guid directories `walk_directories` hands out into the archive.
+struct Converter<'a, F> {
+    out: Output,
+    /// The path of the archive being written.
+    output: &'a Path,
+    spool: &'a Path,
+    meta_suffix: Option<&'a str>,
+    resolve: F,
+}
+
+impl<F> DirectoryVisitor for Converter<'_, F>
+where
+    F: Fn(&Path) -> Result<Option<PathBuf>, UnityPackageReaderError>,
+{
+    /// The spooled payload.
+    type State = Option<SpooledFile>;
+
+    fn file(
+        &mut self,
+        guid: &str,
+        name: &str,
+        info: EntryInfo,
+        content: &mut dyn Read,
+        state: &mut Self::State,
+    ) -> Result<(), UnityPackageReaderError> {
+        if name != "asset" || guid.parse::<Guid>().is_err() {
+            return Ok(());
+        }
+
+        let path = self.spool.join(format!("{}.asset", guid));
+        match fs::File::create(&path).and_then(|mut f| io::copy(content, &mut f)) {
+            Ok(size) => {
+                *state = Some(SpooledFile {
+                    path,
+                    info: EntryInfo { size, ..info },
+                });
+                Ok(())
+            }
+            Err(e) => Err(write_error(&path, e)),
+        }
+    }
+
+    fn directory(
+        &mut self,
+        directory: GuidDirectory<Self::State>,
+    ) -> Result<ControlFlow<()>, UnityPackageReaderError> {
+        let guid = match directory.guid.parse::<Guid>() {
+            Ok(g) => g,
+            Err(_) => return Ok(ControlFlow::Continue(())),
+        };
+
+        let spooled = directory.state.as_ref().map(|s| s.path.clone());
+        let written = self.write_asset(guid, directory);
+        // Only one payload at a time takes up space in the spool directory.
+        if let Some(path) = spooled {
+            let _ = fs::remove_file(path);
+        }
+
+        written.map(|_| ControlFlow::Continue(()))
+    }
+}
+
+impl<F> Converter<'_, F>
+where
+    F: Fn(&Path) -> Result<Option<PathBuf>, UnityPackageReaderError>,
+{
+    /// Write a guid directory into the archive, under the path resolve
+    /// returns for its pathname.
+    fn write_asset(
+        &mut self,
+        guid: Guid,
+        directory: GuidDirectory<Option<SpooledFile>>,
+    ) -> Result<(), UnityPackageReaderError> {
+        let path = match (self.resolve)(Path::new(&directory.pathname))? {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        if !unity_package::is_inside_target(&path) {
+            return Err(UnityPackageReaderError::PathError(ErrorInformation::new(
+                Some(format!("{}: '{}' leaves the archive", guid, path.display())),
+                file!(),
+                line!(),
+            )));
+        }
+
+        let name = checksums::to_forward_slashes(&path);
+        let output = self.output;
+        match &directory.state {
+            Some(content) => fs::File::open(&content.path)
+                .and_then(|f| self.out.add_file(&name, f, content.info)),
+            None if directory.is_folder => {
+                // Not every package stores the guid directories themselves.
+                let meta_mtime = directory.meta.as_ref().map(|m| m.info.mtime);
+                self.out
+                    .add_directory(&name, directory.mtime.or(meta_mtime).unwrap_or(0))
+            }
+            None => return Ok(()),
+        }
+        .map_err(|e| write_error(output, e))?;
+
+        if let (Some(suffix), Some(meta)) = (self.meta_suffix, &directory.meta) {
+            let info = EntryInfo {
+                size: meta.content.len() as u64,
+                ..meta.info
+            };
+            self.out
+                .add_file(
+                    &format!("{}{}", name, suffix),
+                    meta.content.as_bytes(),
+                    info,
+                )
+                .map_err(|e| write_error(output, e))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Stream through a package and write its assets into an archive at output,
+/// under the path resolve returns for their pathname (None leaves the asset
+/// out). Metas are written next to their asset with meta_suffix, or left out
+/// without one. Built on `walk_directories`: the payload of a guid directory
+/// is spooled to disk until the directory is complete, only its pathname and
+/// meta are held in memory. The files keep the mtimes and modes of the
+/// package. The entries count against limits like an extraction does.
+/// output is only replaced once the archive is complete.
+pub(crate) fn convert<R: BufRead>(
+    reader: R,
+    output: &Path,
+    format: ArchiveFormat,
+    meta_suffix: Option<&str>,
+    limits: UnpackLimits,
+    resolve: impl Fn(&Path) -> Result<Option<PathBuf>, UnityPackageReaderError>,
+) -> Result<(), UnityPackageReaderError> {
+    let spool = TempDirGuard::new(
+        std::env::temp_dir()
+            .join(TMP_SUBFOLDER)
+            .join(unity_package::unique_tmp_name(output)),
+        true,
+    );
+    if let Err(e) = fs::create_dir_all(spool.path()) {
+        return Err(UnityPackageReaderError::TmpDirectoryCouldNotBeCreated(
+            ErrorInformation::new(Some(format!("{:?}: {}", spool.path(), e)), file!(), line!()),
+        ));
+    }
+
+    let file = match AtomicFile::create(output) {
+        Ok(f) => BufWriter::new(f),
+        Err(e) => return Err(write_error(output, e)),
+    };
+    let out = match format {
+        #[cfg(feature = "zip")]
+        ArchiveFormat::Zip => Output::Zip(Box::new(ZipWriter::new(file))),
+        ArchiveFormat::Tar => Output::Tar(tar::Builder::new(file)),
+    };
+    let mut converter = Converter {
+        out,
+        output,
+        spool: spool.path(),
+        meta_suffix,
+        resolve,
+    };
+    package_listing::walk_directories(reader, Some(limits), true, &mut converter)?;
+
+    converter.out.finish().map_err(|e| write_error(output, e))
+}
+
+#[cfg(all(test, feature = "zip"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dos_date_time() {
+        // 2021-03-04 05:06:08 UTC
+        let time = dos_date_time(1_614_834_368);
+        assert_eq!(
+            (time.datepart(), time.timepart()),
+            ((41 << 9) | (3 << 5) | 4, (5 << 11) | (6 << 5) | 4)
+        );
+        assert_eq!(dos_date_time(0), DateTime::default());
+        assert_eq!(dos_date_time(u64::from(u32::MAX) * 4).year(), 2107);
+    }
+
+    #[test]
+    fn test_check_zip_name() {
+        assert!(check_zip_name(&"a".repeat(1000)).is_ok());
+        assert_eq!(
+            check_zip_name(&"a".repeat(70_000)).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use serde_json::Value;
+
+use crate::unpacker_error::{ErrorInformation, UnityPackageReaderError};
+
+/// The parts of an assembly definition (`.asmdef`) file this crate looks at.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AssemblyDefinition {
+    pub name: String,
+    /// Referenced assemblies, either by name or as `GUID:<guid of the asmdef>`.
+    pub references: Vec<String>,
+    /// The packages (resources) named by `versionDefines`.
+    pub version_defines: Vec<String>,
+}
+
+impl AssemblyDefinition {
+    pub fn parse(content: &str) -> Result<Self, UnityPackageReaderError> {
+        // Unity writes the files with a byte order mark now and then.
+        let content = content.trim_start_matches('\u{feff}');
+        let json: Value = match serde_json::from_str(content) {
+            Ok(v) => v,
+            Err(e) => {
+                return Err(UnityPackageReaderError::InvalidAssemblyDefinition(
+                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                ));
+            }
+        };
+
+        let name = match json.get("name").and_then(Value::as_str) {
+            Some(n) if !n.is_empty() => String::from(n),
+            _ => {
+                return Err(UnityPackageReaderError::InvalidAssemblyDefinition(
+                    ErrorInformation::new(Some(String::from("missing name")), file!(), line!()),
+                ));
+            }
+        };
+
+        let references = json
+            .get("references")
+            .and_then(Value::as_array)
+            .map(|r| {
+                r.iter()
+                    .filter_map(Value::as_str)
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let version_defines = json
+            .get("versionDefines")
+            .and_then(Value::as_array)
+            .map(|d| {
+                d.iter()
+                    .filter_map(|d| d.get("name").and_then(Value::as_str))
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(AssemblyDefinition {
+            name,
+            references,
+            version_defines,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let asmdef = AssemblyDefinition::parse(
+            "\u{feff}{\n  \"name\": \"Vendor.Tool\",\n  \"references\": [\"Unity.TextMeshPro\", \"GUID:0123\"],\n  \"versionDefines\": [{\"name\": \"com.unity.inputsystem\", \"expression\": \"1.0\", \"define\": \"HAS_INPUT\"}]\n}",
+        )
+        .unwrap();
+
+        assert_eq!(asmdef.name, "Vendor.Tool");
+        assert_eq!(asmdef.references, vec!["Unity.TextMeshPro", "GUID:0123"]);
+        assert_eq!(asmdef.version_defines, vec!["com.unity.inputsystem"]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for content in ["{", "{\"references\": []}", "[]"] {
+            assert!(matches!(
+                AssemblyDefinition::parse(content),
+                Err(UnityPackageReaderError::InvalidAssemblyDefinition(_))
+            ));
+        }
+    }
+}
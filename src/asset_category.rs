@@ -0,0 +1,173 @@
+use std::path::Path;
+
+/// A rough classification of an asset, used to filter what gets installed
+/// (see `UnpackConfig::include_categories`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum AssetCategory {
+    Script,
+    Shader,
+    Texture,
+    Model,
+    Audio,
+    Scene,
+    Prefab,
+    Material,
+    Plugin,
+    /// Everything else, including files without an extension and `.asset`
+    /// files, which can contain almost anything.
+    Other,
+}
+
+/// The extensions (lower case, without the dot) of every category.
+pub const CATEGORY_EXTENSIONS: &[(AssetCategory, &[&str])] = &[
+    (
+        AssetCategory::Script,
+        &["cs", "js", "boo", "asmdef", "asmref"],
+    ),
+    (
+        AssetCategory::Shader,
+        &[
+            "shader",
+            "cginc",
+            "hlsl",
+            "glsl",
+            "compute",
+            "shadergraph",
+            "shadersubgraph",
+        ],
+    ),
+    (
+        AssetCategory::Texture,
+        &[
+            "png", "jpg", "jpeg", "tga", "psd", "tif", "tiff", "bmp", "gif", "exr", "hdr", "iff",
+            "pict",
+        ],
+    ),
+    (
+        AssetCategory::Model,
+        &[
+            "fbx", "obj", "dae", "3ds", "dxf", "blend", "max", "ma", "mb", "lxo", "c4d",
+        ],
+    ),
+    (
+        AssetCategory::Audio,
+        &[
+            "wav", "mp3", "ogg", "aif", "aiff", "flac", "mod", "it", "s3m", "xm",
+        ],
+    ),
+    (AssetCategory::Scene, &["unity"]),
+    (AssetCategory::Prefab, &["prefab"]),
+    (
+        AssetCategory::Material,
+        &["mat", "physicmaterial", "physicsmaterial2d"],
+    ),
+    (
+        AssetCategory::Plugin,
+        &[
+            "dll",
+            "so",
+            "a",
+            "dylib",
+            "bundle",
+            "jar",
+            "aar",
+            "framework",
+        ],
+    ),
+];
+
+/// The extensions (lower case, without the dot) of the native plugins
+/// `code_report` and `compatibility_report` look at.
+pub(crate) const NATIVE_PLUGIN_EXTENSIONS: [&str; 6] = ["dll", "so", "a", "dylib", "bundle", "aar"];
+
+/// The importers of the meta file that identify a category, used for
+/// extensions the table above does not know.
+pub const CATEGORY_IMPORTERS: &[(&str, AssetCategory)] = &[
+    ("MonoImporter", AssetCategory::Script),
+    ("AssemblyDefinitionImporter", AssetCategory::Script),
+    ("ShaderImporter", AssetCategory::Shader),
+    ("ComputeShaderImporter", AssetCategory::Shader),
+    ("TextureImporter", AssetCategory::Texture),
+    ("IHVImageFormatImporter", AssetCategory::Texture),
+    ("ModelImporter", AssetCategory::Model),
+    ("AudioImporter", AssetCategory::Audio),
+    ("PluginImporter", AssetCategory::Plugin),
+];
+
+impl AssetCategory {
+    /// The category of an asset with the given relative path and the importer
+    /// of its meta file. The extension decides, the importer is only consulted
+    /// for unknown extensions. Files without an extension and `.asset` files
+    /// are always `Other`.
+    pub fn of(path: &Path, importer: Option<&str>) -> AssetCategory {
+        let extension = match path.extension() {
+            Some(e) => e.to_string_lossy().to_ascii_lowercase(),
+            None => return AssetCategory::Other,
+        };
+
+        if extension == "asset" {
+            return AssetCategory::Other;
+        }
+
+        let by_extension = CATEGORY_EXTENSIONS
+            .iter()
+            .find(|(_, extensions)| extensions.contains(&extension.as_str()))
+            .map(|(c, _)| *c);
+        let by_importer = || {
+            CATEGORY_IMPORTERS
+                .iter()
+                .find(|(name, _)| Some(*name) == importer)
+                .map(|(_, c)| *c)
+        };
+
+        by_extension
+            .or_else(by_importer)
+            .unwrap_or(AssetCategory::Other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_categories() {
+        let of = |p: &str, i: Option<&str>| AssetCategory::of(Path::new(p), i);
+
+        assert_eq!(of("Assets/Scripts/Player.cs", None), AssetCategory::Script);
+        assert_eq!(
+            of("Assets/Textures/Ground.JPG", None),
+            AssetCategory::Texture
+        );
+        assert_eq!(of("Assets/Models/Tree.fbx", None), AssetCategory::Model);
+        assert_eq!(of("Assets/Scenes/Main.unity", None), AssetCategory::Scene);
+        assert_eq!(of("Assets/Plugins/native.dll", None), AssetCategory::Plugin);
+        assert_eq!(
+            of("Assets/Sounds/hit.sfx", Some("AudioImporter")),
+            AssetCategory::Audio
+        );
+        // The extension wins over the importer.
+        assert_eq!(
+            of("Assets/Materials/Wood.mat", Some("NativeFormatImporter")),
+            AssetCategory::Material
+        );
+
+        assert_eq!(
+            of("Assets/LICENSE", Some("TextureImporter")),
+            AssetCategory::Other
+        );
+        assert_eq!(
+            of("Assets/Settings/Render.asset", Some("NativeFormatImporter")),
+            AssetCategory::Other
+        );
+        assert_eq!(
+            of("Assets/readme.txt", Some("TextScriptImporter")),
+            AssetCategory::Other
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use crate::prelude::{Shared, UnityAssetFile, UnpackConfig};
+
+/// Decides which assets of a package are installed, see
+/// `UnpackOptions::filter`. Filters compose with `All`, `Any` and `Not`.
+pub trait AssetFilter: Send + Sync {
+    /// True, if asset is installed. The asset carries the guid, its path
+    /// inside the package, its meta and the size of its payload.
+    fn keep(&self, asset: &UnityAssetFile) -> bool;
+}
+
+impl<F: Fn(&UnityAssetFile) -> bool + Send + Sync> AssetFilter for F {
+    fn keep(&self, asset: &UnityAssetFile) -> bool {
+        self(asset)
+    }
+}
+
+/// The path, root and category filters of the configuration.
+impl AssetFilter for UnpackConfig {
+    fn keep(&self, asset: &UnityAssetFile) -> bool {
+        self.includes_asset(asset)
+    }
+}
+
+/// The filters of an unpack as one: the configuration, then
+/// `UnpackOptions::filter`, which does not see the assets the configuration
+/// leaves out.
+pub(crate) struct UnpackFilter<'a> {
+    pub(crate) config: &'a UnpackConfig,
+    pub(crate) filter: Option<&'a SharedFilter>,
+}
+
+impl AssetFilter for UnpackFilter<'_> {
+    fn keep(&self, asset: &UnityAssetFile) -> bool {
+        self.config.keep(asset) && self.filter.is_none_or(|f| f.keep(asset))
+    }
+}
+
+/// Keeps an asset if every filter keeps it (or there are none).
+pub struct All(pub Vec<Box<dyn AssetFilter>>);
+
+impl AssetFilter for All {
+    fn keep(&self, asset: &UnityAssetFile) -> bool {
+        self.0.iter().all(|f| f.keep(asset))
+    }
+}
+
+/// Keeps an asset if at least one filter keeps it.
+pub struct Any(pub Vec<Box<dyn AssetFilter>>);
+
+impl AssetFilter for Any {
+    fn keep(&self, asset: &UnityAssetFile) -> bool {
+        self.0.iter().any(|f| f.keep(asset))
+    }
+}
+
+/// Keeps the assets the filter leaves out.
+pub struct Not(pub Box<dyn AssetFilter>);
+
+impl AssetFilter for Not {
+    fn keep(&self, asset: &UnityAssetFile) -> bool {
+        !self.0.keep(asset)
+    }
+}
+
+/// A filter that can be put into `UnpackOptions::filter`.
+pub type SharedFilter = Shared<dyn AssetFilter>;
+
+impl SharedFilter {
+    pub fn new(filter: impl AssetFilter + 'static) -> Self {
+        Shared::from_arc(Arc::new(filter))
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    fs,
+    io::{self, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Tells apart the partial files of outputs written at the same time.
+static NEXT_PARTIAL: AtomicUsize = AtomicUsize::new(0);
+
+/// A file that is written next to its destination and renamed into place by
+/// `commit`, so a failure neither leaves a partial file behind nor destroys
+/// the file that was there before. Dropped without `commit`, the partial
+/// file is removed.
+pub(crate) struct AtomicFile {
+    path: PathBuf,
+    partial: PathBuf,
+    file: Option<fs::File>,
+}
+
+impl AtomicFile {
+    pub(crate) fn create(path: &Path) -> io::Result<Self> {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let partial = path.with_file_name(format!(
+            ".{}.{}-{}.part",
+            name,
+            std::process::id(),
+            NEXT_PARTIAL.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = fs::File::create(&partial)?;
+
+        Ok(AtomicFile {
+            path: path.to_path_buf(),
+            partial,
+            file: Some(file),
+        })
+    }
+
+    /// Replace the destination with what has been written.
+    pub(crate) fn commit(mut self) -> io::Result<()> {
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+            file.sync_all()?;
+        }
+        fs::rename(&self.partial, &self.path)
+    }
+
+    fn file(&mut self) -> io::Result<&mut fs::File> {
+        self.file
+            .as_mut()
+            .ok_or_else(|| io::Error::other("the file has been committed"))
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file()?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file()?.flush()
+    }
+}
+
+impl Seek for AtomicFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file()?.seek(pos)
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        // Also after a failed rename in commit.
+        self.file = None;
+        let _ = fs::remove_file(&self.partial);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_or_discard() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.zip");
+        fs::write(&path, "before").unwrap();
+
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"half").unwrap();
+        drop(file);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "before");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"after").unwrap();
+        file.commit().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "after");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,657 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    checksums,
+    git_ignore::GitIgnoreEdit,
+    guid::Guid,
+    prelude::{UnityPackage, UnpackOptions, UnpackStats},
+    unity_package::{normalize_path_key, unique_tmp_name},
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// Identifies a package that has been installed into a merged target.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PackageRef {
+    /// The path of the package file.
+    pub path: PathBuf,
+    /// The sha256 of the package file (lower case hex).
+    pub sha256: String,
+}
+
+/// What `CombinedIndex::uninstall` did.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct UninstallReport {
+    /// Relative paths that have been deleted from the target.
+    pub removed: Vec<PathBuf>,
+    /// Relative paths that were last written by the uninstalled package but are
+    /// also part of another package. Their content on disk still is the one of
+    /// the uninstalled package, so the remaining owner has to be reinstalled.
+    pub needs_reinstall: Vec<(PathBuf, PackageRef)>,
+    /// The .gitignore whose block (see `UnpackConfig::git_aware`) has been
+    /// removed, because no remaining package needs it.
+    pub reverted_gitignore: Option<PathBuf>,
+}
+
+/// The files several packages installed into one target, together with the
+/// packages that wrote them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CombinedIndex {
+    target: PathBuf,
+    packages: Vec<PackageRef>,
+    /// Installed files (relative, forward slashes) and the indices of the
+    /// packages that wrote them, in install order. The last one is on disk.
+    owners: BTreeMap<String, Vec<usize>>,
+    /// The .gitignore edits of the packages, by package index.
+    gitignore_edits: Vec<(usize, GitIgnoreEdit)>,
+}
+
+impl CombinedIndex {
+    pub fn new(target: &Path) -> Self {
+        CombinedIndex {
+            target: target.to_path_buf(),
+            packages: Vec::new(),
+            owners: BTreeMap::new(),
+            gitignore_edits: Vec::new(),
+        }
+    }
+
+    pub fn get_target_dir(&self) -> &Path {
+        &self.target
+    }
+
+    /// The packages in install order.
+    pub fn packages(&self) -> &[PackageRef] {
+        &self.packages
+    }
+
+    /// Record the files an unpacked package installed. Files already owned by
+    /// an earlier package are now owned by this one as well. A .gitignore edit
+    /// of the unpack is recorded, so `uninstall` can revert it.
+    pub fn add(&mut self, package: &UnityPackage) -> Result<&PackageRef, UnityPackageReaderError> {
+        let path = package.get_package_path().to_path_buf();
+        let sha256 = match checksums::sha256_file(&path) {
+            Ok(h) => checksums::to_hex(&h),
+            Err(e) => {
+                return Err(UnityPackageReaderError::ChecksumFileError(
+                    ErrorInformation::new(Some(format!("{:?}: {}", path, e)), file!(), line!()),
+                ));
+            }
+        };
+
+        let index = self.packages.len();
+        self.packages.push(PackageRef { path, sha256 });
+        if let Some(edit) = package.get_gitignore_edit() {
+            self.gitignore_edits.push((index, edit.clone()));
+        }
+
+        let config = package.get_config();
+        let installed = package
+            .files()
+            .filter(|f| !f.is_skipped() && (config.installs_folders() || !f.is_folder()));
+
+        for file in installed {
+            let asset = checksums::to_forward_slashes(file.get_relative_asset_path());
+            let meta = format!("{}{}", asset, config.meta_naming.suffix());
+
+            if !file.is_folder() {
+                self.insert(asset, index);
+            }
+            if config.installs_metas() && file.has_meta() {
+                self.insert(meta, index);
+            }
+        }
+
+        Ok(&self.packages[index])
+    }
+
+    fn insert(&mut self, relative_path: String, package: usize) {
+        let owners = self.owners.entry(relative_path).or_default();
+        owners.retain(|o| *o != package);
+        owners.push(package);
+    }
+
+    /// The package whose content of relative_path is on disk.
+    pub fn lookup_owner(&self, relative_path: impl AsRef<Path>) -> Option<&PackageRef> {
+        let key = normalize_path_key(&relative_path.as_ref().to_string_lossy());
+        self.owners
+            .get(&key)
+            .and_then(|o| o.last())
+            .map(|i| &self.packages[*i])
+    }
+
+    /// Write every installed file with its owner, one per line, sorted by path:
+    /// `<relative path>\t<package sha256>\t<package path>`.
+    pub fn write_manifest(&self, path: &Path) -> Result<(), UnityPackageReaderError> {
+        let mut content = String::new();
+        for (relative, owners) in &self.owners {
+            if let Some(owner) = owners.last().map(|i| &self.packages[*i]) {
+                content.push_str(&format!(
+                    "{}\t{}\t{}\n",
+                    relative,
+                    owner.sha256,
+                    owner.path.display()
+                ));
+            }
+        }
+
+        let written = fs::File::create(path).and_then(|mut f| f.write_all(content.as_bytes()));
+        match written {
+            Ok(_) => Ok(()),
+            Err(e) => Err(UnityPackageReaderError::CouldNotWriteReport(
+                ErrorInformation::new(Some(format!("{:?}: {}", path, e)), file!(), line!()),
+            )),
+        }
+    }
+
+    /// Remove the files of a package from the target. Files that another
+    /// package wrote as well are kept; see `UninstallReport::needs_reinstall`.
+    /// The .gitignore block is reverted with the last package that wrote it.
+    pub fn uninstall(
+        &mut self,
+        package: &Path,
+    ) -> Result<UninstallReport, UnityPackageReaderError> {
+        let index = match self.packages.iter().position(|p| p.path == package) {
+            Some(i) => i,
+            None => {
+                return Err(UnityPackageReaderError::PackageNotFound(
+                    ErrorInformation::new(
+                        Some(format!("{:?} is not part of the index", package)),
+                        file!(),
+                        line!(),
+                    ),
+                ));
+            }
+        };
+
+        let mut report = UninstallReport::default();
+        let mut orphaned = Vec::new();
+        for (relative, owners) in self.owners.iter_mut() {
+            let was_on_disk = owners.last() == Some(&index);
+            owners.retain(|o| *o != index);
+
+            match owners.last() {
+                None => orphaned.push(relative.clone()),
+                Some(remaining) if was_on_disk => report
+                    .needs_reinstall
+                    .push((PathBuf::from(relative), self.packages[*remaining].clone())),
+                Some(_) => {}
+            }
+        }
+
+        // Deepest paths first, so emptied folders can be removed as well.
+        orphaned.sort_by(|a, b| b.cmp(a));
+        for relative in orphaned {
+            self.owners.remove(&relative);
+            let absolute = self.target.join(&relative);
+            match fs::remove_file(&absolute) {
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(UnityPackageReaderError::RemoveFailed(
+                        ErrorInformation::new(
+                            Some(format!("{:?}: {}", absolute, e)),
+                            file!(),
+                            line!(),
+                        ),
+                    ));
+                }
+            }
+
+            // A folder asset leaves its (now empty) directory behind. The
+            // target itself is kept, even if it ends up empty.
+            let mut parent = absolute.parent();
+            while let Some(p) = parent {
+                if !p.starts_with(&self.target) || p == self.target || fs::remove_dir(p).is_err() {
+                    break;
+                }
+                parent = p.parent();
+            }
+
+            report.removed.push(PathBuf::from(relative));
+        }
+
+        let edits = std::mem::take(&mut self.gitignore_edits);
+        let (removed, remaining): (Vec<_>, Vec<_>) =
+            edits.into_iter().partition(|(i, _)| *i == index);
+        self.gitignore_edits = remaining;
+        for (_, edit) in removed {
+            if self
+                .gitignore_edits
+                .iter()
+                .all(|(_, e)| e.path != edit.path)
+            {
+                edit.revert()?;
+                report.reverted_gitignore = Some(edit.path);
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// Unpack several packages into the same target, in order, and record which
+/// package wrote which file. The tmp directories of the packages are created
+/// inside temp_directory, see `PackageBatch::run_in_order`.
+pub fn unpack_batch(
+    packages: &[impl AsRef<Path>],
+    target: &Path,
+    temp_directory: Option<PathBuf>,
+    options: &UnpackOptions,
+) -> Result<CombinedIndex, UnityPackageReaderError> {
+    let mut batch = PackageBatch::new(packages, target).with_options(options.clone());
+    if let Some(tmp_root) = temp_directory {
+        batch = batch.with_tmp_root(&tmp_root);
+    }
+
+    batch.run_in_order()
+}
+
+/// What happened to a single package of a `PackageBatch`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchStatus {
+    Unpacked(UnpackStats),
+    Failed(UnityPackageReaderError),
+    /// Not started, because another package failed and fail-fast is set.
+    NotStarted,
+}
+
+/// A single package of a `BatchReport`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchEntry {
+    pub path: PathBuf,
+    pub status: BatchStatus,
+    /// The time the unpack of this package took.
+    pub elapsed: Duration,
+}
+
+/// Everything `PackageBatch::run` did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchReport {
+    /// Every package, in the order they have been given to the batch.
+    pub packages: Vec<BatchEntry>,
+    /// Guids that more than one of the unpacked packages contain, with the
+    /// packages that contain them.
+    pub guid_collisions: BTreeMap<Guid, Vec<PathBuf>>,
+    /// The time the whole batch took.
+    pub elapsed: Duration,
+}
+
+impl BatchReport {
+    /// True, if every package has been unpacked.
+    pub fn is_success(&self) -> bool {
+        self.packages
+            .iter()
+            .all(|p| matches!(p.status, BatchStatus::Unpacked(_)))
+    }
+
+    /// The packages that failed, together with their errors.
+    pub fn failures(&self) -> impl Iterator<Item = (&Path, &UnityPackageReaderError)> + '_ {
+        self.packages.iter().filter_map(|p| match &p.status {
+            BatchStatus::Failed(e) => Some((p.path.as_path(), e)),
+            _ => None,
+        })
+    }
+}
+
+/// The report entry of a package together with its guids.
+type Unpacked = (BatchEntry, Vec<Guid>);
+
+/// Unpacks several packages into one target on a number of worker threads.
+/// Every package gets its own tmp directory. If packages share files, which
+/// of them ends up in the target depends on the order they finish in, use
+/// `run_in_order` for a defined order.
+#[derive(Debug, Clone)]
+pub struct PackageBatch {
+    packages: Vec<PathBuf>,
+    target: PathBuf,
+    options: UnpackOptions,
+    tmp_root: Option<PathBuf>,
+    threads: usize,
+    fail_fast: bool,
+}
+
+impl PackageBatch {
+    /// A batch with the default options and one worker per available core.
+    pub fn new(packages: &[impl AsRef<Path>], target: &Path) -> Self {
+        PackageBatch {
+            packages: packages.iter().map(|p| p.as_ref().to_path_buf()).collect(),
+            target: target.to_path_buf(),
+            options: UnpackOptions::default(),
+            tmp_root: None,
+            threads: thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            fail_fast: false,
+        }
+    }
+
+    /// The options every package is unpacked with.
+    pub fn with_options(mut self, options: UnpackOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Create the tmp directories of the packages inside tmp_root instead of
+    /// the default location.
+    pub fn with_tmp_root(mut self, tmp_root: &Path) -> Self {
+        self.tmp_root = Some(tmp_root.to_path_buf());
+        self
+    }
+
+    /// The number of packages unpacked at the same time (at least 1).
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Do not start any further package once one has failed. Packages that
+    /// are being unpacked at that moment are finished.
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Unpack every package. The failure of a package is part of the report,
+    /// the others are unpacked anyway (unless fail-fast is set).
+    pub fn run(&self) -> BatchReport {
+        let start = Instant::now();
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let results: Mutex<Vec<Option<Unpacked>>> = Mutex::new(vec![None; self.packages.len()]);
+
+        let work = || loop {
+            if self.fail_fast && failed.load(Ordering::SeqCst) {
+                return;
+            }
+
+            let index = next.fetch_add(1, Ordering::SeqCst);
+            let path = match self.packages.get(index) {
+                Some(p) => p,
+                None => return,
+            };
+
+            let result = self.unpack(path);
+            if matches!(result.0.status, BatchStatus::Failed(_)) {
+                failed.store(true, Ordering::SeqCst);
+            }
+            if let Ok(mut results) = results.lock() {
+                results[index] = Some(result);
+            }
+        };
+
+        // wasm32 has no threads, and without a file system every package fails
+        // right away there.
+        if cfg!(target_arch = "wasm32") {
+            work();
+        } else {
+            thread::scope(|scope| {
+                for _ in 0..self.threads.min(self.packages.len()) {
+                    scope.spawn(work);
+                }
+            });
+        }
+
+        let results = results.into_inner().unwrap_or_default();
+        let mut packages = Vec::with_capacity(self.packages.len());
+        let mut contained: BTreeMap<Guid, Vec<PathBuf>> = BTreeMap::new();
+        for (path, result) in self.packages.iter().zip(results) {
+            let (entry, guids) = result.unwrap_or_else(|| {
+                let entry = BatchEntry {
+                    path: path.clone(),
+                    status: BatchStatus::NotStarted,
+                    elapsed: Duration::ZERO,
+                };
+                (entry, Vec::new())
+            });
+
+            for guid in guids {
+                contained.entry(guid).or_default().push(path.clone());
+            }
+            packages.push(entry);
+        }
+        contained.retain(|_, p| p.len() > 1);
+
+        BatchReport {
+            packages,
+            guid_collisions: contained,
+            elapsed: start.elapsed(),
+        }
+    }
+
+    /// Unpack the packages one after the other, in the order they have been
+    /// given, and record which package wrote which file. The first failure
+    /// stops the batch, the threads and fail-fast settings are not used.
+    pub fn run_in_order(&self) -> Result<CombinedIndex, UnityPackageReaderError> {
+        let mut index = CombinedIndex::new(&self.target);
+        for path in &self.packages {
+            let package = self.unpack_package(path)?;
+            index.add(&package)?;
+        }
+
+        Ok(index)
+    }
+
+    /// Unpack a single package, returns its entry of the report and its guids.
+    fn unpack(&self, path: &Path) -> Unpacked {
+        let start = Instant::now();
+        let (status, guids) = match self.unpack_package(path) {
+            Ok(package) => {
+                let guids: BTreeSet<Guid> = package.files().map(|f| f.get_guid()).collect();
+                (
+                    BatchStatus::Unpacked(*package.get_stats()),
+                    guids.into_iter().collect(),
+                )
+            }
+            Err(e) => (BatchStatus::Failed(e), Vec::new()),
+        };
+
+        let entry = BatchEntry {
+            path: path.to_path_buf(),
+            status,
+            elapsed: start.elapsed(),
+        };
+        (entry, guids)
+    }
+
+    fn unpack_package(&self, path: &Path) -> Result<UnityPackage, UnityPackageReaderError> {
+        let tmp = self
+            .tmp_root
+            .as_ref()
+            .map(|r| r.join(unique_tmp_name(path)));
+        let mut package = UnityPackage::from_path(path, Some(self.target.clone()), tmp)?;
+        package.unpack_package_with(&self.options)?;
+
+        Ok(package)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prelude::UnpackConfig, test_utils::*};
+
+    const SHARED_PATH: &str = "Assets/Shared/readme.txt";
+
+    fn shared(content: &[u8]) -> PackageBuilder {
+        let guid = "77777777777777777777777777777777";
+        PackageBuilder::new().asset(guid, SHARED_PATH, content, &texture_meta(guid))
+    }
+
+    #[test]
+    fn test_package_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.unitypackage");
+        let second = dir.path().join("second.unitypackage");
+        let broken = dir.path().join("broken.unitypackage");
+        let target = dir.path().join("target");
+        sample_package().write(&first);
+        sample_package().write(&second);
+        fs::write(&broken, b"not a package").unwrap();
+
+        let report = PackageBatch::new(&[&first, &broken, &second], &target)
+            .with_tmp_root(&dir.path().join("tmp"))
+            .with_threads(2)
+            .run();
+
+        assert!(!report.is_success());
+        assert!(matches!(
+            report.packages[0].status,
+            BatchStatus::Unpacked(_)
+        ));
+        assert!(matches!(
+            report.packages[2].status,
+            BatchStatus::Unpacked(_)
+        ));
+        let failures: Vec<&Path> = report.failures().map(|(p, _)| p).collect();
+        assert_eq!(failures, [broken.as_path()]);
+
+        assert_eq!(report.guid_collisions.len(), 3);
+        assert_eq!(
+            report.guid_collisions[&Guid::parse(TEXTURE_GUID).unwrap()],
+            [first.clone(), second.clone()]
+        );
+        assert_eq!(
+            fs::read(target.join(TEXTURE_PATH)).unwrap(),
+            TEXTURE_CONTENT
+        );
+        assert!(report.elapsed >= report.packages[0].elapsed);
+
+        // Nothing after the broken package is started.
+        let report = PackageBatch::new(&[&broken, &first, &second], &target)
+            .with_tmp_root(&dir.path().join("tmp"))
+            .with_threads(1)
+            .with_fail_fast(true)
+            .run();
+        assert!(matches!(report.packages[0].status, BatchStatus::Failed(_)));
+        assert_eq!(report.packages[1].status, BatchStatus::NotStarted);
+        assert_eq!(report.packages[2].status, BatchStatus::NotStarted);
+        assert!(report.guid_collisions.is_empty());
+    }
+
+    #[test]
+    fn test_owners_and_uninstall() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.unitypackage");
+        let second = dir.path().join("second.unitypackage");
+        let target = dir.path().join("target");
+        sample_package()
+            .asset(
+                "77777777777777777777777777777777",
+                SHARED_PATH,
+                b"first",
+                &texture_meta("77777777777777777777777777777777"),
+            )
+            .write(&first);
+        shared(b"second").write(&second);
+
+        let mut index = unpack_batch(
+            &[&first, &second],
+            &target,
+            Some(dir.path().join("tmp")),
+            &UnpackOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(index.packages().len(), 2);
+        assert_eq!(index.lookup_owner(TEXTURE_PATH).unwrap().path, first);
+        assert_eq!(index.lookup_owner(SHARED_PATH).unwrap().path, second);
+        assert_eq!(
+            index
+                .lookup_owner("Assets\\Shared\\readme.txt")
+                .unwrap()
+                .path,
+            second
+        );
+
+        let manifest = dir.path().join("manifest.txt");
+        index.write_manifest(&manifest).unwrap();
+        let content = fs::read_to_string(&manifest).unwrap();
+        assert!(content.contains(&format!(
+            "{}\t{}\t{}",
+            SHARED_PATH,
+            index.packages()[1].sha256,
+            second.display()
+        )));
+
+        // The shared file stays, the first package's own files are gone.
+        let report = index.uninstall(&first).unwrap();
+        assert!(report.removed.contains(&PathBuf::from(TEXTURE_PATH)));
+        assert!(report.needs_reinstall.is_empty());
+        assert!(!target.join(TEXTURE_PATH).exists());
+        assert_eq!(fs::read(target.join(SHARED_PATH)).unwrap(), b"second");
+        assert!(index.lookup_owner(TEXTURE_PATH).is_none());
+    }
+
+    #[test]
+    fn test_uninstall_last_writer_reports_reinstall() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.unitypackage");
+        let second = dir.path().join("second.unitypackage");
+        let target = dir.path().join("target");
+        shared(b"first").write(&first);
+        shared(b"second").write(&second);
+
+        let mut index = unpack_batch(
+            &[&first, &second],
+            &target,
+            Some(dir.path().join("tmp")),
+            &UnpackOptions::default(),
+        )
+        .unwrap();
+
+        let report = index.uninstall(&second).unwrap();
+        assert!(report.removed.is_empty());
+        assert_eq!(report.needs_reinstall.len(), 2);
+        assert_eq!(report.needs_reinstall[0].1.path, first);
+        assert_eq!(index.lookup_owner(SHARED_PATH).unwrap().path, first);
+        assert!(target.join(SHARED_PATH).exists());
+    }
+
+    #[test]
+    fn test_uninstall_reverts_gitignore() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        let first = dir.path().join("first.unitypackage");
+        let second = dir.path().join("second.unitypackage");
+        let target = dir.path().join("target");
+        sample_package().write(&first);
+        shared(b"second").write(&second);
+        let options = UnpackOptions::from(UnpackConfig {
+            git_aware: true,
+            incremental: true,
+            ..Default::default()
+        });
+
+        let mut index = unpack_batch(
+            &[&first, &second],
+            &target,
+            Some(dir.path().join("tmp")),
+            &options,
+        )
+        .unwrap();
+        let gitignore = target.join(".gitignore");
+        assert!(fs::read_to_string(&gitignore)
+            .unwrap()
+            .contains("/.unpacked-assets"));
+
+        let report = index.uninstall(&first).unwrap();
+        assert_eq!(report.reverted_gitignore, None);
+        assert!(gitignore.exists());
+        let report = index.uninstall(&second).unwrap();
+        assert_eq!(report.reverted_gitignore, Some(gitignore.clone()));
+        assert!(!gitignore.exists());
+        assert!(!target.join("Assets").exists());
+        assert!(target.is_dir());
+    }
+}
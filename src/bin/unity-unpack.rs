@@ -0,0 +1,170 @@
+//! `unity-unpack`: list, inspect and extract Unity packages from scripts.
+//!
+//! Exit codes: 0 on success, 1 for any other error, 2 for invalid arguments,
+//! 3 if the package does not exist, 4 if it is corrupt or no package at all
+//! and 5 if the target has conflicting files.
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use unity_unpacker_lib::prelude::*;
+
+const EXIT_FAILURE: u8 = 1;
+const EXIT_NOT_FOUND: u8 = 3;
+const EXIT_CORRUPT: u8 = 4;
+const EXIT_CONFLICT: u8 = 5;
+
+#[derive(Parser)]
+#[command(
+    name = "unity-unpack",
+    version,
+    about = "Inspect and extract Unity packages"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the assets of a package.
+    List {
+        package: PathBuf,
+        /// Print the manifest as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Extract a package into a target directory.
+    Extract {
+        package: PathBuf,
+        /// The target directory. Defaults to the name of the package inside
+        /// the current directory.
+        #[arg(long)]
+        target: Option<PathBuf>,
+        /// The directory the package is decompressed into first.
+        #[arg(long)]
+        tmp: Option<PathBuf>,
+        /// What happens to files the target has already.
+        #[arg(long, value_enum, default_value_t = OverwriteArg::Overwrite)]
+        overwrite: OverwriteArg,
+        /// Keep the tmp directory afterwards.
+        #[arg(long)]
+        keep_tmp: bool,
+    },
+    /// Show the size and the number of assets of a package.
+    Info {
+        package: PathBuf,
+        /// Print the manifest as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// The values of `OverwritePolicy`.
+#[derive(Clone, Copy, ValueEnum)]
+enum OverwriteArg {
+    Overwrite,
+    Skip,
+    Error,
+    IfChanged,
+}
+
+impl From<OverwriteArg> for OverwritePolicy {
+    fn from(value: OverwriteArg) -> Self {
+        match value {
+            OverwriteArg::Overwrite => OverwritePolicy::Overwrite,
+            OverwriteArg::Skip => OverwritePolicy::Skip,
+            OverwriteArg::Error => OverwritePolicy::Error,
+            OverwriteArg::IfChanged => OverwritePolicy::IfChanged,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+fn exit_code(e: &UnityPackageReaderError) -> u8 {
+    match e {
+        UnityPackageReaderError::PackageNotFound(_) => EXIT_NOT_FOUND,
+        UnityPackageReaderError::CorruptPackage(_)
+        | UnityPackageReaderError::NotAPackageFile(_) => EXIT_CORRUPT,
+        UnityPackageReaderError::TargetFileExists(_)
+        | UnityPackageReaderError::CaseCollision(_)
+        | UnityPackageReaderError::ReservedName(_) => EXIT_CONFLICT,
+        _ => EXIT_FAILURE,
+    }
+}
+
+fn run(command: Command) -> Result<(), UnityPackageReaderError> {
+    match command {
+        Command::List { package, json } => {
+            let package = UnityPackage::open(package, None, None)?;
+            if json {
+                return print_json(&package.manifest()?);
+            }
+
+            for entry in package.list_entries()? {
+                println!(
+                    "{}\t{}\t{}",
+                    entry.guid,
+                    entry.size,
+                    entry.relative_path.display()
+                );
+            }
+        }
+        Command::Extract {
+            package,
+            target,
+            tmp,
+            overwrite,
+            keep_tmp,
+        } => {
+            let mut package = UnityPackage::open(package, target, tmp)?;
+            package.set_overwrite_policy(overwrite.into());
+            package.unpack_package(!keep_tmp)?;
+
+            let stats = package.get_stats();
+            println!("target: {}", package.get_target_dir()?.display());
+            print!("{}", package.summary());
+            println!("compressed bytes read: {}", stats.compressed_bytes_read);
+            for warning in package.get_warnings() {
+                eprintln!("warning: {}", warning);
+            }
+        }
+        Command::Info { package, json } => {
+            let manifest = UnityPackage::open(package, None, None)?.manifest()?;
+            if json {
+                return print_json(&manifest);
+            }
+
+            println!("package: {}", manifest.source_file);
+            println!("package size: {}", manifest.package_size);
+            println!("assets: {}", manifest.assets.len());
+            println!(
+                "folders: {}",
+                manifest.assets.iter().filter(|a| a.is_folder).count()
+            );
+            println!("total size: {}", manifest.total_size);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_json(manifest: &PackageManifest) -> Result<(), UnityPackageReaderError> {
+    match serde_json::to_string_pretty(manifest) {
+        Ok(json) => {
+            println!("{}", json);
+            Ok(())
+        }
+        Err(e) => Err(UnityPackageReaderError::CouldNotWriteReport(
+            ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+        )),
+    }
+}
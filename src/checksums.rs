@@ -0,0 +1,235 @@
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::unpacker_error::{ErrorInformation, UnityPackageReaderError};
+
+/// Result of validating an installed tree against a list of checksums.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct VerifyReport {
+    /// Relative paths whose content matches the expected hash.
+    pub verified: Vec<PathBuf>,
+    /// Relative paths that exist but whose content differs.
+    pub modified: Vec<PathBuf>,
+    /// Relative paths that are listed but do not exist in the target.
+    pub missing: Vec<PathBuf>,
+    /// Line numbers (starting at 1) that could not be interpreted.
+    pub malformed_lines: Vec<usize>,
+}
+
+impl VerifyReport {
+    /// True, if every listed file exists and matches its hash.
+    pub fn is_ok(&self) -> bool {
+        self.modified.is_empty() && self.missing.is_empty() && self.malformed_lines.is_empty()
+    }
+}
+
+/// Hash a file without loading it into memory completely.
+pub(crate) fn sha256_file(path: &Path) -> io::Result<[u8; 32]> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finalize().into())
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Join the components of a relative path with forward slashes, so
+/// checksum files are identical on every platform.
+pub(crate) fn to_forward_slashes(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Format a single line the way `sha256sum` does. File names containing a
+/// backslash or a newline are escaped and the line is prefixed with a backslash.
+pub(crate) fn format_checksum_line(hash: &str, relative_path: &str) -> String {
+    if relative_path.contains('\\') || relative_path.contains('\n') {
+        let escaped = relative_path.replace('\\', "\\\\").replace('\n', "\\n");
+        format!("\\{}  {}\n", hash, escaped)
+    } else {
+        format!("{}  {}\n", hash, relative_path)
+    }
+}
+
+/// Parse a single `sha256sum` line into the lower case hash and the path.
+/// Only the two characters after the hash are treated as separator, so paths
+/// containing (even leading) spaces survive. Returns None for malformed lines.
+pub(crate) fn parse_checksum_line(line: &str) -> Option<(String, String)> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(l) => (true, l),
+        None => (false, line),
+    };
+
+    if line.len() < 66 || !line.is_char_boundary(64) {
+        return None;
+    }
+
+    let (hash, rest) = line.split_at(64);
+    if !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    // Text mode uses two spaces, binary mode a space followed by an asterisk.
+    let path = match rest.strip_prefix("  ").or_else(|| rest.strip_prefix(" *")) {
+        Some(p) if !p.is_empty() => p,
+        _ => return None,
+    };
+
+    let path = if escaped {
+        let mut unescaped = String::with_capacity(path.len());
+        let mut chars = path.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                match chars.next() {
+                    Some('\\') => unescaped.push('\\'),
+                    Some('n') => unescaped.push('\n'),
+                    _ => return None,
+                }
+            } else {
+                unescaped.push(c);
+            }
+        }
+        unescaped
+    } else {
+        String::from(path)
+    };
+
+    Some((hash.to_ascii_lowercase(), path))
+}
+
+/// Validate the files below target against a `sha256sum` style checksum file.
+/// CRLF line endings, empty lines and lines starting with '#' are accepted.
+pub fn verify_checksums(
+    target: &Path,
+    checksum_file: &Path,
+) -> Result<VerifyReport, UnityPackageReaderError> {
+    let content = match fs::read_to_string(checksum_file) {
+        Ok(c) => c,
+        Err(e) => {
+            return Err(UnityPackageReaderError::ChecksumFileError(
+                ErrorInformation::new(
+                    Some(format!("{:?}: {}", checksum_file, e)),
+                    file!(),
+                    line!(),
+                ),
+            ));
+        }
+    };
+
+    let mut report = VerifyReport::default();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (expected, relative_path) = match parse_checksum_line(line) {
+            Some(e) => e,
+            None => {
+                report.malformed_lines.push(index + 1);
+                continue;
+            }
+        };
+
+        let relative_path = PathBuf::from(relative_path);
+        match sha256_file(&target.join(&relative_path)) {
+            Ok(hash) => {
+                if to_hex(&hash) == expected {
+                    report.verified.push(relative_path);
+                } else {
+                    report.modified.push(relative_path);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => report.missing.push(relative_path),
+            Err(e) => {
+                return Err(UnityPackageReaderError::ChecksumFileError(
+                    ErrorInformation::new(
+                        Some(format!("{:?}: {}", relative_path, e)),
+                        file!(),
+                        line!(),
+                    ),
+                ));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn test_parse_line_with_spaces() {
+        let line = format!("{}   leading space/file name.txt", HASH);
+        let (hash, path) = parse_checksum_line(&line).unwrap();
+        assert_eq!(hash, HASH);
+        assert_eq!(path, " leading space/file name.txt");
+    }
+
+    #[test]
+    fn test_parse_binary_marker_and_escapes() {
+        let line = format!("\\{} *dir\\\\with backslash\\nnewline", HASH);
+        let (_, path) = parse_checksum_line(&line).unwrap();
+        assert_eq!(path, "dir\\with backslash\nnewline");
+
+        let formatted = format_checksum_line(HASH, &path);
+        assert_eq!(
+            parse_checksum_line(formatted.trim_end_matches('\n'))
+                .unwrap()
+                .1,
+            path
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert!(parse_checksum_line("abc  file").is_none());
+        assert!(parse_checksum_line(&format!("{} file", HASH)).is_none());
+        assert!(parse_checksum_line(&format!("{}  ", HASH)).is_none());
+    }
+
+    #[test]
+    fn test_verify_crlf_and_comments() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a b")).unwrap();
+        fs::write(dir.path().join("a b/c d.txt"), "test").unwrap();
+        fs::write(dir.path().join("changed.txt"), "other").unwrap();
+
+        let sums = dir.path().join("SHA256SUMS");
+        fs::write(
+            &sums,
+            format!(
+                "# comment\r\n{h}  a b/c d.txt\r\n\r\n{h}  changed.txt\r\n{h}  gone.txt\r\nnonsense\r\n",
+                h = HASH
+            ),
+        )
+        .unwrap();
+
+        let report = verify_checksums(dir.path(), &sums).unwrap();
+        assert_eq!(report.verified, vec![PathBuf::from("a b/c d.txt")]);
+        assert_eq!(report.modified, vec![PathBuf::from("changed.txt")]);
+        assert_eq!(report.missing, vec![PathBuf::from("gone.txt")]);
+        assert_eq!(report.malformed_lines, vec![6]);
+        assert!(!report.is_ok());
+    }
+}
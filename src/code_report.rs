@@ -0,0 +1,251 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead},
+    path::{Component, PathBuf},
+};
+use tar::Archive;
+
+use crate::{
+    asset_category::NATIVE_PLUGIN_EXTENSIONS,
+    guid::Guid,
+    package_format,
+    prelude::MetaData,
+    unity_asset_file,
+    unity_meta::{self, LEGACY_META_FILE},
+    unpack_limits::{self, LimitTracker, UnpackLimits},
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// An asset that is code: a script or an assembly definition (reference).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CodeAsset {
+    pub guid: Guid,
+    pub path: PathBuf,
+}
+
+/// A platform entry of the `platformData` of a `PluginImporter`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PluginPlatform {
+    /// The platform, e.g. `Android`, `Win64` or `Any` for every platform.
+    pub platform: String,
+    pub enabled: bool,
+    /// The `CPU` setting, e.g. `x86_64` or `ARMv7`.
+    pub cpu: Option<String>,
+}
+
+/// A native plugin with the platform settings of its meta.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PluginAsset {
+    pub guid: Guid,
+    pub path: PathBuf,
+    /// Empty if the meta has no (readable) `PluginImporter` section.
+    pub platforms: Vec<PluginPlatform>,
+}
+
+impl PluginAsset {
+    /// The platforms the plugin is enabled for.
+    pub fn enabled_platforms(&self) -> impl Iterator<Item = &str> {
+        self.platforms
+            .iter()
+            .filter(|p| p.enabled)
+            .map(|p| p.platform.as_str())
+    }
+}
+
+/// The code a package ships, grouped by kind, see `UnityPackage::code_report`.
+/// Every list is ordered by path.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CodeReport {
+    /// `.asmdef` files.
+    pub assembly_definitions: Vec<CodeAsset>,
+    /// `.asmref` files.
+    pub assembly_references: Vec<CodeAsset>,
+    /// `.cs` files.
+    pub scripts: Vec<CodeAsset>,
+    /// `.dll`, `.so`, `.dylib`, `.a` and `.aar` files.
+    pub plugins: Vec<PluginAsset>,
+}
+
+impl CodeReport {
+    /// True, if the package contains any code at all.
+    pub fn ships_code(&self) -> bool {
+        !(self.assembly_definitions.is_empty()
+            && self.assembly_references.is_empty()
+            && self.scripts.is_empty()
+            && self.plugins.is_empty())
+    }
+
+    fn add(&mut self, guid: Guid, path: PathBuf, meta: Option<&str>) {
+        let extension = match path.extension() {
+            Some(e) => e.to_string_lossy().to_ascii_lowercase(),
+            None => return,
+        };
+
+        let asset = CodeAsset { guid, path };
+        match extension.as_str() {
+            "asmdef" => self.assembly_definitions.push(asset),
+            "asmref" => self.assembly_references.push(asset),
+            "cs" => self.scripts.push(asset),
+            e if NATIVE_PLUGIN_EXTENSIONS.contains(&e) => self.plugins.push(PluginAsset {
+                guid: asset.guid,
+                path: asset.path,
+                platforms: meta.map(meta_platforms).unwrap_or_default(),
+            }),
+            _ => {}
+        }
+    }
+
+    fn sort(&mut self) {
+        self.assembly_definitions
+            .sort_by(|a, b| a.path.cmp(&b.path));
+        self.assembly_references.sort_by(|a, b| a.path.cmp(&b.path));
+        self.scripts.sort_by(|a, b| a.path.cmp(&b.path));
+        self.plugins.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+}
+
+/// The platforms of the `PluginImporter` section of a meta, empty if the meta
+/// cannot be parsed.
+fn meta_platforms(meta: &str) -> Vec<PluginPlatform> {
+    MetaData::parse(meta)
+        .ok()
+        .and_then(|m| m.values.get("PluginImporter").map(|i| plugin_platforms(i)))
+        .unwrap_or_default()
+}
+
+/// Parse the `platformData` list of a `PluginImporter` section. A `first` of
+/// `Standalone: Win64` names the platform `Win64`, `Any: ` names `Any`.
+fn plugin_platforms(importer: &str) -> Vec<PluginPlatform> {
+    let mut result: Vec<PluginPlatform> = Vec::new();
+    let mut expect_platform = false;
+
+    for line in importer.lines().map(str::trim) {
+        if line == "- first:" {
+            expect_platform = true;
+        } else if expect_platform {
+            expect_platform = false;
+            let (group, name) = line.split_once(':').unwrap_or((line, ""));
+            let platform = if name.trim().is_empty() { group } else { name };
+            result.push(PluginPlatform {
+                platform: String::from(platform.trim()),
+                enabled: false,
+                cpu: None,
+            });
+        } else if let Some(current) = result.last_mut() {
+            if let Some(enabled) = line.strip_prefix("enabled:") {
+                current.enabled = enabled.trim() == "1";
+            } else if let Some(cpu) = line.strip_prefix("CPU:") {
+                current.cpu = Some(String::from(cpu.trim())).filter(|c| !c.is_empty());
+            }
+        }
+    }
+
+    result
+}
+
+/// The parts of a guid directory the code report needs.
+#[derive(Default)]
+struct PendingAsset {
+    pathname: Option<String>,
+    is_folder: bool,
+    /// The meta, only kept if it has a `PluginImporter` section.
+    plugin_meta: Option<String>,
+}
+
+/// Stream through a package and collect its code. Only the `pathname` and
+/// meta files are read (up to `unpack_limits::MAX_TEXT_BYTES` each), the
+/// payloads are skipped. The entries count against limits like an
+/// extraction does.
+pub(crate) fn scan_code<R: BufRead>(
+    reader: R,
+    limits: UnpackLimits,
+) -> Result<CodeReport, UnityPackageReaderError> {
+    let corrupt = |e: io::Error| {
+        UnityPackageReaderError::CorruptPackage(ErrorInformation::new(
+            Some(format!("{}", e)),
+            file!(),
+            line!(),
+        ))
+    };
+
+    let mut archive = Archive::new(package_format::open(reader)?);
+    let mut tracker = LimitTracker::new(limits);
+    let mut pending: HashMap<Guid, PendingAsset> = HashMap::new();
+    for entry in archive.entries().map_err(corrupt)? {
+        let mut entry = entry.map_err(corrupt)?;
+        tracker.add_entry(entry.size())?;
+        let path = entry.path().map_err(corrupt)?.into_owned();
+
+        let mut components = path.components().filter_map(|c| match c {
+            Component::Normal(n) => Some(n.to_string_lossy().into_owned()),
+            _ => None,
+        });
+        let (guid, name) = match (components.next(), components.next(), components.next()) {
+            (Some(g), Some(n), None) => match g.parse::<Guid>() {
+                Ok(g) => (g, n),
+                Err(_) => continue,
+            },
+            _ => continue,
+        };
+
+        match name.as_str() {
+            "pathname" => {
+                let content = unpack_limits::read_text(&mut entry).map_err(corrupt)?;
+                let pathname = unity_asset_file::trim_pathname(&content);
+                pending.entry(guid).or_default().pathname = Some(String::from(pathname));
+            }
+            "asset.meta" | LEGACY_META_FILE => {
+                let content = unpack_limits::read_text(&mut entry).map_err(corrupt)?;
+                let asset = pending.entry(guid).or_default();
+                asset.is_folder = unity_meta::is_folder_meta(&content);
+                if content.contains("PluginImporter:") {
+                    asset.plugin_meta = Some(content);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut report = CodeReport::default();
+    for (guid, asset) in pending {
+        match asset.pathname.filter(|p| !p.is_empty()) {
+            Some(pathname) if !asset.is_folder => {
+                let path = PathBuf::from(unity_asset_file::normalize_pathname(&pathname));
+                report.add(guid, path, asset.plugin_meta.as_deref());
+            }
+            _ => {}
+        }
+    }
+
+    report.sort();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plugin_platforms() {
+        let importer = "  isPreloaded: 0\n  platformData:\n  - first:\n      : Any\n    second:\n      enabled: 0\n      settings:\n        Exclude Android: 1\n  - first:\n      Editor: Editor\n    second:\n      enabled: 1\n      settings:\n        CPU: AnyCPU\n  - first:\n      Standalone: Win64\n    second:\n      enabled: 1\n      settings:\n        CPU: x86_64\n";
+        let platform = |name: &str, enabled: bool, cpu: Option<&str>| PluginPlatform {
+            platform: String::from(name),
+            enabled,
+            cpu: cpu.map(String::from),
+        };
+
+        assert_eq!(
+            plugin_platforms(importer),
+            vec![
+                platform("Any", false, None),
+                platform("Editor", true, Some("AnyCPU")),
+                platform("Win64", true, Some("x86_64")),
+            ]
+        );
+        assert!(meta_platforms("not: [a meta").is_empty());
+    }
+}
@@ -0,0 +1,548 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::{
+    asset_category::NATIVE_PLUGIN_EXTENSIONS,
+    prelude::{AssemblyDefinition, MetaData, UnityAssetFile},
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// The file every Unity project has, relative to the project root.
+const PROJECT_VERSION_FILE: &str = "ProjectSettings/ProjectVersion.txt";
+
+/// How bad a finding of a compatibility check is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    /// The package will not work in the project without changes.
+    Error,
+}
+
+/// The check that produced a `CompatibilityIssue`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompatibilityCheck {
+    ExporterVersion,
+    AssemblyReference,
+    PluginPlatform,
+}
+
+/// A single finding of `UnityPackage::compatibility_report`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatibilityIssue {
+    pub severity: Severity,
+    pub check: CompatibilityCheck,
+    /// The relative path of the asset the issue is about, if any.
+    pub path: Option<PathBuf>,
+    pub message: String,
+}
+
+/// A guess at the Unity version a package has been exported with, based on
+/// the fields of its meta files.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExporterEstimate {
+    /// The metas contain `timeCreated` or `licenseType`, which only Unity
+    /// versions before 2019 write.
+    Before2019,
+    /// None of the metas contains fields of older Unity versions.
+    Since2019,
+    /// The package has no metas to judge by.
+    #[default]
+    Unknown,
+}
+
+/// The result of checking a package against a Unity project.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CompatibilityReport {
+    /// `m_EditorVersion` of the project, e.g. `2021.3.5f1`.
+    pub project_version: String,
+    pub exporter_estimate: ExporterEstimate,
+    pub issues: Vec<CompatibilityIssue>,
+}
+
+impl CompatibilityReport {
+    /// The severity of the worst issue, None if there are no issues.
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.issues.iter().map(|i| i.severity).max()
+    }
+
+    /// True, if no issue has `Severity::Error`.
+    pub fn is_compatible(&self) -> bool {
+        self.max_severity() != Some(Severity::Error)
+    }
+}
+
+/// What is known about the Unity project a package is checked against.
+pub(crate) struct ProjectInfo {
+    version: String,
+    /// The packages listed in `Packages/manifest.json`.
+    dependencies: HashSet<String>,
+    /// The platforms configured in `ProjectSettings/ProjectSettings.asset`.
+    platforms: HashSet<String>,
+    /// Assembly names and `GUID:<guid>` references defined in the project.
+    assemblies: HashSet<String>,
+    /// False, if `Library/PackageCache` does not exist (yet), so assemblies of
+    /// packages cannot be resolved.
+    has_package_cache: bool,
+}
+
+fn project_error(path: &Path, message: String) -> UnityPackageReaderError {
+    UnityPackageReaderError::CouldNotReadProjectFile(ErrorInformation::new(
+        Some(format!("{:?}: {}", path, message)),
+        file!(),
+        line!(),
+    ))
+}
+
+impl ProjectInfo {
+    pub(crate) fn read(project_root: &Path) -> Result<Self, UnityPackageReaderError> {
+        let version_file = project_root.join(PROJECT_VERSION_FILE);
+        let version = match fs::read_to_string(&version_file) {
+            Ok(c) => c
+                .lines()
+                .find_map(|l| l.strip_prefix("m_EditorVersion:"))
+                .map(|v| String::from(v.trim())),
+            Err(e) => return Err(project_error(&version_file, format!("{}", e))),
+        };
+
+        let version = match version {
+            Some(v) => v,
+            None => {
+                return Err(project_error(
+                    &version_file,
+                    String::from("missing m_EditorVersion"),
+                ))
+            }
+        };
+
+        let manifest_file = project_root.join("Packages/manifest.json");
+        let manifest: Value = match fs::read_to_string(&manifest_file)
+            .map_err(|e| format!("{}", e))
+            .and_then(|c| serde_json::from_str(&c).map_err(|e| format!("{}", e)))
+        {
+            Ok(m) => m,
+            Err(e) => return Err(project_error(&manifest_file, e)),
+        };
+
+        let dependencies = manifest
+            .get("dependencies")
+            .and_then(Value::as_object)
+            .map(|d| d.keys().cloned().collect())
+            .unwrap_or_default();
+
+        let platforms =
+            fs::read_to_string(project_root.join("ProjectSettings/ProjectSettings.asset"))
+                .map(|c| {
+                    c.lines()
+                        .filter_map(|l| {
+                            l.trim()
+                                .trim_start_matches("- ")
+                                .strip_prefix("m_BuildTarget:")
+                        })
+                        .map(|p| String::from(p.trim()))
+                        .filter(|p| !p.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+        let package_cache = project_root.join("Library/PackageCache");
+        let mut assemblies = HashSet::new();
+        for directory in [
+            project_root.join("Assets"),
+            project_root.join("Packages"),
+            package_cache.clone(),
+        ] {
+            collect_assemblies(&directory, &mut assemblies);
+        }
+
+        Ok(ProjectInfo {
+            version,
+            dependencies,
+            platforms,
+            assemblies,
+            has_package_cache: package_cache.is_dir(),
+        })
+    }
+
+    /// The year of the editor version, e.g. 2021 for `2021.3.5f1`.
+    fn year(&self) -> Option<u32> {
+        self.version.split('.').next().and_then(|y| y.parse().ok())
+    }
+}
+
+/// Make sure target is the root of a Unity project or its `Assets` folder.
+/// The error names the markers that are missing and what target contains.
+pub(crate) fn check_unity_project(target: &Path) -> Result<(), UnityPackageReaderError> {
+    let is_project =
+        |root: &Path| root.join("Assets").is_dir() && root.join(PROJECT_VERSION_FILE).is_file();
+    let is_assets_folder = target.file_name().map(|n| n == "Assets").unwrap_or(false)
+        && target.parent().map(is_project).unwrap_or(false);
+    if is_project(target) || is_assets_folder {
+        return Ok(());
+    }
+
+    let found = match fs::read_dir(target) {
+        Ok(entries) => {
+            let mut names: Vec<String> = entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            if names.is_empty() {
+                String::from("the directory is empty")
+            } else {
+                format!("found {}", names.join(", "))
+            }
+        }
+        Err(e) => format!("{}", e),
+    };
+
+    let missing: Vec<&str> = [
+        ("Assets", target.join("Assets").is_dir()),
+        (
+            PROJECT_VERSION_FILE,
+            target.join(PROJECT_VERSION_FILE).is_file(),
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, present)| !present)
+    .map(|(marker, _)| marker)
+    .collect();
+
+    Err(UnityPackageReaderError::NotAUnityProject(
+        ErrorInformation::new(
+            Some(format!(
+                "{:?}: missing {}; {}",
+                target,
+                missing.join(" and "),
+                found
+            )),
+            file!(),
+            line!(),
+        ),
+    ))
+}
+
+/// Add the names and guid references of every asmdef below directory.
+fn collect_assemblies(directory: &Path, assemblies: &mut HashSet<String>) {
+    let entries = match fs::read_dir(directory) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_assemblies(&path, assemblies);
+            continue;
+        }
+
+        if !has_extension(&path, "asmdef") {
+            continue;
+        }
+
+        if let Some(asmdef) = fs::read_to_string(&path)
+            .ok()
+            .and_then(|c| AssemblyDefinition::parse(&c).ok())
+        {
+            assemblies.insert(asmdef.name);
+        }
+
+        let mut meta = path.into_os_string();
+        meta.push(".meta");
+        if let Some(meta) = fs::read_to_string(meta)
+            .ok()
+            .and_then(|c| MetaData::parse(&c).ok())
+        {
+            assemblies.insert(format!("GUID:{}", meta.guid));
+        }
+    }
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .map(|e| e.to_string_lossy().eq_ignore_ascii_case(extension))
+        .unwrap_or(false)
+}
+
+/// The platforms a plugin is enabled for, read from the `platformData` of its
+/// `PluginImporter` section. `Any` stands for every platform.
+fn enabled_plugin_platforms(importer: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut current: Option<String> = None;
+    let mut expect_platform = false;
+
+    for line in importer.lines().map(str::trim) {
+        if line == "- first:" {
+            expect_platform = true;
+            current = None;
+        } else if expect_platform {
+            expect_platform = false;
+            current = line.split(':').next().map(|p| String::from(p.trim()));
+        } else if let Some(enabled) = line.strip_prefix("enabled:") {
+            if enabled.trim() == "1" {
+                if let Some(platform) = current.take() {
+                    result.push(platform);
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Check the assets of an extracted package against the project.
+pub(crate) fn build_report(
+    project: &ProjectInfo,
+    assets: &[UnityAssetFile],
+) -> CompatibilityReport {
+    let mut report = CompatibilityReport {
+        project_version: project.version.clone(),
+        ..Default::default()
+    };
+
+    check_exporter_version(project, assets, &mut report);
+    check_assembly_references(project, assets, &mut report);
+    check_plugin_platforms(project, assets, &mut report);
+
+    report
+        .issues
+        .sort_by(|a, b| b.severity.cmp(&a.severity).then(a.path.cmp(&b.path)));
+    report
+}
+
+fn check_exporter_version(
+    project: &ProjectInfo,
+    assets: &[UnityAssetFile],
+    report: &mut CompatibilityReport,
+) {
+    if assets.is_empty() {
+        return;
+    }
+
+    let legacy = assets.iter().any(|a| {
+        let values = &a.meta_data().values;
+        values.contains_key("timeCreated") || values.contains_key("licenseType")
+    });
+
+    report.exporter_estimate = if legacy {
+        ExporterEstimate::Before2019
+    } else {
+        ExporterEstimate::Since2019
+    };
+
+    let year = match project.year() {
+        Some(y) => y,
+        None => return,
+    };
+
+    let issue = match report.exporter_estimate {
+        ExporterEstimate::Since2019 if year < 2019 => Some((
+            Severity::Warning,
+            format!(
+                "The package has probably been exported with Unity 2019 or newer, the project uses {}.",
+                project.version
+            ),
+        )),
+        ExporterEstimate::Before2019 if year >= 2019 => Some((
+            Severity::Info,
+            String::from("The package has been exported with an older Unity version, its assets will be upgraded on import."),
+        )),
+        _ => None,
+    };
+
+    if let Some((severity, message)) = issue {
+        report.issues.push(CompatibilityIssue {
+            severity,
+            check: CompatibilityCheck::ExporterVersion,
+            path: None,
+            message,
+        });
+    }
+}
+
+fn check_assembly_references(
+    project: &ProjectInfo,
+    assets: &[UnityAssetFile],
+    report: &mut CompatibilityReport,
+) {
+    let mut definitions: HashMap<PathBuf, AssemblyDefinition> = HashMap::new();
+    let mut defined: HashSet<String> = HashSet::new();
+
+    for asset in assets
+        .iter()
+        .filter(|a| !a.is_folder() && has_extension(a.get_relative_asset_path(), "asmdef"))
+    {
+        let parsed = fs::read_to_string(asset.get_absolute_asset_path())
+            .map_err(|e| format!("{}", e))
+            .and_then(|c| AssemblyDefinition::parse(&c).map_err(|e| format!("{}", e)));
+
+        match parsed {
+            Ok(asmdef) => {
+                defined.insert(asmdef.name.clone());
+                defined.insert(format!("GUID:{}", asset.get_guid()));
+                definitions.insert(asset.get_relative_asset_path().clone(), asmdef);
+            }
+            Err(e) => report.issues.push(CompatibilityIssue {
+                severity: Severity::Warning,
+                check: CompatibilityCheck::AssemblyReference,
+                path: Some(asset.get_relative_asset_path().clone()),
+                message: format!("The assembly definition could not be read: {}", e),
+            }),
+        }
+    }
+
+    let mut paths: Vec<&PathBuf> = definitions.keys().collect();
+    paths.sort();
+    for path in paths {
+        let asmdef = &definitions[path];
+        for reference in &asmdef.references {
+            if defined.contains(reference) || project.assemblies.contains(reference) {
+                continue;
+            }
+
+            let (severity, hint) = if project.has_package_cache {
+                (Severity::Error, "")
+            } else {
+                (
+                    Severity::Warning,
+                    " Library/PackageCache does not exist, so package assemblies could not be checked.",
+                )
+            };
+
+            report.issues.push(CompatibilityIssue {
+                severity,
+                check: CompatibilityCheck::AssemblyReference,
+                path: Some(path.clone()),
+                message: format!(
+                    "{} references '{}', which is neither part of the package nor of the project.{}",
+                    asmdef.name, reference, hint
+                ),
+            });
+        }
+
+        for package in &asmdef.version_defines {
+            if !project.dependencies.contains(package) && !package.starts_with("Unity") {
+                report.issues.push(CompatibilityIssue {
+                    severity: Severity::Info,
+                    check: CompatibilityCheck::AssemblyReference,
+                    path: Some(path.clone()),
+                    message: format!(
+                        "{} has a version define for '{}', which is not in the project manifest.",
+                        asmdef.name, package
+                    ),
+                });
+            }
+        }
+    }
+}
+
+fn check_plugin_platforms(
+    project: &ProjectInfo,
+    assets: &[UnityAssetFile],
+    report: &mut CompatibilityReport,
+) {
+    for asset in assets.iter().filter(|a| !a.is_folder()) {
+        let path = asset.get_relative_asset_path();
+        let meta = asset.meta_data();
+        if meta.importer.as_deref() != Some("PluginImporter")
+            || !NATIVE_PLUGIN_EXTENSIONS
+                .iter()
+                .any(|e| has_extension(path, e))
+        {
+            continue;
+        }
+
+        let importer = meta
+            .values
+            .get("PluginImporter")
+            .map(String::as_str)
+            .unwrap_or("");
+        let platforms: Vec<String> = enabled_plugin_platforms(importer)
+            .into_iter()
+            .filter(|p| p != "Editor")
+            .collect();
+
+        if platforms.iter().any(|p| p == "Any") {
+            continue;
+        }
+
+        let issue = if platforms.is_empty() {
+            Some((
+                Severity::Info,
+                String::from("The plugin is not enabled for any build platform."),
+            ))
+        } else if !project.platforms.is_empty()
+            && platforms.iter().all(|p| !project.platforms.contains(p))
+        {
+            let mut configured: Vec<&String> = project.platforms.iter().collect();
+            configured.sort();
+            Some((
+                Severity::Warning,
+                format!(
+                    "The plugin is only enabled for {}, the project builds for {}.",
+                    platforms.join(", "),
+                    configured
+                        .iter()
+                        .map(|p| p.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            ))
+        } else {
+            None
+        };
+
+        if let Some((severity, message)) = issue {
+            report.issues.push(CompatibilityIssue {
+                severity,
+                check: CompatibilityCheck::PluginPlatform,
+                path: Some(path.clone()),
+                message,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enabled_plugin_platforms() {
+        let importer = "  platformData:\n  - first:\n      Any: \n    second:\n      enabled: 0\n      settings: {}\n  - first:\n      Android: Android\n    second:\n      enabled: 1\n      settings:\n        CPU: ARMv7\n  - first:\n      Editor: Editor\n    second:\n      enabled: 1\n";
+        assert_eq!(
+            enabled_plugin_platforms(importer),
+            vec![String::from("Android"), String::from("Editor")]
+        );
+    }
+
+    #[test]
+    fn test_severity_order() {
+        let report = CompatibilityReport {
+            issues: vec![
+                CompatibilityIssue {
+                    severity: Severity::Info,
+                    check: CompatibilityCheck::ExporterVersion,
+                    path: None,
+                    message: String::new(),
+                },
+                CompatibilityIssue {
+                    severity: Severity::Warning,
+                    check: CompatibilityCheck::PluginPlatform,
+                    path: None,
+                    message: String::new(),
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(report.max_severity(), Some(Severity::Warning));
+        assert!(report.is_compatible());
+    }
+}
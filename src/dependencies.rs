@@ -0,0 +1,314 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    io::{self, BufRead, Read},
+    path::{Component, Path},
+};
+use tar::Archive;
+
+use crate::{
+    guid::Guid,
+    guid_remap::{self, TOKEN},
+    package_format, unpack_limits,
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// The references found in a single asset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum AssetDependencies {
+    /// The guids the asset references (without its own guid). Empty for
+    /// folders and for assets that cannot reference anything, e.g. textures.
+    Scanned(BTreeSet<Guid>),
+    /// The asset is serialized in Unity's binary format, so its references
+    /// are unknown.
+    Unscanned,
+}
+
+/// References to assets the package does not contain, see
+/// `UnityPackage::missing_dependencies`. Both map the referenced guid to the
+/// assets of the package that reference it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MissingDependencies {
+    /// Unity's built-in resources (guids starting with 16 zeros), which every
+    /// project has.
+    pub builtin: BTreeMap<Guid, BTreeSet<Guid>>,
+    /// Everything else. These assets show up as missing after the import.
+    pub external: BTreeMap<Guid, BTreeSet<Guid>>,
+}
+
+impl MissingDependencies {
+    /// True, if no asset references anything outside of the package.
+    pub fn is_empty(&self) -> bool {
+        self.builtin.is_empty() && self.external.is_empty()
+    }
+}
+
+/// True, if guid belongs to one of Unity's built-in resources, like
+/// `0000000000000000e000000000000000` (the default resources).
+pub fn is_builtin_guid(guid: &Guid) -> bool {
+    guid.as_bytes()[..8] == [0u8; 8]
+}
+
+/// Which asset references which, see `UnityPackage::scan_dependencies`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DependencyGraph {
+    assets: BTreeMap<Guid, AssetDependencies>,
+}
+
+impl DependencyGraph {
+    /// The references of a single asset, None if the package does not contain it.
+    pub fn dependencies_of(&self, guid: Guid) -> Option<&AssetDependencies> {
+        self.assets.get(&guid)
+    }
+
+    /// The whole graph: every asset of the package and its references.
+    pub fn assets(&self) -> &BTreeMap<Guid, AssetDependencies> {
+        &self.assets
+    }
+
+    /// The assets whose references are unknown.
+    pub fn unscanned(&self) -> impl Iterator<Item = &Guid> + '_ {
+        self.assets
+            .iter()
+            .filter(|(_, d)| **d == AssetDependencies::Unscanned)
+            .map(|(g, _)| g)
+    }
+
+    /// The references that point to neither an asset of the package nor one of
+    /// known (e.g. the guids of the target project). The all-zero guid is no
+    /// reference and is ignored.
+    pub fn missing(&self, known: &HashSet<Guid>) -> MissingDependencies {
+        let mut result = MissingDependencies::default();
+        for (guid, dependencies) in &self.assets {
+            let references = match dependencies {
+                AssetDependencies::Scanned(r) => r,
+                AssetDependencies::Unscanned => continue,
+            };
+
+            for reference in references {
+                if self.assets.contains_key(reference)
+                    || known.contains(reference)
+                    || *reference == Guid::default()
+                {
+                    continue;
+                }
+
+                let missing = if is_builtin_guid(reference) {
+                    &mut result.builtin
+                } else {
+                    &mut result.external
+                };
+                missing.entry(*reference).or_default().insert(*guid);
+            }
+        }
+
+        result
+    }
+
+    /// Every guid guid references directly or through other assets of the
+    /// package. References leaving the package are included, but not followed.
+    pub fn closure(&self, guid: Guid) -> BTreeSet<Guid> {
+        let mut result = BTreeSet::new();
+        let mut open = vec![guid];
+
+        while let Some(current) = open.pop() {
+            if let Some(AssetDependencies::Scanned(references)) = self.assets.get(&current) {
+                for reference in references {
+                    if *reference != guid && result.insert(*reference) {
+                        open.push(*reference);
+                    }
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Collects the `guid: <32 hex digits>` references of a text serialized
+/// asset that is handed over in chunks of any size.
+#[derive(Default)]
+struct GuidScanner {
+    buffer: Vec<u8>,
+    found: BTreeSet<Guid>,
+}
+
+impl GuidScanner {
+    fn feed(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+        let done = self.scan(false);
+        self.buffer.drain(..done);
+    }
+
+    fn finish(mut self) -> BTreeSet<Guid> {
+        self.scan(true);
+        self.found
+    }
+
+    /// Look at every token of the buffer and return how many bytes at the
+    /// start of the buffer are not needed anymore. A token at the end whose
+    /// value may continue in the next chunk is kept, together with the byte
+    /// in front of it.
+    fn scan(&mut self, last: bool) -> usize {
+        let mut position = 0;
+        while let Some(offset) = guid_remap::find(&self.buffer[position..], TOKEN) {
+            let start = position + offset;
+            if !last && start + TOKEN.len() + 32 >= self.buffer.len() {
+                return start.saturating_sub(1);
+            }
+
+            if let Some(guid) = guid_remap::reference_at(&self.buffer, start) {
+                self.found.insert(guid);
+            }
+            position = start + TOKEN.len();
+        }
+
+        // A part of a token may be left at the end.
+        self.buffer.len().saturating_sub(TOKEN.len()).max(position)
+    }
+}
+
+/// What is known about a guid directory while streaming.
+#[derive(Default)]
+struct PendingAsset {
+    pathname: Option<String>,
+    /// The references of a text serialized payload, None for a binary one.
+    references: Option<Option<BTreeSet<Guid>>>,
+}
+
+/// Read a payload and scan it if it is serialized as text.
+fn scan_payload(mut reader: impl Read) -> io::Result<Option<BTreeSet<Guid>>> {
+    let mut head = Vec::new();
+    let mut scanner: Option<GuidScanner> = None;
+    let mut chunk = vec![0u8; 64 * 1024];
+
+    loop {
+        let read = reader.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+
+        match scanner.as_mut() {
+            Some(s) => s.feed(&chunk[..read]),
+            None => {
+                head.extend_from_slice(&chunk[..read]);
+                if head.len() < 5 {
+                    continue;
+                }
+
+                if !guid_remap::is_text_serialized(&head) {
+                    io::copy(&mut reader, &mut io::sink())?;
+                    return Ok(None);
+                }
+
+                let mut s = GuidScanner::default();
+                s.feed(&head);
+                scanner = Some(s);
+            }
+        }
+    }
+
+    Ok(scanner.map(GuidScanner::finish))
+}
+
+/// Stream through a package and scan the payload of every asset for guid
+/// references. Payloads of assets the Unity editor serializes, but which are
+/// not stored as text, end up as `AssetDependencies::Unscanned`.
+pub(crate) fn scan_dependencies<R: BufRead>(
+    reader: R,
+) -> Result<DependencyGraph, UnityPackageReaderError> {
+    let corrupt = |e: io::Error| {
+        UnityPackageReaderError::CorruptPackage(ErrorInformation::new(
+            Some(format!("{}", e)),
+            file!(),
+            line!(),
+        ))
+    };
+
+    let mut archive = Archive::new(package_format::open(reader)?);
+    let mut pending: HashMap<Guid, PendingAsset> = HashMap::new();
+    for entry in archive.entries().map_err(corrupt)? {
+        let mut entry = entry.map_err(corrupt)?;
+        let path = entry.path().map_err(corrupt)?.into_owned();
+
+        let mut components = path.components().filter_map(|c| match c {
+            Component::Normal(n) => Some(n.to_string_lossy().into_owned()),
+            _ => None,
+        });
+        let (guid, name) = match (components.next(), components.next(), components.next()) {
+            (Some(g), Some(n), None) => match g.parse::<Guid>() {
+                Ok(g) => (g, n),
+                Err(_) => continue,
+            },
+            _ => continue,
+        };
+
+        match name.as_str() {
+            "asset" => {
+                let references = scan_payload(&mut entry).map_err(corrupt)?;
+                pending.entry(guid).or_default().references = Some(references);
+            }
+            "pathname" => {
+                let content = unpack_limits::read_text(&mut entry).map_err(corrupt)?;
+                let pathname = crate::unity_asset_file::trim_pathname(&content);
+                pending.entry(guid).or_default().pathname = Some(String::from(pathname));
+            }
+            _ => {}
+        }
+    }
+
+    let assets = pending
+        .into_iter()
+        .filter_map(|(guid, asset)| {
+            let pathname = asset.pathname.filter(|p| !p.is_empty())?;
+            let dependencies = match asset.references {
+                Some(Some(mut references)) => {
+                    references.remove(&guid);
+                    AssetDependencies::Scanned(references)
+                }
+                Some(None) if guid_remap::is_unity_serialized(Path::new(&pathname)) => {
+                    AssetDependencies::Unscanned
+                }
+                _ => AssetDependencies::Scanned(BTreeSet::new()),
+            };
+
+            Some((guid, dependencies))
+        })
+        .collect();
+
+    Ok(DependencyGraph { assets })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scanner_across_chunks() {
+        let texture: Guid = "1af567ac160bb164fb19b8cb9b55b34b".parse().unwrap();
+        let script: Guid = "9d8c7b6a5f4e3d2c1b0a9f8e7d6c5b4a".parse().unwrap();
+        let content = format!(
+            "%YAML 1.1\n  m_Texture: {{fileID: 2800000, guid: {}, type: 3}}\nm_guid: {}\nguid: {}0\nguid: {}",
+            texture, script, script, script
+        );
+
+        for size in [1, 2, 7, 64, content.len()] {
+            let mut scanner = GuidScanner::default();
+            for chunk in content.as_bytes().chunks(size) {
+                scanner.feed(chunk);
+            }
+            assert_eq!(
+                scanner.finish(),
+                BTreeSet::from([texture, script]),
+                "chunk size {}",
+                size
+            );
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::path::{Path, PathBuf};
+
+use crate::unpacker_error::UnityPackageReaderError;
+
+/// Added to the uncompressed size for the directories and the metadata of
+/// the file system, in percent.
+const SAFETY_MARGIN_PERCENT: u64 = 10;
+
+/// Fail with `InsufficientDiskSpace` if the volumes of tmp and target (None
+/// in analyze-only mode) cannot hold size bytes each. A volume holding both
+/// only has to hold them once, the assets are renamed from tmp into the
+/// target there. Volumes whose free space available cannot tell are not
+/// checked.
+pub(crate) fn check_disk_space(
+    size: u64,
+    tmp: &Path,
+    target: Option<&Path>,
+    available: impl Fn(&Path) -> Option<u64>,
+    volume: impl Fn(&Path) -> Option<u64>,
+) -> Result<(), UnityPackageReaderError> {
+    let required = size.saturating_add(size / 100 * SAFETY_MARGIN_PERCENT);
+    let mut needs: Vec<(PathBuf, u64)> = vec![(existing_ancestor(tmp), required)];
+    if let Some(target) = target {
+        let target = existing_ancestor(target);
+        let shared = volume(&needs[0].0).is_some() && volume(&needs[0].0) == volume(&target);
+        if !shared {
+            needs.push((target, required));
+        }
+    }
+
+    for (path, required) in needs {
+        match available(&path) {
+            Some(available) if available < required => {
+                return Err(UnityPackageReaderError::InsufficientDiskSpace {
+                    path,
+                    required,
+                    available,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// The first directory of path (or path itself) that exists, since tmp and
+/// target are usually created by the unpack.
+fn existing_ancestor(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find(|p| !p.as_os_str().is_empty() && p.exists())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// The bytes an unprivileged user can still write to the volume of path.
+#[cfg(unix)]
+pub(crate) fn available_space(path: &Path) -> Option<u64> {
+    use std::{ffi::CString, mem::MaybeUninit, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: path is a valid C string and stat is only read if statvfs succeeded.
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+
+    // The field types differ between the unix flavours.
+    #[allow(clippy::unnecessary_cast)]
+    Some((stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64))
+}
+
+/// The bytes the current user can still write to the volume of path.
+#[cfg(windows)]
+pub(crate) fn available_space(path: &Path) -> Option<u64> {
+    let path = wide(path);
+    let mut available = 0u64;
+    // SAFETY: path is nul terminated and available outlives the call, the
+    // other counters are optional.
+    let ok = unsafe {
+        windows::GetDiskFreeSpaceExW(
+            path.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+
+    (ok != 0).then_some(available)
+}
+
+/// Not known on this platform, the check is skipped.
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Identifies the volume of path, to tell whether tmp and target share one.
+#[cfg(unix)]
+pub(crate) fn volume_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    std::fs::metadata(path).ok().map(|m| m.dev())
+}
+
+/// Identifies the volume of path by a hash of its mount point (`C:\\` or a
+/// mounted folder).
+#[cfg(windows)]
+pub(crate) fn volume_id(path: &Path) -> Option<u64> {
+    use std::hash::{Hash, Hasher};
+
+    let path = wide(path);
+    let mut volume = [0u16; 261];
+    // SAFETY: path is nul terminated and volume holds as many characters as
+    // passed.
+    let ok = unsafe {
+        windows::GetVolumePathNameW(path.as_ptr(), volume.as_mut_ptr(), volume.len() as u32)
+    };
+    if ok == 0 {
+        return None;
+    }
+
+    let len = volume.iter().position(|c| *c == 0).unwrap_or(volume.len());
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    String::from_utf16_lossy(&volume[..len])
+        .to_lowercase()
+        .hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn volume_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// path as a nul terminated UTF-16 string.
+#[cfg(windows)]
+fn wide(path: &Path) -> Vec<u16> {
+    use std::os::windows::ffi::OsStrExt;
+
+    path.as_os_str().encode_wide().chain(Some(0)).collect()
+}
+
+#[cfg(windows)]
+mod windows {
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub(super) fn GetDiskFreeSpaceExW(
+            directory: *const u16,
+            available_to_caller: *mut u64,
+            total: *mut u64,
+            free: *mut u64,
+        ) -> i32;
+        pub(super) fn GetVolumePathNameW(
+            file_name: *const u16,
+            volume_path_name: *mut u16,
+            buffer_length: u32,
+        ) -> i32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_disk_space() {
+        let dir = tempfile::tempdir().unwrap();
+        let tmp = dir.path().join("tmp");
+        let target = dir.path().join("target/Assets");
+        std::fs::create_dir(dir.path().join("target")).unwrap();
+        let check = |available: u64, same_volume: bool| {
+            check_disk_space(
+                1000,
+                &tmp,
+                Some(&target),
+                |_| Some(available),
+                |p| Some(if same_volume || p == dir.path() { 1 } else { 2 }),
+            )
+        };
+
+        check(1100, false).unwrap();
+        assert!(check(1099, false).is_err());
+        check(1100, true).unwrap();
+        match check(1099, true) {
+            Err(UnityPackageReaderError::InsufficientDiskSpace {
+                path,
+                required,
+                available,
+            }) => assert_eq!(
+                (path.as_path(), required, available),
+                (dir.path(), 1100, 1099)
+            ),
+            other => panic!("{:?}", other),
+        }
+        // A volume that does not tell its free space is not checked.
+        check_disk_space(u64::MAX, &tmp, None, |_| None, |_| None).unwrap();
+        if cfg!(any(unix, windows)) {
+            assert!(available_space(dir.path()).is_some());
+            assert_eq!(volume_id(dir.path()), volume_id(&dir.path().join("target")));
+        }
+    }
+}
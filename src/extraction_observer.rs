@@ -0,0 +1,61 @@
+use std::sync::{Arc, Mutex};
+
+use crate::prelude::{Shared, UnityAssetFile, UnityPackageReaderError};
+
+/// Why an asset has not been written into the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Left out by the filters of the configuration.
+    Filtered,
+    /// The target has the file already (`OverwritePolicy::Skip`).
+    AlreadyExists,
+    /// Additive mode: the target has the guid already.
+    AlreadyPresent,
+    /// Incremental mode or `OverwritePolicy::IfChanged`: the target has the
+    /// same content already.
+    Unchanged,
+    /// Installed by the interrupted unpack that is resumed.
+    Resumed,
+    /// Only indexed: analyze-only mode or a folder that is not created.
+    NotInstalled,
+}
+
+/// How the installation of a single asset ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileStatus {
+    /// Written into the target, possibly over an existing file.
+    Extracted,
+    Skipped(SkipReason),
+    Failed(UnityPackageReaderError),
+}
+
+/// Gets told about every asset while it is installed, e.g. for telemetry.
+/// A panic in a hook aborts the unpack like any other panic, the tmp
+/// directory is removed all the same.
+pub trait ExtractionObserver: Send {
+    /// Called before the asset is installed.
+    fn on_start(&mut self, _asset: &UnityAssetFile) {}
+    /// Called once the asset has been installed, skipped or failed.
+    fn on_finished(&mut self, _asset: &UnityAssetFile, _status: &FileStatus) {}
+}
+
+/// An observer that can be put into `UnpackOptions::observer`. The caller
+/// keeps its own handle to read the observer afterwards.
+pub type SharedObserver = Shared<Mutex<dyn ExtractionObserver>>;
+
+impl SharedObserver {
+    pub fn new(observer: Arc<Mutex<dyn ExtractionObserver>>) -> Self {
+        Shared::from_arc(observer)
+    }
+
+    pub(crate) fn on_start(&self, asset: &UnityAssetFile) {
+        // A hook that panicked before has failed that unpack already.
+        let mut observer = self.lock().unwrap_or_else(|e| e.into_inner());
+        observer.on_start(asset);
+    }
+
+    pub(crate) fn on_finished(&self, asset: &UnityAssetFile, status: &FileStatus) {
+        let mut observer = self.lock().unwrap_or_else(|e| e.into_inner());
+        observer.on_finished(asset, status);
+    }
+}
@@ -0,0 +1,187 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::{guid::Guid, unpacker_error::UnityPackageReaderError, warning::Warning};
+
+/// What an unpack did with a single asset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum AssetStatus {
+    /// Written to a location of the target that did not exist before.
+    Extracted,
+    /// Written over an existing file (`OverwritePolicy::Overwrite`).
+    Overwritten,
+    /// Not written, because the target has it already (`OverwritePolicy::Skip`
+    /// or additive mode).
+    Skipped,
+    /// Left out by `allowed_roots`, the Unity ignore rules, the category
+    /// filters, `skip_unprefixed`, `drop_unmapped` or the path rewrite.
+    Filtered,
+    /// Not written, because an incremental unpack has installed the same
+    /// content at the same path before (`UnpackConfig::incremental`), or the
+    /// target has identical files (`OverwritePolicy::IfChanged`).
+    Unchanged,
+    /// Could not be indexed or installed (`UnpackConfig::continue_on_error`),
+    /// see `ExtractionReport::failures`.
+    Failed,
+    /// Only indexed: analyze-only mode, or a folder with `create_folders` off
+    /// or `flatten` set.
+    NotInstalled,
+}
+
+/// The kind of an archive entry that is neither a file nor a directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SpecialEntryKind {
+    Symlink,
+    HardLink,
+    Device,
+    Fifo,
+}
+
+/// An archive entry that has not been extracted (see `UnpackConfig::special_entries`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SkippedEntry {
+    /// The path of the entry inside the archive.
+    pub path: PathBuf,
+    pub kind: SpecialEntryKind,
+    /// Where the entry points to, for links.
+    pub link: Option<PathBuf>,
+}
+
+/// How a guid directory the archive contains more than once has been
+/// resolved (see `UnpackConfig::duplicate_guids`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum DuplicateResolution {
+    /// The copies do not differ, so they have been collapsed.
+    Identical,
+    KeptFirst,
+    KeptLast,
+}
+
+/// A guid directory the archive contains more than once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DuplicateGuid {
+    pub guid: Guid,
+    pub resolution: DuplicateResolution,
+}
+
+/// An asset that could not be indexed or installed, see
+/// `UnpackConfig::continue_on_error`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AssetFailure {
+    /// The guid, all zeros if the directory inside the archive is no guid.
+    pub guid: Guid,
+    /// The pathname, or the directory inside tmp if that cannot be read.
+    pub path: PathBuf,
+    pub error: UnityPackageReaderError,
+}
+
+/// A single asset of an `ExtractionReport`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AssetReport {
+    pub guid: Guid,
+    /// Path of the asset relative to the target directory.
+    pub relative_path: PathBuf,
+    /// The pathname inside the package, which differs from relative_path if
+    /// the asset has been renamed or flattened.
+    pub package_path: PathBuf,
+    /// Where the asset ends up (or would have ended up) in the target.
+    pub absolute_path: PathBuf,
+    pub status: AssetStatus,
+    /// The index of the `UnpackConfig::root_remap` rule that moved the asset.
+    pub root_rule: Option<usize>,
+    /// The sha256 of the payload (lower case hex), if `UnpackConfig::hash_assets`
+    /// is set. None for folders and filtered assets.
+    pub sha256: Option<String>,
+    /// The copies of the overwritten files, see `UnpackConfig::backup`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub backups: Vec<PathBuf>,
+}
+
+/// Everything an unpack did, see `UnityPackage::unpack_with_report`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtractionReport {
+    /// The number of assets with `AssetStatus::Extracted`.
+    pub extracted: usize,
+    /// The number of assets with `AssetStatus::Skipped`.
+    pub skipped: usize,
+    /// The number of assets with `AssetStatus::Overwritten`.
+    pub overwritten: usize,
+    /// The number of assets with `AssetStatus::Unchanged`.
+    pub unchanged: usize,
+    /// The number of assets with `AssetStatus::Failed`.
+    pub failed: usize,
+    /// Bytes moved or copied into the target directory.
+    pub bytes_written: u64,
+    /// The absolute target directory.
+    pub target_root: PathBuf,
+    /// The directory the package has been extracted into.
+    pub tmp_dir: PathBuf,
+    /// The time the whole unpack took.
+    pub elapsed: Duration,
+    /// Every asset of the package, sorted by relative path and guid.
+    pub assets: Vec<AssetReport>,
+    /// The links and special files of the archive that have been left out.
+    pub skipped_entries: Vec<SkippedEntry>,
+    /// Why the assets with `AssetStatus::Failed` failed, sorted like assets.
+    pub failures: Vec<AssetFailure>,
+    /// What did not stop the unpack but is worth a look.
+    pub warnings: Vec<Warning>,
+    /// The guid directories the archive contains more than once.
+    pub duplicate_guids: Vec<DuplicateGuid>,
+}
+
+impl ExtractionReport {
+    pub(crate) fn new(
+        target_root: PathBuf,
+        tmp_dir: PathBuf,
+        bytes_written: u64,
+        elapsed: Duration,
+        mut assets: Vec<AssetReport>,
+        skipped_entries: Vec<SkippedEntry>,
+        mut failures: Vec<AssetFailure>,
+    ) -> Self {
+        assets.sort_by(|a, b| {
+            a.relative_path
+                .cmp(&b.relative_path)
+                .then_with(|| a.guid.cmp(&b.guid))
+        });
+        failures.sort_by(|a, b| a.path.cmp(&b.path).then_with(|| a.guid.cmp(&b.guid)));
+        let count = |status: AssetStatus| assets.iter().filter(|a| a.status == status).count();
+
+        ExtractionReport {
+            extracted: count(AssetStatus::Extracted),
+            skipped: count(AssetStatus::Skipped),
+            overwritten: count(AssetStatus::Overwritten),
+            unchanged: count(AssetStatus::Unchanged),
+            failed: count(AssetStatus::Failed),
+            bytes_written,
+            target_root,
+            tmp_dir,
+            elapsed,
+            assets,
+            skipped_entries,
+            failures,
+            warnings: Vec::new(),
+            duplicate_guids: Vec::new(),
+        }
+    }
+}
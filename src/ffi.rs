@@ -0,0 +1,256 @@
+//! A C interface for tooling that is not written in Rust. The header is
+//! `include/unity_unpacker.h`, regenerate it with `cbindgen --config
+//! cbindgen.toml --output include/unity_unpacker.h` after changing this file.
+//!
+//! All strings are UTF-8 and null terminated. Strings passed in are only read
+//! during the call. Strings handed out belong to the handle and stay valid
+//! until the next call with the same handle or until it is freed. Panics are
+//! caught and never unwind into the caller.
+use std::{
+    ffi::{c_char, c_int, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    ptr,
+};
+
+use crate::prelude::UnityPackage;
+
+/// The call succeeded.
+pub const UNITY_PACKAGE_OK: c_int = 0;
+/// The call failed, `unity_package_last_error` tells why.
+pub const UNITY_PACKAGE_ERROR: c_int = 1;
+/// The handle is null.
+pub const UNITY_PACKAGE_INVALID_HANDLE: c_int = 2;
+
+/// A package opened by `unity_package_open`. Opaque to C.
+pub struct UnityPackageHandle {
+    package: UnityPackage,
+    last_error: Option<CString>,
+    /// The guids of the files of the last unpack, in index order.
+    guids: Vec<CString>,
+}
+
+impl UnityPackageHandle {
+    fn set_error(&mut self, message: String) {
+        self.last_error = Some(to_c_string(message));
+    }
+}
+
+fn to_c_string(s: String) -> CString {
+    CString::new(s.replace('\0', " ")).unwrap_or_default()
+}
+
+/// None for a null pointer or a string that is no valid UTF-8.
+unsafe fn optional_path(s: *const c_char) -> Result<Option<PathBuf>, ()> {
+    if s.is_null() {
+        return Ok(None);
+    }
+
+    match CStr::from_ptr(s).to_str() {
+        Ok(s) => Ok(Some(PathBuf::from(s))),
+        Err(_) => Err(()),
+    }
+}
+
+/// Open the package at path. target and tmp may be null, the library picks
+/// its defaults then. Returns null if path is null, a string is no valid
+/// UTF-8 or the file is not a package. The handle has to be released with
+/// `unity_package_free`.
+///
+/// # Safety
+/// Every non-null argument has to point to a null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn unity_package_open(
+    path: *const c_char,
+    target: *const c_char,
+    tmp: *const c_char,
+) -> *mut UnityPackageHandle {
+    let opened = panic::catch_unwind(|| {
+        let path = match optional_path(path) {
+            Ok(Some(p)) => p,
+            _ => return None,
+        };
+        let (target, tmp) = match (optional_path(target), optional_path(tmp)) {
+            (Ok(target), Ok(tmp)) => (target, tmp),
+            _ => return None,
+        };
+
+        UnityPackage::open(path, target, tmp).ok()
+    });
+
+    match opened {
+        Ok(Some(package)) => Box::into_raw(Box::new(UnityPackageHandle {
+            package,
+            last_error: None,
+            guids: Vec::new(),
+        })),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Unpack the package into its target. Returns `UNITY_PACKAGE_OK` on
+/// success, otherwise `unity_package_last_error` has the reason.
+///
+/// # Safety
+/// handle has to be null or a handle returned by `unity_package_open` that has
+/// not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn unity_package_unpack(
+    handle: *mut UnityPackageHandle,
+    delete_tmp: bool,
+) -> c_int {
+    let handle = match handle.as_mut() {
+        Some(h) => h,
+        None => return UNITY_PACKAGE_INVALID_HANDLE,
+    };
+    handle.last_error = None;
+
+    let unpacked = panic::catch_unwind(AssertUnwindSafe(|| {
+        handle.package.unpack_package(delete_tmp)
+    }));
+
+    handle.guids = handle
+        .package
+        .files()
+        .map(|f| to_c_string(f.get_guid().to_string()))
+        .collect();
+
+    match unpacked {
+        Ok(Ok(_)) => UNITY_PACKAGE_OK,
+        Ok(Err(e)) => {
+            handle.set_error(format!("{}", e));
+            UNITY_PACKAGE_ERROR
+        }
+        Err(_) => {
+            handle.set_error(String::from("The unpack panicked"));
+            UNITY_PACKAGE_ERROR
+        }
+    }
+}
+
+/// The error of the last failed call, null if it succeeded. Owned by the
+/// handle.
+///
+/// # Safety
+/// handle has to be null or a handle returned by `unity_package_open` that has
+/// not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn unity_package_last_error(
+    handle: *const UnityPackageHandle,
+) -> *const c_char {
+    match handle.as_ref().and_then(|h| h.last_error.as_ref()) {
+        Some(e) => e.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// The number of files the last unpack indexed, 0 before the first unpack.
+///
+/// # Safety
+/// handle has to be null or a handle returned by `unity_package_open` that has
+/// not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn unity_package_file_count(handle: *const UnityPackageHandle) -> usize {
+    match handle.as_ref() {
+        Some(h) => h.guids.len(),
+        None => 0,
+    }
+}
+
+/// The guid (32 hex digits) of the file at index, null if index is out of
+/// range. Owned by the handle, valid until the next unpack.
+///
+/// # Safety
+/// handle has to be null or a handle returned by `unity_package_open` that has
+/// not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn unity_package_get_guid(
+    handle: *const UnityPackageHandle,
+    index: usize,
+) -> *const c_char {
+    match handle.as_ref().and_then(|h| h.guids.get(index)) {
+        Some(g) => g.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Release a handle and every string it handed out. Null is ignored.
+///
+/// # Safety
+/// handle has to be null or a handle returned by `unity_package_open` that has
+/// not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn unity_package_free(handle: *mut UnityPackageHandle) {
+    if !handle.is_null() {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(handle))));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn c(path: &std::path::Path) -> CString {
+        CString::new(path.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_ffi_unpack() {
+        let fixture = Fixture::sample();
+        let (path, target, tmp) = (c(&fixture.package), c(&fixture.target), c(&fixture.tmp));
+
+        unsafe {
+            let handle = unity_package_open(path.as_ptr(), target.as_ptr(), tmp.as_ptr());
+            assert!(!handle.is_null());
+            assert_eq!(unity_package_file_count(handle), 0);
+
+            assert_eq!(unity_package_unpack(handle, true), UNITY_PACKAGE_OK);
+            assert!(unity_package_last_error(handle).is_null());
+            assert_eq!(unity_package_file_count(handle), 3);
+
+            let guids: Vec<String> = (0..3)
+                .map(|i| {
+                    let guid = unity_package_get_guid(handle, i);
+                    CStr::from_ptr(guid).to_str().unwrap().to_string()
+                })
+                .collect();
+            assert!(guids.iter().any(|g| g == TEXTURE_GUID));
+            assert!(unity_package_get_guid(handle, 3).is_null());
+
+            // The target has the files now, so a strict second unpack fails.
+            (*handle)
+                .package
+                .set_overwrite_policy(crate::prelude::OverwritePolicy::Error);
+            assert_eq!(unity_package_unpack(handle, true), UNITY_PACKAGE_ERROR);
+            let error = CStr::from_ptr(unity_package_last_error(handle));
+            assert!(error.to_str().unwrap().contains("already exists"));
+
+            unity_package_free(handle);
+        }
+        assert!(fixture.target.join(TEXTURE_PATH).is_file());
+    }
+
+    #[test]
+    fn test_ffi_invalid_arguments() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = c(&dir.path().join("missing.unitypackage"));
+
+        unsafe {
+            assert!(unity_package_open(ptr::null(), ptr::null(), ptr::null()).is_null());
+            assert!(unity_package_open(missing.as_ptr(), ptr::null(), ptr::null()).is_null());
+
+            let invalid = [0xffu8 as c_char, 0];
+            assert!(unity_package_open(invalid.as_ptr(), ptr::null(), ptr::null()).is_null());
+
+            assert_eq!(
+                unity_package_unpack(ptr::null_mut(), true),
+                UNITY_PACKAGE_INVALID_HANDLE
+            );
+            assert!(unity_package_last_error(ptr::null()).is_null());
+            assert_eq!(unity_package_file_count(ptr::null()), 0);
+            assert!(unity_package_get_guid(ptr::null(), 0).is_null());
+            unity_package_free(ptr::null_mut());
+        }
+    }
+}
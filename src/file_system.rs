@@ -0,0 +1,419 @@
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    checksums,
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// Fails with `UnsupportedOnThisTarget` on targets without a file system
+/// (wasm32), for operations that read or write files on the disk.
+pub(crate) fn require_file_system(operation: &str) -> Result<(), UnityPackageReaderError> {
+    if cfg!(target_arch = "wasm32") {
+        return Err(UnityPackageReaderError::UnsupportedOnThisTarget(
+            ErrorInformation::new(Some(String::from(operation)), file!(), line!()),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The file operations used to prepare, index and clean up the tmp
+/// directory and to install the assets into the target, see
+/// `UnityPackage::with_file_system`. Writing the extracted entries and
+/// rewriting guids always work on the disk.
+pub trait FileSystem: Send + Sync {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    /// Copy a file and return the number of bytes copied.
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64>;
+    /// `copy` through a buffer of buffer_size bytes, see `BufferSizes::copy`.
+    /// Ignores the size by default.
+    fn copy_with_buffer(&self, from: &Path, to: &Path, buffer_size: usize) -> io::Result<u64> {
+        let _ = buffer_size;
+        self.copy(from, to)
+    }
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// The paths of the entries of a directory. Reading a single entry can
+    /// fail without failing the whole listing.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<io::Result<PathBuf>>>;
+    /// The size of a file, None if path is no file.
+    fn file_size(&self, path: &Path) -> Option<u64>;
+    fn exists(&self, path: &Path) -> bool;
+    /// True, if path is a directory. Tries to list it by default.
+    fn is_dir(&self, path: &Path) -> bool {
+        self.read_dir(path).is_ok()
+    }
+    /// True, if path itself is a symbolic link. False by default.
+    fn is_symlink(&self, path: &Path) -> bool {
+        let _ = path;
+        false
+    }
+    /// True, if the file at path cannot be written, see
+    /// `UnpackConfig::clear_readonly`. False by default.
+    fn is_readonly(&self, path: &Path) -> bool {
+        let _ = path;
+        false
+    }
+    /// Set or clear the read-only flag of a file. Does nothing by default.
+    fn set_readonly(&self, path: &Path, readonly: bool) -> io::Result<()> {
+        let _ = (path, readonly);
+        Ok(())
+    }
+    /// The sha256 of a file, see `OverwritePolicy::IfChanged`. Reads the
+    /// whole file by default.
+    fn sha256(&self, path: &Path) -> io::Result<[u8; 32]> {
+        Ok(Sha256::digest(self.read(path)?).into())
+    }
+    /// The first len bytes of a file (fewer if it is shorter), e.g. to check
+    /// a signature. Reads the whole file by default.
+    fn read_prefix(&self, path: &Path, len: usize) -> io::Result<Vec<u8>> {
+        let mut content = self.read(path)?;
+        content.truncate(len);
+        Ok(content)
+    }
+}
+
+/// `FileSystem` on top of `std::fs`, the default of every package.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OsFileSystem;
+
+impl FileSystem for OsFileSystem {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        fs::copy(from, to)
+    }
+
+    /// Copies the content and the permissions, like `fs::copy`.
+    fn copy_with_buffer(&self, from: &Path, to: &Path, buffer_size: usize) -> io::Result<u64> {
+        let mut reader = fs::File::open(from)?;
+        let permissions = reader.metadata()?.permissions();
+        let mut writer = fs::File::create(to)?;
+        let mut buffer = vec![0u8; buffer_size.max(1)];
+        let mut copied = 0;
+        loop {
+            let read = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            writer.write_all(&buffer[..read])?;
+            copied += read as u64;
+        }
+
+        fs::set_permissions(to, permissions)?;
+        Ok(copied)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir_all(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<io::Result<PathBuf>>> {
+        Ok(fs::read_dir(path)?.map(|e| e.map(|e| e.path())).collect())
+    }
+
+    fn file_size(&self, path: &Path) -> Option<u64> {
+        fs::metadata(path)
+            .ok()
+            .filter(|m| m.is_file())
+            .map(|m| m.len())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink())
+    }
+
+    fn is_readonly(&self, path: &Path) -> bool {
+        fs::symlink_metadata(path).is_ok_and(|m| m.is_file() && m.permissions().readonly())
+    }
+
+    /// On unix, readonly removes all write bits and clearing it restores the
+    /// one of the owner.
+    fn set_readonly(&self, path: &Path, readonly: bool) -> io::Result<()> {
+        let mut permissions = fs::metadata(path)?.permissions();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = permissions.mode();
+            permissions.set_mode(if readonly {
+                mode & !0o222
+            } else {
+                mode | 0o200
+            });
+        }
+        #[cfg(not(unix))]
+        permissions.set_readonly(readonly);
+        fs::set_permissions(path, permissions)
+    }
+
+    fn sha256(&self, path: &Path) -> io::Result<[u8; 32]> {
+        checksums::sha256_file(path)
+    }
+
+    fn read_prefix(&self, path: &Path, len: usize) -> io::Result<Vec<u8>> {
+        let mut content = Vec::with_capacity(len);
+        fs::File::open(path)?
+            .take(len as u64)
+            .read_to_end(&mut content)?;
+        Ok(content)
+    }
+}
+
+#[cfg(test)]
+pub(crate) use memory::MemoryFileSystem;
+
+#[cfg(test)]
+mod memory {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        io,
+        path::{Path, PathBuf},
+        sync::Mutex,
+    };
+
+    use super::FileSystem;
+
+    /// A `FileSystem` that lives in memory. Every operation can be made to
+    /// fail for a path with `fail`. Read-only files behave like on Windows:
+    /// they cannot be replaced or removed.
+    #[derive(Default)]
+    pub(crate) struct MemoryFileSystem {
+        files: Mutex<BTreeMap<PathBuf, Vec<u8>>>,
+        dirs: Mutex<BTreeSet<PathBuf>>,
+        /// The operation (the method name), the path and the error to return.
+        failures: Mutex<Vec<(&'static str, PathBuf, io::ErrorKind)>>,
+        /// The buffer size of every `copy_with_buffer` call.
+        copy_buffers: Mutex<Vec<usize>>,
+        readonly: Mutex<BTreeSet<PathBuf>>,
+    }
+
+    impl MemoryFileSystem {
+        /// Add a file, together with its parent directories.
+        pub(crate) fn write(&self, path: impl AsRef<Path>, content: impl AsRef<[u8]>) {
+            let path = path.as_ref();
+            if let Some(parent) = path.parent() {
+                self.create_dir_all(parent).unwrap();
+            }
+            self.files
+                .lock()
+                .unwrap()
+                .insert(path.to_path_buf(), content.as_ref().to_vec());
+        }
+
+        pub(crate) fn read(&self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+            self.files.lock().unwrap().get(path.as_ref()).cloned()
+        }
+
+        /// Make operation fail with kind for path. `read_dir_entry` makes a
+        /// single entry of the listing of path fail.
+        pub(crate) fn fail(
+            &self,
+            operation: &'static str,
+            path: impl AsRef<Path>,
+            kind: io::ErrorKind,
+        ) {
+            self.failures
+                .lock()
+                .unwrap()
+                .push((operation, path.as_ref().to_path_buf(), kind));
+        }
+
+        pub(crate) fn copy_buffers(&self) -> Vec<usize> {
+            self.copy_buffers.lock().unwrap().clone()
+        }
+
+        fn check(&self, operation: &str, path: &Path) -> io::Result<()> {
+            let failures = self.failures.lock().unwrap();
+            match failures
+                .iter()
+                .find(|(o, p, _)| *o == operation && p == path)
+            {
+                Some((_, _, kind)) => Err(io::Error::from(*kind)),
+                None => Ok(()),
+            }
+        }
+
+        fn check_writable(&self, path: &Path) -> io::Result<()> {
+            if self.readonly.lock().unwrap().contains(path) {
+                return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+            }
+            Ok(())
+        }
+
+        fn check_parent(&self, path: &Path) -> io::Result<()> {
+            match path.parent() {
+                Some(p) if !self.is_dir(p) => Err(io::Error::from(io::ErrorKind::NotFound)),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    impl FileSystem for MemoryFileSystem {
+        fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+            self.check("read", path)?;
+            MemoryFileSystem::read(self, path)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        }
+
+        fn read_to_string(&self, path: &Path) -> io::Result<String> {
+            self.check("read_to_string", path)?;
+            match MemoryFileSystem::read(self, path) {
+                Some(c) => {
+                    String::from_utf8(c).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+                }
+                None => Err(io::Error::from(io::ErrorKind::NotFound)),
+            }
+        }
+
+        fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+            self.check("create_dir_all", path)?;
+            let mut dirs = self.dirs.lock().unwrap();
+            for ancestor in path.ancestors().filter(|a| a.parent().is_some()) {
+                dirs.insert(ancestor.to_path_buf());
+            }
+            Ok(())
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            self.check("rename", from)?;
+            self.check_parent(to)?;
+            self.check_writable(to)?;
+            let mut files = self.files.lock().unwrap();
+            match files.remove(from) {
+                Some(c) => {
+                    files.insert(to.to_path_buf(), c);
+                    let mut readonly = self.readonly.lock().unwrap();
+                    if readonly.remove(from) {
+                        readonly.insert(to.to_path_buf());
+                    }
+                    Ok(())
+                }
+                None => Err(io::Error::from(io::ErrorKind::NotFound)),
+            }
+        }
+
+        fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+            self.check("copy", from)?;
+            self.check_parent(to)?;
+            self.check_writable(to)?;
+            let content = self
+                .read(from)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+            let size = content.len() as u64;
+            self.files.lock().unwrap().insert(to.to_path_buf(), content);
+            Ok(size)
+        }
+
+        fn copy_with_buffer(&self, from: &Path, to: &Path, buffer_size: usize) -> io::Result<u64> {
+            self.copy_buffers.lock().unwrap().push(buffer_size);
+            self.copy(from, to)
+        }
+
+        fn remove_file(&self, path: &Path) -> io::Result<()> {
+            self.check("remove_file", path)?;
+            self.check_writable(path)?;
+            match self.files.lock().unwrap().remove(path) {
+                Some(_) => Ok(()),
+                None => Err(io::Error::from(io::ErrorKind::NotFound)),
+            }
+        }
+
+        fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+            self.check("remove_dir_all", path)?;
+            self.files
+                .lock()
+                .unwrap()
+                .retain(|p, _| !p.starts_with(path));
+            self.dirs.lock().unwrap().retain(|p| !p.starts_with(path));
+            Ok(())
+        }
+
+        fn read_dir(&self, path: &Path) -> io::Result<Vec<io::Result<PathBuf>>> {
+            self.check("read_dir", path)?;
+            if !self.is_dir(path) {
+                return Err(io::Error::from(io::ErrorKind::NotFound));
+            }
+
+            let files = self.files.lock().unwrap();
+            let dirs = self.dirs.lock().unwrap();
+            let children: BTreeSet<&PathBuf> = files
+                .keys()
+                .chain(dirs.iter())
+                .filter(|p| p.parent() == Some(path))
+                .collect();
+
+            let mut entries: Vec<io::Result<PathBuf>> =
+                children.into_iter().map(|p| Ok(p.clone())).collect();
+            if let Err(e) = self.check("read_dir_entry", path) {
+                entries.push(Err(e));
+            }
+            Ok(entries)
+        }
+
+        fn file_size(&self, path: &Path) -> Option<u64> {
+            self.read(path).map(|c| c.len() as u64)
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            self.is_dir(path) || self.files.lock().unwrap().contains_key(path)
+        }
+
+        fn is_dir(&self, path: &Path) -> bool {
+            path.parent().is_none() || self.dirs.lock().unwrap().contains(path)
+        }
+
+        fn is_readonly(&self, path: &Path) -> bool {
+            self.readonly.lock().unwrap().contains(path)
+        }
+
+        fn set_readonly(&self, path: &Path, readonly: bool) -> io::Result<()> {
+            self.check("set_readonly", path)?;
+            if !self.files.lock().unwrap().contains_key(path) {
+                return Err(io::Error::from(io::ErrorKind::NotFound));
+            }
+            let mut set = self.readonly.lock().unwrap();
+            if readonly {
+                set.insert(path.to_path_buf());
+            } else {
+                set.remove(path);
+            }
+            Ok(())
+        }
+    }
+}
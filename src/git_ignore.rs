@@ -0,0 +1,255 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    incremental,
+    prelude::{Backup, UnpackConfig},
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+const BLOCK_START: &str = "# >>> unity_unpacker_lib (generated, do not edit)";
+const BLOCK_END: &str = "# <<< unity_unpacker_lib";
+
+/// A change made to a `.gitignore` file, so it can be reverted later.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitIgnoreEdit {
+    /// The .gitignore file that has been written.
+    pub path: PathBuf,
+    /// True, if the file did not exist before.
+    pub created: bool,
+}
+
+impl GitIgnoreEdit {
+    /// Remove the block written by this crate. A file that has been created by
+    /// this crate and contains nothing else afterwards is deleted.
+    pub fn revert(&self) -> Result<(), UnityPackageReaderError> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(error(&self.path, e)),
+        };
+
+        let remaining = remove_block(&content);
+        let result = if self.created && remaining.trim().is_empty() {
+            fs::remove_file(&self.path)
+        } else {
+            fs::write(&self.path, remaining)
+        };
+
+        result.map_err(|e| error(&self.path, e))
+    }
+}
+
+fn error(path: &Path, e: std::io::Error) -> UnityPackageReaderError {
+    UnityPackageReaderError::CouldNotUpdateGitIgnore(ErrorInformation::new(
+        Some(format!("{:?}: {}", path, e)),
+        file!(),
+        line!(),
+    ))
+}
+
+/// The closest directory (starting with the path itself) containing a `.git`
+/// directory or file. The path itself does not need to exist yet.
+pub(crate) fn find_git_root(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|p| p.join(".git").exists())
+        .map(|p| p.to_path_buf())
+}
+
+/// The patterns (relative to target) for the auxiliary outputs of an unpack
+/// the configuration leaves in the target: the tmp directory (None once it
+/// has been deleted), the manifest of incremental mode and the backup
+/// directory. Only paths the crate writes itself are listed, no assets.
+pub(crate) fn auxiliary_patterns(
+    target: &Path,
+    tmp: Option<&Path>,
+    config: &UnpackConfig,
+) -> Vec<String> {
+    let mut result = Vec::new();
+    if let Some(pattern) = tmp.and_then(|t| directory_pattern(target, t)) {
+        result.push(pattern);
+    }
+    if config.incremental && config.install {
+        result.push(format!("/{}", incremental::MANIFEST_FILE));
+    }
+    if let Some(Backup::Directory(directory)) = &config.backup {
+        if let Some(pattern) = directory_pattern(target, directory) {
+            result.push(pattern);
+        }
+    }
+
+    result
+}
+
+/// The pattern for directory, None if it is not inside target.
+fn directory_pattern(target: &Path, directory: &Path) -> Option<String> {
+    let relative = directory.strip_prefix(target).ok()?;
+    let parts: Vec<String> = relative
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(n) => Some(n.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+
+    (!parts.is_empty()).then(|| format!("/{}/", parts.join("/")))
+}
+
+fn remove_block(content: &str) -> String {
+    let mut result = String::new();
+    let mut inside = false;
+    for line in content.lines() {
+        if line == BLOCK_START {
+            inside = true;
+        } else if line == BLOCK_END {
+            inside = false;
+        } else if !inside {
+            result.push_str(line);
+            result.push('\n');
+        }
+    }
+
+    // Drop the empty line that separated the block from the rest of the file.
+    let trimmed = result.trim_end_matches('\n');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", trimmed)
+    }
+}
+
+/// Write (or replace) the block of this crate inside `<target>/.gitignore`.
+/// Running this twice with the same patterns leaves the file unchanged.
+/// Without patterns the block of an earlier run is removed (like
+/// `GitIgnoreEdit::revert`, a file holding nothing else is deleted) and None
+/// is returned.
+pub(crate) fn update_gitignore(
+    target: &Path,
+    patterns: &[String],
+) -> Result<Option<GitIgnoreEdit>, UnityPackageReaderError> {
+    let path = target.join(".gitignore");
+    let existing = match fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(error(&path, e)),
+    };
+
+    if patterns.is_empty() {
+        if existing.lines().any(|l| l == BLOCK_START) {
+            GitIgnoreEdit {
+                path,
+                created: true,
+            }
+            .revert()?;
+        }
+        return Ok(None);
+    }
+
+    // A file that only contains our block has been created by an earlier run.
+    let mut content = remove_block(&existing);
+    let created = content.is_empty() && (existing.is_empty() || existing.starts_with(BLOCK_START));
+    if !content.is_empty() && !content.ends_with("\n\n") {
+        content.push('\n');
+    }
+
+    content.push_str(BLOCK_START);
+    content.push('\n');
+    for pattern in patterns {
+        content.push_str(pattern);
+        content.push('\n');
+    }
+    content.push_str(BLOCK_END);
+    content.push('\n');
+
+    if content != existing {
+        if let Err(e) = fs::create_dir_all(target).and_then(|_| fs::write(&path, &content)) {
+            return Err(error(&path, e));
+        }
+    }
+
+    Ok(Some(GitIgnoreEdit { path, created }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_git_root() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(find_git_root(&dir.path().join("a/b")), None);
+
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        assert_eq!(
+            find_git_root(&dir.path().join("a/b")),
+            Some(dir.path().to_path_buf())
+        );
+    }
+
+    #[test]
+    fn test_auxiliary_patterns() {
+        let target = Path::new("/project/Assets");
+        let config = UnpackConfig::default();
+        assert_eq!(
+            auxiliary_patterns(
+                target,
+                Some(Path::new("/project/Assets/tmp/unpack")),
+                &config
+            ),
+            vec![String::from("/tmp/unpack/")]
+        );
+        assert!(auxiliary_patterns(target, Some(Path::new("/tmp")), &config).is_empty());
+        assert!(auxiliary_patterns(target, None, &config).is_empty());
+
+        let config = UnpackConfig {
+            incremental: true,
+            backup: Some(Backup::Directory(PathBuf::from("/project/Assets/.backup"))),
+            ..Default::default()
+        };
+        assert_eq!(
+            auxiliary_patterns(target, None, &config),
+            vec![String::from("/.unpacked-assets"), String::from("/.backup/")]
+        );
+    }
+
+    #[test]
+    fn test_update_is_idempotent_and_revertible() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".gitignore");
+        fs::write(&path, "*.log\n").unwrap();
+
+        let patterns = vec![String::from("/tmp/")];
+        let edit = update_gitignore(dir.path(), &patterns).unwrap().unwrap();
+        let first = fs::read_to_string(&path).unwrap();
+        update_gitignore(dir.path(), &patterns).unwrap();
+        let second = fs::read_to_string(&path).unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(first.matches("/tmp/").count(), 1);
+        assert!(!edit.created);
+
+        edit.revert().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "*.log\n");
+
+        // An update without patterns removes the block of an earlier one.
+        update_gitignore(dir.path(), &patterns).unwrap();
+        assert_eq!(update_gitignore(dir.path(), &[]).unwrap(), None);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "*.log\n");
+        update_gitignore(dir.path(), &[]).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "*.log\n");
+    }
+
+    #[test]
+    fn test_revert_deletes_created_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let edit = update_gitignore(dir.path(), &[String::from("/tmp/")])
+            .unwrap()
+            .unwrap();
+        assert!(edit.created);
+
+        edit.revert().unwrap();
+        assert!(!dir.path().join(".gitignore").exists());
+    }
+}
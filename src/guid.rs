@@ -0,0 +1,135 @@
+use std::{fmt, str::FromStr};
+
+use crate::unpacker_error::{ErrorInformation, UnityPackageReaderError};
+
+/// The guid Unity assigns to every asset: 32 lower case hex digits, stored as
+/// 16 bytes. Unity never writes upper case digits, so parsing rejects them
+/// like the guid directory names of a package would.
+#[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Guid([u8; 16]);
+
+impl Guid {
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Guid(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    /// Same as `str::parse`, fails with `InvalidGuid` unless s is 32 lower case
+    /// hex digits.
+    pub fn parse(s: &str) -> Result<Self, UnityPackageReaderError> {
+        s.parse()
+    }
+}
+
+impl FromStr for Guid {
+    type Err = UnityPackageReaderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.as_bytes();
+        let is_digit = |d: &u8| d.is_ascii_digit() || (b'a'..=b'f').contains(d);
+        if digits.len() != 32 || !digits.iter().all(is_digit) {
+            return Err(UnityPackageReaderError::InvalidGuid(ErrorInformation::new(
+                Some(format!("'{}' is not 32 lower case hex digits", s)),
+                file!(),
+                line!(),
+            )));
+        }
+
+        let mut bytes = [0u8; 16];
+        for (byte, pair) in bytes.iter_mut().zip(digits.chunks(2)) {
+            *byte = (hex_value(pair[0]) << 4) | hex_value(pair[1]);
+        }
+
+        Ok(Guid(bytes))
+    }
+}
+
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        _ => digit - b'a' + 10,
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for b in self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Guid({})", self)
+    }
+}
+
+impl PartialEq<str> for Guid {
+    fn eq(&self, other: &str) -> bool {
+        other.parse::<Guid>().is_ok_and(|g| g == *self)
+    }
+}
+
+impl PartialEq<&str> for Guid {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Guid {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Guid {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        let guid: Guid = TEXTURE_GUID.parse().unwrap();
+        assert_eq!(guid.to_string(), TEXTURE_GUID);
+        assert_eq!(guid.as_bytes()[0], 0x1a);
+        assert_eq!(guid, TEXTURE_GUID);
+        assert_eq!(Guid::parse(TEXTURE_GUID).unwrap(), guid);
+        assert_ne!(guid, "not a guid");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for s in [
+            "",
+            "1af567ac160bb164fb19b8cb9b55b34",
+            " 1af567ac160bb164fb19b8cb9b55b34b",
+            "1af567ac160bb164fb19b8cb9b55b34g",
+            "1AF567AC160BB164FB19B8CB9B55B34B",
+            "Assets/Textures/Ground/IMGP1287",
+        ] {
+            assert!(
+                matches!(
+                    s.parse::<Guid>(),
+                    Err(UnityPackageReaderError::InvalidGuid(_))
+                ),
+                "{}",
+                s
+            );
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    checksums, file_system,
+    guid::Guid,
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// The header row of `GuidMap::to_tsv`.
+pub const TSV_COLUMNS: &[&str] = &["guid", "path"];
+
+/// The guids of a package and their paths in both directions, see
+/// `UnityPackage::guid_map`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GuidMap {
+    pub by_guid: BTreeMap<Guid, PathBuf>,
+    /// A path used by several guids maps to the smallest of them, the others
+    /// are listed in duplicate_paths.
+    pub by_path: BTreeMap<PathBuf, Guid>,
+    /// The paths used by more than one guid, with all of their guids.
+    pub duplicate_paths: BTreeMap<PathBuf, Vec<Guid>>,
+}
+
+impl GuidMap {
+    pub(crate) fn new(entries: impl IntoIterator<Item = (Guid, PathBuf)>) -> Self {
+        let mut map = GuidMap::default();
+        let mut guids: BTreeMap<PathBuf, Vec<Guid>> = BTreeMap::new();
+        for (guid, path) in entries {
+            guids.entry(path.clone()).or_default().push(guid);
+            map.by_guid.insert(guid, path);
+        }
+
+        for (path, mut guids) in guids {
+            guids.sort();
+            guids.dedup();
+            map.by_path.insert(path.clone(), guids[0]);
+            if guids.len() > 1 {
+                map.duplicate_paths.insert(path, guids);
+            }
+        }
+
+        map
+    }
+
+    /// True, if no path is used by more than one guid.
+    pub fn is_unique(&self) -> bool {
+        self.duplicate_paths.is_empty()
+    }
+
+    /// One row per guid, ordered by guid, after a header row with
+    /// `TSV_COLUMNS`. Paths use forward slashes, tabs, line breaks and
+    /// backslashes in them are escaped as `\t`, `\n`, `\r` and `\\`.
+    pub fn to_tsv(&self) -> String {
+        let mut tsv = format!("{}\n", TSV_COLUMNS.join("\t"));
+        for (guid, path) in &self.by_guid {
+            tsv.push_str(&format!(
+                "{}\t{}\n",
+                guid,
+                tsv_field(&checksums::to_forward_slashes(path))
+            ));
+        }
+
+        tsv
+    }
+
+    /// Write `to_tsv` into the file at path.
+    pub fn write_tsv(&self, path: &Path) -> Result<(), UnityPackageReaderError> {
+        file_system::require_file_system("Writing a guid map")?;
+        match fs::write(path, self.to_tsv()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(UnityPackageReaderError::CouldNotWriteReport(
+                ErrorInformation::new(Some(format!("{:?}: {}", path, e)), file!(), line!()),
+            )),
+        }
+    }
+}
+
+/// field with backslashes, tabs and line breaks escaped, so it fits into a
+/// single cell of a tab separated file.
+pub(crate) fn tsv_field(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// The inverse of `tsv_field`, None for an unknown escape sequence.
+pub(crate) fn parse_tsv_field(field: &str) -> Option<String> {
+    let mut result = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        result.push(match chars.next()? {
+            '\\' => '\\',
+            't' => '\t',
+            'n' => '\n',
+            'r' => '\r',
+            _ => return None,
+        });
+    }
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_paths() {
+        let first: Guid = "1af567ac160bb164fb19b8cb9b55b34b".parse().unwrap();
+        let second: Guid = "9d8c7b6a5f4e3d2c1b0a9f8e7d6c5b4a".parse().unwrap();
+        let map = GuidMap::new([
+            (second, PathBuf::from("Assets/Same.txt")),
+            (first, PathBuf::from("Assets/Same.txt")),
+        ]);
+
+        assert_eq!(map.by_guid.len(), 2);
+        assert_eq!(map.by_path[Path::new("Assets/Same.txt")], first);
+        assert_eq!(
+            map.duplicate_paths[Path::new("Assets/Same.txt")],
+            [first, second]
+        );
+        assert!(!map.is_unique());
+        assert_eq!(
+            map.to_tsv(),
+            format!(
+                "guid\tpath\n{}\tAssets/Same.txt\n{}\tAssets/Same.txt\n",
+                first, second
+            )
+        );
+        assert_eq!(tsv_field("a\tb\\c"), "a\\tb\\\\c");
+    }
+}
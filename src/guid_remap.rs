@@ -0,0 +1,155 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crate::guid::Guid;
+
+/// Extensions of the assets the Unity editor serializes itself, either as
+/// YAML or in its binary format.
+const SERIALIZED_EXTENSIONS: &[&str] = &[
+    "anim",
+    "asset",
+    "controller",
+    "cubemap",
+    "flare",
+    "fontsettings",
+    "guiskin",
+    "lighting",
+    "mask",
+    "mat",
+    "mixer",
+    "overridecontroller",
+    "physicmaterial",
+    "physicsmaterial2d",
+    "playable",
+    "prefab",
+    "preset",
+    "rendertexture",
+    "shadervariants",
+    "signal",
+    "spriteatlas",
+    "terrainlayer",
+    "unity",
+];
+
+pub(crate) const TOKEN: &[u8] = b"guid: ";
+
+/// True, if the Unity editor writes assets like path itself, so they may
+/// reference other assets by guid.
+pub(crate) fn is_unity_serialized(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .is_some_and(|e| SERIALIZED_EXTENSIONS.contains(&e.as_str()))
+}
+
+/// True, if content has been serialized as text (starts with the YAML header).
+pub(crate) fn is_text_serialized(content: &[u8]) -> bool {
+    content.starts_with(b"%YAML")
+}
+
+/// Replace every guid in content that is written as `guid: <32 hex digits>`
+/// and is a key of remap. Anything else, e.g. `m_guid: ...` or a longer hex
+/// string, is left alone. Returns None if nothing has been replaced.
+pub(crate) fn rewrite_references(content: &[u8], remap: &BTreeMap<Guid, Guid>) -> Option<Vec<u8>> {
+    let mut result = Vec::with_capacity(content.len());
+    let mut changed = false;
+    let mut position = 0;
+
+    while let Some(offset) = find(&content[position..], TOKEN) {
+        let start = position + offset;
+        let value = start + TOKEN.len();
+        let end = value + 32;
+
+        let replacement = reference_at(content, start).and_then(|g| remap.get(&g));
+
+        match replacement {
+            Some(new) => {
+                result.extend_from_slice(&content[position..value]);
+                result.extend_from_slice(new.to_string().as_bytes());
+                position = end;
+                changed = true;
+            }
+            None => {
+                result.extend_from_slice(&content[position..value]);
+                position = value;
+            }
+        }
+    }
+
+    if !changed {
+        return None;
+    }
+
+    result.extend_from_slice(&content[position..]);
+    Some(result)
+}
+
+/// The guid of the reference whose `guid: ` token starts at start, if the
+/// token is a whole key and is followed by exactly 32 hex digits.
+pub(crate) fn reference_at(content: &[u8], start: usize) -> Option<Guid> {
+    let value = start + TOKEN.len();
+    let end = value + 32;
+
+    let whole_key = start == 0 || !is_word(content[start - 1]);
+    let whole_value = end <= content.len() && (end == content.len() || !is_word(content[end]));
+    if !whole_key || !whole_value {
+        return None;
+    }
+
+    std::str::from_utf8(&content[value..end])
+        .ok()
+        .and_then(|g| g.parse::<Guid>().ok())
+}
+
+pub(crate) fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn is_word(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    const NEW_GUID: &str = "0123456789abcdef0123456789abcdef";
+
+    fn remap() -> BTreeMap<Guid, Guid> {
+        BTreeMap::from([(TEXTURE_GUID.parse().unwrap(), NEW_GUID.parse().unwrap())])
+    }
+
+    #[test]
+    fn test_rewrite_references() {
+        let content = format!(
+            "m_Texture: {{fileID: 2800000, guid: {t}, type: 3}}\nm_Other: {{fileID: 1, guid: {s}, type: 3}}\nm_guid: {t}\nguid: {t}0\nguid: {t}",
+            t = TEXTURE_GUID,
+            s = SCRIPT_GUID
+        );
+
+        let rewritten =
+            String::from_utf8(rewrite_references(content.as_bytes(), &remap()).unwrap()).unwrap();
+        assert_eq!(
+            rewritten,
+            format!(
+                "m_Texture: {{fileID: 2800000, guid: {n}, type: 3}}\nm_Other: {{fileID: 1, guid: {s}, type: 3}}\nm_guid: {t}\nguid: {t}0\nguid: {n}",
+                n = NEW_GUID,
+                t = TEXTURE_GUID,
+                s = SCRIPT_GUID
+            )
+        );
+
+        assert!(rewrite_references(b"guid: ", &remap()).is_none());
+        assert!(rewrite_references(SCRIPT_CONTENT, &remap()).is_none());
+    }
+
+    #[test]
+    fn test_detection() {
+        assert!(is_unity_serialized(Path::new("Assets/Player.prefab")));
+        assert!(is_unity_serialized(Path::new("Assets/Ground.MAT")));
+        assert!(!is_unity_serialized(Path::new("Assets/ground.jpg")));
+        assert!(is_text_serialized(
+            b"%YAML 1.1\n%TAG !u! tag:unity3d.com,2011:\n"
+        ));
+        assert!(!is_text_serialized(b"\x00\x00\x00\x00UnityFS"));
+    }
+}
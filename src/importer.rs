@@ -0,0 +1,106 @@
+use std::fmt;
+
+/// The importer Unity uses for an asset, taken from the importer section of
+/// its meta file (see `UnityAssetFile::importer`). More reliable than the
+/// extension, since it is what Unity itself decided.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Importer {
+    Texture,
+    Mono,
+    Model,
+    Audio,
+    NativeFormat,
+    /// Folders and files Unity has no importer for.
+    Default,
+    TextScript,
+    Shader,
+    Prefab,
+    Plugin,
+    VideoClip,
+    AssemblyDefinition,
+    /// Any other importer, by the name of its section (e.g. `IHVImageFormatImporter`).
+    Other(String),
+    /// The asset has no meta file or its meta names no importer.
+    Unknown,
+}
+
+/// The section names of the importers with a variant of their own.
+const IMPORTER_NAMES: &[(&str, Importer)] = &[
+    ("TextureImporter", Importer::Texture),
+    ("MonoImporter", Importer::Mono),
+    ("ModelImporter", Importer::Model),
+    ("AudioImporter", Importer::Audio),
+    ("NativeFormatImporter", Importer::NativeFormat),
+    ("DefaultImporter", Importer::Default),
+    ("TextScriptImporter", Importer::TextScript),
+    ("ShaderImporter", Importer::Shader),
+    ("PrefabImporter", Importer::Prefab),
+    ("PluginImporter", Importer::Plugin),
+    ("VideoClipImporter", Importer::VideoClip),
+    ("AssemblyDefinitionImporter", Importer::AssemblyDefinition),
+];
+
+impl Importer {
+    /// The importer of a meta section name like `TextureImporter`. None
+    /// gives `Unknown`.
+    pub fn from_name(name: Option<&str>) -> Importer {
+        let name = match name {
+            Some(n) => n,
+            None => return Importer::Unknown,
+        };
+
+        IMPORTER_NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, i)| i.clone())
+            .unwrap_or_else(|| Importer::Other(String::from(name)))
+    }
+
+    /// The name of the meta section, None for `Unknown`.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Importer::Other(name) => Some(name),
+            Importer::Unknown => None,
+            known => IMPORTER_NAMES
+                .iter()
+                .find(|(_, i)| i == known)
+                .map(|(n, _)| *n),
+        }
+    }
+}
+
+impl fmt::Display for Importer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name().unwrap_or("Unknown"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_importer_names() {
+        assert_eq!(
+            Importer::from_name(Some("TextureImporter")),
+            Importer::Texture
+        );
+        assert_eq!(Importer::from_name(None), Importer::Unknown);
+        let other = Importer::from_name(Some("IHVImageFormatImporter"));
+        assert_eq!(
+            other,
+            Importer::Other(String::from("IHVImageFormatImporter"))
+        );
+        assert_eq!(other.to_string(), "IHVImageFormatImporter");
+
+        for (name, importer) in IMPORTER_NAMES {
+            assert_eq!(importer.name(), Some(*name));
+        }
+        assert_eq!(Importer::Unknown.to_string(), "Unknown");
+    }
+}
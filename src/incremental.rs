@@ -0,0 +1,196 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    checksums,
+    guid::Guid,
+    guid_map,
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// The file in the target root that records what incremental unpacks have
+/// installed. Unity does not import files whose name starts with a dot.
+pub(crate) const MANIFEST_FILE: &str = ".unpacked-assets";
+
+/// An asset an incremental unpack has installed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct InstalledEntry {
+    /// The sha256 of the payload, None for folders.
+    pub(crate) sha256: Option<[u8; 32]>,
+    /// The path relative to the target.
+    pub(crate) path: PathBuf,
+}
+
+/// The content of `MANIFEST_FILE`: one line per asset with the guid, the
+/// sha256 (`-` for folders) and the relative path, separated by tabs. The
+/// path is escaped like the cells of `GuidMap::to_tsv`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct InstalledManifest {
+    entries: BTreeMap<Guid, InstalledEntry>,
+}
+
+impl InstalledManifest {
+    /// Read the manifest of target. A target without one gives an empty manifest.
+    pub(crate) fn read(target: &Path) -> Result<Self, UnityPackageReaderError> {
+        let content = match fs::read_to_string(target.join(MANIFEST_FILE)) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(UnityPackageReaderError::CouldNotReadTarget(
+                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                ))
+            }
+        };
+
+        let mut entries = BTreeMap::new();
+        for (number, line) in content.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+
+            match parse_line(line) {
+                Some((guid, entry)) => {
+                    entries.insert(guid, entry);
+                }
+                None => {
+                    return Err(UnityPackageReaderError::CouldNotReadTarget(
+                        ErrorInformation::new(
+                            Some(format!(
+                                "{}: line {} is malformed",
+                                MANIFEST_FILE,
+                                number + 1
+                            )),
+                            file!(),
+                            line!(),
+                        ),
+                    ))
+                }
+            }
+        }
+
+        Ok(InstalledManifest { entries })
+    }
+
+    /// Replace the manifest of target.
+    pub(crate) fn write(&self, target: &Path) -> Result<(), UnityPackageReaderError> {
+        let content: String = self
+            .entries
+            .iter()
+            .map(|(guid, entry)| {
+                format!(
+                    "{}\t{}\t{}\n",
+                    guid,
+                    entry
+                        .sha256
+                        .map(|h| checksums::to_hex(&h))
+                        .unwrap_or_else(|| String::from("-")),
+                    guid_map::tsv_field(&checksums::to_forward_slashes(&entry.path))
+                )
+            })
+            .collect();
+
+        match fs::write(target.join(MANIFEST_FILE), content) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(UnityPackageReaderError::CouldNotWriteReport(
+                ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+            )),
+        }
+    }
+
+    pub(crate) fn get(&self, guid: &Guid) -> Option<&InstalledEntry> {
+        self.entries.get(guid)
+    }
+
+    pub(crate) fn insert(&mut self, guid: Guid, entry: InstalledEntry) {
+        self.entries.insert(guid, entry);
+    }
+
+    pub(crate) fn remove(&mut self, guid: &Guid) {
+        self.entries.remove(guid);
+    }
+
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (&Guid, &InstalledEntry)> {
+        self.entries.iter()
+    }
+}
+
+fn parse_line(line: &str) -> Option<(Guid, InstalledEntry)> {
+    let mut parts = line.splitn(3, '\t');
+    let guid = parts.next()?.parse::<Guid>().ok()?;
+    let sha256 = match parts.next()? {
+        "-" => None,
+        hex => Some(from_hex(hex)?),
+    };
+    let path = parts.next().filter(|p| !p.is_empty())?;
+
+    Some((
+        guid,
+        InstalledEntry {
+            sha256,
+            path: PathBuf::from(guid_map::parse_tsv_field(path)?),
+        },
+    ))
+}
+
+fn from_hex(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut result = [0u8; 32];
+    for (i, byte) in result.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            InstalledManifest::read(dir.path()).unwrap(),
+            InstalledManifest::default()
+        );
+
+        let mut manifest = InstalledManifest::default();
+        manifest.insert(
+            Guid::from_bytes([1; 16]),
+            InstalledEntry {
+                sha256: Some([0xab; 32]),
+                path: PathBuf::from("Assets/With Space\tand tab.png"),
+            },
+        );
+        manifest.insert(
+            Guid::from_bytes([3; 16]),
+            InstalledEntry {
+                sha256: Some([0xcd; 32]),
+                path: PathBuf::from("Assets/Line\nbreak.txt"),
+            },
+        );
+        manifest.insert(
+            Guid::from_bytes([2; 16]),
+            InstalledEntry {
+                sha256: None,
+                path: PathBuf::from("Assets"),
+            },
+        );
+        manifest.write(dir.path()).unwrap();
+        assert_eq!(InstalledManifest::read(dir.path()).unwrap(), manifest);
+
+        let content = fs::read_to_string(dir.path().join(MANIFEST_FILE)).unwrap();
+        assert_eq!(content.lines().count(), 3);
+
+        fs::write(dir.path().join(MANIFEST_FILE), "not a manifest\n").unwrap();
+        assert!(matches!(
+            InstalledManifest::read(dir.path()),
+            Err(UnityPackageReaderError::CouldNotReadTarget(_))
+        ));
+    }
+}
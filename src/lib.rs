@@ -1,14 +1,213 @@
+mod archive_convert;
+mod asmdef;
+mod asset_category;
+mod asset_filter;
+mod atomic_file;
+mod batch;
+mod checksums;
+mod code_report;
+mod compatibility;
+mod counting;
+mod dependencies;
+mod disk_space;
+mod extraction_observer;
+mod extraction_report;
+mod file_system;
+mod git_ignore;
+mod guid;
+mod guid_map;
+mod guid_remap;
+mod importer;
+mod incremental;
+mod manifest;
+mod memory_unpack;
+mod overwrite_policy;
+mod package_diff;
+mod package_format;
+mod package_listing;
+mod package_source;
+mod package_writer;
+#[cfg(feature = "parallel-gzip")]
+mod parallel_gzip;
+mod path_search;
+mod reserved_names;
+mod shared;
+mod single_asset;
+mod size_estimate;
+mod staging;
+mod tar_stream;
+mod target_diff;
+mod tmp_guard;
 mod unity_asset_file;
+mod unity_meta;
 mod unity_package;
+mod unpack_config;
+mod unpack_limits;
+mod unpack_options;
+mod unpack_plan;
+mod unpack_session;
+mod unpack_stats;
 mod unpacker_error;
+mod upm_manifest;
+mod validation;
+mod warning;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(test)]
+mod test_utils;
 
 pub mod prelude {
+    use crate::archive_convert;
+    use crate::asmdef;
+    use crate::asset_category;
+    use crate::asset_filter;
+    use crate::batch;
+    use crate::checksums;
+    use crate::code_report;
+    use crate::compatibility;
+    use crate::dependencies;
+    use crate::extraction_observer;
+    use crate::extraction_report;
+    use crate::file_system;
+    use crate::git_ignore;
+    use crate::guid;
+    use crate::guid_map;
+    use crate::importer;
+    use crate::manifest;
+    use crate::memory_unpack;
+    use crate::overwrite_policy;
+    use crate::package_diff;
+    use crate::package_listing;
+    use crate::package_writer;
+    use crate::reserved_names;
+    use crate::shared;
+    use crate::single_asset;
+    use crate::size_estimate;
+    use crate::target_diff;
     use crate::unity_asset_file;
+    use crate::unity_meta;
     use crate::unity_package;
+    use crate::unpack_config;
+    use crate::unpack_limits;
+    use crate::unpack_options;
+    use crate::unpack_plan;
+    use crate::unpack_session;
+    use crate::unpack_stats;
     use crate::unpacker_error;
+    use crate::upm_manifest;
+    use crate::validation;
+    use crate::warning;
 
+    pub use archive_convert::ArchiveFormat;
+    pub use asmdef::AssemblyDefinition;
+    pub use asset_category::AssetCategory;
+    pub use asset_category::CATEGORY_EXTENSIONS;
+    pub use asset_category::CATEGORY_IMPORTERS;
+    pub use asset_filter::All;
+    pub use asset_filter::Any;
+    pub use asset_filter::AssetFilter;
+    pub use asset_filter::Not;
+    pub use asset_filter::SharedFilter;
+    pub use batch::unpack_batch;
+    pub use batch::BatchEntry;
+    pub use batch::BatchReport;
+    pub use batch::BatchStatus;
+    pub use batch::CombinedIndex;
+    pub use batch::PackageBatch;
+    pub use batch::PackageRef;
+    pub use batch::UninstallReport;
+    pub use checksums::VerifyReport;
+    pub use code_report::CodeAsset;
+    pub use code_report::CodeReport;
+    pub use code_report::PluginAsset;
+    pub use code_report::PluginPlatform;
+    pub use compatibility::CompatibilityCheck;
+    pub use compatibility::CompatibilityIssue;
+    pub use compatibility::CompatibilityReport;
+    pub use compatibility::ExporterEstimate;
+    pub use compatibility::Severity;
+    pub use dependencies::is_builtin_guid;
+    pub use dependencies::AssetDependencies;
+    pub use dependencies::DependencyGraph;
+    pub use dependencies::MissingDependencies;
+    pub use extraction_observer::ExtractionObserver;
+    pub use extraction_observer::FileStatus;
+    pub use extraction_observer::SharedObserver;
+    pub use extraction_observer::SkipReason;
+    pub use extraction_report::AssetFailure;
+    pub use extraction_report::AssetReport;
+    pub use extraction_report::AssetStatus;
+    pub use extraction_report::DuplicateGuid;
+    pub use extraction_report::DuplicateResolution;
+    pub use extraction_report::ExtractionReport;
+    pub use extraction_report::SkippedEntry;
+    pub use extraction_report::SpecialEntryKind;
+    pub use file_system::FileSystem;
+    pub use file_system::OsFileSystem;
+    pub use git_ignore::GitIgnoreEdit;
+    pub use guid::Guid;
+    pub use guid_map::GuidMap;
+    pub use guid_map::TSV_COLUMNS;
+    pub use importer::Importer;
+    pub use manifest::ManifestEntry;
+    pub use manifest::PackageManifest;
+    pub use manifest::CSV_COLUMNS;
+    pub use memory_unpack::MemoryAsset;
+    pub use overwrite_policy::OverwritePolicy;
+    pub use package_diff::ChangedAsset;
+    pub use package_diff::MovedAsset;
+    pub use package_diff::PackageDiff;
+    pub use package_listing::EntrySummary;
+    pub use package_writer::GuidGeneration;
+    pub use package_writer::UnityPackageWriter;
+    pub use reserved_names::is_reserved_name;
+    pub use shared::Shared;
+    pub use single_asset::ExtractedAsset;
+    pub use size_estimate::SizeEstimate;
+    pub use target_diff::AssetComparison;
+    pub use target_diff::DiffEntry;
+    pub use target_diff::TargetDiff;
+    pub use target_diff::TargetStatus;
     pub use unity_asset_file::UnityAssetFile;
+    pub use unity_meta::MetaData;
+    pub use unity_meta::MetaFormat;
     pub use unity_package::UnityPackage;
+    pub use unity_package::TMP_SUBFOLDER;
+    pub use unpack_config::Backup;
+    pub use unpack_config::CaseCollisions;
+    pub use unpack_config::DuplicateGuids;
+    pub use unpack_config::ExtractOrdering;
+    pub use unpack_config::FileModes;
+    pub use unpack_config::Flatten;
+    pub use unpack_config::FlattenCollisions;
+    pub use unpack_config::FlattenMetas;
+    pub use unpack_config::FolderPayloadResolution;
+    pub use unpack_config::MetaNaming;
+    pub use unpack_config::ReservedNames;
+    pub use unpack_config::RootRemap;
+    pub use unpack_config::SpecialEntries;
+    pub use unpack_config::StripPrefix;
+    pub use unpack_config::UnpackConfig;
+    pub use unpack_config::CONFIG_VERSION;
+    pub use unpack_limits::UnpackLimits;
+    pub use unpack_options::BufferSizes;
+    pub use unpack_options::PathRewrite;
+    pub use unpack_options::UnpackOptions;
+    pub use unpack_options::MAX_BUFFER_SIZE;
+    pub use unpack_plan::PlannedAction;
+    pub use unpack_plan::PlannedOperation;
+    pub use unpack_session::StepBudget;
+    pub use unpack_session::StepResult;
+    pub use unpack_session::UnpackSession;
+    pub use unpack_stats::UnpackStats;
     pub use unpacker_error::ErrorInformation;
     pub use unpacker_error::UnityPackageReaderError;
+    pub use upm_manifest::UpmManifestOptions;
+    pub use validation::GuidFolder;
+    pub use validation::GuidFolderState;
+    pub use validation::ValidationIssue;
+    pub use validation::ValidationReport;
+    pub use warning::Warning;
 }
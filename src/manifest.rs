@@ -0,0 +1,188 @@
+use std::path::Path;
+
+use crate::{checksums, prelude::EntrySummary};
+
+/// The header row of `PackageManifest::to_csv`, also the column order.
+pub const CSV_COLUMNS: &[&str] = &["guid", "relative_path", "extension", "size", "is_folder"];
+
+/// A single asset of a `PackageManifest`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ManifestEntry {
+    /// `guid`: the guid directory of the asset inside the package.
+    pub guid: String,
+    /// `relative_path`: the pathname of the asset, always with forward slashes.
+    pub relative_path: String,
+    /// `is_folder`: true for folder assets (`folderAsset: yes`).
+    pub is_folder: bool,
+    /// `size`: the payload size in bytes (0 for folders).
+    pub size: u64,
+    /// `has_preview`: true, if the package contains a `preview.png` for the asset.
+    pub has_preview: bool,
+    /// `has_meta`: true, if the package contains an `asset.meta` for the asset.
+    #[cfg_attr(feature = "serde", serde(default = "has_meta_default"))]
+    pub has_meta: bool,
+}
+
+/// Manifests written before `has_meta` existed are read as if every asset had one.
+#[cfg(feature = "serde")]
+fn has_meta_default() -> bool {
+    true
+}
+
+/// A machine readable inventory of a package, see `UnityPackage::manifest`.
+/// The field names are part of the serialized format and will not change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackageManifest {
+    /// `source_file`: the file name of the package, without directories.
+    pub source_file: String,
+    /// `package_size`: the size of the package file in bytes.
+    pub package_size: u64,
+    /// `total_size`: the sum of the payload sizes of all assets.
+    pub total_size: u64,
+    /// `assets`: every asset, sorted by relative path.
+    pub assets: Vec<ManifestEntry>,
+}
+
+impl PackageManifest {
+    pub(crate) fn new(source_file: String, package_size: u64, entries: Vec<EntrySummary>) -> Self {
+        let mut assets: Vec<ManifestEntry> = entries
+            .into_iter()
+            .map(|e| ManifestEntry {
+                relative_path: checksums::to_forward_slashes(&e.relative_path),
+                guid: e.guid,
+                is_folder: e.is_folder,
+                size: e.size,
+                has_preview: e.has_preview,
+                has_meta: e.has_meta,
+            })
+            .collect();
+        assets.sort_by(|a, b| {
+            a.relative_path
+                .cmp(&b.relative_path)
+                .then_with(|| a.guid.cmp(&b.guid))
+        });
+
+        PackageManifest {
+            source_file,
+            package_size,
+            total_size: assets.iter().map(|a| a.size).sum(),
+            assets,
+        }
+    }
+
+    /// One row per asset with the columns of `CSV_COLUMNS`, after a header
+    /// row. Fields are quoted as described in RFC 4180 where necessary.
+    /// Folder assets are left out unless include_folders is set.
+    pub fn to_csv(&self, include_folders: bool) -> String {
+        let mut csv = csv_row(CSV_COLUMNS.iter().map(|c| c.to_string()));
+        for asset in self
+            .assets
+            .iter()
+            .filter(|a| include_folders || !a.is_folder)
+        {
+            let extension = Path::new(&asset.relative_path)
+                .extension()
+                .map(|e| e.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            csv.push_str(&csv_row([
+                asset.guid.clone(),
+                asset.relative_path.clone(),
+                extension,
+                asset.size.to_string(),
+                asset.is_folder.to_string(),
+            ]));
+        }
+
+        csv
+    }
+}
+
+fn csv_row(fields: impl IntoIterator<Item = String>) -> String {
+    let fields: Vec<String> = fields.into_iter().map(|f| csv_field(&f)).collect();
+    format!("{}\r\n", fields.join(","))
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        String::from(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Just enough of RFC 4180 to read back what to_csv writes.
+    fn parse_csv(csv: &str) -> Vec<Vec<String>> {
+        let mut rows = Vec::new();
+        let mut row = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = csv.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (quoted, c) {
+                (true, '"') if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                (true, '"') => quoted = false,
+                (true, c) => field.push(c),
+                (false, '"') => quoted = true,
+                (false, ',') => row.push(std::mem::take(&mut field)),
+                (false, '\r') => {}
+                (false, '\n') => {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                (false, c) => field.push(c),
+            }
+        }
+
+        rows
+    }
+
+    fn summary(guid: &str, path: &str, size: u64, is_folder: bool) -> EntrySummary {
+        EntrySummary {
+            guid: String::from(guid),
+            relative_path: PathBuf::from(path),
+            size,
+            is_folder,
+            has_preview: false,
+            has_meta: true,
+        }
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let manifest = PackageManifest::new(
+            String::from("a.unitypackage"),
+            10,
+            vec![
+                summary("1", "Assets/Docs, \"Read me\".txt", 5, false),
+                summary("2", "Assets/Docs", 0, true),
+                summary("3", "Assets/Line\nBreak", 7, false),
+            ],
+        );
+
+        let rows = parse_csv(&manifest.to_csv(true));
+        assert_eq!(rows[0], CSV_COLUMNS);
+        assert_eq!(rows.len(), manifest.assets.len() + 1);
+        for (row, asset) in rows[1..].iter().zip(&manifest.assets) {
+            assert_eq!(row[0], asset.guid);
+            assert_eq!(row[1], asset.relative_path);
+            assert_eq!(row[3], asset.size.to_string());
+            assert_eq!(row[4], asset.is_folder.to_string());
+        }
+        assert_eq!(rows[2][2], "txt");
+
+        let files = parse_csv(&manifest.to_csv(false));
+        assert_eq!(files.len(), 3);
+        assert!(files[1..].iter().all(|r| r[4] == "false"));
+    }
+}
@@ -0,0 +1,95 @@
+use std::{
+    io::{BufRead, Read},
+    ops::ControlFlow,
+    path::PathBuf,
+};
+
+use crate::{
+    guid::Guid,
+    package_listing::{self, DirectoryVisitor, EntryInfo, GuidDirectory},
+    unpack_limits::UnpackLimits,
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// An asset unpacked by `UnityPackage::unpack_to_memory`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryAsset {
+    pub guid: Guid,
+    /// The content of the pathname file, with forward slashes.
+    pub relative_path: PathBuf,
+    pub is_folder: bool,
+    /// The payload, empty for folders.
+    pub content: Vec<u8>,
+    /// The content of the meta file, empty if the package has none.
+    pub meta: Vec<u8>,
+}
+
+/// Collects the assets `walk_directories` hands out, with their payloads.
+#[derive(Default)]
+struct MemoryAssets {
+    assets: Vec<MemoryAsset>,
+}
+
+impl DirectoryVisitor for MemoryAssets {
+    /// The payload.
+    type State = Vec<u8>;
+
+    fn file(
+        &mut self,
+        guid: &str,
+        name: &str,
+        _info: EntryInfo,
+        content: &mut dyn Read,
+        state: &mut Self::State,
+    ) -> Result<(), UnityPackageReaderError> {
+        // Directories that are no guid are left out, their payloads are not needed.
+        if name != "asset" || guid.parse::<Guid>().is_err() {
+            return Ok(());
+        }
+
+        match content.read_to_end(state) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(UnityPackageReaderError::CorruptPackage(
+                ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+            )),
+        }
+    }
+
+    fn directory(
+        &mut self,
+        directory: GuidDirectory<Self::State>,
+    ) -> Result<ControlFlow<()>, UnityPackageReaderError> {
+        if let Ok(guid) = directory.guid.parse::<Guid>() {
+            self.assets.push(MemoryAsset {
+                guid,
+                relative_path: PathBuf::from(directory.pathname),
+                is_folder: directory.is_folder,
+                content: directory.state,
+                meta: directory
+                    .meta
+                    .map(|m| m.content.into_bytes())
+                    .unwrap_or_default(),
+            });
+        }
+
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+/// Read every asset of a package into memory, nothing touches the disk.
+/// Links, special files and directories that are no guid are left out. The
+/// sizes announced by the archive are checked against limits before an entry
+/// is read, pathnames and metas are read up to `MAX_TEXT_BYTES`. The assets
+/// are sorted by their relative path.
+pub(crate) fn unpack_to_memory<R: BufRead>(
+    reader: R,
+    limits: UnpackLimits,
+) -> Result<Vec<MemoryAsset>, UnityPackageReaderError> {
+    let mut visitor = MemoryAssets::default();
+    package_listing::walk_directories(reader, Some(limits), true, &mut visitor)?;
+
+    let mut result = visitor.assets;
+    result.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+
+    Ok(result)
+}
@@ -0,0 +1,21 @@
+/// Decides what happens when an asset is about to be copied to a location
+/// in the target directory that already exists.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OverwritePolicy {
+    /// Replace the existing file (and its meta file).
+    #[default]
+    Overwrite,
+    /// Keep the existing file. The asset is still recorded, but marked as skipped.
+    Skip,
+    /// Abort with `UnityPackageReaderError::TargetFileExists`.
+    Error,
+    /// Compare the hashes of the existing asset and meta with the incoming
+    /// ones and only write the files that differ, so identical files keep
+    /// their mtime. An asset whose files all match is marked as unchanged.
+    IfChanged,
+}
@@ -0,0 +1,99 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{guid::Guid, package_listing::HashedEntry, unpacker_error::UnityPackageReaderError};
+
+/// An asset that exists in one package only, or in both with another payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChangedAsset {
+    pub guid: Guid,
+    pub path: PathBuf,
+}
+
+/// An asset whose guid exists in both packages under different paths.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MovedAsset {
+    pub guid: Guid,
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// The differences between two packages, matched by guid. All lists are
+/// sorted by path. See `UnityPackage::diff`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackageDiff {
+    /// Assets that only the newer package contains.
+    pub added: Vec<ChangedAsset>,
+    /// Assets that only the older package contains.
+    pub removed: Vec<ChangedAsset>,
+    /// Assets with the same guid but another relative path.
+    pub moved: Vec<MovedAsset>,
+    /// Assets with the same guid, but a different payload, with their path
+    /// in the newer package. An asset that moved as well is part of `moved`, too.
+    pub modified: Vec<ChangedAsset>,
+}
+
+impl PackageDiff {
+    /// True, if both packages contain the same assets.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.moved.is_empty()
+            && self.modified.is_empty()
+    }
+}
+
+pub(crate) fn build_diff(
+    old: Vec<HashedEntry>,
+    new: Vec<HashedEntry>,
+) -> Result<PackageDiff, UnityPackageReaderError> {
+    let mut old: HashMap<Guid, HashedEntry> = old
+        .into_iter()
+        .map(|e| Ok((e.0.guid.parse()?, e)))
+        .collect::<Result<_, UnityPackageReaderError>>()?;
+
+    let mut diff = PackageDiff::default();
+    for (entry, hash) in new {
+        let guid: Guid = entry.guid.parse()?;
+        let (previous, previous_hash) = match old.remove(&guid) {
+            Some(p) => p,
+            None => {
+                diff.added.push(ChangedAsset {
+                    guid,
+                    path: entry.relative_path,
+                });
+                continue;
+            }
+        };
+
+        if previous_hash != hash {
+            diff.modified.push(ChangedAsset {
+                guid,
+                path: entry.relative_path.clone(),
+            });
+        }
+        if previous.relative_path != entry.relative_path {
+            diff.moved.push(MovedAsset {
+                guid,
+                from: previous.relative_path,
+                to: entry.relative_path,
+            });
+        }
+    }
+
+    diff.removed = old
+        .into_iter()
+        .map(|(guid, (e, _))| ChangedAsset {
+            guid,
+            path: e.relative_path,
+        })
+        .collect();
+
+    diff.added.sort_by(|a, b| a.path.cmp(&b.path));
+    diff.removed.sort_by(|a, b| a.path.cmp(&b.path));
+    diff.moved.sort_by(|a, b| a.to.cmp(&b.to));
+    diff.modified.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(diff)
+}
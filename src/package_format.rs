@@ -0,0 +1,201 @@
+use flate2::read::{GzDecoder, MultiGzDecoder};
+use std::io::{self, BufRead, Read};
+
+#[cfg(feature = "parallel-gzip")]
+use crate::parallel_gzip::BlockDecoder;
+use crate::{
+    tar_stream,
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// How the tar archive of a package is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PackageFormat {
+    /// A gzip compressed tar archive, which is what Unity writes.
+    Gzip,
+    /// A gzip file made of independent members that carry their compressed
+    /// size (BGZF, as written by `bgzip`). With the `parallel-gzip` feature
+    /// the members are decompressed on all cores, if there is more than one.
+    BlockGzip,
+    /// A plain tar archive, written by some export tools.
+    Tar,
+}
+
+/// Look at the first bytes of reader, without consuming them, to find out
+/// how the package is stored.
+pub(crate) fn sniff<R: BufRead>(reader: &mut R) -> Result<PackageFormat, UnityPackageReaderError> {
+    let start = match reader.fill_buf() {
+        Ok(b) => b,
+        Err(e) => {
+            return Err(UnityPackageReaderError::CorruptPackage(
+                ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+            ));
+        }
+    };
+
+    if start.starts_with(&GZIP_MAGIC) {
+        return match block_size(start) {
+            Some(_) => Ok(PackageFormat::BlockGzip),
+            None => Ok(PackageFormat::Gzip),
+        };
+    }
+
+    if start.len() >= 512 && tar_stream::is_valid_header(&start[..512]) {
+        return Ok(PackageFormat::Tar);
+    }
+
+    let magic: Vec<String> = start.iter().take(4).map(|b| format!("{:02x}", b)).collect();
+    Err(UnityPackageReaderError::CorruptPackage(
+        ErrorInformation::new(
+            Some(format!(
+                "Unknown package format, expected gzip (1f 8b) or tar, found '{}'",
+                magic.join(" ")
+            )),
+            file!(),
+            line!(),
+        ),
+    ))
+}
+
+/// The total size of the gzip member starting with header, if it is a BGZF
+/// block (its extra field has a `BC` subfield with the size). None for other
+/// gzip members and for headers that are cut off.
+pub(crate) fn block_size(header: &[u8]) -> Option<usize> {
+    const FEXTRA: u8 = 0x04;
+    if header.len() < 12 || !header.starts_with(&GZIP_MAGIC) || header[3] & FEXTRA == 0 {
+        return None;
+    }
+
+    let xlen = u16::from_le_bytes([header[10], header[11]]) as usize;
+    let mut extra = header.get(12..12 + xlen)?;
+    while extra.len() >= 4 {
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let data = extra.get(4..4 + len)?;
+        if extra[..2] == *b"BC" && len == 2 {
+            return Some(u16::from_le_bytes([data[0], data[1]]) as usize + 1);
+        }
+        extra = &extra[4 + len..];
+    }
+
+    None
+}
+
+/// The tar stream of a package, decompressed if necessary.
+pub(crate) enum Decompressed<R> {
+    Gzip(GzDecoder<R>),
+    BlockGzip(MultiGzDecoder<R>),
+    #[cfg(feature = "parallel-gzip")]
+    ParallelGzip(BlockDecoder<R>),
+    Tar(R),
+}
+
+impl<R: Read> Decompressed<R> {
+    pub(crate) fn new(format: PackageFormat, reader: R) -> Self {
+        match format {
+            PackageFormat::Gzip => Decompressed::Gzip(GzDecoder::new(reader)),
+            #[cfg(feature = "parallel-gzip")]
+            PackageFormat::BlockGzip if BlockDecoder::<R>::threads() > 1 => {
+                Decompressed::ParallelGzip(BlockDecoder::new(reader))
+            }
+            PackageFormat::BlockGzip => Decompressed::BlockGzip(MultiGzDecoder::new(reader)),
+            PackageFormat::Tar => Decompressed::Tar(reader),
+        }
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        match self {
+            Decompressed::Gzip(d) => d.into_inner(),
+            Decompressed::BlockGzip(d) => d.into_inner(),
+            #[cfg(feature = "parallel-gzip")]
+            Decompressed::ParallelGzip(d) => d.into_inner(),
+            Decompressed::Tar(r) => r,
+        }
+    }
+}
+
+impl<R: Read> Read for Decompressed<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Decompressed::Gzip(d) => d.read(buf),
+            Decompressed::BlockGzip(d) => d.read(buf),
+            #[cfg(feature = "parallel-gzip")]
+            Decompressed::ParallelGzip(d) => d.read(buf),
+            Decompressed::Tar(r) => r.read(buf),
+        }
+    }
+}
+
+/// Sniff the format of reader and return its tar stream.
+pub(crate) fn open<R: BufRead>(mut reader: R) -> Result<Decompressed<R>, UnityPackageReaderError> {
+    let format = sniff(&mut reader)?;
+    Ok(Decompressed::new(format, reader))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_sniff() {
+        let package = sample_package();
+        assert_eq!(
+            sniff(&mut &package.bytes()[..]).unwrap(),
+            PackageFormat::Gzip
+        );
+        assert_eq!(
+            sniff(&mut &package.tar_bytes()[..]).unwrap(),
+            PackageFormat::Tar
+        );
+        assert_eq!(
+            sniff(&mut &package.block_bytes(1000)[..]).unwrap(),
+            PackageFormat::BlockGzip
+        );
+
+        match sniff(&mut &b"PK\x03\x04 a zip file"[..]) {
+            Err(UnityPackageReaderError::CorruptPackage(e)) => {
+                assert!(e.message.unwrap().contains("50 4b 03 04"))
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_open_reads_all_formats() {
+        let package = sample_package();
+        for bytes in [
+            package.bytes(),
+            package.block_bytes(1000),
+            package.tar_bytes(),
+        ] {
+            let mut tar = Vec::new();
+            open(&bytes[..]).unwrap().read_to_end(&mut tar).unwrap();
+            assert_eq!(tar, package.tar_bytes());
+        }
+    }
+
+    #[test]
+    fn test_block_gzip_errors() {
+        let package = sample_package();
+        let mut bytes = package.block_bytes(1000);
+        let first = block_size(&bytes).unwrap();
+        assert!(bytes[first..].starts_with(&GZIP_MAGIC));
+        assert_eq!(block_size(&package.bytes()), None);
+        assert_eq!(block_size(&bytes[..14]), None);
+
+        // A block whose checksum does not match fails like a damaged gzip
+        // file does.
+        bytes[first - 8] ^= 0xff;
+        assert!(open(&bytes[..])
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .is_err());
+
+        // A cut off package.
+        let bytes = package.block_bytes(1000);
+        let cut = &bytes[..bytes.len() - 100];
+        assert!(open(cut).unwrap().read_to_end(&mut Vec::new()).is_err());
+    }
+}
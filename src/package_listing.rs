@@ -0,0 +1,690 @@
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, BufRead, Read, Write},
+    ops::ControlFlow,
+    path::{Component, Path, PathBuf},
+};
+use tar::{Archive, Builder};
+
+use crate::{
+    guid::Guid,
+    package_format, unity_asset_file,
+    unity_meta::{self, LEGACY_META_FILE},
+    unpack_limits::{self, LimitTracker, UnpackLimits},
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// What is known about an asset from reading the archive, without extracting it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntrySummary {
+    pub guid: String,
+    /// The content of the pathname file, with forward slashes.
+    pub relative_path: PathBuf,
+    /// Size of the asset payload in bytes (0 for folders).
+    pub size: u64,
+    pub is_folder: bool,
+    /// True, if the package contains a `preview.png` for the asset.
+    pub has_preview: bool,
+    /// True, if the package contains an `asset.meta` for the asset.
+    pub has_meta: bool,
+}
+
+/// An entry of the package, with the sha256 of its payload if it has one.
+pub(crate) type HashedEntry = (EntrySummary, Option<[u8; 32]>);
+
+/// The header fields of an entry of a guid directory.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct EntryInfo {
+    pub(crate) size: u64,
+    pub(crate) mtime: u64,
+    /// The permission bits.
+    pub(crate) mode: u32,
+}
+
+/// The meta file of a guid directory, see `walk_directories`.
+pub(crate) struct MetaEntry {
+    pub(crate) content: String,
+    pub(crate) info: EntryInfo,
+}
+
+/// A guid directory with a pathname, handed to `DirectoryVisitor::directory`
+/// once all its files have been read.
+pub(crate) struct GuidDirectory<T> {
+    /// The name of the directory, which need not be a guid.
+    pub(crate) guid: String,
+    /// The first line of the pathname file, with forward slashes. Never empty.
+    pub(crate) pathname: String,
+    /// Size of the payload in bytes (0 without one).
+    pub(crate) size: u64,
+    pub(crate) is_folder: bool,
+    pub(crate) has_preview: bool,
+    pub(crate) has_meta: bool,
+    /// The meta file, only kept if `walk_directories` is asked to.
+    pub(crate) meta: Option<MetaEntry>,
+    /// The mtime of the entry of the directory itself, if the archive has one.
+    pub(crate) mtime: Option<u64>,
+    /// What the visitor has collected from the other files.
+    pub(crate) state: T,
+}
+
+/// What `walk_directories` does with a package besides collecting pathnames
+/// and metas.
+pub(crate) trait DirectoryVisitor {
+    /// Collected per guid directory from its files, e.g. the payload.
+    type State: Default;
+
+    /// Called for every file of a guid directory but the pathname and the
+    /// meta, e.g. `asset` or `preview.png`. Whatever is not read of content
+    /// is skipped.
+    fn file(
+        &mut self,
+        guid: &str,
+        name: &str,
+        info: EntryInfo,
+        content: &mut dyn Read,
+        state: &mut Self::State,
+    ) -> Result<(), UnityPackageReaderError>;
+
+    /// Called once a guid directory is complete. `ControlFlow::Break` stops
+    /// reading the archive.
+    fn directory(
+        &mut self,
+        directory: GuidDirectory<Self::State>,
+    ) -> Result<ControlFlow<()>, UnityPackageReaderError>;
+}
+
+/// The parts of a guid directory collected so far.
+struct PendingDirectory<T> {
+    pathname: Option<String>,
+    size: u64,
+    is_folder: bool,
+    has_preview: bool,
+    has_meta: bool,
+    meta: Option<MetaEntry>,
+    mtime: Option<u64>,
+    state: T,
+}
+
+impl<T: Default> Default for PendingDirectory<T> {
+    fn default() -> Self {
+        PendingDirectory {
+            pathname: None,
+            size: 0,
+            is_folder: false,
+            has_preview: false,
+            has_meta: false,
+            meta: None,
+            mtime: None,
+            state: T::default(),
+        }
+    }
+}
+
+impl<T> PendingDirectory<T> {
+    fn into_directory(self, guid: String) -> Option<GuidDirectory<T>> {
+        let pathname = self.pathname.filter(|p| !p.is_empty())?;
+        Some(GuidDirectory {
+            guid,
+            pathname,
+            size: self.size,
+            is_folder: self.is_folder,
+            has_preview: self.has_preview,
+            has_meta: self.has_meta,
+            meta: self.meta,
+            mtime: self.mtime,
+            state: self.state,
+        })
+    }
+}
+
+/// Split an archive path like `<guid>/asset` into guid and file name. The
+/// file name is None for the guid directory itself.
+fn split_entry_path(path: &Path) -> Option<(String, Option<String>)> {
+    let mut components = path
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir));
+
+    let guid = match components.next() {
+        Some(Component::Normal(g)) => g.to_str()?.to_string(),
+        _ => return None,
+    };
+
+    let name = match components.next() {
+        Some(Component::Normal(n)) => Some(n.to_str()?.to_string()),
+        None => None,
+        _ => return None,
+    };
+
+    if components.next().is_some() {
+        return None;
+    }
+
+    Some((guid, name))
+}
+
+/// The streaming core every reader of packages that does not extract them is
+/// built on. Streams through a package (gzip compressed or plain tar), reads
+/// the pathname and the meta of every guid directory and hands its other
+/// files to visitor. The files of a guid directory are expected to be stored
+/// next to each other, so a directory is handed out as soon as the archive
+/// moves on to the next guid; directories that are not stored contiguously
+/// follow at the end, ordered by name. Directories without a pathname are
+/// left out, so are links and other entries that are no regular files. With
+/// limits, every entry is checked against them before it is read. With
+/// keep_metas the metas are kept in `GuidDirectory::meta`. Text entries are
+/// read up to `MAX_TEXT_BYTES`.
+pub(crate) fn walk_directories<R: BufRead, V: DirectoryVisitor>(
+    reader: R,
+    limits: Option<UnpackLimits>,
+    keep_metas: bool,
+    visitor: &mut V,
+) -> Result<(), UnityPackageReaderError> {
+    let mut archive = Archive::new(package_format::open(reader)?);
+    let entries = match archive.entries() {
+        Ok(e) => e,
+        Err(e) => return Err(corrupt(e)),
+    };
+
+    let mut tracker = limits.map(LimitTracker::new);
+    let mut pending: HashMap<String, PendingDirectory<V::State>> = HashMap::new();
+    let mut current: Option<String> = None;
+
+    for entry in entries {
+        let mut entry = match entry {
+            Ok(e) => e,
+            Err(e) => return Err(corrupt(e)),
+        };
+        if let Some(tracker) = tracker.as_mut() {
+            tracker.add_entry(entry.size())?;
+        }
+
+        let entry_type = entry.header().entry_type();
+        let (guid, name) = match entry.path() {
+            Ok(p) => match split_entry_path(&p) {
+                Some((_, None)) if !entry_type.is_dir() => continue,
+                Some(e) => e,
+                None => continue,
+            },
+            Err(e) => return Err(corrupt(e)),
+        };
+
+        if current.as_ref() != Some(&guid) {
+            if let Some(previous) = current.take() {
+                if let Some(directory) = pending
+                    .remove(&previous)
+                    .and_then(|p| p.into_directory(previous))
+                {
+                    if visitor.directory(directory)?.is_break() {
+                        return Ok(());
+                    }
+                }
+            }
+            current = Some(guid.clone());
+        }
+
+        let info = EntryInfo {
+            size: entry.size(),
+            mtime: entry.header().mtime().unwrap_or(0),
+            mode: entry.header().mode().unwrap_or(0o644) & 0o777,
+        };
+        let state = pending.entry(guid.clone()).or_default();
+        let name = match name {
+            Some(n) if entry_type.is_file() => n,
+            Some(_) => continue,
+            None => {
+                state.mtime = Some(info.mtime);
+                continue;
+            }
+        };
+
+        match name.as_str() {
+            "pathname" => {
+                let content = unpack_limits::read_text(&mut entry).map_err(corrupt)?;
+                state.pathname = Some(unity_asset_file::normalize_pathname(
+                    unity_asset_file::trim_pathname(&content),
+                ));
+            }
+            "asset.meta" | LEGACY_META_FILE => {
+                let content = unpack_limits::read_text(&mut entry).map_err(corrupt)?;
+                state.is_folder = unity_meta::is_folder_meta(&content);
+                state.has_meta = true;
+                if keep_metas {
+                    state.meta = Some(MetaEntry { content, info });
+                }
+            }
+            _ => {
+                match name.as_str() {
+                    "asset" => state.size = info.size,
+                    "preview.png" => state.has_preview = true,
+                    _ => {}
+                }
+                visitor.file(&guid, &name, info, &mut entry, &mut state.state)?;
+            }
+        }
+    }
+
+    // Whatever is left, including guid directories that were not stored contiguously.
+    let mut rest: Vec<GuidDirectory<V::State>> = pending
+        .into_iter()
+        .filter_map(|(guid, p)| p.into_directory(guid))
+        .collect();
+    rest.sort_by(|a, b| a.guid.cmp(&b.guid));
+
+    for directory in rest {
+        if visitor.directory(directory)?.is_break() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Hands the summaries of `walk_directories` to a callback, with the sha256
+/// of the payload if payloads are hashed.
+struct Summaries<F> {
+    hash_payloads: bool,
+    f: F,
+}
+
+impl<F: FnMut(&EntrySummary, Option<[u8; 32]>) -> ControlFlow<()>> DirectoryVisitor
+    for Summaries<F>
+{
+    type State = Option<[u8; 32]>;
+
+    fn file(
+        &mut self,
+        _guid: &str,
+        name: &str,
+        _info: EntryInfo,
+        content: &mut dyn Read,
+        state: &mut Self::State,
+    ) -> Result<(), UnityPackageReaderError> {
+        if self.hash_payloads && name == "asset" {
+            let mut hasher = Sha256::new();
+            io::copy(content, &mut hasher).map_err(corrupt)?;
+            *state = Some(hasher.finalize().into());
+        }
+
+        Ok(())
+    }
+
+    fn directory(
+        &mut self,
+        directory: GuidDirectory<Self::State>,
+    ) -> Result<ControlFlow<()>, UnityPackageReaderError> {
+        let summary = EntrySummary {
+            guid: directory.guid,
+            relative_path: PathBuf::from(directory.pathname),
+            size: directory.size,
+            is_folder: directory.is_folder,
+            has_preview: directory.has_preview,
+            has_meta: directory.has_meta,
+        };
+
+        Ok((self.f)(&summary, directory.state))
+    }
+}
+
+/// Stream through a package (gzip compressed or plain tar) and call f for every asset.
+/// The files of a guid directory are expected to be stored next to each other,
+/// so a summary is handed out as soon as the archive moves on to the next guid.
+/// Returning `ControlFlow::Break` stops reading the archive immediately.
+pub(crate) fn visit_entries<R: BufRead>(
+    reader: R,
+    mut f: impl FnMut(&EntrySummary) -> ControlFlow<()>,
+) -> Result<(), UnityPackageReaderError> {
+    let mut summaries = Summaries {
+        hash_payloads: false,
+        f: |summary: &EntrySummary, _| f(summary),
+    };
+
+    walk_directories(reader, None, false, &mut summaries)
+}
+
+/// Every asset together with the sha256 of its payload (None for assets
+/// without one, e.g. folders). The payloads are hashed while streaming.
+pub(crate) fn hash_entries<R: BufRead>(
+    reader: R,
+) -> Result<Vec<HashedEntry>, UnityPackageReaderError> {
+    let mut result = Vec::new();
+    let mut summaries = Summaries {
+        hash_payloads: true,
+        f: |summary: &EntrySummary, hash| {
+            result.push((summary.clone(), hash));
+            ControlFlow::Continue(())
+        },
+    };
+    walk_directories(reader, None, false, &mut summaries)?;
+
+    Ok(result)
+}
+
+fn corrupt(e: io::Error) -> UnityPackageReaderError {
+    UnityPackageReaderError::CorruptPackage(ErrorInformation::new(
+        Some(format!("{}", e)),
+        file!(),
+        line!(),
+    ))
+}
+
+/// Stream through a package and write every `preview.png` of a guid
+/// directory to `<dir>/<guid>.png`. Nothing else is extracted. The entries
+/// count against limits like an extraction does. Returns the number of
+/// previews written.
+pub(crate) fn extract_previews<R: BufRead>(
+    reader: R,
+    dir: &Path,
+    limits: UnpackLimits,
+) -> Result<usize, UnityPackageReaderError> {
+    let mut archive = Archive::new(package_format::open(reader)?);
+    let entries = match archive.entries() {
+        Ok(e) => e,
+        Err(e) => {
+            return Err(UnityPackageReaderError::CorruptPackage(
+                ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+            ));
+        }
+    };
+
+    if let Err(e) = fs::create_dir_all(dir) {
+        return Err(UnityPackageReaderError::TargetDirectoryCouldNotBeCreated(
+            ErrorInformation::new(Some(format!("{:?}: {}", dir, e)), file!(), line!()),
+        ));
+    }
+
+    let mut tracker = LimitTracker::new(limits);
+    let mut written = 0;
+    for entry in entries {
+        let mut entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                return Err(UnityPackageReaderError::CorruptPackage(
+                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                ));
+            }
+        };
+        tracker.add_entry(entry.size())?;
+
+        // The directory name ends up in the file name, only guids are safe.
+        let guid = match entry.path().ok().and_then(|p| split_entry_path(&p)) {
+            Some((guid, Some(name))) if name == "preview.png" => match guid.parse::<Guid>() {
+                Ok(g) => g,
+                Err(_) => continue,
+            },
+            _ => continue,
+        };
+
+        let destination = dir.join(format!("{}.png", guid));
+        let copied = fs::File::create(&destination).and_then(|mut f| io::copy(&mut entry, &mut f));
+        if let Err(e) = copied {
+            return Err(UnityPackageReaderError::CopyFailed(ErrorInformation::new(
+                Some(format!("{:?}: {}", destination, e)),
+                file!(),
+                line!(),
+            )));
+        }
+        written += 1;
+    }
+
+    Ok(written)
+}
+
+/// Stream through a package and append every entry of the guid directories in
+/// keep to builder, with its original header and content. Paths that need a
+/// GNU long name are written with one, the PAX records of an entry are
+/// written in front of it again. Returns the number of entries copied.
+pub(crate) fn copy_entries<R: BufRead, W: Write>(
+    reader: R,
+    keep: &HashSet<String>,
+    builder: &mut Builder<W>,
+) -> Result<usize, UnityPackageReaderError> {
+    let mut archive = Archive::new(package_format::open(reader)?);
+    let entries = match archive.entries() {
+        Ok(e) => e,
+        Err(e) => {
+            return Err(UnityPackageReaderError::CorruptPackage(
+                ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+            ));
+        }
+    };
+
+    let mut copied = 0;
+    for entry in entries {
+        let mut entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                return Err(UnityPackageReaderError::CorruptPackage(
+                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                ));
+            }
+        };
+
+        // The path and link name including the GNU and PAX extensions, which
+        // the header of the entry itself only holds truncated.
+        let names = entry.path().map(|p| p.into_owned()).and_then(|path| {
+            let link = entry.link_name()?.map(|l| l.into_owned());
+            Ok((path, link))
+        });
+        let (path, link) = match names {
+            Ok(n) => n,
+            Err(e) => {
+                return Err(UnityPackageReaderError::CorruptPackage(
+                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                ));
+            }
+        };
+
+        // The guid directory entries themselves have no file name.
+        let guid = match path.components().find(|c| !matches!(c, Component::CurDir)) {
+            Some(Component::Normal(g)) => g.to_string_lossy().into_owned(),
+            _ => continue,
+        };
+
+        if !keep.contains(&guid) {
+            continue;
+        }
+
+        let records = match pax_records(&mut entry) {
+            Ok(r) => r,
+            Err(e) => {
+                return Err(UnityPackageReaderError::CorruptPackage(
+                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                ));
+            }
+        };
+
+        let mut header = entry.header().clone();
+        header.set_size(entry.size());
+        let appended = records
+            .map_or(Ok(()), |records| {
+                let mut pax = tar::Header::new_ustar();
+                pax.set_entry_type(tar::EntryType::XHeader);
+                pax.set_mode(0o644);
+                pax.set_size(records.len() as u64);
+                builder.append_data(&mut pax, "PaxHeader", &records[..])
+            })
+            .and_then(|_| match &link {
+                Some(link) => builder.append_link(&mut header, &path, link),
+                None => builder.append_data(&mut header, &path, entry),
+            });
+        if let Err(e) = appended {
+            return Err(UnityPackageReaderError::CouldNotWritePackage(
+                ErrorInformation::new(Some(format!("{:?}: {}", path, e)), file!(), line!()),
+            ));
+        }
+        copied += 1;
+    }
+
+    Ok(copied)
+}
+
+/// The PAX records of entry, encoded for an extended header, None if it has none.
+fn pax_records<R: Read>(entry: &mut tar::Entry<R>) -> io::Result<Option<Vec<u8>>> {
+    let extensions = match entry.pax_extensions()? {
+        Some(e) => e,
+        None => return Ok(None),
+    };
+
+    let mut result = Vec::new();
+    for extension in extensions {
+        let extension = extension?;
+        let (key, value) = (extension.key_bytes(), extension.value_bytes());
+        // `<length> <key>=<value>\n`, the length counts its own digits as well.
+        let rest = key.len() + value.len() + 3;
+        let mut length = rest + 1;
+        while rest + length.to_string().len() != length {
+            length = rest + length.to_string().len();
+        }
+        result.extend_from_slice(format!("{} ", length).as_bytes());
+        result.extend_from_slice(key);
+        result.push(b'=');
+        result.extend_from_slice(value);
+        result.push(b'\n');
+    }
+
+    Ok(Some(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{counting::CountingReader, test_utils::*};
+
+    /// Poorly compressible content, so the compressed size reflects the payload.
+    fn noise(seed: u32, len: usize) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(2_654_435_761).wrapping_add(1);
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn count_bytes_read(bytes: &[u8], stop_after: Option<usize>) -> (u64, usize) {
+        let mut reader = CountingReader::new(bytes);
+
+        let mut visited = 0;
+        visit_entries(std::io::BufReader::new(&mut reader), |_| {
+            visited += 1;
+            if Some(visited) == stop_after {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .unwrap();
+
+        (reader.count(), visited)
+    }
+
+    #[test]
+    fn test_visit_entries_summaries() {
+        let mut summaries = Vec::new();
+        visit_entries(&sample_package().bytes()[..], |s| {
+            summaries.push(s.clone());
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+
+        assert_eq!(summaries.len(), 3);
+        let texture = summaries.iter().find(|s| s.guid == TEXTURE_GUID).unwrap();
+        assert_eq!(texture.relative_path, PathBuf::from(TEXTURE_PATH));
+        assert_eq!(texture.size, TEXTURE_CONTENT.len() as u64);
+        assert!(!texture.is_folder);
+
+        let folder = summaries.iter().find(|s| s.guid == FOLDER_GUID).unwrap();
+        assert!(folder.is_folder);
+        assert_eq!(folder.size, 0);
+        assert!(!folder.has_preview);
+    }
+
+    #[test]
+    fn test_extract_previews() {
+        let bytes = PackageBuilder::new()
+            .folder(FOLDER_GUID, FOLDER_PATH)
+            .asset(
+                TEXTURE_GUID,
+                TEXTURE_PATH,
+                TEXTURE_CONTENT,
+                &texture_meta(TEXTURE_GUID),
+            )
+            .file(&format!("{}/preview.png", TEXTURE_GUID), b"\x89PNG")
+            .file("not-a-guid/preview.png", b"\x89PNG")
+            .bytes();
+
+        let mut summaries = Vec::new();
+        visit_entries(&bytes[..], |s| {
+            summaries.push(s.clone());
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        let with_preview: Vec<&str> = summaries
+            .iter()
+            .filter(|s| s.has_preview)
+            .map(|s| s.guid.as_str())
+            .collect();
+        assert_eq!(with_preview, vec![TEXTURE_GUID]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let previews = dir.path().join("previews");
+        assert_eq!(
+            extract_previews(&bytes[..], &previews, UnpackLimits::default()).unwrap(),
+            1
+        );
+        assert_eq!(
+            fs::read(previews.join(format!("{}.png", TEXTURE_GUID))).unwrap(),
+            b"\x89PNG"
+        );
+        assert_eq!(fs::read_dir(&previews).unwrap().count(), 1);
+
+        let limits = UnpackLimits {
+            max_entries: 2,
+            ..Default::default()
+        };
+        match extract_previews(&bytes[..], &previews, limits) {
+            Err(UnityPackageReaderError::LimitExceeded(_)) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_oversized_text_entries() {
+        let pathname = "a".repeat(unpack_limits::MAX_TEXT_BYTES as usize + 1);
+        let bytes = PackageBuilder::new()
+            .asset(TEXTURE_GUID, &pathname, b"", &texture_meta(TEXTURE_GUID))
+            .bytes();
+
+        let result = visit_entries(&bytes[..], |_| ControlFlow::Continue(()));
+        assert!(matches!(
+            result,
+            Err(UnityPackageReaderError::CorruptPackage(_))
+        ));
+    }
+
+    #[test]
+    fn test_break_stops_reading() {
+        let mut builder = PackageBuilder::new();
+        for i in 0..32u32 {
+            let guid = format!("{:032x}", i);
+            builder = builder.asset(
+                &guid,
+                &format!("Assets/Noise/{}.bin", i),
+                &noise(i, 64 * 1024),
+                &texture_meta(&guid),
+            );
+        }
+        let bytes = builder.bytes();
+
+        let (full, visited) = count_bytes_read(&bytes, None);
+        assert_eq!(visited, 32);
+
+        let (partial, visited) = count_bytes_read(&bytes, Some(1));
+        assert_eq!(visited, 1);
+        assert!(partial * 8 < full, "{} vs {}", partial, full);
+    }
+}
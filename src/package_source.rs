@@ -0,0 +1,227 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, ErrorKind, Read},
+    path::Path,
+    sync::Arc,
+};
+
+use crate::unpacker_error::{ErrorInformation, UnityPackageReaderError};
+
+/// The package file, either read through a buffer or (with the `mmap`
+/// feature and `UnityPackage::with_memory_map`) mapped into memory, or the
+/// content of a package created with `UnityPackage::from_bytes`.
+pub(crate) enum PackageSource {
+    File(BufReader<fs::File>),
+    Memory(io::Cursor<Arc<[u8]>>),
+    #[cfg(feature = "mmap")]
+    Mapped(MappedFile),
+}
+
+impl PackageSource {
+    /// Open the package file for streaming through a buffer of capacity
+    /// bytes. With the `mmap` feature and map set the file is mapped
+    /// instead, unless it is empty (which cannot be mapped). map is ignored
+    /// without the feature.
+    pub(crate) fn open(
+        path: &Path,
+        capacity: usize,
+        map: bool,
+    ) -> Result<Self, UnityPackageReaderError> {
+        let file = match fs::File::open(path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Err(UnityPackageReaderError::PackageNotFound(
+                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                ))
+            }
+            Err(e) => {
+                return Err(UnityPackageReaderError::CorruptPackage(
+                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                ))
+            }
+        };
+
+        #[cfg(feature = "mmap")]
+        if map && file.metadata().map(|m| m.len() > 0).unwrap_or(false) {
+            return MappedFile::new(file).map(PackageSource::Mapped);
+        }
+
+        #[cfg(not(feature = "mmap"))]
+        let _ = map;
+        Ok(PackageSource::File(BufReader::with_capacity(
+            capacity, file,
+        )))
+    }
+}
+
+impl Read for PackageSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            PackageSource::File(f) => f.read(buf),
+            PackageSource::Memory(m) => m.read(buf),
+            #[cfg(feature = "mmap")]
+            PackageSource::Mapped(m) => m.read(buf),
+        }
+    }
+}
+
+impl BufRead for PackageSource {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            PackageSource::File(f) => f.fill_buf(),
+            PackageSource::Memory(m) => m.fill_buf(),
+            #[cfg(feature = "mmap")]
+            PackageSource::Mapped(m) => m.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amount: usize) {
+        match self {
+            PackageSource::File(f) => f.consume(amount),
+            PackageSource::Memory(m) => m.consume(amount),
+            #[cfg(feature = "mmap")]
+            PackageSource::Mapped(m) => m.consume(amount),
+        }
+    }
+}
+
+/// A memory mapped package file. Touching a page of the mapping that is no
+/// longer backed by the file (because it has been truncated in the meantime)
+/// kills the process. The size of the file is checked before every chunk is
+/// handed out and a shrunken file is reported as `InvalidData`, which only
+/// narrows the window: a truncation after the check is not noticed. That is
+/// why mapping is opt-in through the unsafe `UnityPackage::with_memory_map`.
+#[cfg(feature = "mmap")]
+pub(crate) struct MappedFile {
+    map: memmap2::Mmap,
+    file: fs::File,
+    position: usize,
+    /// The end of the chunk that has been checked against the file size.
+    checked: usize,
+}
+
+#[cfg(feature = "mmap")]
+impl MappedFile {
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    fn new(file: fs::File) -> Result<Self, UnityPackageReaderError> {
+        // Safety: the caller of `UnityPackage::with_memory_map` guarantees
+        // the file is not truncated or modified while it is mapped. The size
+        // check in fill_buf only catches truncations that happen before a
+        // chunk is handed out.
+        let map = match unsafe { memmap2::Mmap::map(&file) } {
+            Ok(m) => m,
+            Err(e) => {
+                return Err(UnityPackageReaderError::CorruptPackage(
+                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                ))
+            }
+        };
+
+        Ok(MappedFile {
+            map,
+            file,
+            position: 0,
+            checked: 0,
+        })
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl Read for MappedFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+
+        Ok(len)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl BufRead for MappedFile {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position >= self.checked && self.position < self.map.len() {
+            let end = (self.position + Self::CHUNK_SIZE).min(self.map.len());
+            let len = self.file.metadata()?.len();
+            if len < end as u64 {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "the package file has been truncated to {} bytes while reading it",
+                        len
+                    ),
+                ));
+            }
+            self.checked = end;
+        }
+
+        Ok(&self.map[self.position..self.checked.max(self.position)])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.checked);
+    }
+}
+
+#[cfg(all(test, feature = "mmap"))]
+mod tests {
+    use super::*;
+    use crate::{package_listing, test_utils::*};
+    use std::ops::ControlFlow;
+
+    #[test]
+    fn test_mapped_file_reads_everything() {
+        let fixture = Fixture::sample();
+        let source = PackageSource::open(&fixture.package, 8 * 1024, false).unwrap();
+        assert!(matches!(source, PackageSource::File(_)));
+        let mut source = PackageSource::open(&fixture.package, 8 * 1024, true).unwrap();
+        assert!(matches!(source, PackageSource::Mapped(_)));
+
+        let mut content = Vec::new();
+        source.read_to_end(&mut content).unwrap();
+        assert_eq!(content, std::fs::read(&fixture.package).unwrap());
+    }
+
+    #[test]
+    fn test_truncated_after_mapping() {
+        let mut builder = PackageBuilder::new();
+        let mut state = 0x2545_f491u32;
+        for i in 0..64u32 {
+            let guid = format!("{:032x}", i);
+            // Poorly compressible, so the package file is large.
+            let content: Vec<u8> = (0..16 * 1024)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                })
+                .collect();
+            builder = builder.asset(
+                &guid,
+                &format!("Assets/{}.bin", i),
+                &content,
+                &texture_meta(&guid),
+            );
+        }
+        let fixture = Fixture::new(&builder);
+        let size = std::fs::metadata(&fixture.package).unwrap().len();
+        assert!(size > 4 * MappedFile::CHUNK_SIZE as u64);
+
+        let source = PackageSource::open(&fixture.package, 8 * 1024, true).unwrap();
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&fixture.package)
+            .unwrap()
+            .set_len(size / 2)
+            .unwrap();
+
+        let result = package_listing::visit_entries(source, |_| ControlFlow::Continue(()));
+        assert!(matches!(
+            result,
+            Err(UnityPackageReaderError::CorruptPackage(_))
+        ));
+    }
+}
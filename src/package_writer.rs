@@ -0,0 +1,562 @@
+use flate2::{write::GzEncoder, Compression};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{hash_map::RandomState, BTreeMap},
+    fs,
+    hash::{BuildHasher, Hasher},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    checksums,
+    guid::Guid,
+    prelude::MetaData,
+    unpack_config::is_ignored_by_unity,
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// How `UnityPackageWriter` creates guids for assets without a `.meta` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GuidGeneration {
+    /// Derived from the pathname, so packing the same tree twice yields the
+    /// same package.
+    Deterministic,
+    /// A new guid on every run, like the Unity editor does.
+    Random,
+}
+
+/// Where the `asset.meta` of a pending asset comes from.
+#[derive(Debug, Clone)]
+enum MetaSource {
+    File(PathBuf),
+    Generated(String),
+}
+
+/// An asset that will be written into the package.
+#[derive(Debug, Clone)]
+struct PendingAsset {
+    /// The pathname inside the package (relative to the root, forward slashes).
+    pathname: String,
+    /// The file to store as `asset`, None for folder assets.
+    asset: Option<PathBuf>,
+    meta: MetaSource,
+}
+
+/// Builds a unitypackage from assets and their existing `.meta` files, the
+/// inverse of `UnityPackage::unpack_package`.
+#[derive(Debug, Clone)]
+pub struct UnityPackageWriter {
+    /// The directory the pathnames are relative to, usually the Unity project.
+    root: PathBuf,
+    assets: BTreeMap<Guid, PendingAsset>,
+    /// If set, files and directories without `.meta` get a generated one
+    /// instead of being left out.
+    generate_metas: Option<GuidGeneration>,
+}
+
+impl UnityPackageWriter {
+    /// Pathnames in the package are relative to root, so pass the project
+    /// directory to get the usual `Assets/...` paths.
+    pub fn new(root: &Path) -> Self {
+        UnityPackageWriter {
+            root: root.to_path_buf(),
+            assets: BTreeMap::new(),
+            generate_metas: None,
+        }
+    }
+
+    /// Also pack files and directories that have no `.meta` file. They get a
+    /// minimal meta (`DefaultImporter`) with a guid created as given. Files the
+    /// Unity editor ignores (hidden files, names ending with '~', ...) are
+    /// still left out.
+    pub fn with_generated_metas(mut self, guids: GuidGeneration) -> Self {
+        self.generate_metas = Some(guids);
+        self
+    }
+
+    /// The number of assets added so far.
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+
+    /// Add every file and directory below path (and path itself) that has a
+    /// `.meta` file next to it. Entries without one are not Unity assets and
+    /// are left out, unless metas are generated (see `with_generated_metas`).
+    /// Relative paths are resolved against the root.
+    pub fn add_directory(&mut self, path: &Path) -> Result<(), UnityPackageReaderError> {
+        let path = self.root.join(path);
+        let own_meta = meta_path(&path);
+        if own_meta.is_file() {
+            self.add_file(&path, &own_meta)?;
+        }
+
+        let mut pending = vec![path];
+        while let Some(dir) = pending.pop() {
+            let entries = match fs::read_dir(&dir) {
+                Ok(e) => e,
+                Err(e) => return Err(write_error(&dir, e)),
+            };
+
+            let mut children = Vec::new();
+            for entry in entries {
+                match entry {
+                    Ok(e) => children.push(e.path()),
+                    Err(e) => return Err(write_error(&dir, e)),
+                }
+            }
+            children.sort();
+
+            for child in children {
+                let meta = meta_path(&child);
+                if child.is_dir() {
+                    pending.push(child.clone());
+                }
+
+                if meta.is_file() {
+                    self.add_file(&child, &meta)?;
+                } else if let Some(guids) = self.generate_metas {
+                    self.add_generated(&child, guids)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add a single asset (a file, or a directory for a folder asset) with its
+    /// meta file. The guid is read from the meta file.
+    pub fn add_file(
+        &mut self,
+        asset_path: &Path,
+        meta_path: &Path,
+    ) -> Result<(), UnityPackageReaderError> {
+        let asset_path = self.root.join(asset_path);
+        let meta_path = self.root.join(meta_path);
+
+        let relative = match asset_path.strip_prefix(&self.root) {
+            Ok(r) if r.components().next().is_some() => r,
+            _ => {
+                return Err(UnityPackageReaderError::PathError(ErrorInformation::new(
+                    Some(format!("{:?} is not inside {:?}", asset_path, self.root)),
+                    file!(),
+                    line!(),
+                )));
+            }
+        };
+        let pathname = checksums::to_forward_slashes(relative);
+        let content = match fs::read_to_string(&meta_path) {
+            Ok(c) => c,
+            Err(e) => {
+                return Err(UnityPackageReaderError::CouldReadMetaFile(
+                    ErrorInformation::new(
+                        Some(format!("{:?}: {}", meta_path, e)),
+                        file!(),
+                        line!(),
+                    ),
+                ));
+            }
+        };
+        let meta_data = MetaData::parse(&content)?;
+        let guid: Guid = meta_data.guid.parse()?;
+
+        if let Some(existing) = self.assets.get(&guid) {
+            if existing.pathname != pathname {
+                return Err(UnityPackageReaderError::CouldNotWritePackage(
+                    ErrorInformation::new(
+                        Some(format!(
+                            "guid {} is used by '{}' and '{}'",
+                            guid, existing.pathname, pathname
+                        )),
+                        file!(),
+                        line!(),
+                    ),
+                ));
+            }
+        }
+
+        // Unity only treats a directory as folder asset with `folderAsset: yes`
+        // and would import a file marked like that as an empty folder.
+        let is_folder = asset_path.is_dir();
+        if is_folder != meta_data.folder_asset {
+            return Err(UnityPackageReaderError::CouldNotWritePackage(
+                ErrorInformation::new(
+                    Some(format!(
+                        "'{}': folderAsset in the meta does not match the {}",
+                        pathname,
+                        if is_folder { "directory" } else { "file" }
+                    )),
+                    file!(),
+                    line!(),
+                ),
+            ));
+        }
+
+        let asset = if is_folder {
+            None
+        } else {
+            Some(asset_path.clone())
+        };
+
+        self.assets.insert(
+            guid,
+            PendingAsset {
+                pathname,
+                asset,
+                meta: MetaSource::File(meta_path),
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Add an asset without meta file. Meta files themselves, orphaned ones
+    /// included, and files Unity ignores are skipped.
+    fn add_generated(
+        &mut self,
+        asset_path: &Path,
+        guids: GuidGeneration,
+    ) -> Result<(), UnityPackageReaderError> {
+        let relative = match asset_path.strip_prefix(&self.root) {
+            Ok(r) => r,
+            Err(_) => return Ok(()),
+        };
+
+        let is_meta = relative.extension().is_some_and(|e| e == "meta");
+        if is_meta || is_ignored_by_unity(relative) {
+            return Ok(());
+        }
+
+        let pathname = checksums::to_forward_slashes(relative);
+        let mut attempt = 0u32;
+        let guid = loop {
+            let guid = generate_guid(&pathname, guids, attempt);
+            if !self.assets.contains_key(&guid) {
+                break guid;
+            }
+            attempt += 1;
+        };
+
+        let is_folder = asset_path.is_dir();
+        self.assets.insert(
+            guid,
+            PendingAsset {
+                pathname,
+                asset: if is_folder {
+                    None
+                } else {
+                    Some(asset_path.to_path_buf())
+                },
+                meta: MetaSource::Generated(generated_meta(guid, is_folder)),
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Write the gzip compressed package. Every asset becomes a directory
+    /// named by its guid, holding `asset`, `asset.meta` and `pathname`.
+    pub fn write_to(&self, path: &Path) -> Result<(), UnityPackageReaderError> {
+        let file = match fs::File::create(path) {
+            Ok(f) => f,
+            Err(e) => return Err(write_error(path, e)),
+        };
+
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        for (guid, asset) in &self.assets {
+            let result = append_asset(&mut builder, *guid, asset);
+            if let Err(e) = result {
+                return Err(write_error(path, e));
+            }
+        }
+
+        let finished = builder.into_inner().and_then(|encoder| encoder.finish());
+        match finished {
+            Ok(_) => Ok(()),
+            Err(e) => Err(write_error(path, e)),
+        }
+    }
+}
+
+fn append_asset<W: io::Write>(
+    builder: &mut tar::Builder<W>,
+    guid: Guid,
+    asset: &PendingAsset,
+) -> io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Directory);
+    header.set_size(0);
+    header.set_mode(0o755);
+    header.set_cksum();
+    builder.append_data(&mut header, format!("{}/", guid), io::empty())?;
+
+    if let Some(source) = &asset.asset {
+        let file = fs::File::open(source)?;
+        let size = file.metadata()?.len();
+        append(builder, &format!("{}/asset", guid), size, file)?;
+    }
+
+    let name = format!("{}/asset.meta", guid);
+    match &asset.meta {
+        MetaSource::File(path) => {
+            let meta = fs::File::open(path)?;
+            let size = meta.metadata()?.len();
+            append(builder, &name, size, meta)?;
+        }
+        MetaSource::Generated(content) => {
+            append(builder, &name, content.len() as u64, content.as_bytes())?;
+        }
+    }
+
+    let pathname = asset.pathname.as_bytes();
+    append(
+        builder,
+        &format!("{}/pathname", guid),
+        pathname.len() as u64,
+        pathname,
+    )
+}
+
+fn append<W: io::Write>(
+    builder: &mut tar::Builder<W>,
+    name: &str,
+    size: u64,
+    content: impl Read,
+) -> io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, name, content)
+}
+
+/// A guid for pathname. Deterministic guids hash the pathname (and attempt,
+/// to get out of a collision); random ones mix in the time and a random seed.
+fn generate_guid(pathname: &str, guids: GuidGeneration, attempt: u32) -> Guid {
+    let mut hasher = Sha256::new();
+    hasher.update(pathname.as_bytes());
+    hasher.update(attempt.to_le_bytes());
+
+    if guids == GuidGeneration::Random {
+        let mut random = RandomState::new().build_hasher();
+        random.write(pathname.as_bytes());
+        hasher.update(random.finish().to_le_bytes());
+        if let Ok(elapsed) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+            hasher.update(elapsed.as_nanos().to_le_bytes());
+        }
+    }
+
+    let hash: [u8; 32] = hasher.finalize().into();
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hash[..16]);
+    Guid::from_bytes(bytes)
+}
+
+/// The smallest meta file Unity imports without complaints.
+fn generated_meta(guid: Guid, is_folder: bool) -> String {
+    let folder = if is_folder { "folderAsset: yes\n" } else { "" };
+    format!(
+        "fileFormatVersion: 2\nguid: {}\n{}DefaultImporter:\n  externalObjects: {{}}\n  userData: \n  assetBundleName: \n  assetBundleVariant: \n",
+        guid, folder
+    )
+}
+
+/// `Foo.png` -> `Foo.png.meta`
+fn meta_path(asset: &Path) -> PathBuf {
+    let mut name = asset.as_os_str().to_os_string();
+    name.push(".meta");
+    PathBuf::from(name)
+}
+
+fn write_error(path: &Path, e: io::Error) -> UnityPackageReaderError {
+    UnityPackageReaderError::CouldNotWritePackage(ErrorInformation::new(
+        Some(format!("{:?}: {}", path, e)),
+        file!(),
+        line!(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn project() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("Assets/Textures/Ground")).unwrap();
+        fs::create_dir_all(root.join("Assets/Scripts")).unwrap();
+        fs::write(root.join("Assets/Textures.meta"), folder_meta(FOLDER_GUID)).unwrap();
+        fs::write(root.join(TEXTURE_PATH), TEXTURE_CONTENT).unwrap();
+        fs::write(
+            root.join(format!("{}.meta", TEXTURE_PATH)),
+            texture_meta(TEXTURE_GUID),
+        )
+        .unwrap();
+        fs::write(root.join(SCRIPT_PATH), SCRIPT_CONTENT).unwrap();
+        fs::write(
+            root.join(format!("{}.meta", SCRIPT_PATH)),
+            script_meta(SCRIPT_GUID),
+        )
+        .unwrap();
+        // Neither a .meta of its own nor inside one: not an asset.
+        fs::write(root.join("Assets/notes.txt"), b"no meta").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let project = project();
+        let mut writer = UnityPackageWriter::new(project.path());
+        writer.add_directory(Path::new("Assets")).unwrap();
+        assert_eq!(writer.len(), 3);
+
+        let package = project.path().join("out.unitypackage");
+        writer.write_to(&package).unwrap();
+
+        let target = project.path().join("target");
+        let mut unpacked = crate::prelude::UnityPackage::from_path(
+            &package,
+            Some(target.clone()),
+            Some(project.path().join("tmp")),
+        )
+        .unwrap();
+        unpacked.unpack_package(true).unwrap();
+
+        for (guid, path) in [
+            (TEXTURE_GUID, TEXTURE_PATH),
+            (SCRIPT_GUID, SCRIPT_PATH),
+            (FOLDER_GUID, FOLDER_PATH),
+        ] {
+            assert_eq!(unpacked.get_file_by_path(path).unwrap().get_guid(), guid);
+        }
+        assert!(unpacked.get_file(FOLDER_GUID).unwrap().is_folder());
+        assert_eq!(
+            fs::read(target.join(TEXTURE_PATH)).unwrap(),
+            TEXTURE_CONTENT
+        );
+        assert!(unpacked.get_file_by_path("Assets/notes.txt").is_none());
+    }
+
+    fn unpack(package: &Path, dir: &Path) -> crate::prelude::UnityPackage {
+        let mut unpacked = crate::prelude::UnityPackage::from_path(
+            package,
+            Some(dir.join("target")),
+            Some(dir.join("tmp")),
+        )
+        .unwrap();
+        unpacked.unpack_package(true).unwrap();
+        unpacked
+    }
+
+    #[test]
+    fn test_generated_metas() {
+        let tree = tempfile::tempdir().unwrap();
+        let root = tree.path().join("tree");
+        fs::create_dir_all(root.join("Assets/Sub")).unwrap();
+        fs::write(root.join("Assets/a.txt"), b"a").unwrap();
+        fs::write(root.join("Assets/Sub/b.txt"), b"b").unwrap();
+        fs::write(root.join("Assets/.hidden"), b"hidden").unwrap();
+
+        let pack = |guids, name: &str| {
+            let mut writer = UnityPackageWriter::new(&root).with_generated_metas(guids);
+            writer.add_directory(Path::new("Assets")).unwrap();
+            let package = tree.path().join(name);
+            writer.write_to(&package).unwrap();
+            package
+        };
+
+        let first = pack(GuidGeneration::Deterministic, "first.unitypackage");
+        let second = pack(GuidGeneration::Deterministic, "second.unitypackage");
+        assert_eq!(fs::read(&first).unwrap(), fs::read(&second).unwrap());
+
+        let unpacked = unpack(&first, &tree.path().join("first"));
+        let files: Vec<_> = unpacked.files().collect();
+        let paths: Vec<_> = files.iter().map(|f| f.get_relative_asset_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("Assets/Sub"),
+                Path::new("Assets/Sub/b.txt"),
+                Path::new("Assets/a.txt")
+            ]
+        );
+        assert!(files[0].is_folder());
+
+        let mut guids = std::collections::HashSet::new();
+        for file in &files {
+            // Indexing the package ran the generated meta through MetaData::parse.
+            let parsed = file.meta_data();
+            assert_eq!(parsed.guid, file.get_guid().to_string());
+            assert_eq!(parsed.importer.as_deref(), Some("DefaultImporter"));
+            assert!(guids.insert(file.get_guid()));
+        }
+
+        let random = pack(GuidGeneration::Random, "random.unitypackage");
+        let random = unpack(&random, &tree.path().join("random"));
+        assert_ne!(
+            random.get_file_by_path("Assets/a.txt").unwrap().get_guid(),
+            unpacked
+                .get_file_by_path("Assets/a.txt")
+                .unwrap()
+                .get_guid()
+        );
+    }
+
+    #[test]
+    fn test_add_file_errors() {
+        let project = project();
+        let root = project.path();
+        let mut writer = UnityPackageWriter::new(root);
+
+        let outside = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            writer.add_file(outside.path(), &root.join("Assets/Textures.meta")),
+            Err(UnityPackageReaderError::PathError(_))
+        ));
+
+        fs::write(root.join("Assets/copy.txt"), b"copy").unwrap();
+        fs::write(root.join("Assets/copy.txt.meta"), script_meta(SCRIPT_GUID)).unwrap();
+        writer
+            .add_file(
+                Path::new(SCRIPT_PATH),
+                &root.join(format!("{}.meta", SCRIPT_PATH)),
+            )
+            .unwrap();
+        assert!(matches!(
+            writer.add_file(
+                Path::new("Assets/copy.txt"),
+                Path::new("Assets/copy.txt.meta")
+            ),
+            Err(UnityPackageReaderError::CouldNotWritePackage(_))
+        ));
+
+        fs::write(root.join("Assets/folder.txt"), b"file").unwrap();
+        fs::write(
+            root.join("Assets/folder.txt.meta"),
+            folder_meta(TEXTURE_GUID),
+        )
+        .unwrap();
+        assert!(matches!(
+            writer.add_file(
+                Path::new("Assets/folder.txt"),
+                Path::new("Assets/folder.txt.meta")
+            ),
+            Err(UnityPackageReaderError::CouldNotWritePackage(_))
+        ));
+
+        fs::write(root.join("Assets/bad.txt.meta"), "guid: not-a-guid\n").unwrap();
+        assert!(matches!(
+            writer.add_file(
+                Path::new("Assets/bad.txt"),
+                Path::new("Assets/bad.txt.meta")
+            ),
+            Err(UnityPackageReaderError::InvalidGuid(_))
+        ));
+    }
+}
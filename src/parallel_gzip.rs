@@ -0,0 +1,337 @@
+use flate2::read::{GzDecoder, MultiGzDecoder};
+use std::{
+    io::{self, Chain, Cursor, ErrorKind, Read},
+    thread,
+};
+
+use crate::package_format;
+
+/// The blocks every thread decompresses per batch. BGZF blocks hold at most
+/// 64 KiB, so a batch stays small while the threads have enough to do.
+const BLOCKS_PER_THREAD: usize = 16;
+
+/// The most a BGZF block may inflate to. A block that claims more is damaged
+/// or crafted to exhaust the memory of a batch.
+const MAX_BLOCK_OUTPUT: usize = 65536;
+
+/// Decompresses a BGZF stream (see `PackageFormat::BlockGzip`). The blocks
+/// are read in batches, every thread decompresses a slice of the batch and
+/// the result is handed out in order. Errors are the ones `GzDecoder`
+/// reports for the same damage, so they map to the same
+/// `UnityPackageReaderError`. From the first gzip member that is no BGZF
+/// block on, the rest of the stream is read like `MultiGzDecoder` does.
+pub(crate) struct BlockDecoder<R> {
+    /// None only while switching to `Source::Sequential`.
+    source: Option<Source<R>>,
+    threads: usize,
+    output: Vec<u8>,
+    position: usize,
+    finished: bool,
+    /// The bytes read of a member that is no BGZF block.
+    foreign: Option<Vec<u8>>,
+}
+
+enum Source<R> {
+    Blocks(R),
+    Sequential(Box<MultiGzDecoder<Chain<Cursor<Vec<u8>>, R>>>),
+}
+
+/// What `BlockDecoder::read_block` found.
+enum Member {
+    Block(Vec<u8>),
+    /// The start of a member that is no BGZF block, or of trailing bytes.
+    Foreign(Vec<u8>),
+    End,
+}
+
+impl<R: Read> BlockDecoder<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        BlockDecoder {
+            source: Some(Source::Blocks(reader)),
+            threads: Self::threads(),
+            output: Vec::new(),
+            position: 0,
+            finished: false,
+            foreign: None,
+        }
+    }
+
+    /// The number of threads a decoder uses, the number of cores.
+    pub(crate) fn threads() -> usize {
+        thread::available_parallelism().map_or(1, |n| n.get())
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        match self.source {
+            Some(Source::Blocks(r)) => r,
+            Some(Source::Sequential(d)) => d.into_inner().into_inner().1,
+            None => unreachable!("the source is only taken while switching"),
+        }
+    }
+
+    /// The next compressed block.
+    fn read_block(reader: &mut R) -> io::Result<Member> {
+        let mut block = vec![0u8; 12];
+        let read = read_up_to(reader, &mut block)?;
+        if read == 0 {
+            return Ok(Member::End);
+        }
+        if read < block.len() {
+            block.truncate(read);
+            return Ok(Member::Foreign(block));
+        }
+
+        let xlen = u16::from_le_bytes([block[10], block[11]]) as usize;
+        block.resize(12 + xlen, 0);
+        let read = read_up_to(reader, &mut block[12..])?;
+        if read < xlen {
+            block.truncate(12 + read);
+            return Ok(Member::Foreign(block));
+        }
+        let size = match package_format::block_size(&block) {
+            Some(s) if s >= block.len() + 8 => s,
+            Some(_) => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "BGZF block with an invalid block size",
+                ))
+            }
+            None => return Ok(Member::Foreign(block)),
+        };
+
+        let header = block.len();
+        block.resize(size, 0);
+        reader.read_exact(&mut block[header..])?;
+        Ok(Member::Block(block))
+    }
+
+    /// Read and decompress the next batch of blocks into output.
+    fn fill(&mut self) -> io::Result<()> {
+        let reader = match &mut self.source {
+            Some(Source::Blocks(r)) => r,
+            _ => unreachable!("fill is only called while reading blocks"),
+        };
+        let mut blocks = Vec::new();
+        while blocks.len() < self.threads * BLOCKS_PER_THREAD {
+            match Self::read_block(reader)? {
+                Member::Block(b) => blocks.push(b),
+                Member::Foreign(start) => {
+                    self.foreign = Some(start);
+                    self.finished = true;
+                    break;
+                }
+                Member::End => {
+                    self.finished = true;
+                    break;
+                }
+            }
+        }
+
+        self.output.clear();
+        self.position = 0;
+        if self.threads == 1 || blocks.len() <= 1 {
+            for block in &blocks {
+                decode_block(block, &mut self.output)?;
+            }
+            return Ok(());
+        }
+
+        let per_thread = blocks.len().div_ceil(self.threads);
+        let results: Vec<io::Result<Vec<u8>>> = thread::scope(|scope| {
+            let handles: Vec<_> = blocks
+                .chunks(per_thread)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        let mut output = Vec::new();
+                        for block in chunk {
+                            decode_block(block, &mut output)?;
+                        }
+                        Ok(output)
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|h| {
+                    h.join()
+                        .unwrap_or_else(|_| Err(io::Error::other("decompression thread panicked")))
+                })
+                .collect()
+        });
+
+        for result in results {
+            self.output.extend_from_slice(&result?);
+        }
+
+        Ok(())
+    }
+
+    /// Continue with `MultiGzDecoder` from the foreign member on.
+    fn switch_to_sequential(&mut self, start: Vec<u8>) {
+        if let Some(Source::Blocks(reader)) = self.source.take() {
+            self.source = Some(Source::Sequential(Box::new(MultiGzDecoder::new(
+                Cursor::new(start).chain(reader),
+            ))));
+        }
+    }
+}
+
+/// Inflate a single block into output, at most `MAX_BLOCK_OUTPUT` bytes.
+fn decode_block(block: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+    let start = output.len();
+    GzDecoder::new(block)
+        .take(MAX_BLOCK_OUTPUT as u64 + 1)
+        .read_to_end(output)?;
+    if output.len() - start > MAX_BLOCK_OUTPUT {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "BGZF block inflates to more than 64 KiB",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Read until buf is full or the stream ends, returning the bytes read.
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(read)
+}
+
+impl<R: Read> Read for BlockDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.output.len() {
+            if let Some(Source::Sequential(decoder)) = &mut self.source {
+                return decoder.read(buf);
+            }
+            if self.finished {
+                match self.foreign.take() {
+                    Some(start) => {
+                        self.switch_to_sequential(start);
+                        continue;
+                    }
+                    None => return Ok(0),
+                }
+            }
+            self.fill()?;
+        }
+
+        let len = (self.output.len() - self.position).min(buf.len());
+        buf[..len].copy_from_slice(&self.output[self.position..self.position + len]);
+        self.position += len;
+
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use flate2::read::MultiGzDecoder;
+    use std::time::Instant;
+
+    #[test]
+    fn test_blocks_in_order() {
+        let data: Vec<u8> = (0..1_000_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = block_gzip(&data, 65_280);
+        for threads in [1, 3] {
+            let mut decoder = BlockDecoder::new(&compressed[..]);
+            decoder.threads = threads;
+
+            let mut result = Vec::new();
+            decoder.read_to_end(&mut result).unwrap();
+            assert_eq!(result, data);
+        }
+    }
+
+    #[test]
+    fn test_block_output_is_limited() {
+        // A single member claiming to be a block, inflating to 1 MiB.
+        let data = vec![0u8; 1 << 20];
+        let mut bomb = block_gzip(&data, data.len());
+        bomb.extend(block_gzip(b"after", 65_280));
+
+        for threads in [1, 3] {
+            let mut decoder = BlockDecoder::new(&bomb[..]);
+            decoder.threads = threads;
+            let error = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_foreign_member_continues_sequentially() {
+        let first: Vec<u8> = (0..200_000u32).map(|i| (i % 7) as u8).collect();
+        let mut compressed = block_gzip(&first, 65_280);
+        let mut plain = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        std::io::Write::write_all(&mut plain, b"a plain gzip member").unwrap();
+        compressed.extend(plain.finish().unwrap());
+        compressed.extend(block_gzip(b" and blocks again", 65_280));
+
+        let mut expected = first.clone();
+        expected.extend_from_slice(b"a plain gzip member and blocks again");
+        for threads in [1, 3] {
+            let mut decoder = BlockDecoder::new(&compressed[..]);
+            decoder.threads = threads;
+            let mut result = Vec::new();
+            decoder.read_to_end(&mut result).unwrap();
+            assert_eq!(result, expected);
+            assert!(decoder.into_inner().is_empty());
+        }
+    }
+
+    /// Compares the serial and the parallel decoder on a 256 MiB package,
+    /// run with `cargo test --release --features parallel-gzip -- --ignored
+    /// --nocapture bench_`.
+    #[test]
+    #[ignore]
+    fn bench_serial_against_parallel() {
+        let mut state = 0x2545_f491u32;
+        let data: Vec<u8> = (0..256 << 20)
+            .map(|i| {
+                // Compressible, but not trivially.
+                if i % 4 == 0 {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                }
+                (state >> (i % 4 * 8)) as u8 & 0x3f
+            })
+            .collect();
+        let compressed = block_gzip(&data, 65_280);
+
+        let start = Instant::now();
+        let mut serial = Vec::with_capacity(data.len());
+        MultiGzDecoder::new(&compressed[..])
+            .read_to_end(&mut serial)
+            .unwrap();
+        let serial_time = start.elapsed();
+
+        let start = Instant::now();
+        let mut parallel = Vec::with_capacity(data.len());
+        BlockDecoder::new(&compressed[..])
+            .read_to_end(&mut parallel)
+            .unwrap();
+        let parallel_time = start.elapsed();
+
+        assert!(serial == data && parallel == data);
+        println!(
+            "{} MiB compressed to {} MiB: serial {:?}, parallel {:?}",
+            data.len() >> 20,
+            compressed.len() >> 20,
+            serial_time,
+            parallel_time
+        );
+    }
+}
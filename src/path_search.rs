@@ -0,0 +1,52 @@
+use regex::{Regex, RegexBuilder};
+use std::path::Path;
+
+use crate::{
+    checksums,
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// A compiled search over relative asset paths, see `UnityPackage::find`.
+pub(crate) struct PathSearch(Regex);
+
+impl PathSearch {
+    /// Fails with `InvalidPattern` if pattern is no valid regular expression.
+    pub(crate) fn new(pattern: &str, ignore_case: bool) -> Result<Self, UnityPackageReaderError> {
+        match RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .build()
+        {
+            Ok(regex) => Ok(PathSearch(regex)),
+            Err(e) => Err(UnityPackageReaderError::InvalidPattern(
+                ErrorInformation::new(Some(format!("'{}': {}", pattern, e)), file!(), line!()),
+            )),
+        }
+    }
+
+    /// True, if the pattern matches anywhere in path, which is compared with
+    /// forward slashes on every platform.
+    pub(crate) fn matches(&self, path: &Path) -> bool {
+        self.0.is_match(&checksums::to_forward_slashes(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_search() {
+        let path = Path::new("Assets/Scripts/Player.cs");
+        assert!(PathSearch::new(r"\.cs$", false).unwrap().matches(path));
+        assert!(PathSearch::new("^assets/scripts/", true)
+            .unwrap()
+            .matches(path));
+        assert!(!PathSearch::new("^assets/scripts/", false)
+            .unwrap()
+            .matches(path));
+        assert!(matches!(
+            PathSearch::new("Assets/(", false),
+            Err(UnityPackageReaderError::InvalidPattern(_))
+        ));
+    }
+}
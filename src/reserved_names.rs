@@ -0,0 +1,86 @@
+use std::path::{Component, Path, PathBuf};
+
+/// The device names Windows reserves in every directory, with or without an
+/// extension.
+const RESERVED_NAMES: &[&str] = &[
+    "con", "prn", "aux", "nul", "conin$", "conout$", "com0", "com1", "com2", "com3", "com4",
+    "com5", "com6", "com7", "com8", "com9", "com¹", "com²", "com³", "lpt0", "lpt1", "lpt2", "lpt3",
+    "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9", "lpt¹", "lpt²", "lpt³",
+];
+
+/// True, if Windows reads the file name as a device (`con`, `aux.shader`,
+/// `LPT1.tar.gz`). Case and trailing spaces or dots do not matter.
+pub fn is_reserved_name(name: &str) -> bool {
+    let base = name.split('.').next().unwrap_or_default();
+    let base = base.trim_end_matches(' ').to_lowercase();
+    RESERVED_NAMES.contains(&base.as_str())
+}
+
+/// path with an underscore appended to the base name of every reserved
+/// component (`Assets/con/aux.shader` becomes `Assets/con_/aux_.shader`),
+/// None if no component is reserved. The mapping only depends on the
+/// component, so a folder and its content end up in the same place.
+pub(crate) fn sanitize_path(path: &Path) -> Option<PathBuf> {
+    let mut renamed = false;
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) if is_reserved_name(&name.to_string_lossy()) => {
+                let name = name.to_string_lossy();
+                let (base, rest) = name.split_at(name.find('.').unwrap_or(name.len()));
+                result.push(format!("{}_{}", base.trim_end_matches(' '), rest));
+                renamed = true;
+            }
+            c => result.push(c),
+        }
+    }
+
+    renamed.then_some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserved_names() {
+        for name in [
+            "con",
+            "CON",
+            "Con.txt",
+            "aux.shader",
+            "prn",
+            "nul.tar.gz",
+            "LPT1",
+            "com9.cs",
+            "con .txt",
+        ] {
+            assert!(is_reserved_name(name), "{}", name);
+        }
+        for name in [
+            "console.txt",
+            "icon",
+            "com10",
+            "lpt",
+            "aux_.shader",
+            "my.con",
+            "",
+        ] {
+            assert!(!is_reserved_name(name), "{}", name);
+        }
+
+        assert_eq!(
+            sanitize_path(Path::new("Assets/Con/aux.shader")),
+            Some(PathBuf::from("Assets/Con_/aux_.shader"))
+        );
+        assert_eq!(
+            sanitize_path(Path::new("Assets/prn")),
+            Some(PathBuf::from("Assets/prn_"))
+        );
+        assert_eq!(
+            sanitize_path(Path::new("Assets/con .txt")),
+            Some(PathBuf::from("Assets/con_.txt"))
+        );
+        assert_eq!(sanitize_path(Path::new("Assets/Icon.png")), None);
+    }
+}
@@ -0,0 +1,53 @@
+use std::{fmt, ops::Deref, sync::Arc};
+
+/// A handle to a caller supplied hook (a filter, an observer or a path
+/// rewrite) that can be put into `UnpackOptions`. Cloning shares the hook,
+/// two handles are equal if they share the same one.
+pub struct Shared<T: ?Sized>(Arc<T>);
+
+impl<T: ?Sized> Shared<T> {
+    pub(crate) fn from_arc(inner: Arc<T>) -> Self {
+        Shared(inner)
+    }
+}
+
+impl<T: ?Sized> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Shared(self.0.clone())
+    }
+}
+
+impl<T: ?Sized> Deref for Shared<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for Shared<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Shared(..)")
+    }
+}
+
+impl<T: ?Sized> PartialEq for Shared<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equal_if_shared() {
+        let a: Shared<dyn Fn() -> u32 + Send + Sync> = Shared::from_arc(Arc::new(|| 1));
+        let b: Shared<dyn Fn() -> u32 + Send + Sync> = Shared::from_arc(Arc::new(|| 1));
+        assert_eq!(a, a.clone());
+        assert_ne!(a, b);
+        assert_eq!(a(), 1);
+        assert_eq!(format!("{:?}", a), "Shared(..)");
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    fs,
+    io::{self, BufRead, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+use tar::Archive;
+
+use crate::{
+    atomic_file::AtomicFile,
+    guid::Guid,
+    package_format, tar_stream, unity_asset_file, unpack_limits,
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// The files written by `UnityPackage::extract_asset`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractedAsset {
+    /// Where the payload has been written.
+    pub path: PathBuf,
+    /// The size of the payload in bytes.
+    pub size: u64,
+    /// Where the meta file has been written, if it was requested.
+    pub meta: Option<PathBuf>,
+}
+
+/// The parts of a guid directory to look for.
+pub(crate) struct Wanted {
+    pub(crate) meta: bool,
+    pub(crate) pathname: bool,
+}
+
+/// The parts of a guid directory found by `stream_asset`.
+#[derive(Default)]
+pub(crate) struct Found {
+    /// The value returned by the payload callback.
+    pub(crate) payload: Option<u64>,
+    pub(crate) meta: Option<Vec<u8>>,
+    pub(crate) pathname: Option<String>,
+}
+
+fn corrupt(e: io::Error) -> UnityPackageReaderError {
+    UnityPackageReaderError::CorruptPackage(ErrorInformation::new(
+        Some(format!("{}", e)),
+        file!(),
+        line!(),
+    ))
+}
+
+/// Stream through a package until the payload of guid and the wanted parts
+/// have been found, and stop reading there. The payload entry is handed to
+/// payload as soon as it comes up, nothing else of the archive is extracted.
+pub(crate) fn stream_asset<R: BufRead>(
+    reader: R,
+    guid: Guid,
+    wanted: Wanted,
+    mut payload: impl FnMut(&mut dyn Read) -> Result<u64, UnityPackageReaderError>,
+) -> Result<Found, UnityPackageReaderError> {
+    let mut archive = Archive::new(package_format::open(reader)?);
+    let entries = match archive.entries() {
+        Ok(e) => e,
+        Err(e) => return Err(corrupt(e)),
+    };
+
+    let mut found = Found::default();
+    let mut seen = false;
+    for entry in entries {
+        let mut entry = match entry {
+            Ok(e) => e,
+            Err(e) => return Err(corrupt(e)),
+        };
+
+        let path = match entry.path() {
+            Ok(p) => p.into_owned(),
+            Err(e) => return Err(corrupt(e)),
+        };
+
+        let mut components = path.components().filter_map(|c| match c {
+            Component::Normal(n) => Some(n.to_string_lossy().into_owned()),
+            _ => None,
+        });
+        let name = match (components.next(), components.next(), components.next()) {
+            (Some(g), Some(n), None) if g.parse::<Guid>().ok() == Some(guid) => n,
+            _ => continue,
+        };
+        seen = true;
+
+        match name.as_str() {
+            "asset" => found.payload = Some(payload(&mut entry)?),
+            "asset.meta" | "metaData" if wanted.meta => {
+                let content = match unpack_limits::read_text(&mut entry) {
+                    Ok(c) => c,
+                    Err(e) => return Err(corrupt(e)),
+                };
+                found.meta = Some(content.into_bytes());
+            }
+            "pathname" if wanted.pathname => {
+                let content = match unpack_limits::read_text(&mut entry) {
+                    Ok(c) => c,
+                    Err(e) => return Err(corrupt(e)),
+                };
+                found.pathname = Some(unity_asset_file::normalize_pathname(
+                    unity_asset_file::trim_pathname(&content),
+                ));
+            }
+            _ => {}
+        }
+
+        if found.payload.is_some()
+            && (!wanted.meta || found.meta.is_some())
+            && (!wanted.pathname || found.pathname.is_some())
+        {
+            return Ok(found);
+        }
+    }
+
+    // The meta or the pathname is missing, that is up to the caller.
+    if found.payload.is_some() {
+        return Ok(found);
+    }
+
+    let message = if seen {
+        format!("The asset {} has no payload", guid)
+    } else {
+        format!("The package does not contain the guid {}", guid)
+    };
+
+    Err(UnityPackageReaderError::AssetNotFound(
+        ErrorInformation::new(Some(message), file!(), line!()),
+    ))
+}
+
+/// Copy the payload of guid into writer and stop reading the archive right
+/// after it. Errors of the writer are reported as `CopyFailed`, together with
+/// the number of bytes written before.
+pub(crate) fn copy_asset<R: BufRead, W: Write + ?Sized>(
+    reader: R,
+    guid: Guid,
+    writer: &mut W,
+) -> Result<u64, UnityPackageReaderError> {
+    let wanted = Wanted {
+        meta: false,
+        pathname: false,
+    };
+
+    let found = stream_asset(reader, guid, wanted, |payload| {
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut written = 0u64;
+        loop {
+            let read = match payload.read(&mut buffer) {
+                Ok(0) => return Ok(written),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(corrupt(e)),
+            };
+
+            if let Err(e) = writer.write_all(&buffer[..read]) {
+                return Err(UnityPackageReaderError::CopyFailed(ErrorInformation::new(
+                    Some(format!(
+                        "Writing the asset {} failed after {} bytes: {}",
+                        guid, written, e
+                    )),
+                    file!(),
+                    line!(),
+                )));
+            }
+            written += read as u64;
+        }
+    })?;
+
+    Ok(found.payload.unwrap_or(0))
+}
+
+/// Write the payload of guid to destination and, with a meta suffix, its meta
+/// file next to it. The pathname of the asset has to pass the same checks as
+/// the entries of an unpack, otherwise nothing is left behind.
+pub(crate) fn extract_asset<R: BufRead>(
+    reader: R,
+    guid: Guid,
+    destination: &Path,
+    meta_suffix: Option<&str>,
+) -> Result<ExtractedAsset, UnityPackageReaderError> {
+    let copy_failed = |e: io::Error| {
+        UnityPackageReaderError::CopyFailed(ErrorInformation::new(
+            Some(format!("{:?}: {}", destination, e)),
+            file!(),
+            line!(),
+        ))
+    };
+
+    if let Some(parent) = destination.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            return Err(UnityPackageReaderError::TargetDirectoryCouldNotBeCreated(
+                ErrorInformation::new(Some(format!("{:?}: {}", parent, e)), file!(), line!()),
+            ));
+        }
+    }
+
+    let wanted = Wanted {
+        meta: meta_suffix.is_some(),
+        pathname: true,
+    };
+    // The payload goes to a partial file that only replaces destination once
+    // everything has been checked, so a failure keeps the file that was there.
+    let mut partial = None;
+    let found = stream_asset(reader, guid, wanted, |payload| {
+        let mut file = AtomicFile::create(destination).map_err(copy_failed)?;
+        let size = io::copy(payload, &mut file).map_err(copy_failed)?;
+        partial = Some(file);
+        Ok(size)
+    })?;
+
+    let pathname = found.pathname.unwrap_or_default();
+    if tar_stream::sanitize(Path::new(&pathname)).is_none() {
+        return Err(UnityPackageReaderError::PathError(ErrorInformation::new(
+            Some(format!(
+                "The asset {} has the unsafe pathname {:?}",
+                guid, pathname
+            )),
+            file!(),
+            line!(),
+        )));
+    }
+
+    let meta = match (meta_suffix, found.meta) {
+        (Some(suffix), Some(content)) => {
+            let mut name = destination.as_os_str().to_owned();
+            name.push(suffix);
+            let meta = PathBuf::from(name);
+            let mut file = AtomicFile::create(&meta).map_err(copy_failed)?;
+            file.write_all(&content).map_err(copy_failed)?;
+            file.commit().map_err(copy_failed)?;
+            Some(meta)
+        }
+        _ => None,
+    };
+    if let Some(file) = partial {
+        file.commit().map_err(copy_failed)?;
+    }
+
+    Ok(ExtractedAsset {
+        path: destination.to_path_buf(),
+        size: found.payload.unwrap_or(0),
+        meta,
+    })
+}
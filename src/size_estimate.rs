@@ -0,0 +1,76 @@
+use std::io::{self, BufRead};
+use tar::Archive;
+
+use crate::{
+    package_format,
+    unity_meta::LEGACY_META_FILE,
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// The uncompressed sizes of a package by kind of payload, read from the tar
+/// headers without extracting anything, see `UnityPackage::estimated_size`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SizeEstimate {
+    /// Bytes of the `asset` payloads.
+    pub assets: u64,
+    /// Bytes of the `asset.meta` (or `metaData`) files.
+    pub metas: u64,
+    /// Bytes of the `preview.png` files.
+    pub previews: u64,
+    /// Bytes of the `pathname` files and of entries that are not recognized.
+    pub other: u64,
+    /// The number of tar entries read, including directories.
+    pub entries: usize,
+}
+
+impl SizeEstimate {
+    /// What an unpack writes into the target: payloads and metas.
+    pub fn installed(&self) -> u64 {
+        self.assets.saturating_add(self.metas)
+    }
+
+    /// Everything in the archive, i.e. what extracting it into the tmp
+    /// directory takes.
+    pub fn total(&self) -> u64 {
+        self.installed()
+            .saturating_add(self.previews)
+            .saturating_add(self.other)
+    }
+}
+
+/// Sum the sizes in the tar headers of a package (gzip compressed or plain tar).
+pub(crate) fn estimate_size<R: BufRead>(
+    reader: R,
+) -> Result<SizeEstimate, UnityPackageReaderError> {
+    let corrupt = |e: io::Error| {
+        UnityPackageReaderError::CorruptPackage(ErrorInformation::new(
+            Some(format!("{}", e)),
+            file!(),
+            line!(),
+        ))
+    };
+
+    let mut archive = Archive::new(package_format::open(reader)?);
+    let mut estimate = SizeEstimate::default();
+    for entry in archive.entries().map_err(corrupt)? {
+        let entry = entry.map_err(corrupt)?;
+        let size = entry.size();
+        let name = entry
+            .path()
+            .ok()
+            .and_then(|p| p.file_name().and_then(|n| n.to_str()).map(String::from));
+
+        let bucket = match name.as_deref() {
+            Some("asset") => &mut estimate.assets,
+            Some("asset.meta") => &mut estimate.metas,
+            Some(name) if name == LEGACY_META_FILE => &mut estimate.metas,
+            Some("preview.png") => &mut estimate.previews,
+            _ => &mut estimate.other,
+        };
+        *bucket = bucket.saturating_add(size);
+        estimate.entries += 1;
+    }
+
+    Ok(estimate)
+}
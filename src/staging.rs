@@ -0,0 +1,263 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::unpacker_error::{ErrorInformation, UnityPackageReaderError};
+
+/// Tells apart the stagings of packages unpacked into one target at the
+/// same time (see `PackageBatch`).
+static NEXT_STAGING: AtomicUsize = AtomicUsize::new(0);
+
+/// What `Staging::commit` did to the target, so it can be undone.
+enum Move {
+    /// A directory that did not exist before.
+    CreatedDirectory(PathBuf),
+    /// A file that has been moved into the target. backup holds the file it
+    /// replaced, if there was one.
+    Placed {
+        path: PathBuf,
+        backup: Option<PathBuf>,
+    },
+}
+
+/// A directory next to the target that receives the assets of an atomic
+/// unpack (see `UnpackConfig::atomic`). The staged tree is moved into the
+/// target by `commit`; if the staging is dropped without being committed, it
+/// is deleted and the target stays untouched.
+pub(crate) struct Staging {
+    root: PathBuf,
+    backup: PathBuf,
+    target: PathBuf,
+    committed: bool,
+    /// Set if a rollback could not restore every replaced file, so the
+    /// backup directory holds the only copy of them and is not deleted.
+    keep_backup: bool,
+    /// Moves a replaced file back on rollback, `fs::rename` outside of tests.
+    restore: fn(&Path, &Path) -> io::Result<()>,
+}
+
+impl Staging {
+    pub(crate) fn new(target: &Path) -> Result<Self, UnityPackageReaderError> {
+        let name = target
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let id = NEXT_STAGING.fetch_add(1, Ordering::Relaxed);
+        let sibling = |kind: &str| {
+            target.with_file_name(format!(".{}.{}-{}-{}", name, kind, std::process::id(), id))
+        };
+
+        let staging = Staging {
+            root: sibling("staging"),
+            backup: sibling("backup"),
+            target: target.to_path_buf(),
+            committed: false,
+            keep_backup: false,
+            restore: |from, to| fs::rename(from, to),
+        };
+
+        let _ = fs::remove_dir_all(&staging.root);
+        if let Err(e) = fs::create_dir_all(&staging.root) {
+            return Err(UnityPackageReaderError::TargetDirectoryCouldNotBeCreated(
+                ErrorInformation::new(Some(format!("{:?}: {}", staging.root, e)), file!(), line!()),
+            ));
+        }
+
+        Ok(staging)
+    }
+
+    /// The directory the assets are installed into instead of the target.
+    pub(crate) fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Move everything staged into the target. Files the target already has
+    /// are replaced. If a move fails, the moves done so far are undone, so
+    /// the target ends up as it was before. If undoing fails as well, the
+    /// replaced files are kept in the backup directory the error names.
+    pub(crate) fn commit(mut self) -> Result<(), UnityPackageReaderError> {
+        let mut entries = Vec::new();
+        if let Err(e) = collect(&self.root, Path::new(""), &mut entries) {
+            return Err(move_error(&self.root, e));
+        }
+
+        let mut moves = Vec::new();
+        if !self.target.is_dir() {
+            if let Err(e) = fs::create_dir_all(&self.target) {
+                return Err(move_error(&self.target, e));
+            }
+            moves.push(Move::CreatedDirectory(self.target.clone()));
+        }
+
+        for (relative, is_dir) in entries {
+            if let Err(e) = self.place(&relative, is_dir, &mut moves) {
+                let failed = rollback(moves, self.restore);
+                if failed.is_empty() {
+                    return Err(move_error(&self.target.join(&relative), e));
+                }
+
+                self.keep_backup = true;
+                return Err(UnityPackageReaderError::CopyFailed(ErrorInformation::new(
+                    Some(format!(
+                        "{:?}: {}. Undoing the install failed for {}, the replaced files are kept in {:?}",
+                        self.target.join(&relative),
+                        e,
+                        failed.join(", "),
+                        self.backup
+                    )),
+                    file!(),
+                    line!(),
+                )));
+            }
+        }
+
+        self.committed = true;
+        let _ = fs::remove_dir_all(&self.root);
+        let _ = fs::remove_dir_all(&self.backup);
+        Ok(())
+    }
+
+    fn place(&self, relative: &Path, is_dir: bool, moves: &mut Vec<Move>) -> io::Result<()> {
+        let destination = self.target.join(relative);
+        if is_dir {
+            if !destination.is_dir() {
+                fs::create_dir(&destination)?;
+                moves.push(Move::CreatedDirectory(destination));
+            }
+            return Ok(());
+        }
+
+        let backup = if destination.is_file() {
+            let backup = self.backup.join(relative);
+            if let Some(parent) = backup.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(&destination, &backup)?;
+            Some(backup)
+        } else {
+            None
+        };
+
+        let placed = fs::rename(self.root.join(relative), &destination);
+        // A file that could not be placed only needs its backup put back.
+        if placed.is_ok() || backup.is_some() {
+            moves.push(Move::Placed {
+                path: destination,
+                backup,
+            });
+        }
+
+        placed
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_dir_all(&self.root);
+            if !self.keep_backup {
+                let _ = fs::remove_dir_all(&self.backup);
+            }
+        }
+    }
+}
+
+/// Undo the moves in reverse order, moving replaced files back with
+/// restore. Returns the files that could not be put back or removed.
+fn rollback(moves: Vec<Move>, restore: fn(&Path, &Path) -> io::Result<()>) -> Vec<String> {
+    let mut failed = Vec::new();
+    for m in moves.into_iter().rev() {
+        match m {
+            Move::CreatedDirectory(path) => {
+                // Fails if a file could not be removed, which is reported already.
+                let _ = fs::remove_dir(path);
+            }
+            Move::Placed { path, backup } => {
+                let result = match backup {
+                    Some(backup) => restore(&backup, &path),
+                    None => fs::remove_file(&path),
+                };
+                if let Err(e) = result {
+                    failed.push(format!("{:?}: {}", path, e));
+                }
+            }
+        }
+    }
+
+    failed
+}
+
+/// Every directory and file below dir (relative to it), parents before their
+/// content, sorted by name.
+fn collect(dir: &Path, relative: &Path, result: &mut Vec<(PathBuf, bool)>) -> io::Result<()> {
+    let mut children: Vec<fs::DirEntry> =
+        fs::read_dir(dir.join(relative))?.collect::<Result<_, _>>()?;
+    children.sort_by_key(|c| c.file_name());
+
+    for child in children {
+        let path = relative.join(child.file_name());
+        if child.file_type()?.is_dir() {
+            result.push((path.clone(), true));
+            collect(dir, &path, result)?;
+        } else {
+            result.push((path, false));
+        }
+    }
+
+    Ok(())
+}
+
+fn move_error(path: &Path, e: io::Error) -> UnityPackageReaderError {
+    UnityPackageReaderError::CopyFailed(ErrorInformation::new(
+        Some(format!("{:?}: {}", path, e)),
+        file!(),
+        line!(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failed_rollback_keeps_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        fs::create_dir_all(target.join("b.txt/occupied")).unwrap();
+        fs::write(target.join("a.txt"), "original").unwrap();
+
+        let stage = |restore: fn(&Path, &Path) -> io::Result<()>| {
+            let mut staging = Staging::new(&target).unwrap();
+            staging.restore = restore;
+            fs::write(staging.root().join("a.txt"), "new").unwrap();
+            // Cannot replace the directory, so the commit fails after a.txt.
+            fs::write(staging.root().join("b.txt"), "new").unwrap();
+            (staging.backup.clone(), staging.commit())
+        };
+
+        let (backup, result) = stage(|from, to| fs::rename(from, to));
+        assert!(matches!(
+            result,
+            Err(UnityPackageReaderError::CopyFailed(_))
+        ));
+        assert_eq!(
+            fs::read_to_string(target.join("a.txt")).unwrap(),
+            "original"
+        );
+        assert!(!backup.exists());
+
+        let (backup, result) = stage(|_, _| Err(io::Error::from(io::ErrorKind::PermissionDenied)));
+        match result {
+            Err(UnityPackageReaderError::CopyFailed(e)) => {
+                assert!(e.message.unwrap().contains(&format!("{:?}", backup)));
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(
+            fs::read_to_string(backup.join("a.txt")).unwrap(),
+            "original"
+        );
+    }
+}
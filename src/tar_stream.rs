@@ -0,0 +1,760 @@
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Read, Write},
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tar::{EntryType, Header, PaxExtensions};
+
+use crate::{
+    extraction_report::{SkippedEntry, SpecialEntryKind},
+    prelude::{FileModes, SpecialEntries, UnpackLimits},
+    unpack_limits::LimitTracker,
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// The directory below the unpack directory that receives the files the
+/// archive contains more than once, so both copies can be compared.
+pub(crate) const DUPLICATES_DIR: &str = ".duplicates";
+
+const BLOCK_SIZE: u64 = 512;
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+/// What a single call of `TarStream::advance` did.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Advance {
+    /// Bytes of the tar stream consumed.
+    pub consumed: u64,
+    /// Bytes written into files.
+    pub written: u64,
+    /// True, if an entry has been unpacked completely.
+    pub completed_entry: bool,
+    /// The path (relative to the unpack directory) of an entry that has been started.
+    pub started_entry: Option<PathBuf>,
+}
+
+enum State {
+    Header,
+    Data {
+        /// None for entries whose content is skipped.
+        file: Option<fs::File>,
+        /// The modification time to give the file once it is complete.
+        mtime: Option<SystemTime>,
+        /// The relative path of the file and the hash of its content so far.
+        hasher: Option<(PathBuf, Sha256)>,
+        remaining: u64,
+        padding: u64,
+    },
+    Finished,
+}
+
+/// Unpacks a tar stream piece by piece. Unlike `tar::Archive`, whose entry
+/// iterator borrows the archive, this keeps all of its state in itself, so
+/// it can be stored and resumed between (and inside) entries.
+pub(crate) struct TarStream<R> {
+    reader: R,
+    state: State,
+    /// The path announced by a GNU long name or pax header for the next entry.
+    next_path: Option<PathBuf>,
+    /// Give the unpacked files the modification time of their header.
+    preserve_mtimes: bool,
+    /// The permissions given to the unpacked files.
+    file_modes: FileModes,
+    limits: LimitTracker,
+    special_entries: SpecialEntries,
+    /// The links and special files that have not been extracted.
+    skipped: Vec<SkippedEntry>,
+    /// Internal symlinks (path and the file it points to, both relative to
+    /// the unpack directory), copied once the archive is complete.
+    symlinks: Vec<(PathBuf, PathBuf, SkippedEntry)>,
+    /// Hash the content of the files while writing them.
+    hash_files: bool,
+    /// The most bytes of an entry read and written at once.
+    chunk_size: u64,
+    /// The relative paths of the files unpacked so far and their sha256.
+    hashes: Vec<(PathBuf, [u8; 32])>,
+    /// The relative paths of the files unpacked so far.
+    files: HashSet<PathBuf>,
+    /// The relative paths of the files the archive contains more than once.
+    /// Every later copy is written below `DUPLICATES_DIR`.
+    duplicates: Vec<PathBuf>,
+}
+
+impl<R: Read> TarStream<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        TarStream {
+            reader,
+            state: State::Header,
+            next_path: None,
+            preserve_mtimes: false,
+            file_modes: FileModes::default(),
+            limits: LimitTracker::default(),
+            special_entries: SpecialEntries::default(),
+            skipped: Vec::new(),
+            symlinks: Vec::new(),
+            hash_files: false,
+            chunk_size: CHUNK_SIZE,
+            hashes: Vec::new(),
+            files: HashSet::new(),
+            duplicates: Vec::new(),
+        }
+    }
+
+    /// Fail with `LimitExceeded` before an entry would exceed one of limits.
+    pub(crate) fn with_limits(mut self, limits: UnpackLimits) -> Self {
+        self.limits = LimitTracker::new(limits);
+        self
+    }
+
+    /// Decide which unix permissions the unpacked files get.
+    pub(crate) fn with_modes(mut self, file_modes: FileModes) -> Self {
+        self.file_modes = file_modes;
+        self
+    }
+
+    /// Give every unpacked file the modification time stored in its header.
+    /// Files whose header has no valid time (zero) keep the current time.
+    pub(crate) fn with_mtimes(mut self, preserve: bool) -> Self {
+        self.preserve_mtimes = preserve;
+        self
+    }
+
+    /// Decide what happens to links and other special entries.
+    pub(crate) fn with_special_entries(mut self, special_entries: SpecialEntries) -> Self {
+        self.special_entries = special_entries;
+        self
+    }
+
+    /// Compute the sha256 of every file from the bytes written into it.
+    pub(crate) fn with_hashes(mut self, hash_files: bool) -> Self {
+        self.hash_files = hash_files;
+        self
+    }
+
+    /// Read and write the content of the entries in pieces of at most
+    /// chunk_size bytes.
+    pub(crate) fn with_chunk_size(mut self, chunk_size: u64) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// The files unpacked so far (relative to the unpack directory) and their
+    /// sha256, if hashing is on.
+    pub(crate) fn hashes(&self) -> &[(PathBuf, [u8; 32])] {
+        &self.hashes
+    }
+
+    /// The files the archive contains more than once (relative to the unpack
+    /// directory). The last copy of each is below `DUPLICATES_DIR`.
+    pub(crate) fn duplicates(&self) -> &[PathBuf] {
+        &self.duplicates
+    }
+
+    /// The links and special files that have been left out so far.
+    pub(crate) fn skipped_entries(&self) -> &[SkippedEntry] {
+        &self.skipped
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        matches!(self.state, State::Finished)
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Read the next header or at most max_bytes (but at least one byte) of
+    /// the current entry's content and unpack it below dir. Entries whose
+    /// path would end up outside of dir are skipped, like `unpack_in` does.
+    pub(crate) fn advance(
+        &mut self,
+        dir: &Path,
+        max_bytes: u64,
+    ) -> Result<Advance, UnityPackageReaderError> {
+        // A stream that failed once is not resumed.
+        let result = self.advance_state(dir, max_bytes);
+        if result.is_err() {
+            self.state = State::Finished;
+        }
+
+        result
+    }
+
+    fn advance_state(
+        &mut self,
+        dir: &Path,
+        max_bytes: u64,
+    ) -> Result<Advance, UnityPackageReaderError> {
+        match &mut self.state {
+            State::Header => self.read_header(dir),
+            State::Data {
+                file,
+                mtime,
+                hasher,
+                remaining,
+                padding,
+            } => {
+                let mut advance = Advance::default();
+                let len = (*remaining).min(max_bytes.max(1)).min(self.chunk_size) as usize;
+                let mut buffer = vec![0u8; len];
+                if let Err(e) = self.reader.read_exact(&mut buffer) {
+                    return Err(corrupt(e));
+                }
+                if let Some(file) = file {
+                    if let Err(e) = file.write_all(&buffer) {
+                        return Err(corrupt(e));
+                    }
+                    advance.written = len as u64;
+                }
+                if let Some((_, hasher)) = hasher {
+                    hasher.update(&buffer);
+                }
+
+                *remaining -= len as u64;
+                advance.consumed = len as u64;
+                if *remaining == 0 {
+                    if let Some((path, hasher)) = hasher.take() {
+                        self.hashes.push((path, hasher.finalize().into()));
+                    }
+                    set_mtime(file.as_ref(), *mtime)?;
+                    skip(&mut self.reader, *padding)?;
+                    advance.consumed += *padding;
+                    advance.completed_entry = true;
+                    self.state = State::Header;
+                }
+
+                Ok(advance)
+            }
+            State::Finished => Ok(Advance::default()),
+        }
+    }
+
+    fn read_header(&mut self, dir: &Path) -> Result<Advance, UnityPackageReaderError> {
+        let mut block = [0u8; BLOCK_SIZE as usize];
+        let read = self.read_block(&mut block)?;
+        let mut advance = Advance {
+            consumed: read,
+            ..Default::default()
+        };
+
+        // A missing or empty block marks the end of the archive.
+        if read == 0 || block.iter().all(|b| *b == 0) {
+            self.state = State::Finished;
+            advance.written += self.copy_symlinks(dir)?;
+            return Ok(advance);
+        }
+
+        let header = Header::from_byte_slice(&block);
+        if !is_valid_header(&block) {
+            return Err(UnityPackageReaderError::CorruptPackage(
+                ErrorInformation::new(
+                    Some(String::from("archive header checksum mismatch")),
+                    file!(),
+                    line!(),
+                ),
+            ));
+        }
+
+        let size = header.entry_size().map_err(corrupt)?;
+        self.limits.add_entry(size)?;
+        let padding = (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE;
+        let entry_type = header.entry_type();
+
+        // Extension headers describe the entry that follows them.
+        if entry_type.is_gnu_longname() || entry_type.is_pax_local_extensions() {
+            let mut data = vec![0u8; size as usize];
+            if let Err(e) = self.reader.read_exact(&mut data) {
+                return Err(corrupt(e));
+            }
+            skip(&mut self.reader, padding)?;
+            advance.consumed += size + padding;
+
+            if entry_type.is_gnu_longname() {
+                let name = data.split(|b| *b == 0).next().unwrap_or(&[]);
+                self.next_path = Some(PathBuf::from(String::from_utf8_lossy(name).as_ref()));
+            } else {
+                for extension in PaxExtensions::new(&data).flatten() {
+                    if extension.key() == Ok("path") {
+                        if let Ok(path) = extension.value() {
+                            self.next_path = Some(PathBuf::from(path));
+                        }
+                    }
+                }
+            }
+
+            return Ok(advance);
+        }
+
+        let path = match self.next_path.take() {
+            Some(p) => p,
+            None => header.path().map_err(corrupt)?.into_owned(),
+        };
+
+        if let Some(kind) = special_kind(entry_type) {
+            let link = header.link_name().ok().flatten().map(|l| l.into_owned());
+            self.special_entry(&path, kind, link)?;
+        }
+
+        let mut relative = sanitize(&path);
+        if let (Some(r), EntryType::Regular | EntryType::Continuous) = (&relative, entry_type) {
+            if !self.files.insert(r.clone()) {
+                if !self.duplicates.contains(r) {
+                    self.duplicates.push(r.clone());
+                }
+                relative = Some(Path::new(DUPLICATES_DIR).join(r));
+            }
+        }
+        let file = match (&relative, entry_type) {
+            (Some(relative), EntryType::Regular | EntryType::Continuous) => {
+                let destination = dir.join(relative);
+                let created = match destination.parent() {
+                    Some(parent) => fs::create_dir_all(parent),
+                    None => Ok(()),
+                }
+                .and_then(|_| fs::File::create(&destination));
+
+                let file = match created {
+                    Ok(f) => f,
+                    Err(e) => return Err(corrupt(e)),
+                };
+                set_mode(&file, self.file_modes.apply(header.mode().unwrap_or(0o644)))?;
+                Some(file)
+            }
+            (Some(relative), EntryType::Directory) => {
+                if let Err(e) = fs::create_dir_all(dir.join(relative)) {
+                    return Err(corrupt(e));
+                }
+                None
+            }
+            // Links and other special files are never created, see `special_entry`.
+            _ => None,
+        };
+        let mut hasher = match (&file, &relative) {
+            (Some(_), Some(relative)) if self.hash_files => Some((relative.clone(), Sha256::new())),
+            _ => None,
+        };
+        advance.started_entry = relative;
+
+        let mtime = match header.mtime() {
+            Ok(m) if self.preserve_mtimes && m > 0 => {
+                Some(SystemTime::UNIX_EPOCH + Duration::from_secs(m))
+            }
+            _ => None,
+        };
+
+        if size == 0 {
+            if let Some((path, hasher)) = hasher.take() {
+                self.hashes.push((path, hasher.finalize().into()));
+            }
+            set_mtime(file.as_ref(), mtime)?;
+            skip(&mut self.reader, padding)?;
+            advance.consumed += padding;
+            advance.completed_entry = true;
+        } else {
+            self.state = State::Data {
+                file,
+                mtime,
+                hasher,
+                remaining: size,
+                padding,
+            };
+        }
+
+        Ok(advance)
+    }
+
+    /// Reject, skip or remember a link or special file, depending on
+    /// `special_entries`. Nothing is created for it in the unpack directory.
+    fn special_entry(
+        &mut self,
+        path: &Path,
+        kind: SpecialEntryKind,
+        link: Option<PathBuf>,
+    ) -> Result<(), UnityPackageReaderError> {
+        if self.special_entries == SpecialEntries::Reject {
+            return Err(UnityPackageReaderError::UnsafeEntry(ErrorInformation::new(
+                Some(format!("{:?} ({:?} to {:?})", path, kind, link)),
+                file!(),
+                line!(),
+            )));
+        }
+
+        let internal = match (&link, sanitize(path)) {
+            (Some(link), Some(relative))
+                if kind == SpecialEntryKind::Symlink
+                    && self.special_entries == SpecialEntries::CopyInternalSymlinks =>
+            {
+                resolve_symlink(&relative, link).map(|source| (relative, source))
+            }
+            _ => None,
+        };
+
+        let entry = SkippedEntry {
+            path: path.to_path_buf(),
+            kind,
+            link,
+        };
+        match internal {
+            Some((relative, source)) => self.symlinks.push((relative, source, entry)),
+            None => self.skipped.push(entry),
+        }
+
+        Ok(())
+    }
+
+    /// Replace the internal symlinks by copies of the files they point to.
+    /// Links to anything else than a regular file of the package are skipped.
+    /// The copies count against the limits like the entries of the archive.
+    /// Returns the number of bytes written.
+    fn copy_symlinks(&mut self, dir: &Path) -> Result<u64, UnityPackageReaderError> {
+        let mut written = 0;
+        for (relative, source, entry) in std::mem::take(&mut self.symlinks) {
+            let source = dir.join(source);
+            let size = match fs::symlink_metadata(&source) {
+                Ok(m) if m.is_file() => m.len(),
+                _ => {
+                    self.skipped.push(entry);
+                    continue;
+                }
+            };
+            self.limits.add_bytes(size)?;
+
+            let destination = dir.join(relative);
+            let copied = match destination.parent() {
+                Some(parent) => fs::create_dir_all(parent),
+                None => Ok(()),
+            }
+            .and_then(|_| fs::copy(&source, &destination));
+            written += copied.map_err(corrupt)?;
+        }
+
+        Ok(written)
+    }
+
+    /// Fill block and return the number of bytes read. Zero means the stream ended.
+    fn read_block(&mut self, block: &mut [u8]) -> Result<u64, UnityPackageReaderError> {
+        let mut read = 0;
+        while read < block.len() {
+            match self.reader.read(&mut block[read..]) {
+                Ok(0) if read == 0 => return Ok(0),
+                Ok(0) => {
+                    return Err(corrupt(io::Error::from(io::ErrorKind::UnexpectedEof)));
+                }
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(corrupt(e)),
+            }
+        }
+
+        Ok(read as u64)
+    }
+}
+
+/// True, if block is a tar header with a correct checksum.
+pub(crate) fn is_valid_header(block: &[u8]) -> bool {
+    if block.len() != BLOCK_SIZE as usize || block.iter().all(|b| *b == 0) {
+        return false;
+    }
+
+    let sum: u32 = block[..148]
+        .iter()
+        .chain(&block[156..])
+        .fold(8 * b' ' as u32, |a, b| a + *b as u32);
+    Header::from_byte_slice(block).cksum().ok() == Some(sum)
+}
+
+fn set_mtime(
+    file: Option<&fs::File>,
+    mtime: Option<SystemTime>,
+) -> Result<(), UnityPackageReaderError> {
+    match (file, mtime) {
+        (Some(file), Some(mtime)) => file.set_modified(mtime).map_err(corrupt),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(file: &fs::File, mode: u32) -> Result<(), UnityPackageReaderError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    file.set_permissions(fs::Permissions::from_mode(mode))
+        .map_err(corrupt)
+}
+
+#[cfg(not(unix))]
+fn set_mode(_file: &fs::File, _mode: u32) -> Result<(), UnityPackageReaderError> {
+    Ok(())
+}
+
+fn skip<R: Read>(reader: &mut R, len: u64) -> Result<(), UnityPackageReaderError> {
+    match io::copy(&mut reader.take(len), &mut io::sink()) {
+        Ok(n) if n == len => Ok(()),
+        Ok(_) => Err(corrupt(io::Error::from(io::ErrorKind::UnexpectedEof))),
+        Err(e) => Err(corrupt(e)),
+    }
+}
+
+fn corrupt(e: io::Error) -> UnityPackageReaderError {
+    UnityPackageReaderError::CorruptPackage(ErrorInformation::new(
+        Some(format!("{}", e)),
+        file!(),
+        line!(),
+    ))
+}
+
+fn special_kind(entry_type: EntryType) -> Option<SpecialEntryKind> {
+    match entry_type {
+        EntryType::Symlink => Some(SpecialEntryKind::Symlink),
+        EntryType::Link => Some(SpecialEntryKind::HardLink),
+        EntryType::Char | EntryType::Block => Some(SpecialEntryKind::Device),
+        EntryType::Fifo => Some(SpecialEntryKind::Fifo),
+        _ => None,
+    }
+}
+
+/// The file a relative symlink at path points to, relative to the unpack
+/// directory. None for absolute links and links leaving the unpack directory.
+fn resolve_symlink(path: &Path, link: &Path) -> Option<PathBuf> {
+    let mut result = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    for component in link.components() {
+        match component {
+            Component::Normal(n) => result.push(n),
+            Component::ParentDir => {
+                if !result.pop() {
+                    return None;
+                }
+            }
+            Component::CurDir => {}
+            Component::Prefix(_) | Component::RootDir => return None,
+        }
+    }
+
+    (!result.as_os_str().is_empty()).then_some(result)
+}
+
+/// The path relative to the unpack directory. Leading slashes are dropped, like
+/// `tar::Entry::unpack_in` does, and paths containing '..' are rejected.
+pub(crate) fn sanitize(path: &Path) -> Option<PathBuf> {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(n) => result.push(n),
+            Component::ParentDir => return None,
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+        }
+    }
+
+    if result.as_os_str().is_empty() {
+        None
+    } else {
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unpack(bytes: &[u8], dir: &Path, max_bytes: u64) -> (u64, usize) {
+        let mut stream = TarStream::new(bytes);
+        let mut written = 0;
+        let mut entries = 0;
+        while !stream.is_finished() {
+            let advance = stream.advance(dir, max_bytes).unwrap();
+            written += advance.written;
+            entries += advance.completed_entry as usize;
+        }
+
+        (written, entries)
+    }
+
+    fn append(builder: &mut tar::Builder<Vec<u8>>, path: &str, content: &[u8]) {
+        let mut header = Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, path, content).unwrap();
+    }
+
+    #[test]
+    fn test_unpack_in_small_pieces() {
+        let content = vec![7u8; 1500];
+        let mut builder = tar::Builder::new(Vec::new());
+        append(&mut builder, "guid/asset", &content);
+        append(&mut builder, "guid/pathname", b"Assets/a.bin");
+        let bytes = builder.into_inner().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(unpack(&bytes, dir.path(), 100), (1512, 2));
+        assert_eq!(fs::read(dir.path().join("guid/asset")).unwrap(), content);
+        assert_eq!(
+            fs::read(dir.path().join("guid/pathname")).unwrap(),
+            b"Assets/a.bin"
+        );
+    }
+
+    #[test]
+    fn test_chunk_size() {
+        let content = vec![7u8; 1500];
+        let mut builder = tar::Builder::new(Vec::new());
+        append(&mut builder, "guid/asset", &content);
+        let bytes = builder.into_inner().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut stream = TarStream::new(&bytes[..]).with_chunk_size(256);
+        let mut writes = Vec::new();
+        while !stream.is_finished() {
+            let advance = stream.advance(dir.path(), u64::MAX).unwrap();
+            if advance.written > 0 {
+                writes.push(advance.written);
+            }
+        }
+        assert_eq!(writes, [256, 256, 256, 256, 256, 220]);
+        assert_eq!(fs::read(dir.path().join("guid/asset")).unwrap(), content);
+    }
+
+    #[test]
+    fn test_hashes_while_unpacking() {
+        let content = vec![7u8; 1500];
+        let mut builder = tar::Builder::new(Vec::new());
+        append(&mut builder, "guid/asset", &content);
+        append(&mut builder, "guid/empty", b"");
+        let bytes = builder.into_inner().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut stream = TarStream::new(&bytes[..]).with_hashes(true);
+        while !stream.is_finished() {
+            stream.advance(dir.path(), 100).unwrap();
+        }
+        assert_eq!(
+            stream.hashes(),
+            [
+                (PathBuf::from("guid/asset"), Sha256::digest(&content).into()),
+                (PathBuf::from("guid/empty"), Sha256::digest(b"").into()),
+            ]
+        );
+
+        let mut stream = TarStream::new(&bytes[..]);
+        while !stream.is_finished() {
+            stream.advance(dir.path(), u64::MAX).unwrap();
+        }
+        assert!(stream.hashes().is_empty());
+    }
+
+    #[test]
+    fn test_long_names() {
+        let long = format!("{}/{}", "d".repeat(80), "f".repeat(80));
+        let mut builder = tar::Builder::new(Vec::new());
+        append(&mut builder, &long, b"content");
+        let bytes = builder.into_inner().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(unpack(&bytes, dir.path(), u64::MAX), (7, 1));
+        assert_eq!(fs::read(dir.path().join(long)).unwrap(), b"content");
+    }
+
+    #[test]
+    fn test_parent_dir_entries_are_skipped() {
+        let mut header = Header::new_gnu();
+        header.as_gnu_mut().unwrap().name[..9].copy_from_slice(b"../escape");
+        header.set_size(3);
+        header.set_mode(0o644);
+        header.set_cksum();
+        let mut builder = tar::Builder::new(Vec::new());
+        builder.append(&header, &b"bad"[..]).unwrap();
+        let bytes = builder.into_inner().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let inner = dir.path().join("inner");
+        assert_eq!(unpack(&bytes, &inner, u64::MAX), (0, 1));
+        assert!(!dir.path().join("escape").exists());
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let mut builder = tar::Builder::new(Vec::new());
+        append(&mut builder, "guid/asset", b"content");
+        let mut bytes = builder.into_inner().unwrap();
+        bytes[0] = b'x';
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut stream = TarStream::new(&bytes[..]);
+        assert!(matches!(
+            stream.advance(dir.path(), u64::MAX),
+            Err(UnityPackageReaderError::CorruptPackage(_))
+        ));
+        assert!(stream.is_finished());
+    }
+
+    #[test]
+    fn test_symlinks_are_not_followed() {
+        let outside = tempfile::tempdir().unwrap();
+        let secret = outside.path().join("secret");
+        fs::write(&secret, "secret").unwrap();
+        let bytes = crate::test_utils::PackageBuilder::new()
+            .file("a/asset", "content")
+            .symlink("b/asset", "../a/asset")
+            .symlink("c/asset", &secret.to_string_lossy())
+            .symlink("d/asset", "../../escape")
+            .tar_bytes();
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut stream = TarStream::new(&bytes[..]);
+        while !stream.is_finished() {
+            stream.advance(dir.path(), u64::MAX).unwrap();
+        }
+        for name in ["b", "c", "d"] {
+            assert!(fs::symlink_metadata(dir.path().join(name).join("asset")).is_err());
+        }
+        let skipped: Vec<&Path> = stream
+            .skipped_entries()
+            .iter()
+            .map(|e| e.path.as_path())
+            .collect();
+        assert_eq!(skipped, ["b/asset", "c/asset", "d/asset"].map(Path::new));
+        assert!(stream
+            .skipped_entries()
+            .iter()
+            .all(|e| e.kind == SpecialEntryKind::Symlink));
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut stream =
+            TarStream::new(&bytes[..]).with_special_entries(SpecialEntries::CopyInternalSymlinks);
+        while !stream.is_finished() {
+            stream.advance(dir.path(), u64::MAX).unwrap();
+        }
+        let copy = dir.path().join("b/asset");
+        assert!(fs::symlink_metadata(&copy).unwrap().is_file());
+        assert_eq!(fs::read(&copy).unwrap(), b"content");
+        assert!(fs::symlink_metadata(dir.path().join("c/asset")).is_err());
+        assert_eq!(stream.skipped_entries().len(), 2);
+        assert_eq!(fs::read(&secret).unwrap(), b"secret");
+
+        // The copy counts against the limits.
+        let dir = tempfile::tempdir().unwrap();
+        let mut stream = TarStream::new(&bytes[..])
+            .with_special_entries(SpecialEntries::CopyInternalSymlinks)
+            .with_limits(UnpackLimits {
+                max_total_bytes: 10,
+                ..Default::default()
+            });
+        let mut result = Ok(());
+        while result.is_ok() && !stream.is_finished() {
+            result = stream.advance(dir.path(), u64::MAX).map(|_| ());
+        }
+        assert!(matches!(
+            result,
+            Err(UnityPackageReaderError::LimitExceeded(_))
+        ));
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut stream = TarStream::new(&bytes[..]).with_special_entries(SpecialEntries::Reject);
+        let result = (0..3).try_for_each(|_| stream.advance(dir.path(), u64::MAX).map(|_| ()));
+        assert!(matches!(
+            result,
+            Err(UnityPackageReaderError::UnsafeEntry(_))
+        ));
+    }
+}
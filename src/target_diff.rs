@@ -0,0 +1,299 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    checksums,
+    guid::Guid,
+    prelude::{MetaData, MetaNaming, UnityAssetFile, UnpackConfig},
+    unity_package::normalize_path_key,
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// A single difference between a package and an installed target tree. All
+/// paths are relative to the target directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DiffEntry {
+    /// The installed asset differs from the one in the package.
+    ContentChanged { guid: Guid, path: PathBuf },
+    /// The asset is identical, only its meta file differs.
+    MetaChanged { guid: Guid, path: PathBuf },
+    /// The target has the guid at a different path than the package.
+    Moved {
+        guid: Guid,
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// The package contains an asset the target does not have.
+    OnlyInPackage { guid: Guid, path: PathBuf },
+    /// The target contains an asset (a file with a meta file) that is not
+    /// part of the package.
+    OnlyInTarget { guid: Option<Guid>, path: PathBuf },
+}
+
+/// The result of `UnityPackage::diff_against_target`. Unchanged assets are
+/// not listed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TargetDiff {
+    pub entries: Vec<DiffEntry>,
+}
+
+impl TargetDiff {
+    /// True, if the target matches the package.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// How an asset of the package compares with the target, see
+/// `UnityPackage::compare_with_target`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum TargetStatus {
+    /// Neither the asset nor its meta file exist in the target.
+    Missing,
+    /// The target has the asset and its meta file with the same content.
+    /// Folders are identical if the directory exists with the same meta file.
+    Identical,
+    /// The content or the meta file differs, or the meta file is missing.
+    Modified,
+    /// The target only has the meta file of the asset.
+    MetaOnly,
+}
+
+/// A single asset of the package and how it compares with the target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AssetComparison {
+    pub guid: Guid,
+    /// Path of the asset relative to the target directory.
+    pub relative_path: PathBuf,
+    pub is_folder: bool,
+    pub status: TargetStatus,
+}
+
+/// Classify the compared assets of a `TargetDiff` (see `build_diff`) one by
+/// one. Sorted like assets.
+pub(crate) fn compare_assets(
+    target: &Path,
+    diff: TargetDiff,
+    assets: &[UnityAssetFile],
+    meta_naming: &MetaNaming,
+) -> Result<Vec<AssetComparison>, UnityPackageReaderError> {
+    let changes: HashMap<Guid, DiffEntry> = diff
+        .entries
+        .into_iter()
+        .filter_map(|e| match e {
+            DiffEntry::ContentChanged { guid, .. }
+            | DiffEntry::MetaChanged { guid, .. }
+            | DiffEntry::Moved { guid, .. }
+            | DiffEntry::OnlyInPackage { guid, .. } => Some((guid, e)),
+            DiffEntry::OnlyInTarget { .. } => None,
+        })
+        .collect();
+
+    let mut result = Vec::with_capacity(assets.len());
+    for asset in assets {
+        let (destination, _) = asset.get_target_paths(target, meta_naming)?;
+        let exists = match asset.is_folder() {
+            true => destination.is_dir(),
+            false => destination.is_file(),
+        };
+
+        // The diff only knows the assets whose meta files have been found.
+        let status = match (changes.get(&asset.get_guid()), exists) {
+            (None, true) => TargetStatus::Identical,
+            (None, false) | (Some(DiffEntry::ContentChanged { .. }), false) => {
+                TargetStatus::MetaOnly
+            }
+            (Some(DiffEntry::ContentChanged { .. } | DiffEntry::MetaChanged { .. }), _) => {
+                TargetStatus::Modified
+            }
+            (Some(_), true) => TargetStatus::Modified,
+            (Some(_), false) => TargetStatus::Missing,
+        };
+
+        result.push(AssetComparison {
+            guid: asset.get_guid(),
+            relative_path: asset.get_relative_asset_path().clone(),
+            is_folder: asset.is_folder(),
+            status,
+        });
+    }
+
+    Ok(result)
+}
+
+/// An asset installed in the target, found through its meta file.
+#[derive(Debug)]
+pub(crate) struct InstalledAsset {
+    path: PathBuf,
+    guid: Option<Guid>,
+}
+
+/// The assets of a target tree by their normalized relative path.
+#[derive(Debug, Default)]
+pub(crate) struct TargetTree {
+    assets: BTreeMap<String, InstalledAsset>,
+}
+
+impl TargetTree {
+    /// Collect every meta file below target. A missing target is an empty
+    /// tree. Directories in skip (e.g. the tmp directory) are not entered.
+    pub(crate) fn read(
+        target: &Path,
+        config: &UnpackConfig,
+        skip: &Path,
+    ) -> Result<Self, UnityPackageReaderError> {
+        let mut tree = TargetTree::default();
+        let mut pending = vec![target.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            let entries = match fs::read_dir(&dir) {
+                Ok(e) => e,
+                Err(e) if e.kind() == ErrorKind::NotFound && dir == target => break,
+                Err(e) => return Err(read_error(&dir, e)),
+            };
+
+            for entry in entries {
+                let path = match entry {
+                    Ok(e) => e.path(),
+                    Err(e) => return Err(read_error(&dir, e)),
+                };
+
+                if path.is_dir() {
+                    if path != skip {
+                        pending.push(path);
+                    }
+                    continue;
+                }
+
+                let name = path.to_string_lossy();
+                let asset = match name.strip_suffix(config.meta_naming.suffix()) {
+                    Some(a) => PathBuf::from(a),
+                    None => continue,
+                };
+
+                // An unreadable meta still marks an installed asset.
+                let guid = fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|c| MetaData::parse(&c).ok())
+                    .and_then(|m| m.guid.parse().ok());
+
+                let relative = match asset.strip_prefix(target) {
+                    Ok(r) => r.to_path_buf(),
+                    Err(_) => continue,
+                };
+                tree.assets.insert(
+                    normalize_path_key(&relative.to_string_lossy()),
+                    InstalledAsset {
+                        path: relative,
+                        guid,
+                    },
+                );
+            }
+        }
+
+        Ok(tree)
+    }
+
+    /// The relative path of every asset whose meta could be read, by guid.
+    pub(crate) fn guids(&self) -> HashMap<Guid, PathBuf> {
+        self.assets
+            .values()
+            .filter_map(|a| a.guid.map(|g| (g, a.path.clone())))
+            .collect()
+    }
+}
+
+/// Compare the extracted assets of a package with an installed tree.
+pub(crate) fn build_diff(
+    target: &Path,
+    tree: TargetTree,
+    assets: &[UnityAssetFile],
+    config: &UnpackConfig,
+) -> Result<TargetDiff, UnityPackageReaderError> {
+    let mut remaining = tree.assets;
+    let by_guid: BTreeMap<Guid, String> = remaining
+        .iter()
+        .filter_map(|(key, a)| a.guid.map(|g| (g, key.clone())))
+        .collect();
+
+    let package_keys: BTreeSet<String> = assets
+        .iter()
+        .map(|a| normalize_path_key(&a.get_relative_asset_path().to_string_lossy()))
+        .collect();
+
+    let mut diff = TargetDiff::default();
+    for asset in assets {
+        let guid = asset.get_guid();
+        let path = asset.get_relative_asset_path().clone();
+        let key = normalize_path_key(&path.to_string_lossy());
+
+        if remaining.remove(&key).is_none() {
+            let moved_from = by_guid
+                .get(&guid)
+                .filter(|from| !package_keys.contains(*from))
+                .and_then(|from| remaining.remove(from));
+
+            diff.entries.push(match moved_from {
+                Some(from) => DiffEntry::Moved {
+                    guid,
+                    from: from.path,
+                    to: path,
+                },
+                None => DiffEntry::OnlyInPackage { guid, path },
+            });
+            continue;
+        }
+
+        let (destination, meta) = asset.get_target_paths(target, &config.meta_naming)?;
+        if !asset.is_folder() && !same_content(asset.get_absolute_asset_path(), &destination)? {
+            diff.entries.push(DiffEntry::ContentChanged { guid, path });
+        } else if !same_content(asset.get_absolute_meta_file_path(), &meta)? {
+            diff.entries.push(DiffEntry::MetaChanged { guid, path });
+        }
+    }
+
+    for (_, installed) in remaining {
+        diff.entries.push(DiffEntry::OnlyInTarget {
+            guid: installed.guid,
+            path: installed.path,
+        });
+    }
+
+    Ok(diff)
+}
+
+fn same_content(package: &Path, installed: &Path) -> Result<bool, UnityPackageReaderError> {
+    let expected = match checksums::sha256_file(package) {
+        Ok(h) => h,
+        Err(e) => {
+            return Err(UnityPackageReaderError::CorruptPackage(
+                ErrorInformation::new(Some(format!("{:?}: {}", package, e)), file!(), line!()),
+            ));
+        }
+    };
+
+    match checksums::sha256_file(installed) {
+        Ok(h) => Ok(h == expected),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(read_error(installed, e)),
+    }
+}
+
+fn read_error(path: &Path, e: std::io::Error) -> UnityPackageReaderError {
+    UnityPackageReaderError::CouldNotReadTarget(ErrorInformation::new(
+        Some(format!("{:?}: {}", path, e)),
+        file!(),
+        line!(),
+    ))
+}
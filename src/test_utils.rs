@@ -0,0 +1,157 @@
+//! Helpers shared by the unit tests. Packages are built on the fly so the
+//! tests do not depend on binary fixtures living outside of the repository.
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+use tempfile::TempDir;
+
+use crate::prelude::UnityPackage;
+
+mod package_builder;
+
+#[cfg(feature = "parallel-gzip")]
+pub use package_builder::block_gzip;
+pub use package_builder::{folder_meta, PackageBuilder};
+
+/// The guid of the texture every sample package contains.
+pub const TEXTURE_GUID: &str = "1af567ac160bb164fb19b8cb9b55b34b";
+/// The pathname of the texture every sample package contains.
+pub const TEXTURE_PATH: &str = "Assets/Textures/Ground/IMGP1287.jpg";
+/// The payload of the sample texture.
+pub const TEXTURE_CONTENT: &[u8] = b"\xff\xd8\xff\xe0 not really a jpeg";
+
+pub const FOLDER_GUID: &str = "5b2a0c3e8f1d4a6b9c7e2f1a0b3c4d5e";
+pub const FOLDER_PATH: &str = "Assets/Textures";
+
+pub const SCRIPT_GUID: &str = "9d8c7b6a5f4e3d2c1b0a9f8e7d6c5b4a";
+pub const SCRIPT_PATH: &str = "Assets/Scripts/Player Controller.cs";
+pub const SCRIPT_CONTENT: &[u8] = b"public class PlayerController {}\n";
+
+pub fn texture_meta(guid: &str) -> String {
+    format!(
+        "fileFormatVersion: 2\nguid: {}\nTextureImporter:\n  serializedVersion: 11\n  mipmaps:\n    mipMapMode: 0\n",
+        guid
+    )
+}
+
+pub fn script_meta(guid: &str) -> String {
+    format!(
+        "fileFormatVersion: 2\nguid: {}\nMonoImporter:\n  externalObjects: {{}}\n  serializedVersion: 2\n",
+        guid
+    )
+}
+
+/// A package containing a folder, the sample texture and a script.
+pub fn sample_package() -> PackageBuilder {
+    PackageBuilder::new()
+        .folder(FOLDER_GUID, FOLDER_PATH)
+        .asset(
+            TEXTURE_GUID,
+            TEXTURE_PATH,
+            TEXTURE_CONTENT,
+            &texture_meta(TEXTURE_GUID),
+        )
+        .asset(
+            SCRIPT_GUID,
+            SCRIPT_PATH,
+            SCRIPT_CONTENT,
+            &script_meta(SCRIPT_GUID),
+        )
+}
+
+/// A scratch directory containing a package, a target and a tmp directory.
+pub struct Fixture {
+    pub dir: TempDir,
+    pub package: PathBuf,
+    pub target: PathBuf,
+    pub tmp: PathBuf,
+}
+
+impl Fixture {
+    pub fn new(builder: &PackageBuilder) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let package = dir.path().join("sample.unitypackage");
+        builder.write(&package);
+
+        Fixture {
+            target: dir.path().join("target"),
+            tmp: dir.path().join("tmp"),
+            package,
+            dir,
+        }
+    }
+
+    pub fn sample() -> Self {
+        Fixture::new(&sample_package())
+    }
+
+    pub fn open(&self) -> UnityPackage {
+        UnityPackage::from_path(
+            &self.package,
+            Some(self.target.clone()),
+            Some(self.tmp.clone()),
+        )
+        .unwrap()
+    }
+}
+
+/// The entries of a zip file by name, directories end with a slash. Read
+/// with the zip crate, which checks the crc of every file.
+#[cfg(feature = "zip")]
+pub fn read_zip(path: &Path) -> BTreeMap<String, Vec<u8>> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(path).unwrap()).unwrap();
+    let mut result = BTreeMap::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).unwrap();
+        let mut content = Vec::new();
+        file.read_to_end(&mut content).unwrap();
+        result.insert(file.name().to_string(), content);
+    }
+
+    result
+}
+
+/// The entries of a plain tar file by name, directories end with a slash.
+pub fn read_tar(path: &Path) -> BTreeMap<String, Vec<u8>> {
+    let mut archive = tar::Archive::new(fs::File::open(path).unwrap());
+    let mut result = BTreeMap::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let mut name = entry.path().unwrap().to_string_lossy().into_owned();
+        if entry.header().entry_type().is_dir() && !name.ends_with('/') {
+            name.push('/');
+        }
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content).unwrap();
+        result.insert(name, content);
+    }
+
+    result
+}
+
+/// Every file and directory below dir in the form of `read_zip`.
+pub fn read_tree(dir: &Path) -> BTreeMap<String, Vec<u8>> {
+    fn walk(root: &Path, dir: &Path, result: &mut BTreeMap<String, Vec<u8>>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path
+                .strip_prefix(root)
+                .unwrap()
+                .to_string_lossy()
+                .replace('\\', "/");
+            if path.is_dir() {
+                result.insert(format!("{}/", name), Vec::new());
+                walk(root, &path, result);
+            } else {
+                result.insert(name, fs::read(&path).unwrap());
+            }
+        }
+    }
+
+    let mut result = BTreeMap::new();
+    walk(dir, dir, &mut result);
+    result
+}
@@ -0,0 +1,168 @@
+//! Builds packages for the tests. Shared by the unit tests (through
+//! `test_utils`) and the integration tests (through `tests/common`), so it
+//! only depends on std, flate2 and tar.
+use flate2::{write::GzEncoder, Compression, GzBuilder};
+use std::path::Path;
+
+/// Compress data as BGZF, see `PackageBuilder::block_bytes`.
+pub fn block_gzip(data: &[u8], block_size: usize) -> Vec<u8> {
+    use std::io::Write;
+
+    let mut result = Vec::new();
+    for chunk in data.chunks(block_size).chain([&[][..]]) {
+        let mut encoder = GzBuilder::new()
+            .extra(vec![b'B', b'C', 2, 0, 0, 0])
+            .write(Vec::new(), Compression::default());
+        encoder.write_all(chunk).unwrap();
+        let mut block = encoder.finish().unwrap();
+        let size = (block.len() - 1) as u16;
+        block[16..18].copy_from_slice(&size.to_le_bytes());
+        result.extend_from_slice(&block);
+    }
+
+    result
+}
+
+pub fn folder_meta(guid: &str) -> String {
+    format!(
+        "fileFormatVersion: 2\nguid: {}\nfolderAsset: yes\nDefaultImporter:\n  externalObjects: {{}}\n",
+        guid
+    )
+}
+
+/// How an entry of a `PackageBuilder` is written.
+#[derive(Clone)]
+enum Kind {
+    File,
+    /// A symlink to the target.
+    Symlink(String),
+    /// A file whose name is only given by a PAX record.
+    Pax,
+}
+
+/// Builds a unitypackage (gzip compressed tar) from raw entries.
+#[derive(Default, Clone)]
+pub struct PackageBuilder {
+    /// Name, content, mode and kind.
+    entries: Vec<(String, Vec<u8>, u32, Kind)>,
+}
+
+impl PackageBuilder {
+    pub fn new() -> Self {
+        PackageBuilder::default()
+    }
+
+    /// Add a raw entry, e.g. `<guid>/pathname`.
+    pub fn file(self, name: &str, content: impl AsRef<[u8]>) -> Self {
+        self.file_with_mode(name, content, 0o644)
+    }
+
+    /// Add a raw entry with the given unix permissions.
+    pub fn file_with_mode(mut self, name: &str, content: impl AsRef<[u8]>, mode: u32) -> Self {
+        self.entries.push((
+            String::from(name),
+            content.as_ref().to_vec(),
+            mode,
+            Kind::File,
+        ));
+        self
+    }
+
+    /// Add a raw entry whose name is stored in a PAX extended header, the
+    /// header of the entry itself only has a placeholder.
+    pub fn pax_file(mut self, name: &str, content: impl AsRef<[u8]>) -> Self {
+        self.entries.push((
+            String::from(name),
+            content.as_ref().to_vec(),
+            0o644,
+            Kind::Pax,
+        ));
+        self
+    }
+
+    /// Add a symlink entry pointing to link.
+    pub fn symlink(mut self, name: &str, link: &str) -> Self {
+        self.entries.push((
+            String::from(name),
+            Vec::new(),
+            0o777,
+            Kind::Symlink(String::from(link)),
+        ));
+        self
+    }
+
+    /// Add a complete asset consisting of `asset`, `asset.meta` and `pathname`.
+    pub fn asset(self, guid: &str, pathname: &str, content: &[u8], meta: &str) -> Self {
+        self.file(&format!("{}/asset", guid), content)
+            .file(&format!("{}/asset.meta", guid), meta)
+            .file(&format!("{}/pathname", guid), pathname)
+    }
+
+    /// Add a folder asset which only consists of `asset.meta` and `pathname`.
+    pub fn folder(self, guid: &str, pathname: &str) -> Self {
+        self.file(&format!("{}/asset.meta", guid), folder_meta(guid))
+            .file(&format!("{}/pathname", guid), pathname)
+    }
+
+    /// The uncompressed tar stream.
+    pub fn tar_bytes(&self) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content, mode, kind) in &self.entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(*mode);
+            header.set_mtime(1_600_000_000);
+            match kind {
+                Kind::File => {
+                    header.set_cksum();
+                    builder
+                        .append_data(&mut header, name, &content[..])
+                        .unwrap();
+                }
+                Kind::Symlink(link) => {
+                    header.set_entry_type(tar::EntryType::Symlink);
+                    builder.append_link(&mut header, name, link).unwrap();
+                }
+                Kind::Pax => {
+                    let record = format!(" path={}\n", name);
+                    let digits = (record.len() + 2).to_string().len();
+                    let record = format!("{}{}", record.len() + digits, record);
+                    let mut pax = tar::Header::new_ustar();
+                    pax.set_entry_type(tar::EntryType::XHeader);
+                    pax.set_size(record.len() as u64);
+                    pax.set_path("PaxHeader").unwrap();
+                    pax.set_cksum();
+                    builder.append(&pax, record.as_bytes()).unwrap();
+
+                    let mut header = tar::Header::new_ustar();
+                    header.set_size(content.len() as u64);
+                    header.set_mode(*mode);
+                    header.set_path("placeholder").unwrap();
+                    header.set_cksum();
+                    builder.append(&header, &content[..]).unwrap();
+                }
+            }
+        }
+
+        builder.into_inner().unwrap()
+    }
+
+    /// The gzip compressed tar stream, i.e. the content of a unitypackage.
+    pub fn bytes(&self) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&self.tar_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// The package as BGZF, gzip members of block_size uncompressed bytes
+    /// that carry their compressed size, followed by an empty member.
+    pub fn block_bytes(&self, block_size: usize) -> Vec<u8> {
+        block_gzip(&self.tar_bytes(), block_size)
+    }
+
+    pub fn write(&self, path: &Path) {
+        std::fs::write(path, self.bytes()).unwrap();
+    }
+}
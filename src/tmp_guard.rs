@@ -0,0 +1,65 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Owns the tmp directory of an unpack. Unless it is kept, the directory is
+/// removed when the guard is dropped, so an unpack that returns early with an
+/// error or panics does not leave a half extracted package behind.
+pub(crate) struct TempDirGuard {
+    path: PathBuf,
+    delete: bool,
+}
+
+impl TempDirGuard {
+    /// Guard path. With delete false, the guard never removes anything
+    /// (`UnpackOptions::delete_tmp` off).
+    pub(crate) fn new(path: PathBuf, delete: bool) -> Self {
+        TempDirGuard { path, delete }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Leave the directory where it is and hand out its path.
+    pub(crate) fn keep(mut self) -> PathBuf {
+        self.delete = false;
+        std::mem::take(&mut self.path)
+    }
+}
+
+impl Drop for TempDirGuard {
+    fn drop(&mut self) {
+        // Nothing can be reported from here, the directory is left behind.
+        if self.delete {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_removes_unless_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let tmp = dir.path().join("tmp");
+
+        fs::create_dir_all(tmp.join("a")).unwrap();
+        drop(TempDirGuard::new(tmp.clone(), true));
+        assert!(!tmp.exists());
+
+        fs::create_dir_all(tmp.join("a")).unwrap();
+        drop(TempDirGuard::new(tmp.clone(), false));
+        assert!(tmp.is_dir());
+
+        assert_eq!(TempDirGuard::new(tmp.clone(), true).keep(), tmp);
+        assert!(tmp.is_dir());
+
+        // A directory that is gone already is no error.
+        fs::remove_dir_all(&tmp).unwrap();
+        drop(TempDirGuard::new(tmp, true));
+    }
+}
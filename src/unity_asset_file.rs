@@ -1,27 +1,80 @@
-use crate::{prelude::UnityPackageReaderError, unpacker_error::ErrorInformation};
+use crate::{
+    checksums,
+    file_system::{FileSystem, OsFileSystem},
+    guid::Guid,
+    guid_remap,
+    prelude::{
+        AssetCategory, Backup, Importer, MetaData, MetaFormat, MetaNaming, OverwritePolicy,
+        UnityPackageReaderError, UnpackConfig,
+    },
+    unity_meta::LEGACY_META_FILE,
+    unpacker_error::ErrorInformation,
+};
 use std::{
-    fs,
+    collections::BTreeMap,
+    fmt, fs, io,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct UnityAssetFile {
     /// The guid of this asset. This equals
     /// the directory of the asset in the tmp directory.
-    guid: String,
+    guid: Guid,
     /// Absolute path to the asset.
     asset: PathBuf,
     /// Relative path inside the target folder.
     target: PathBuf,
+    /// The pathname inside the package, if the asset is installed at another path.
+    package_path: Option<PathBuf>,
     /// Absolute path to the meta data file
     meta: PathBuf,
     /// True, if an asset is a folder (which means, there is none)
     is_folder: bool,
+    /// False, if the package has no meta file for the asset (`UnpackConfig::lenient`).
+    has_meta: bool,
+    /// Whether the meta has been found as `asset.meta` or as legacy `metaData`.
+    meta_format: MetaFormat,
+    /// True, if the asset has not been copied because the target already existed.
+    skipped: bool,
+    /// True, if an incremental unpack found the asset in the target already.
+    unchanged: bool,
+    /// True, if the last copy replaced an existing file.
+    overwritten: bool,
+    /// True, if the meta marks this asset as folder but there is a non-empty payload.
+    folder_with_payload: bool,
+    /// Bytes moved into the target by the last copy.
+    installed_bytes: u64,
+    /// The meta file written into the target by the last copy.
+    installed_meta: Option<PathBuf>,
+    /// Where the payload has been moved to by the last copy.
+    installed_asset: Option<PathBuf>,
+    /// The copies of the files the last copy has overwritten.
+    backups: Vec<PathBuf>,
+    /// Why the last copy could not set the modification times.
+    mtime_error: Option<String>,
+    /// The lines of the pathname file after the path.
+    pathname_extra: Vec<String>,
+    /// True, if the pathname file used backslashes as separators.
+    backslash_pathname: bool,
+    /// Modification times of the extracted asset and meta file, which are
+    /// the times of the archive if `UnpackConfig::preserve_mtimes` is set.
+    mtimes: (Option<SystemTime>, Option<SystemTime>),
+    /// Size of the asset payload in bytes (0 for folders).
+    size: u64,
+    /// The parsed content of the meta file.
+    meta_data: MetaData,
+    /// The raw content of the meta file, kept up to date with `meta_data`.
+    meta_content: String,
+    /// The sha256 of the payload, computed while unpacking if
+    /// `UnpackConfig::hash_assets` is set.
+    sha256: Option<[u8; 32]>,
 }
 
 impl UnityAssetFile {
-    pub fn get_guid(&self) -> &String {
-        &self.guid
+    pub fn get_guid(&self) -> Guid {
+        self.guid
     }
     pub fn get_absolute_asset_path(&self) -> &PathBuf {
         &self.asset
@@ -29,14 +82,349 @@ impl UnityAssetFile {
     pub fn get_relative_asset_path(&self) -> &PathBuf {
         &self.target
     }
+    /// The pathname of the asset inside the package. Differs from
+    /// `get_relative_asset_path` if the asset is installed at another path
+    /// (`CaseCollisions::RenameWithSuffix`, `UnpackConfig::flatten`).
+    pub fn get_package_path(&self) -> &PathBuf {
+        self.package_path.as_ref().unwrap_or(&self.target)
+    }
     pub fn get_absolute_meta_file_path(&self) -> &PathBuf {
         &self.meta
     }
     pub fn is_folder(&self) -> bool {
         self.is_folder
     }
+    /// Size of the asset payload in bytes.
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+    /// True, if `copy_asset_with_policy` left an existing file in the target alone.
+    pub fn is_skipped(&self) -> bool {
+        self.skipped
+    }
+    /// True, if an incremental unpack has left the asset in the target alone,
+    /// because it is the same as the last time (`UnpackConfig::incremental`).
+    pub fn is_unchanged(&self) -> bool {
+        self.unchanged
+    }
+    /// True, if the last copy replaced a file (or meta file) of the target.
+    pub fn is_overwritten(&self) -> bool {
+        self.overwritten
+    }
+    /// True, if the meta file says `folderAsset: yes`, but the package also
+    /// contains a non-empty asset payload for this guid.
+    pub fn is_folder_with_payload(&self) -> bool {
+        self.folder_with_payload
+    }
+    /// The absolute path of the meta file written into the target, named
+    /// according to `UnpackConfig::meta_naming`. None if nothing has been copied.
+    pub fn get_installed_meta_path(&self) -> Option<&PathBuf> {
+        self.installed_meta.as_ref()
+    }
+    /// The backups of the files the last copy has overwritten, see
+    /// `UnpackConfig::backup`.
+    pub fn get_backups(&self) -> &[PathBuf] {
+        &self.backups
+    }
+    /// False, if the package has no meta file for the asset, so none is
+    /// installed (`UnpackConfig::lenient`).
+    pub fn has_meta(&self) -> bool {
+        self.has_meta
+    }
+    /// Whether the package carries the meta as `asset.meta` or, like Unity
+    /// 4.x, as `metaData`. The installed meta is named by `UnpackConfig::meta_naming`
+    /// either way.
+    pub fn get_meta_format(&self) -> MetaFormat {
+        self.meta_format
+    }
+    /// True, if the payload or the meta file is in the target.
+    pub(crate) fn is_installed(&self) -> bool {
+        self.installed_meta.is_some() || self.installed_asset.is_some()
+    }
+    /// The lines the `pathname` file contains after the path, e.g. `00` in
+    /// packages of newer Unity versions. They are not part of the path.
+    pub fn get_pathname_extra_lines(&self) -> &[String] {
+        &self.pathname_extra
+    }
+    /// True, if the `pathname` file has lines after the path other than the
+    /// single number newer Unity versions write.
+    pub(crate) fn has_unknown_pathname_lines(&self) -> bool {
+        match &self.pathname_extra[..] {
+            [] => false,
+            [line] => !line.bytes().all(|b| b.is_ascii_digit()),
+            _ => true,
+        }
+    }
+    /// Why the last copy could not set the modification times, see
+    /// `Warning::TimestampNotApplied`. Cleared by reading it.
+    pub(crate) fn take_mtime_error(&mut self) -> Option<String> {
+        self.mtime_error.take()
+    }
+    /// True, if the `pathname` file separated the path with backslashes. The
+    /// relative path always uses forward slashes.
+    pub fn has_backslash_pathname(&self) -> bool {
+        self.backslash_pathname
+    }
+    /// The content of the meta file, read while indexing the package.
+    pub fn meta_data(&self) -> &MetaData {
+        &self.meta_data
+    }
+    /// The category derived from the extension and the importer, see
+    /// `AssetCategory::of`. Folders are `AssetCategory::Other`.
+    pub fn category(&self) -> AssetCategory {
+        if self.is_folder {
+            return AssetCategory::Other;
+        }
+
+        AssetCategory::of(&self.target, self.meta_data.importer.as_deref())
+    }
+    /// The importer named by the meta file, `Importer::Unknown` if the asset
+    /// has no meta or the meta names none.
+    pub fn importer(&self) -> Importer {
+        Importer::from_name(self.meta_data.importer.as_deref())
+    }
+
+    /// Where the payload is right now: in tmp before `copy_asset`, in the target
+    /// afterwards.
+    pub fn get_current_asset_path(&self) -> &PathBuf {
+        self.installed_asset.as_ref().unwrap_or(&self.asset)
+    }
+
+    /// The content of the payload, read from `get_current_asset_path`.
+    /// Fails with `AssetIsFolder` for folder assets.
+    pub fn read_asset_bytes(&self) -> Result<Vec<u8>, UnityPackageReaderError> {
+        if self.is_folder {
+            return Err(UnityPackageReaderError::AssetIsFolder(
+                ErrorInformation::new(
+                    Some(format!("{} ('{}')", self.guid, self.target.display())),
+                    file!(),
+                    line!(),
+                ),
+            ));
+        }
+
+        let path = self.get_current_asset_path();
+        match fs::read(path) {
+            Ok(c) => Ok(c),
+            Err(e) => Err(UnityPackageReaderError::CouldNotReadAsset(
+                ErrorInformation::new(Some(format!("{:?}: {}", path, e)), file!(), line!()),
+            )),
+        }
+    }
+
+    /// The sha256 of the payload. Hashed while unpacking if
+    /// `UnpackConfig::hash_assets` is set, otherwise `get_current_asset_path`
+    /// is read in chunks. Fails with `AssetIsFolder` for folder assets.
+    pub fn sha256(&self) -> Result<[u8; 32], UnityPackageReaderError> {
+        if self.is_folder {
+            return Err(UnityPackageReaderError::AssetIsFolder(
+                ErrorInformation::new(
+                    Some(format!("{} ('{}')", self.guid, self.target.display())),
+                    file!(),
+                    line!(),
+                ),
+            ));
+        }
+
+        match self.sha256 {
+            Some(hash) => Ok(hash),
+            None => hash_file(self.get_current_asset_path()),
+        }
+    }
+
+    /// The hash computed while unpacking, None for folders or if
+    /// `UnpackConfig::hash_assets` is off.
+    pub(crate) fn get_stored_sha256(&self) -> Option<[u8; 32]> {
+        self.sha256
+    }
+
+    pub(crate) fn set_sha256(&mut self, hash: [u8; 32]) {
+        self.sha256 = Some(hash);
+    }
+
+    /// Same as `read_asset_bytes`, for assets stored as UTF-8 text.
+    pub fn read_asset_string(&self) -> Result<String, UnityPackageReaderError> {
+        match String::from_utf8(self.read_asset_bytes()?) {
+            Ok(s) => Ok(s),
+            Err(e) => Err(UnityPackageReaderError::CouldNotReadAsset(
+                ErrorInformation::new(
+                    Some(format!("{:?}: {}", self.get_current_asset_path(), e)),
+                    file!(),
+                    line!(),
+                ),
+            )),
+        }
+    }
+
+    /// Where the meta file is right now: in tmp before `copy_asset`, in the
+    /// target (named according to `UnpackConfig::meta_naming`) afterwards.
+    pub fn get_current_meta_path(&self) -> &PathBuf {
+        self.installed_meta.as_ref().unwrap_or(&self.meta)
+    }
+
+    /// The raw YAML of the meta file. The content is read once while indexing
+    /// and kept, so this does not touch the disk.
+    pub fn read_meta(&self) -> Result<String, UnityPackageReaderError> {
+        Ok(self.meta_content.clone())
+    }
+
+    /// The number of bytes (asset and meta) moved into the target by the last copy.
+    pub(crate) fn get_installed_bytes(&self) -> u64 {
+        self.installed_bytes
+    }
+
+    /// Apply `UnpackConfig::guid_remap` to the extracted meta file and, if
+    /// `rewrite_references` is set, to the references inside a text serialized
+    /// asset. Returns false if the asset is a binary serialized Unity asset
+    /// whose references could not be rewritten.
+    pub(crate) fn remap_guids(
+        &mut self,
+        config: &UnpackConfig,
+    ) -> Result<bool, UnityPackageReaderError> {
+        if config.guid_remap.is_empty() {
+            return Ok(true);
+        }
+
+        if self.has_meta {
+            rewrite_file(&self.meta, &config.guid_remap)?;
+            (self.meta_content, self.meta_data) = Self::read_meta_data(
+                &self.meta,
+                self.meta_format,
+                &self.guid.to_string(),
+                &OsFileSystem,
+            )?;
+        }
+        if !config.rewrite_references
+            || self.is_folder
+            || !guid_remap::is_unity_serialized(&self.target)
+        {
+            return Ok(true);
+        }
+
+        let rewritten = rewrite_file(&self.asset, &config.guid_remap)?;
+        // The stored hash has to match what ends up in the target.
+        if rewritten && self.sha256.is_some() {
+            self.sha256 = Some(hash_file(&self.asset)?);
+        }
+
+        Ok(rewritten)
+    }
+
+    /// Leave the asset out of the target without looking at it.
+    pub(crate) fn mark_skipped(&mut self) {
+        self.skipped = true;
+        self.unchanged = false;
+        self.overwritten = false;
+        self.installed_bytes = 0;
+        self.installed_meta = None;
+        self.installed_asset = None;
+    }
+
+    /// Leave the asset in the target alone, because it is installed at asset
+    /// and meta already.
+    pub(crate) fn mark_unchanged(&mut self, asset: PathBuf, meta: Option<PathBuf>) {
+        self.unchanged = true;
+        self.skipped = false;
+        self.overwritten = false;
+        self.installed_bytes = 0;
+        self.installed_meta = meta;
+        self.installed_asset = (!self.is_folder()).then_some(asset);
+    }
+
+    /// `OverwritePolicy::IfChanged`: true, if the target has the asset (or
+    /// folder) and its meta with the same content already.
+    pub(crate) fn is_identical_in(
+        &self,
+        target_path: &Path,
+        config: &UnpackConfig,
+        fs: &dyn FileSystem,
+    ) -> Result<bool, UnityPackageReaderError> {
+        let (existing_asset, existing_meta) =
+            self.get_target_paths(target_path, &config.meta_naming)?;
+        let asset_matches = if self.is_folder() {
+            fs.exists(&existing_asset)
+        } else {
+            same_content(fs, &self.asset, &existing_asset)
+        };
+        let meta_matches = !(config.installs_metas() && self.has_meta)
+            || same_content(fs, &self.meta, &existing_meta);
+
+        Ok(asset_matches && meta_matches)
+    }
+
+    /// Install the asset at another path inside the target.
+    pub(crate) fn set_relative_asset_path(&mut self, target: PathBuf) {
+        if self.package_path.is_none() {
+            self.package_path = Some(self.target.clone());
+        }
+        self.target = target;
+    }
+
+    /// Treat a folder asset that carries a payload as a regular file.
+    pub(crate) fn install_payload_as_file(&mut self) {
+        if self.folder_with_payload {
+            self.is_folder = false;
+        }
+    }
 
     pub fn from(path: PathBuf) -> Result<Self, UnityPackageReaderError> {
+        Self::from_file_system(path, &OsFileSystem)
+    }
+
+    /// Same as `from`, but the guid directory is read through fs.
+    pub(crate) fn from_file_system(
+        path: PathBuf,
+        fs: &dyn FileSystem,
+    ) -> Result<Self, UnityPackageReaderError> {
+        let asset = path.join("asset");
+        let (meta, meta_format) = meta_file(&path, fs)
+            .unwrap_or_else(|| (path.join("asset.meta"), MetaFormat::AssetMeta));
+
+        Self::from_files(path, asset, meta, Some(meta_format), fs)
+    }
+
+    /// Same as `from_file_system` for a guid directory without `asset.meta`
+    /// (`UnpackConfig::lenient`). The asset is no folder and has no meta to copy.
+    pub(crate) fn from_file_system_without_meta(
+        path: PathBuf,
+        fs: &dyn FileSystem,
+    ) -> Result<Self, UnityPackageReaderError> {
+        let asset = path.join("asset");
+        let meta = path.join("asset.meta");
+
+        Self::from_files(path, asset, meta, None, fs)
+    }
+
+    /// Index a guid directory of the tmp directory whose asset and meta file
+    /// an interrupted unpack has moved into target_root already. The asset is
+    /// marked as skipped. Returns None if the meta is not in the target either.
+    pub(crate) fn from_installed(
+        path: PathBuf,
+        target_root: &Path,
+        meta_naming: &MetaNaming,
+        fs: &dyn FileSystem,
+    ) -> Result<Option<Self>, UnityPackageReaderError> {
+        let (relative, _, _) = Self::get_relative_path(&path.join("pathname"), fs)?;
+        let asset = target_root.join(relative);
+        let meta = Self::get_meta_target_path(&asset, meta_naming)?;
+        if fs.file_size(&meta).is_none() {
+            return Ok(None);
+        }
+
+        let mut result =
+            Self::from_files(path, asset, meta.clone(), Some(MetaFormat::AssetMeta), fs)?;
+        result.mark_skipped();
+        result.installed_meta = Some(meta);
+        Ok(Some(result))
+    }
+
+    fn from_files(
+        path: PathBuf,
+        asset: PathBuf,
+        meta: PathBuf,
+        meta_format: Option<MetaFormat>,
+        fs: &dyn FileSystem,
+    ) -> Result<Self, UnityPackageReaderError> {
         let h = match path.file_name() {
             Some(h) => h.to_str(),
             None => {
@@ -59,45 +447,81 @@ impl UnityAssetFile {
             }
         };
 
-        let origin = path.clone();
-        let mut asset = path.clone();
-        asset.push("asset");
+        let guid = match Guid::parse(&hash) {
+            Ok(g) => g,
+            Err(_) => {
+                return Err(UnityPackageReaderError::InvalidGuid(ErrorInformation::new(
+                    Some(format!("The directory {:?} is not named by a guid", path)),
+                    file!(),
+                    line!(),
+                )));
+            }
+        };
 
-        let mut pathname = origin.clone();
-        pathname.push("pathname");
+        let pathname = path.join("pathname");
 
-        let mut meta = path.clone();
-        meta.push("asset.meta");
+        let (target, pathname_extra, backslash_pathname) =
+            match Self::get_relative_path(&pathname, fs) {
+                Ok(e) => e,
+                Err(e) => {
+                    return Err(UnityPackageReaderError::CorruptPackage(
+                        ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                    ));
+                }
+            };
 
-        let target = match Self::get_relative_path(&pathname) {
-            Ok(e) => e,
-            Err(e) => {
-                return Err(UnityPackageReaderError::CorruptPackage(
-                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
-                ));
-            }
+        let meta_data = match meta_format {
+            Some(format) => Self::read_meta_data(&meta, format, &hash, fs),
+            None => Ok((String::new(), MetaData::default())),
         };
-
-        let is_folder = match Self::get_is_folder(&meta) {
+        let (meta_content, meta_data) = match meta_data {
             Ok(e) => e,
             Err(e) => {
                 return Err(UnityPackageReaderError::CouldReadMetaFile(
-                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                    ErrorInformation::new(Some(format!("{}: {}", hash, e)), file!(), line!()),
                 ));
             }
         };
+        let is_folder = meta_data.folder_asset;
+
+        let size = fs.file_size(&asset).unwrap_or(0);
+        let mtimes = (modified(&asset), modified(&meta));
+        // Some exporters mix up a folder and a file with the same path.
+        let folder_with_payload = is_folder && size > 0;
 
         Ok(UnityAssetFile {
-            guid: hash,
+            guid,
             asset,
             target,
             meta,
             is_folder,
+            has_meta: meta_format.is_some(),
+            meta_format: meta_format.unwrap_or_default(),
+            package_path: None,
+            skipped: false,
+            unchanged: false,
+            overwritten: false,
+            folder_with_payload,
+            installed_bytes: 0,
+            installed_meta: None,
+            installed_asset: None,
+            backups: Vec::new(),
+            mtime_error: None,
+            pathname_extra,
+            backslash_pathname,
+            mtimes,
+            size,
+            meta_data,
+            meta_content,
+            sha256: None,
         })
     }
 
-    fn get_relative_path(file: &PathBuf) -> Result<PathBuf, UnityPackageReaderError> {
-        let content = match fs::read_to_string(file) {
+    fn get_relative_path(
+        file: &Path,
+        fs: &dyn FileSystem,
+    ) -> Result<(PathBuf, Vec<String>, bool), UnityPackageReaderError> {
+        let content = match fs.read_to_string(file) {
             Ok(e) => e,
             Err(e) => {
                 return Err(UnityPackageReaderError::CorruptPackage(
@@ -106,11 +530,25 @@ impl UnityAssetFile {
             }
         };
 
-        Ok(PathBuf::from(content))
+        match trim_pathname(&content) {
+            "" => Err(UnityPackageReaderError::CorruptPackage(
+                ErrorInformation::new(Some(format!("{:?} is empty", file)), file!(), line!()),
+            )),
+            p => Ok((
+                PathBuf::from(normalize_pathname(p)),
+                pathname_extra_lines(&content),
+                p.contains('\\'),
+            )),
+        }
     }
 
-    fn get_is_folder(file: &PathBuf) -> Result<bool, UnityPackageReaderError> {
-        let content = match fs::read_to_string(file) {
+    fn read_meta_data(
+        file: &Path,
+        format: MetaFormat,
+        guid: &str,
+        fs: &dyn FileSystem,
+    ) -> Result<(String, MetaData), UnityPackageReaderError> {
+        let content = match fs.read_to_string(file) {
             Ok(e) => e,
             Err(e) => {
                 return Err(UnityPackageReaderError::CorruptPackage(
@@ -119,22 +557,70 @@ impl UnityAssetFile {
             }
         };
 
-        Ok(content.contains("folderAsset: yes"))
+        let meta_data = MetaData::parse_format(&content, format, Some(guid))?;
+        Ok((content, meta_data))
     }
 
     /// Copy this file from the tmp folder to the target folder. The folder structure
     /// inside the unitypackage file will be maintained. So this method creates all
     /// directories inside the target folder, that are needed to achive this.
     /// Besides the asset itself the meta file is copied over as well. However its
-    /// extension is changed to .unitymeta to destinguish it from other meta files.
+    /// extension is changed to .unitymeta to destinguish it from other meta files
+    /// (see `UnpackConfig::meta_naming`). Existing files in the target are overwritten.
     pub fn copy_asset(&mut self, target_path: &Path) -> Result<(), UnityPackageReaderError> {
-        if self.is_folder() {
+        self.copy_asset_with_config(target_path, &UnpackConfig::default())
+    }
+
+    /// Same as `copy_asset`, but the given policy decides what happens if the asset
+    /// or its meta file already exist in the target. The meta file always follows
+    /// the decision made for its asset, so both never get out of sync.
+    pub fn copy_asset_with_policy(
+        &mut self,
+        target_path: &Path,
+        policy: OverwritePolicy,
+    ) -> Result<(), UnityPackageReaderError> {
+        let config = UnpackConfig {
+            overwrite_policy: policy,
+            ..Default::default()
+        };
+
+        self.copy_asset_with_config(target_path, &config)
+    }
+
+    /// Same as `copy_asset`, but honors the overwrite policy, the meta naming and
+    /// the folder creation setting of the given configuration. Folder assets are
+    /// only copied (as a directory plus its meta file) if `create_folders` is
+    /// set and `flatten` is not.
+    pub fn copy_asset_with_config(
+        &mut self,
+        target_path: &Path,
+        config: &UnpackConfig,
+    ) -> Result<(), UnityPackageReaderError> {
+        self.copy_asset_into(target_path, target_path, config, &OsFileSystem, None)
+    }
+
+    /// Same as `copy_asset_with_config`, but the files are written below
+    /// destination instead of target_path, e.g. into a staging directory.
+    /// The existing files are still looked up in target_path. All file
+    /// operations go through fs, copies use a buffer of copy_buffer bytes
+    /// (see `BufferSizes::copy`).
+    pub(crate) fn copy_asset_into(
+        &mut self,
+        target_path: &Path,
+        destination: &Path,
+        config: &UnpackConfig,
+        fs: &dyn FileSystem,
+        copy_buffer: Option<usize>,
+    ) -> Result<(), UnityPackageReaderError> {
+        if self.is_folder() && !config.installs_folders() {
             return Ok(());
         }
 
-        let mut absolute_target_path = target_path.to_path_buf();
-        // add the path we extracted from to the target directory.
-        absolute_target_path.push(&self.target);
+        let installs_meta = config.installs_metas() && self.has_meta;
+        let (existing_asset, existing_meta) =
+            self.get_target_paths(target_path, &config.meta_naming)?;
+        let (absolute_target_path, meta_target_file_name) =
+            self.get_target_paths(destination, &config.meta_naming)?;
         let parent = match absolute_target_path.parent() {
             Some(e) => e.to_path_buf(),
             None => {
@@ -148,8 +634,63 @@ impl UnityAssetFile {
             }
         };
 
-        if !parent.as_path().exists() {
-            match std::fs::create_dir_all(parent.clone()) {
+        // An existing directory is fine for a folder asset, only its meta can clash.
+        let existing = if !self.is_folder() && fs.exists(&existing_asset) {
+            Some(&existing_asset)
+        } else if installs_meta && fs.exists(&existing_meta) {
+            Some(&existing_meta)
+        } else {
+            None
+        };
+
+        if let Some(existing) = existing {
+            match config.overwrite_policy {
+                OverwritePolicy::Overwrite | OverwritePolicy::IfChanged => {}
+                OverwritePolicy::Skip => {
+                    self.mark_skipped();
+                    return Ok(());
+                }
+                OverwritePolicy::Error => {
+                    return Err(UnityPackageReaderError::TargetFileExists(
+                        ErrorInformation::new(
+                            Some(format!("'{}'", existing.display())),
+                            file!(),
+                            line!(),
+                        ),
+                    ));
+                }
+            }
+        }
+
+        // Only the files that differ from the target are written.
+        let (write_asset, write_meta) = match existing {
+            Some(_) if config.overwrite_policy == OverwritePolicy::IfChanged => (
+                !self.is_folder() && !same_content(fs, &self.asset, &existing_asset),
+                installs_meta && !same_content(fs, &self.meta, &existing_meta),
+            ),
+            _ => (!self.is_folder(), installs_meta),
+        };
+        if existing.is_some()
+            && !write_asset
+            && !write_meta
+            && (!self.is_folder() || fs.exists(&existing_asset))
+        {
+            self.mark_unchanged(
+                existing_asset.clone(),
+                installs_meta.then_some(existing_meta.clone()),
+            );
+            return Ok(());
+        }
+
+        self.overwritten = existing.is_some();
+        let directory = if self.is_folder() {
+            absolute_target_path.clone()
+        } else {
+            parent
+        };
+
+        if !fs.exists(&directory) {
+            match fs.create_dir_all(&directory) {
                 Ok(_) => {}
                 Err(e) => {
                     return Err(UnityPackageReaderError::TargetDirectoryCouldNotBeCreated(
@@ -159,22 +700,113 @@ impl UnityAssetFile {
             }
         }
 
-        let asset = match std::fs::rename(&self.asset, absolute_target_path.clone()) {
-            Ok(_) => absolute_target_path,
-            Err(e) => {
-                return Err(UnityPackageReaderError::CorruptPackage(
-                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
-                ));
+        // Replacing a read-only file fails on Windows, so the flag is checked
+        // before anything is written.
+        let mut readonly = Vec::new();
+        if write_asset && fs.is_readonly(&existing_asset) {
+            readonly.push((existing_asset.clone(), absolute_target_path.clone()));
+        }
+        if write_meta && fs.is_readonly(&existing_meta) {
+            readonly.push((existing_meta.clone(), meta_target_file_name.clone()));
+        }
+        if let Some((path, _)) = readonly.first().filter(|_| !config.clear_readonly) {
+            return Err(UnityPackageReaderError::ReadOnlyTarget(
+                ErrorInformation::new(
+                    Some(format!(
+                        "'{}' is read-only and clear_readonly is off.",
+                        path.display()
+                    )),
+                    file!(),
+                    line!(),
+                ),
+            ));
+        }
+
+        self.backups.clear();
+        if let Some(backup) = &config.backup {
+            let mut overwritten = Vec::new();
+            if write_asset && fs.exists(&existing_asset) {
+                overwritten.push(existing_asset.as_path());
             }
-        };
+            if write_meta && fs.exists(&existing_meta) {
+                overwritten.push(existing_meta.as_path());
+            }
+            // Staged files replace the target later, so the originals stay.
+            let keep = destination != target_path;
+            self.backups = back_up(fs, target_path, &overwritten, backup, keep, copy_buffer)?;
+        }
 
-        let mut meta_target_file_name = asset.to_path_buf();
-        let f = match meta_target_file_name.file_name() {
+        // Staged files are placed by renaming the originals away first, only
+        // a direct install replaces them.
+        if destination == target_path {
+            for (path, _) in readonly.iter().filter(|(p, _)| fs.exists(p)) {
+                set_readonly(fs, path, false)?;
+            }
+        }
+
+        let mut installed_bytes = 0;
+        if write_asset {
+            installed_bytes += move_file(fs, &self.asset, &absolute_target_path, copy_buffer)?;
+        }
+
+        if write_meta {
+            installed_bytes += move_file(fs, &self.meta, &meta_target_file_name, copy_buffer)?;
+        }
+        self.installed_bytes = installed_bytes;
+
+        if config.restore_readonly {
+            for (_, path) in &readonly {
+                set_readonly(fs, path, true)?;
+            }
+        }
+
+        // Moving keeps the time, but copying or rewriting the file does not.
+        // The files are in place already, so a failure is only a warning.
+        if config.preserve_mtimes {
+            let mut result = Ok(());
+            if write_asset {
+                result = set_modified(&absolute_target_path, self.mtimes.0);
+            }
+            if write_meta {
+                result = result.and(set_modified(&meta_target_file_name, self.mtimes.1));
+            }
+            self.mtime_error = result.err();
+        }
+        self.installed_meta = installs_meta.then_some(existing_meta);
+        self.installed_asset = (!self.is_folder()).then_some(existing_asset);
+
+        self.skipped = false;
+        self.unchanged = false;
+        Ok(())
+    }
+
+    /// The absolute paths the asset and its meta file are copied to.
+    pub(crate) fn get_target_paths(
+        &self,
+        target_path: &Path,
+        meta_naming: &MetaNaming,
+    ) -> Result<(PathBuf, PathBuf), UnityPackageReaderError> {
+        let mut absolute_target_path = target_path.to_path_buf();
+        // add the path we extracted from to the target directory.
+        absolute_target_path.push(&self.target);
+        let meta = Self::get_meta_target_path(&absolute_target_path, meta_naming)?;
+
+        Ok((absolute_target_path, meta))
+    }
+
+    /// The meta file is stored next to the asset, its name is the name of the
+    /// asset with the suffix of the meta naming (.unitymeta by default) appended.
+    fn get_meta_target_path(
+        asset: &Path,
+        meta_naming: &MetaNaming,
+    ) -> Result<PathBuf, UnityPackageReaderError> {
+        meta_naming.check()?;
+        let f = match asset.file_name() {
             Some(s) => s.to_str(),
             None => {
                 return Err(UnityPackageReaderError::CorruptPackage(
                     ErrorInformation::new(
-                        Some(format!("{:?} is a directory", meta_target_file_name)),
+                        Some(format!("{:?} is a directory", asset)),
                         file!(),
                         line!(),
                     ),
@@ -195,13 +827,13 @@ impl UnityAssetFile {
             }
         };
 
-        file_name.push_str(".unitymeta");
-        meta_target_file_name = match meta_target_file_name.parent() {
+        file_name.push_str(meta_naming.suffix());
+        let mut meta_target_file_name = match asset.parent() {
             Some(s) => s.to_path_buf(),
             None => {
                 return Err(UnityPackageReaderError::CorruptPackage(
                     ErrorInformation::new(
-                        Some(format!("'{:?}' is a root directory", meta_target_file_name)),
+                        Some(format!("'{:?}' is a root directory", asset)),
                         file!(),
                         line!(),
                     ),
@@ -210,15 +842,891 @@ impl UnityAssetFile {
         };
 
         meta_target_file_name.push(file_name);
-        match std::fs::rename(&self.meta, meta_target_file_name.clone()) {
-            Ok(_) => {}
-            Err(e) => {
-                return Err(UnityPackageReaderError::CorruptPackage(
-                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+        Ok(meta_target_file_name)
+    }
+}
+
+/// A one-liner for logs: the first 8 digits of the guid, the relative path and
+/// whether it is a file or a folder.
+impl fmt::Display for UnityAssetFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let guid = self.guid.to_string();
+        write!(
+            f,
+            "{}\u{2026} -> {} ({})",
+            &guid[..8],
+            self.target.display(),
+            if self.is_folder { "folder" } else { "file" }
+        )
+    }
+}
+
+/// The guid and the relative path first, the meta data is left out.
+impl fmt::Debug for UnityAssetFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UnityAssetFile")
+            .field("guid", &format_args!("{}", self.guid))
+            .field("relative_path", &self.target)
+            .field("is_folder", &self.is_folder)
+            .field("size", &self.size)
+            .field("skipped", &self.skipped)
+            .field("overwritten", &self.overwritten)
+            .field("asset", &self.asset)
+            .field("meta", &self.meta)
+            .field("installed_asset", &self.installed_asset)
+            .field("installed_meta", &self.installed_meta)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The relative path stored in a `pathname` file, which is its first line.
+/// Several exporters terminate it with a newline, which must not end up in
+/// the file name, and newer Unity versions add a second line (e.g. `00`).
+pub(crate) fn trim_pathname(content: &str) -> &str {
+    content.lines().next().unwrap_or_default().trim()
+}
+
+/// The path of a `pathname` file with forward slashes. Broken exporters
+/// write backslashes, which would end up in a single file name on Unix.
+pub(crate) fn normalize_pathname(pathname: &str) -> String {
+    pathname.replace('\\', "/")
+}
+
+/// The meta file of a guid directory: `asset.meta` or, in packages of Unity
+/// 4.x, `metaData`. None if it has neither.
+pub(crate) fn meta_file(dir: &Path, fs: &dyn FileSystem) -> Option<(PathBuf, MetaFormat)> {
+    [
+        ("asset.meta", MetaFormat::AssetMeta),
+        (LEGACY_META_FILE, MetaFormat::LegacyMetaData),
+    ]
+    .into_iter()
+    .map(|(name, format)| (dir.join(name), format))
+    .find(|(path, _)| fs.exists(path))
+}
+
+/// The lines of a `pathname` file after the path, without line endings.
+fn pathname_extra_lines(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .skip(1)
+        .map(|l| String::from(l.trim_end()))
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+fn hash_file(path: &Path) -> Result<[u8; 32], UnityPackageReaderError> {
+    match checksums::sha256_file(path) {
+        Ok(h) => Ok(h),
+        Err(e) => Err(UnityPackageReaderError::CouldNotReadAsset(
+            ErrorInformation::new(Some(format!("{:?}: {}", path, e)), file!(), line!()),
+        )),
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Set the modification time of path, the error is the description of a
+/// `Warning::TimestampNotApplied`.
+fn set_modified(path: &Path, time: Option<SystemTime>) -> Result<(), String> {
+    let time = match time {
+        Some(t) => t,
+        None => return Ok(()),
+    };
+
+    match fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|f| f.set_modified(time))
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("{:?}: {}", path, e)),
+    }
+}
+
+/// Rewrite the guid references of a file in tmp in place. Returns false for a
+/// binary serialized file, which is left untouched.
+fn rewrite_file(
+    path: &Path,
+    remap: &BTreeMap<Guid, Guid>,
+) -> Result<bool, UnityPackageReaderError> {
+    let content = match fs::read(path) {
+        Ok(c) => c,
+        Err(e) => {
+            return Err(UnityPackageReaderError::CorruptPackage(
+                ErrorInformation::new(Some(format!("{:?}: {}", path, e)), file!(), line!()),
+            ));
+        }
+    };
+
+    // Meta files have no YAML header, but are always text.
+    let is_meta = path
+        .file_name()
+        .is_some_and(|n| n == "asset.meta" || n == LEGACY_META_FILE);
+    if !is_meta && !guid_remap::is_text_serialized(&content) {
+        return Ok(false);
+    }
+
+    if let Some(rewritten) = guid_remap::rewrite_references(&content, remap) {
+        if let Err(e) = fs::write(path, rewritten) {
+            return Err(UnityPackageReaderError::CopyFailed(ErrorInformation::new(
+                Some(format!("{:?}: {}", path, e)),
+                file!(),
+                line!(),
+            )));
+        }
+    }
+
+    Ok(true)
+}
+
+/// Move (or with keep, copy) files out of the way according to backup and
+/// return where they went. files are below target_path.
+fn back_up(
+    fs: &dyn FileSystem,
+    target_path: &Path,
+    files: &[&Path],
+    backup: &Backup,
+    keep: bool,
+    copy_buffer: Option<usize>,
+) -> Result<Vec<PathBuf>, UnityPackageReaderError> {
+    let bases: Vec<PathBuf> = files
+        .iter()
+        .map(|f| match backup {
+            Backup::Suffix => f.to_path_buf(),
+            Backup::Directory(dir) => dir.join(f.strip_prefix(target_path).unwrap_or(f)),
+        })
+        .collect();
+    let numbered = |base: &Path, n: usize| {
+        let mut name = base.as_os_str().to_os_string();
+        match n {
+            0 => name.push(".bak"),
+            n => name.push(format!(".bak.{}", n)),
+        }
+        PathBuf::from(name)
+    };
+
+    let mut n = 0;
+    while bases.iter().any(|b| fs.exists(&numbered(b, n))) {
+        n += 1;
+    }
+
+    let mut result = Vec::new();
+    for (file, base) in files.iter().zip(&bases) {
+        let destination = numbered(base, n);
+        if let Some(parent) = destination.parent() {
+            if let Err(e) = fs.create_dir_all(parent) {
+                return Err(UnityPackageReaderError::TargetDirectoryCouldNotBeCreated(
+                    ErrorInformation::new(Some(format!("{:?}: {}", parent, e)), file!(), line!()),
                 ));
             }
+        }
+
+        if !keep {
+            move_file(fs, file, &destination, copy_buffer)?;
+        } else if let Err(e) = copy_file(fs, file, &destination, copy_buffer) {
+            return Err(UnityPackageReaderError::CopyFailed(ErrorInformation::new(
+                Some(format!(
+                    "'{}' -> '{}': {}",
+                    file.display(),
+                    destination.display(),
+                    e
+                )),
+                file!(),
+                line!(),
+            )));
+        }
+        result.push(destination);
+    }
+
+    Ok(result)
+}
+
+/// True, if the files at incoming and existing have the same content. A file
+/// that cannot be read counts as different, so it is written.
+fn same_content(fs: &dyn FileSystem, incoming: &Path, existing: &Path) -> bool {
+    if fs.file_size(incoming).is_none() || fs.file_size(incoming) != fs.file_size(existing) {
+        return false;
+    }
+
+    match (fs.sha256(incoming), fs.sha256(existing)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn set_readonly(
+    fs: &dyn FileSystem,
+    path: &Path,
+    readonly: bool,
+) -> Result<(), UnityPackageReaderError> {
+    match fs.set_readonly(path, readonly) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(UnityPackageReaderError::ReadOnlyTarget(
+            ErrorInformation::new(
+                Some(format!(
+                    "Could not {} the read-only flag of '{}': {}",
+                    if readonly { "set" } else { "clear" },
+                    path.display(),
+                    e
+                )),
+                file!(),
+                line!(),
+            ),
+        )),
+    }
+}
+
+/// Move a file from tmp into the target and return its size. Falls back to copy
+/// and delete when the tmp directory and the target live on different file systems.
+fn move_file(
+    fs: &dyn FileSystem,
+    source: &Path,
+    destination: &Path,
+    copy_buffer: Option<usize>,
+) -> Result<u64, UnityPackageReaderError> {
+    let size = fs.file_size(source).unwrap_or(0);
+    match fs.rename(source, destination) {
+        Ok(_) => Ok(size),
+        Err(e) if is_cross_device(&e) => {
+            match copy_file(fs, source, destination, copy_buffer) {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::StorageFull => {
+                    return Err(disk_full(destination, &e));
+                }
+                Err(e) => {
+                    return Err(UnityPackageReaderError::CopyFailed(ErrorInformation::new(
+                        Some(format!(
+                            "'{}' -> '{}': {}",
+                            source.display(),
+                            destination.display(),
+                            e
+                        )),
+                        file!(),
+                        line!(),
+                    )));
+                }
+            }
+
+            match fs.remove_file(source) {
+                Ok(_) => Ok(size),
+                Err(e) => Err(UnityPackageReaderError::CopyFailed(ErrorInformation::new(
+                    Some(format!("Could not remove '{}': {}", source.display(), e)),
+                    file!(),
+                    line!(),
+                ))),
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::StorageFull => Err(disk_full(destination, &e)),
+        Err(e) => Err(UnityPackageReaderError::CorruptPackage(
+            ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+        )),
+    }
+}
+
+fn copy_file(
+    fs: &dyn FileSystem,
+    source: &Path,
+    destination: &Path,
+    copy_buffer: Option<usize>,
+) -> io::Result<u64> {
+    match copy_buffer {
+        Some(size) => fs.copy_with_buffer(source, destination, size),
+        None => fs.copy(source, destination),
+    }
+}
+
+fn disk_full(destination: &Path, e: &io::Error) -> UnityPackageReaderError {
+    UnityPackageReaderError::DiskFull(ErrorInformation::new(
+        Some(format!("'{}': {}", destination.display(), e)),
+        file!(),
+        line!(),
+    ))
+}
+
+/// EXDEV on unix, ERROR_NOT_SAME_DEVICE on windows.
+fn is_cross_device(e: &io::Error) -> bool {
+    if e.kind() == io::ErrorKind::CrossesDevices {
+        return true;
+    }
+
+    #[cfg(unix)]
+    let cross_device = 18;
+    #[cfg(windows)]
+    let cross_device = 17;
+    #[cfg(not(any(unix, windows)))]
+    let cross_device = -1;
+
+    e.raw_os_error() == Some(cross_device)
+}
+
+/// What the serde support of `UnityAssetFile` reads and writes. The field
+/// names are part of the format and must not change.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerializedAssetFile {
+    guid: Guid,
+    relative_path: String,
+    absolute_asset_path: String,
+    absolute_meta_path: String,
+    installed_asset_path: Option<String>,
+    installed_meta_path: Option<String>,
+    is_folder: bool,
+    size: u64,
+}
+
+/// path with forward slashes, also on Windows.
+#[cfg(feature = "serde")]
+fn path_to_string(path: &Path) -> String {
+    let s = path.to_string_lossy();
+    if std::path::MAIN_SEPARATOR == '\\' {
+        s.replace('\\', "/")
+    } else {
+        s.into_owned()
+    }
+}
+
+/// Serializes the guid, the paths (as strings with forward slashes), the
+/// folder flag and the size. The meta data is not part of it.
+#[cfg(feature = "serde")]
+impl serde::Serialize for UnityAssetFile {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedAssetFile {
+            guid: self.guid,
+            relative_path: path_to_string(&self.target),
+            absolute_asset_path: path_to_string(&self.asset),
+            absolute_meta_path: path_to_string(&self.meta),
+            installed_asset_path: self.installed_asset.as_deref().map(path_to_string),
+            installed_meta_path: self.installed_meta.as_deref().map(path_to_string),
+            is_folder: self.is_folder,
+            size: self.size,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// The counterpart of the `Serialize` impl. A deserialized file has an empty
+/// `MetaData`, since the meta file is not serialized.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for UnityAssetFile {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = SerializedAssetFile::deserialize(deserializer)?;
+        Ok(UnityAssetFile {
+            guid: s.guid,
+            asset: PathBuf::from(s.absolute_asset_path),
+            target: PathBuf::from(s.relative_path),
+            meta: PathBuf::from(s.absolute_meta_path),
+            is_folder: s.is_folder,
+            has_meta: true,
+            meta_format: MetaFormat::AssetMeta,
+            package_path: None,
+            skipped: false,
+            unchanged: false,
+            overwritten: false,
+            folder_with_payload: false,
+            installed_bytes: 0,
+            installed_meta: s.installed_meta_path.map(PathBuf::from),
+            installed_asset: s.installed_asset_path.map(PathBuf::from),
+            backups: Vec::new(),
+            mtime_error: None,
+            pathname_extra: Vec::new(),
+            backslash_pathname: false,
+            mtimes: (None, None),
+            size: s.size,
+            meta_data: MetaData::default(),
+            meta_content: String::new(),
+            sha256: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_system::MemoryFileSystem;
+    use crate::test_utils::*;
+
+    /// Lay out a guid directory the way it looks after the package has been
+    /// unpacked into tmp.
+    fn create_tmp_asset(tmp: &Path, content: &[u8]) -> PathBuf {
+        let dir = tmp.join(TEXTURE_GUID);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("asset"), content).unwrap();
+        fs::write(dir.join("asset.meta"), texture_meta(TEXTURE_GUID)).unwrap();
+        fs::write(dir.join("pathname"), TEXTURE_PATH).unwrap();
+        dir
+    }
+
+    fn create_existing_target(target: &Path) -> PathBuf {
+        let existing = target.join(TEXTURE_PATH);
+        fs::create_dir_all(existing.parent().unwrap()).unwrap();
+        fs::write(&existing, "local change").unwrap();
+        existing
+    }
+
+    #[test]
+    fn test_read_asset_before_and_after_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let tmp = create_tmp_asset(&dir.path().join("tmp"), b"key: value\n");
+        let target = dir.path().join("target");
+        let mut subject = UnityAssetFile::from(tmp.clone()).unwrap();
+
+        assert_eq!(subject.get_current_asset_path(), &tmp.join("asset"));
+        assert_eq!(subject.read_asset_string().unwrap(), "key: value\n");
+
+        subject.copy_asset(&target).unwrap();
+        assert_eq!(subject.get_current_asset_path(), &target.join(TEXTURE_PATH));
+        assert!(!tmp.join("asset").exists());
+        assert_eq!(subject.read_asset_bytes().unwrap(), b"key: value\n");
+
+        fs::write(target.join(TEXTURE_PATH), [0xff, 0xfe]).unwrap();
+        assert!(matches!(
+            subject.read_asset_string(),
+            Err(UnityPackageReaderError::CouldNotReadAsset(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_meta_before_and_after_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let tmp = create_tmp_asset(&dir.path().join("tmp"), TEXTURE_CONTENT);
+        let target = dir.path().join("target");
+        let mut subject = UnityAssetFile::from(tmp.clone()).unwrap();
+
+        assert_eq!(subject.get_current_meta_path(), &tmp.join("asset.meta"));
+        assert_eq!(subject.read_meta().unwrap(), texture_meta(TEXTURE_GUID));
+
+        let new_guid: Guid = "0123456789abcdef0123456789abcdef".parse().unwrap();
+        let config = UnpackConfig {
+            guid_remap: [(TEXTURE_GUID.parse().unwrap(), new_guid)].into(),
+            ..Default::default()
         };
+        assert!(subject.remap_guids(&config).unwrap());
+        assert!(subject.read_meta().unwrap().contains(&new_guid.to_string()));
+        assert_eq!(subject.meta_data().guid, new_guid.to_string());
 
-        Ok(())
+        subject.copy_asset(&target).unwrap();
+        let installed = target.join(format!("{}.unitymeta", TEXTURE_PATH));
+        assert_eq!(subject.get_current_meta_path(), &installed);
+        assert_eq!(
+            subject.read_meta().unwrap(),
+            fs::read_to_string(&installed).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_read_folder_asset() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join(FOLDER_GUID);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("asset.meta"), folder_meta(FOLDER_GUID)).unwrap();
+        fs::write(folder.join("pathname"), FOLDER_PATH).unwrap();
+
+        let subject = UnityAssetFile::from(folder).unwrap();
+        assert!(matches!(
+            subject.read_asset_bytes(),
+            Err(UnityPackageReaderError::AssetIsFolder(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_guid_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let tmp = create_tmp_asset(&dir.path().join("tmp"), TEXTURE_CONTENT);
+        let invalid = dir.path().join("tmp").join("not-a-guid");
+        fs::rename(&tmp, &invalid).unwrap();
+
+        match UnityAssetFile::from(invalid) {
+            Err(UnityPackageReaderError::InvalidGuid(e)) => {
+                assert!(e.message.unwrap().contains("not-a-guid"))
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_meta_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let tmp = create_tmp_asset(&dir.path().join("tmp"), TEXTURE_CONTENT);
+        let subject = UnityAssetFile::from(tmp.clone()).unwrap();
+        assert_eq!(subject.meta_data().guid, TEXTURE_GUID);
+        assert_eq!(
+            subject.meta_data().importer.as_deref(),
+            Some("TextureImporter")
+        );
+
+        fs::write(tmp.join("asset.meta"), "fileFormatVersion: 2\n").unwrap();
+        match UnityAssetFile::from(tmp) {
+            Err(UnityPackageReaderError::CouldReadMetaFile(e)) => {
+                assert!(e.message.unwrap().contains(TEXTURE_GUID))
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_overwrite_policy_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        let existing = create_existing_target(&target);
+
+        let mut subject =
+            UnityAssetFile::from(create_tmp_asset(&dir.path().join("tmp"), TEXTURE_CONTENT))
+                .unwrap();
+        subject
+            .copy_asset_with_policy(&target, OverwritePolicy::Overwrite)
+            .unwrap();
+
+        assert!(!subject.is_skipped());
+        assert_eq!(fs::read(&existing).unwrap(), TEXTURE_CONTENT);
+        assert!(target.join(format!("{}.unitymeta", TEXTURE_PATH)).exists());
+    }
+
+    #[test]
+    fn test_overwrite_policy_skip() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        let existing = create_existing_target(&target);
+
+        let mut subject =
+            UnityAssetFile::from(create_tmp_asset(&dir.path().join("tmp"), TEXTURE_CONTENT))
+                .unwrap();
+        subject
+            .copy_asset_with_policy(&target, OverwritePolicy::Skip)
+            .unwrap();
+
+        assert!(subject.is_skipped());
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "local change");
+        // The meta follows its asset.
+        assert!(!target.join(format!("{}.unitymeta", TEXTURE_PATH)).exists());
+    }
+
+    #[test]
+    fn test_overwrite_policy_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        let existing = create_existing_target(&target);
+
+        let mut subject =
+            UnityAssetFile::from(create_tmp_asset(&dir.path().join("tmp"), TEXTURE_CONTENT))
+                .unwrap();
+        let result = subject.copy_asset_with_policy(&target, OverwritePolicy::Error);
+
+        match result {
+            Err(UnityPackageReaderError::TargetFileExists(e)) => {
+                assert!(e.message.unwrap().contains(TEXTURE_PATH));
+            }
+            _ => panic!("Expected TargetFileExists"),
+        }
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "local change");
+        assert!(!target.join(format!("{}.unitymeta", TEXTURE_PATH)).exists());
+    }
+
+    #[test]
+    fn test_overwrite_policy_error_on_meta_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        let meta = target.join(format!("{}.unitymeta", TEXTURE_PATH));
+        fs::create_dir_all(meta.parent().unwrap()).unwrap();
+        fs::write(&meta, "local meta").unwrap();
+
+        let mut subject =
+            UnityAssetFile::from(create_tmp_asset(&dir.path().join("tmp"), TEXTURE_CONTENT))
+                .unwrap();
+        assert!(subject
+            .copy_asset_with_policy(&target, OverwritePolicy::Error)
+            .is_err());
+        assert!(!target.join(TEXTURE_PATH).exists());
+    }
+
+    #[test]
+    fn test_copy_folder_asset_with_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        let folder = dir.path().join("tmp").join(FOLDER_GUID);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("asset.meta"), folder_meta(FOLDER_GUID)).unwrap();
+        fs::write(folder.join("pathname"), FOLDER_PATH).unwrap();
+
+        let mut subject = UnityAssetFile::from(folder.clone()).unwrap();
+        let without_folders = UnpackConfig {
+            create_folders: false,
+            ..Default::default()
+        };
+        subject
+            .copy_asset_with_config(&target, &without_folders)
+            .unwrap();
+        assert!(!target.exists());
+
+        let config = UnpackConfig {
+            create_folders: true,
+            meta_naming: MetaNaming::Meta,
+            ..Default::default()
+        };
+        subject.copy_asset_with_config(&target, &config).unwrap();
+        assert!(target.join(FOLDER_PATH).is_dir());
+        assert!(target.join(format!("{}.meta", FOLDER_PATH)).is_file());
+    }
+
+    #[test]
+    fn test_move_falls_back_to_copy_across_devices() {
+        let fs = MemoryFileSystem::default();
+        let (source, destination) = (Path::new("/tmp/source"), Path::new("/target/destination"));
+        fs.write(source, "content");
+        fs.create_dir_all(Path::new("/target")).unwrap();
+        fs.fail("rename", source, io::ErrorKind::CrossesDevices);
+
+        assert_eq!(move_file(&fs, source, destination, None).unwrap(), 7);
+        assert!(!fs.exists(source));
+        assert_eq!(fs.read(destination).unwrap(), b"content");
+    }
+
+    #[test]
+    fn test_move_copies_with_buffer() {
+        let fs = MemoryFileSystem::default();
+        let (source, destination) = (Path::new("/tmp/source"), Path::new("/target/destination"));
+        fs.create_dir_all(Path::new("/target")).unwrap();
+        for buffer in [None, Some(4096)] {
+            fs.write(source, "content");
+            fs.fail("rename", source, io::ErrorKind::CrossesDevices);
+            assert_eq!(move_file(&fs, source, destination, buffer).unwrap(), 7);
+        }
+        assert_eq!(fs.copy_buffers(), [4096]);
+        assert_eq!(fs.read(destination).unwrap(), b"content");
+
+        let dir = tempfile::tempdir().unwrap();
+        let (source, destination) = (dir.path().join("source"), dir.path().join("destination"));
+        std::fs::write(&source, vec![3u8; 10_000]).unwrap();
+        assert_eq!(
+            OsFileSystem
+                .copy_with_buffer(&source, &destination, 1000)
+                .unwrap(),
+            10_000
+        );
+        assert_eq!(std::fs::read(&destination).unwrap(), vec![3u8; 10_000]);
+    }
+
+    #[test]
+    fn test_move_reports_failed_copy() {
+        let fs = MemoryFileSystem::default();
+        let source = Path::new("/tmp/source");
+        let destination = Path::new("/missing/destination");
+        fs.write(source, "content");
+        fs.fail("rename", source, io::ErrorKind::CrossesDevices);
+
+        match move_file(&fs, source, destination, None) {
+            Err(UnityPackageReaderError::CopyFailed(e)) => {
+                let message = e.message.unwrap();
+                assert!(message.contains("source"));
+                assert!(message.contains("destination"));
+            }
+            _ => panic!("Expected CopyFailed"),
+        }
+        assert!(fs.exists(source));
+    }
+
+    #[test]
+    fn test_file_system_failures() {
+        let tmp = PathBuf::from("/tmp").join(TEXTURE_GUID);
+        let target = Path::new("/target");
+        let config = UnpackConfig::default();
+        let setup = || {
+            let fs = MemoryFileSystem::default();
+            fs.write(tmp.join("asset"), TEXTURE_CONTENT);
+            fs.write(tmp.join("asset.meta"), texture_meta(TEXTURE_GUID));
+            fs.write(tmp.join("pathname"), TEXTURE_PATH);
+            fs
+        };
+
+        let fs = setup();
+        fs.fail(
+            "read_to_string",
+            tmp.join("asset.meta"),
+            io::ErrorKind::PermissionDenied,
+        );
+        assert!(matches!(
+            UnityAssetFile::from_file_system(tmp.clone(), &fs),
+            Err(UnityPackageReaderError::CouldReadMetaFile(_))
+        ));
+
+        let fs = setup();
+        let directory = target.join(TEXTURE_PATH).parent().unwrap().to_path_buf();
+        fs.fail(
+            "create_dir_all",
+            &directory,
+            io::ErrorKind::PermissionDenied,
+        );
+        let mut subject = UnityAssetFile::from_file_system(tmp.clone(), &fs).unwrap();
+        assert!(matches!(
+            subject.copy_asset_into(target, target, &config, &fs, None),
+            Err(UnityPackageReaderError::TargetDirectoryCouldNotBeCreated(_))
+        ));
+
+        let fs = setup();
+        fs.fail("rename", tmp.join("asset"), io::ErrorKind::PermissionDenied);
+        let mut subject = UnityAssetFile::from_file_system(tmp.clone(), &fs).unwrap();
+        assert!(subject
+            .copy_asset_into(target, target, &config, &fs, None)
+            .is_err());
+        assert!(fs.exists(&tmp.join("asset")));
+        assert!(!fs.exists(&target.join(TEXTURE_PATH)));
+
+        // The copy across devices succeeds, but tmp cannot be cleaned up.
+        let fs = setup();
+        fs.fail("rename", tmp.join("asset"), io::ErrorKind::CrossesDevices);
+        fs.fail(
+            "remove_file",
+            tmp.join("asset"),
+            io::ErrorKind::PermissionDenied,
+        );
+        let mut subject = UnityAssetFile::from_file_system(tmp.clone(), &fs).unwrap();
+        match subject.copy_asset_into(target, target, &config, &fs, None) {
+            Err(UnityPackageReaderError::CopyFailed(e)) => {
+                assert!(e.message.unwrap().contains("Could not remove"));
+            }
+            _ => panic!("Expected CopyFailed"),
+        }
+
+        // Without failures everything ends up in the target.
+        let fs = setup();
+        let mut subject = UnityAssetFile::from_file_system(tmp.clone(), &fs).unwrap();
+        subject
+            .copy_asset_into(target, target, &config, &fs, None)
+            .unwrap();
+        assert_eq!(fs.read(target.join(TEXTURE_PATH)).unwrap(), TEXTURE_CONTENT);
+        assert!(fs.exists(&target.join(format!("{}.unitymeta", TEXTURE_PATH))));
+        assert!(!fs.exists(&tmp.join("asset")));
+    }
+
+    #[test]
+    fn test_readonly_target() {
+        let tmp = PathBuf::from("/tmp").join(TEXTURE_GUID);
+        let target = Path::new("/target");
+        let existing = target.join(TEXTURE_PATH);
+        let setup = || {
+            let fs = MemoryFileSystem::default();
+            fs.write(tmp.join("asset"), TEXTURE_CONTENT);
+            fs.write(tmp.join("asset.meta"), texture_meta(TEXTURE_GUID));
+            fs.write(tmp.join("pathname"), TEXTURE_PATH);
+            fs.write(&existing, "locked");
+            fs.set_readonly(&existing, true).unwrap();
+            fs
+        };
+
+        let fs = setup();
+        let config = UnpackConfig {
+            clear_readonly: false,
+            ..Default::default()
+        };
+        let mut subject = UnityAssetFile::from_file_system(tmp.clone(), &fs).unwrap();
+        match subject.copy_asset_into(target, target, &config, &fs, None) {
+            Err(UnityPackageReaderError::ReadOnlyTarget(e)) => {
+                assert!(e.message.unwrap().contains(&existing.display().to_string()));
+            }
+            other => panic!("Expected ReadOnlyTarget, got {:?}", other),
+        }
+        assert_eq!(fs.read(&existing).unwrap(), b"locked");
+        assert!(!fs.exists(&target.join(format!("{}.unitymeta", TEXTURE_PATH))));
+
+        for restore_readonly in [false, true] {
+            let fs = setup();
+            let config = UnpackConfig {
+                restore_readonly,
+                ..Default::default()
+            };
+            let mut subject = UnityAssetFile::from_file_system(tmp.clone(), &fs).unwrap();
+            subject
+                .copy_asset_into(target, target, &config, &fs, None)
+                .unwrap();
+            assert_eq!(fs.read(&existing).unwrap(), TEXTURE_CONTENT);
+            assert_eq!(fs.is_readonly(&existing), restore_readonly);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let tmp = create_tmp_asset(&dir.path().join("tmp"), TEXTURE_CONTENT);
+        let target = dir.path().join("target");
+        let mut subject = UnityAssetFile::from(tmp).unwrap();
+        subject.copy_asset(&target).unwrap();
+
+        let json = serde_json::to_value(&subject).unwrap();
+        assert_eq!(json["guid"], TEXTURE_GUID);
+        assert_eq!(json["relative_path"], TEXTURE_PATH);
+        assert_eq!(json["is_folder"], false);
+        assert_eq!(json["size"], TEXTURE_CONTENT.len());
+        let installed = json["installed_asset_path"].as_str().unwrap();
+        assert!(!installed.contains('\\'));
+        assert!(installed.ends_with(TEXTURE_PATH));
+
+        let restored: UnityAssetFile = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(restored.get_guid(), subject.get_guid());
+        assert_eq!(
+            restored.get_relative_asset_path(),
+            subject.get_relative_asset_path()
+        );
+        assert_eq!(
+            restored.get_installed_meta_path(),
+            subject.get_installed_meta_path()
+        );
+        assert_eq!(serde_json::to_value(&restored).unwrap(), json);
+    }
+
+    #[test]
+    fn test_display_and_debug() {
+        let dir = tempfile::tempdir().unwrap();
+        let tmp = create_tmp_asset(&dir.path().join("tmp"), TEXTURE_CONTENT);
+        let subject = UnityAssetFile::from(tmp.clone()).unwrap();
+
+        assert_eq!(
+            subject.to_string(),
+            "1af567ac\u{2026} -> Assets/Textures/Ground/IMGP1287.jpg (file)"
+        );
+        assert_eq!(
+            format!("{:?}", subject),
+            format!(
+                "UnityAssetFile {{ guid: {}, relative_path: \"{}\", is_folder: false, \
+                 size: {}, skipped: false, overwritten: false, asset: {:?}, meta: {:?}, \
+                 installed_asset: None, installed_meta: None, .. }}",
+                TEXTURE_GUID,
+                TEXTURE_PATH,
+                TEXTURE_CONTENT.len(),
+                tmp.join("asset"),
+                tmp.join("asset.meta")
+            )
+        );
+
+        let folder = dir.path().join("tmp").join(FOLDER_GUID);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("asset.meta"), folder_meta(FOLDER_GUID)).unwrap();
+        fs::write(folder.join("pathname"), FOLDER_PATH).unwrap();
+        assert_eq!(
+            UnityAssetFile::from(folder).unwrap().to_string(),
+            "5b2a0c3e\u{2026} -> Assets/Textures (folder)"
+        );
+    }
+
+    #[test]
+    fn test_sha256() {
+        let dir = tempfile::tempdir().unwrap();
+        let tmp = create_tmp_asset(&dir.path().join("tmp"), TEXTURE_CONTENT);
+        let mut subject = UnityAssetFile::from(tmp).unwrap();
+        let expected = "5368e7b92b51f4d19d0319af90a37a3795fb9f0ac3c0a64edd06eb7792bd4f78";
+        assert_eq!(checksums::to_hex(&subject.sha256().unwrap()), expected);
+
+        subject.copy_asset(&dir.path().join("target")).unwrap();
+        assert_eq!(checksums::to_hex(&subject.sha256().unwrap()), expected);
+
+        let folder = dir.path().join("tmp").join(FOLDER_GUID);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("asset.meta"), folder_meta(FOLDER_GUID)).unwrap();
+        fs::write(folder.join("pathname"), FOLDER_PATH).unwrap();
+        assert!(matches!(
+            UnityAssetFile::from(folder).unwrap().sha256(),
+            Err(UnityPackageReaderError::AssetIsFolder(_))
+        ));
     }
 }
@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+
+use crate::unpacker_error::{ErrorInformation, UnityPackageReaderError};
+
+/// The file name of the meta in guid directories of packages exported by
+/// Unity 4.x, instead of `asset.meta`.
+pub(crate) const LEGACY_META_FILE: &str = "metaData";
+
+/// Which kind of meta file a guid directory carries.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum MetaFormat {
+    /// `asset.meta`.
+    #[default]
+    AssetMeta,
+    /// `metaData` of Unity 4.x, which may leave out the guid and marks
+    /// folders with `isFolder` instead of `folderAsset`.
+    LegacyMetaData,
+}
+
+/// True, if the content of a meta file of either format marks a folder.
+pub(crate) fn is_folder_meta(content: &str) -> bool {
+    content.lines().any(|l| {
+        matches!(
+            l.trim_end().split_once(':'),
+            Some(("folderAsset" | "isFolder", v)) if matches!(v.trim(), "yes" | "true" | "1")
+        )
+    })
+}
+
+/// The content of an `asset.meta` file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MetaData {
+    pub guid: String,
+    pub file_format_version: Option<u32>,
+    /// True, if the meta says `folderAsset: yes` (`isFolder: 1` in `metaData`).
+    pub folder_asset: bool,
+    /// The name of the importer section, e.g. `TextureImporter`.
+    pub importer: Option<String>,
+    pub labels: Vec<String>,
+    /// All other top level keys. Nested sections (including the importer)
+    /// are kept as their raw, indented text.
+    pub values: BTreeMap<String, String>,
+}
+
+impl MetaData {
+    /// Parse the YAML of a meta file. Only the top level is interpreted, which
+    /// is all the fields of this struct need.
+    pub fn parse(content: &str) -> Result<Self, UnityPackageReaderError> {
+        Self::parse_format(content, MetaFormat::AssetMeta, None)
+    }
+
+    /// Parse a meta file of the given format. A `metaData` without a guid
+    /// gets guid, the name of its guid directory.
+    pub fn parse_format(
+        content: &str,
+        format: MetaFormat,
+        guid: Option<&str>,
+    ) -> Result<Self, UnityPackageReaderError> {
+        let legacy = format == MetaFormat::LegacyMetaData;
+        let fallback = guid.filter(|_| legacy).map(String::from);
+        let mut result = MetaData::default();
+        let mut guid = None;
+        let mut sections: Vec<(String, String, Vec<String>)> = Vec::new();
+
+        for (index, line) in content.lines().enumerate() {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+
+            // Indented lines and list items belong to the previous key.
+            if line.starts_with([' ', '\t']) || line.starts_with("- ") {
+                match sections.last_mut() {
+                    Some((_, _, block)) => block.push(String::from(line)),
+                    None => return Err(parse_error(index, "unexpected indentation")),
+                }
+                continue;
+            }
+
+            let (key, value) = match line.split_once(':') {
+                Some((k, v)) if !k.trim().is_empty() => (k.trim(), unquote(v.trim())),
+                _ => return Err(parse_error(index, "expected 'key: value'")),
+            };
+
+            match key {
+                "guid" if !value.is_empty() => guid = Some(String::from(value)),
+                "fileFormatVersion" => match value.parse() {
+                    Ok(v) => result.file_format_version = Some(v),
+                    Err(_) => return Err(parse_error(index, "invalid fileFormatVersion")),
+                },
+                "folderAsset" => result.folder_asset = value == "yes" || value == "true",
+                "isFolder" if legacy => result.folder_asset = matches!(value, "yes" | "true" | "1"),
+                _ => sections.push((String::from(key), String::from(value), Vec::new())),
+            }
+        }
+
+        for (key, value, block) in sections {
+            if key == "labels" {
+                result.labels = parse_list(&value, &block);
+                continue;
+            }
+
+            if result.importer.is_none() && key.ends_with("Importer") {
+                result.importer = Some(key.clone());
+            }
+
+            let raw = if block.is_empty() {
+                value
+            } else {
+                block.join("\n")
+            };
+            result.values.insert(key, raw);
+        }
+
+        result.guid = match guid.or(fallback) {
+            Some(g) => g,
+            None => {
+                return Err(UnityPackageReaderError::CouldReadMetaFile(
+                    ErrorInformation::new(Some(String::from("missing guid")), file!(), line!()),
+                ));
+            }
+        };
+
+        Ok(result)
+    }
+}
+
+fn parse_error(index: usize, message: &str) -> UnityPackageReaderError {
+    UnityPackageReaderError::CouldReadMetaFile(ErrorInformation::new(
+        Some(format!("line {}: {}", index + 1, message)),
+        file!(),
+        line!(),
+    ))
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(v) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return v;
+        }
+    }
+
+    value
+}
+
+/// A list either written inline (`[a, b]`) or as block (`- a`).
+fn parse_list(inline: &str, block: &[String]) -> Vec<String> {
+    if let Some(items) = inline.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        return items
+            .split(',')
+            .map(|i| String::from(unquote(i.trim())))
+            .filter(|i| !i.is_empty())
+            .collect();
+    }
+
+    block
+        .iter()
+        .filter_map(|l| l.trim_start().strip_prefix("- "))
+        .map(|i| String::from(unquote(i.trim())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_parse_texture_meta() {
+        let meta = MetaData::parse(&texture_meta(TEXTURE_GUID)).unwrap();
+        assert_eq!(meta.guid, TEXTURE_GUID);
+        assert_eq!(meta.file_format_version, Some(2));
+        assert!(!meta.folder_asset);
+        assert_eq!(meta.importer.as_deref(), Some("TextureImporter"));
+        assert!(meta.values["TextureImporter"].contains("mipMapMode: 0"));
+    }
+
+    #[test]
+    fn test_parse_folder_and_labels() {
+        let content = format!(
+            "{}labels:\r\n- Environment\r\n- 'Ground'\r\ntimeCreated: 1600000000\r\n",
+            folder_meta(FOLDER_GUID)
+        );
+        let meta = MetaData::parse(&content).unwrap();
+        assert!(meta.folder_asset);
+        assert_eq!(meta.labels, vec!["Environment", "Ground"]);
+        assert_eq!(meta.values["timeCreated"], "1600000000");
+        assert_eq!(meta.importer.as_deref(), Some("DefaultImporter"));
+
+        let inline = MetaData::parse("guid: abc\nlabels: [a, b]\n").unwrap();
+        assert_eq!(inline.labels, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_parse_legacy_meta_data() {
+        let content = "fileFormatVersion: 1\nisFolder: 1\n";
+        assert!(MetaData::parse(content).is_err());
+        let meta =
+            MetaData::parse_format(content, MetaFormat::LegacyMetaData, Some(FOLDER_GUID)).unwrap();
+        assert_eq!(meta.guid, FOLDER_GUID);
+        assert!(meta.folder_asset);
+        assert!(is_folder_meta(content));
+        assert!(is_folder_meta(&folder_meta(FOLDER_GUID)));
+        assert!(!is_folder_meta(&texture_meta(TEXTURE_GUID)));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for content in [
+            "fileFormatVersion: 2\n",
+            "guid: abc\nfileFormatVersion: two\n",
+            "  indented: first\nguid: abc\n",
+            "guid: abc\nnot yaml\n",
+        ] {
+            assert!(
+                matches!(
+                    MetaData::parse(content),
+                    Err(UnityPackageReaderError::CouldReadMetaFile(_))
+                ),
+                "{}",
+                content
+            );
+        }
+    }
+}
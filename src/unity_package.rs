@@ -1,98 +1,554 @@
-use flate2::read::GzDecoder;
-use rust_tools::prelude::*;
+use flate2::{write::GzEncoder, Compression};
 use std::{
-    collections::HashMap,
+    collections::{hash_map::RandomState, BTreeMap, HashMap, HashSet},
     fs,
-    path::{Path, PathBuf},
+    hash::{BuildHasher, Hasher},
+    io::Write,
+    ops::ControlFlow,
+    panic::{self, AssertUnwindSafe},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::{Instant, SystemTime},
 };
-use tar::Archive;
 
 use crate::{
-    prelude::UnityAssetFile,
+    archive_convert::{self, ArchiveFormat},
+    asset_filter::{AssetFilter, UnpackFilter},
+    atomic_file::AtomicFile,
+    checksums::{self, VerifyReport},
+    code_report::{self, CodeReport},
+    compatibility::{self, CompatibilityReport, ProjectInfo},
+    counting,
+    dependencies::{self, DependencyGraph, MissingDependencies},
+    extraction_report::{
+        AssetFailure, AssetReport, AssetStatus, DuplicateGuid, ExtractionReport, SkippedEntry,
+    },
+    file_system::{self, FileSystem, OsFileSystem},
+    git_ignore::{self, GitIgnoreEdit},
+    guid::Guid,
+    guid_map::GuidMap,
+    incremental::{InstalledEntry, InstalledManifest},
+    manifest::PackageManifest,
+    memory_unpack::{self, MemoryAsset},
+    package_diff::{self, PackageDiff},
+    package_format,
+    package_listing::{self, EntrySummary},
+    package_source::PackageSource,
+    path_search::PathSearch,
+    prelude::{
+        BufferSizes, CaseCollisions, FileStatus, FlattenCollisions, FolderPayloadResolution,
+        Importer, MetaNaming, OverwritePolicy, PathRewrite, PlannedAction, PlannedOperation,
+        ReservedNames, SharedFilter, SharedObserver, SkipReason, UnityAssetFile, UnpackConfig,
+        UnpackOptions, UnpackStats, Warning,
+    },
+    reserved_names,
+    single_asset::{self, ExtractedAsset},
+    size_estimate::{self, SizeEstimate},
+    target_diff::{self, AssetComparison, TargetDiff, TargetTree},
+    tmp_guard::TempDirGuard,
+    unity_asset_file,
+    unity_meta::LEGACY_META_FILE,
+    unpack_session::{Extraction, UnpackSession},
     unpacker_error::{ErrorInformation, UnityPackageReaderError},
+    upm_manifest::{self, UpmManifestOptions, UPM_MANIFEST_FILE},
+    validation::{self, GuidFolder, ValidationReport},
 };
 
+/// The files a guid directory of a package can contain.
+const ASSET_ENTRIES: [&str; 5] = [
+    "asset",
+    "asset.meta",
+    LEGACY_META_FILE,
+    "pathname",
+    "preview.png",
+];
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The directory below the temp dir of the OS that receives the default tmp
+/// directories, see `UnityPackage::get_tmp_dir`.
+pub const TMP_SUBFOLDER: &str = "unity_unpacker";
+
+/// The assets of a package in install order with the paths they are
+/// installed at, see `UnityPackage::resolve_paths`.
+struct ResolvedPaths {
+    assets: Vec<UnityAssetFile>,
+    /// The guids the filters reject. These assets keep the path of the package.
+    excluded: HashSet<Guid>,
+    /// The index of the `UnpackConfig::root_remap` rule each asset matched.
+    root_rules: HashMap<Guid, usize>,
+    /// The assets the configuration, the path rewrite or a case collision
+    /// left out. They are not part of assets anymore.
+    filtered: Vec<(Guid, PathBuf)>,
+    /// The renames of reserved names and case collisions.
+    warnings: Vec<Warning>,
+}
+
+impl ResolvedPaths {
+    /// True, if the filters keep asset.
+    fn includes(&self, asset: &UnityAssetFile) -> bool {
+        !self.excluded.contains(&asset.get_guid())
+    }
+}
+
 pub struct UnityPackage {
     /// The name of the file to unpack.
-    path: String,
+    path: PathBuf,
     /// The target directory. If none is set the current working directory and the name of the package will be used
-    target_path: Option<String>,
+    target_path: Option<PathBuf>,
     /// We have to unpack the file into a tmp directory
-    temp_directory: Option<String>,
+    temp_directory: Option<PathBuf>,
+    /// The name of the directory below [OS temp dir]/`TMP_SUBFOLDER` used if
+    /// no tmp directory is set, unique for every instance.
+    default_tmp_name: String,
     /// The files we found hashed by the guid
-    files: HashMap<String, UnityAssetFile>,
+    files: HashMap<Guid, UnityAssetFile>,
+    /// The guids of the files by their relative path (forward slashes)
+    paths: HashMap<String, Guid>,
+    /// Additive mode: the guids found in the target before installing and
+    /// their relative paths.
+    present: HashMap<Guid, PathBuf>,
+    /// The index of the `UnpackConfig::root_remap` rule each asset of the
+    /// last unpack matched.
+    root_rules: HashMap<Guid, usize>,
+    /// `UnpackOptions::path_rewrite` of the running unpack.
+    path_rewrite: Option<PathRewrite>,
+    /// `UnpackOptions::observer` of the running unpack.
+    observer: Option<SharedObserver>,
+    /// `UnpackOptions::filter` of the running unpack.
+    filter: Option<SharedFilter>,
+    /// `UnpackOptions::buffer_sizes` of the running (or last) unpack.
+    buffer_sizes: BufferSizes,
+    /// Controls how the assets end up in the target directory
+    config: UnpackConfig,
+    /// The .gitignore change made by the last unpack (see `UnpackConfig::git_aware`)
+    gitignore_edit: Option<GitIgnoreEdit>,
+    /// Non-fatal findings of the last unpack
+    warnings: Vec<Warning>,
+    /// Byte accounting of the last unpack
+    stats: UnpackStats,
+    /// The assets the filters left out in the last unpack
+    filtered: Vec<(Guid, PathBuf)>,
+    /// The guids `asset_filter` rejects, decided once per unpack in
+    /// `begin_install`.
+    excluded: HashSet<Guid>,
+    /// The links and special files the last unpack did not extract
+    skipped_entries: Vec<SkippedEntry>,
+    /// The guid directories the last unpacked archive contained more than once
+    duplicate_guids: Vec<DuplicateGuid>,
+    /// The assets the last unpack could not install (`UnpackConfig::continue_on_error`)
+    failures: Vec<AssetFailure>,
+    /// Used to index the tmp directory and to install into the target
+    file_system: Arc<dyn FileSystem>,
+    /// The content of the package if it has been created with `from_bytes`.
+    bytes: Option<Arc<[u8]>>,
+    /// Incremental mode: what earlier unpacks have installed into the target.
+    installed: Option<InstalledManifest>,
+    /// Read the package file through a memory map, see `with_memory_map`.
+    #[cfg(feature = "mmap")]
+    memory_map: bool,
 }
 
 impl UnityPackage {
-    /// Creates a new UnityPackage. The given file name is either the absolute path
+    /// Creates a new UnityPackage. The given path is either the absolute path
     /// to the package on disk or the name of the file in the current
     /// working directory (or a subdirectory of the current working directory).
+    pub fn from_path(
+        path: impl AsRef<Path>,
+        target_path: Option<PathBuf>,
+        temp_directory: Option<PathBuf>,
+    ) -> Result<Self, UnityPackageReaderError> {
+        file_system::require_file_system("Opening a package by its path")?;
+
+        let mut path = path.as_ref().to_path_buf();
+        if !path.exists() {
+            match std::env::current_dir() {
+                Ok(working_dir) => path = working_dir.join(path),
+                Err(e) => {
+                    return Err(UnityPackageReaderError::WorkingDirectoryError(
+                        ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                    ));
+                }
+            }
+        }
+
+        Ok(UnityPackage::with_paths(path, target_path, temp_directory))
+    }
+
+    /// Creates a UnityPackage from the content of a package file, e.g. a file
+    /// uploaded in a browser. name takes the place of the file name (in the
+    /// manifest and for the default target directory). Listing, validating
+    /// and `unpack_to_memory` need no file system, so they work on targets
+    /// without one (wasm32). Fails with `NotAPackageFile` if bytes do not
+    /// start like a gzip or tar stream.
+    pub fn from_bytes(
+        name: impl AsRef<Path>,
+        bytes: impl Into<Arc<[u8]>>,
+    ) -> Result<Self, UnityPackageReaderError> {
+        let name = name.as_ref();
+        let bytes = bytes.into();
+        if let Err(e) = package_format::sniff(&mut &bytes[..]) {
+            return Err(UnityPackageReaderError::NotAPackageFile(
+                ErrorInformation::new(Some(format!("{:?}: {}", name, e)), file!(), line!()),
+            ));
+        }
+
+        let mut package = UnityPackage::with_paths(name.to_path_buf(), None, None);
+        package.bytes = Some(bytes);
+        Ok(package)
+    }
+
+    fn with_paths(
+        path: PathBuf,
+        target_path: Option<PathBuf>,
+        temp_directory: Option<PathBuf>,
+    ) -> Self {
+        UnityPackage {
+            target_path,
+            temp_directory,
+            files: HashMap::new(),
+            paths: HashMap::new(),
+            present: HashMap::new(),
+            root_rules: HashMap::new(),
+            path_rewrite: None,
+            observer: None,
+            filter: None,
+            buffer_sizes: BufferSizes::default(),
+            config: UnpackConfig::default(),
+            gitignore_edit: None,
+            warnings: Vec::new(),
+            stats: UnpackStats::default(),
+            filtered: Vec::new(),
+            excluded: HashSet::new(),
+            skipped_entries: Vec::new(),
+            duplicate_guids: Vec::new(),
+            failures: Vec::new(),
+            file_system: Arc::new(OsFileSystem),
+            bytes: None,
+            installed: None,
+            #[cfg(feature = "mmap")]
+            memory_map: false,
+            default_tmp_name: unique_tmp_name(&path),
+            path,
+        }
+    }
+
+    /// Same as `from_path`, for paths given as strings.
+    #[deprecated(
+        since = "0.1.0",
+        note = "use `UnityPackage::open` or `UnityPackage::from_path`"
+    )]
     pub fn new(
         file_name: &str,
         target_path: Option<String>,
         temp_directory: Option<String>,
     ) -> Result<Self, UnityPackageReaderError> {
-        let p = Path::new(file_name);
-
-        let mut path = String::from(file_name);
-        if !p.exists() {
-            if let Ok(mut working_dir) = std::env::current_dir() {
-                working_dir.push(file_name);
-                path = match working_dir.into_os_string().into_string() {
-                    Ok(p) => p,
-                    Err(e) => {
-                        return Err(UnityPackageReaderError::PathError(ErrorInformation::new(
-                            Some(format!("{:?}", e)),
-                            file!(),
-                            line!(),
-                        )))
-                    }
-                }
-            } else {
-                return Err(UnityPackageReaderError::WorkingDirectoryError(
-                    ErrorInformation::new(None, file!(), line!()),
+        Self::from_path(
+            file_name,
+            target_path.map(PathBuf::from),
+            temp_directory.map(PathBuf::from),
+        )
+    }
+
+    /// Creates a new UnityPackage for a package file that is checked right
+    /// away, which makes this the recommended constructor. Unlike `from_path`, path
+    /// is not looked up in the working directory: it has to exist
+    /// (`PackageNotFound`) and be a regular file starting like a gzip or tar
+    /// stream (`NotAPackageFile`).
+    pub fn open(
+        path: impl AsRef<Path>,
+        target_path: Option<PathBuf>,
+        temp_directory: Option<PathBuf>,
+    ) -> Result<Self, UnityPackageReaderError> {
+        let path = path.as_ref();
+        let metadata = match fs::metadata(path) {
+            Ok(m) => m,
+            Err(e) => {
+                return Err(UnityPackageReaderError::PackageNotFound(
+                    ErrorInformation::new(Some(format!("{:?}: {}", path, e)), file!(), line!()),
                 ));
             }
+        };
+
+        if !metadata.is_file() {
+            return Err(UnityPackageReaderError::NotAPackageFile(
+                ErrorInformation::new(
+                    Some(format!("{:?} is not a regular file", path)),
+                    file!(),
+                    line!(),
+                ),
+            ));
         }
 
-        Ok(UnityPackage {
-            path,
-            target_path,
-            temp_directory,
-            files: HashMap::new(),
-        })
+        let mut reader = match fs::File::open(path) {
+            Ok(f) => std::io::BufReader::new(f),
+            Err(e) => {
+                return Err(UnityPackageReaderError::PackageNotFound(
+                    ErrorInformation::new(Some(format!("{:?}: {}", path, e)), file!(), line!()),
+                ));
+            }
+        };
+
+        if let Err(e) = package_format::sniff(&mut reader) {
+            return Err(UnityPackageReaderError::NotAPackageFile(
+                ErrorInformation::new(Some(format!("{:?}: {}", path, e)), file!(), line!()),
+            ));
+        }
+
+        UnityPackage::from_path(path, target_path, temp_directory)
+    }
+
+    /// The path of the package file.
+    pub fn get_package_path(&self) -> &Path {
+        &self.path
     }
 
+    #[deprecated(since = "0.1.0", note = "use `get_package_path`")]
     pub fn get_path(&self) -> String {
-        self.path.clone()
+        self.path.to_string_lossy().into_owned()
+    }
+
+    pub fn get_overwrite_policy(&self) -> OverwritePolicy {
+        self.config.overwrite_policy
+    }
+
+    /// Decide what happens to files that already exist in the target directory.
+    /// Skipped assets are still recorded, see `UnityAssetFile::is_skipped`.
+    pub fn set_overwrite_policy(&mut self, policy: OverwritePolicy) {
+        self.config.overwrite_policy = policy;
+    }
+
+    pub fn get_meta_naming(&self) -> &MetaNaming {
+        &self.config.meta_naming
+    }
+
+    /// Decide how meta files are named in the target, e.g. `MetaNaming::Meta`
+    /// when unpacking into a Unity project. The written name of each meta file
+    /// is recorded, see `UnityAssetFile::get_installed_meta_path`.
+    pub fn set_meta_naming(&mut self, meta_naming: MetaNaming) {
+        self.config.meta_naming = meta_naming;
+    }
+
+    pub(crate) fn set_path_rewrite(&mut self, path_rewrite: Option<PathRewrite>) {
+        self.path_rewrite = path_rewrite;
+    }
+
+    pub(crate) fn set_observer(&mut self, observer: Option<SharedObserver>) {
+        self.observer = observer;
+    }
+
+    pub(crate) fn set_filter(&mut self, filter: Option<SharedFilter>) {
+        self.filter = filter;
+    }
+
+    pub(crate) fn get_buffer_sizes(&self) -> &BufferSizes {
+        &self.buffer_sizes
+    }
+
+    pub(crate) fn set_buffer_sizes(&mut self, buffer_sizes: BufferSizes) {
+        self.buffer_sizes = buffer_sizes;
+    }
+
+    pub fn get_config(&self) -> &UnpackConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: UnpackConfig) {
+        self.config = config;
+    }
+
+    /// Read the package file through a memory map instead of a buffer.
+    ///
+    /// # Safety
+    ///
+    /// The package file must not be truncated or modified while the package
+    /// is read. The size of the file is checked before every chunk of the
+    /// mapping is read, but the file can still shrink between the check and
+    /// the read, and touching a page that is no longer backed by the file
+    /// kills the process with SIGBUS (on Windows, the file cannot be
+    /// truncated while it is mapped).
+    #[cfg(feature = "mmap")]
+    pub unsafe fn with_memory_map(mut self) -> Self {
+        self.memory_map = true;
+        self
+    }
+
+    /// Builder style variant of `set_config`, e.g.
+    /// `UnityPackage::open(..)?.with_config(UnpackConfig::unity_project_import())`.
+    pub fn with_config(mut self, config: UnpackConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Index the tmp directory and install the assets through file_system
+    /// instead of `std::fs`, e.g. to test how failures are handled.
+    pub fn with_file_system(mut self, file_system: Arc<dyn FileSystem>) -> Self {
+        self.file_system = file_system;
+        self
+    }
+
+    pub(crate) fn file_system(&self) -> &dyn FileSystem {
+        self.file_system.as_ref()
+    }
+
+    pub(crate) fn shared_file_system(&self) -> Arc<dyn FileSystem> {
+        self.file_system.clone()
+    }
+
+    /// The .gitignore file written during the last unpack, if any. Call
+    /// `GitIgnoreEdit::revert` to undo the change.
+    pub fn get_gitignore_edit(&self) -> Option<&GitIgnoreEdit> {
+        self.gitignore_edit.as_ref()
+    }
+
+    /// Conditions found during the last unpack that did not abort it.
+    pub fn get_warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// The assets the last unpack could not index or install, see
+    /// `UnpackConfig::continue_on_error`.
+    pub fn get_failures(&self) -> &[AssetFailure] {
+        &self.failures
+    }
+
+    /// The links and special files of the archive the last unpack left out
+    /// (see `UnpackConfig::special_entries`).
+    pub fn get_skipped_entries(&self) -> &[SkippedEntry] {
+        &self.skipped_entries
+    }
+
+    pub(crate) fn set_skipped_entries(&mut self, skipped_entries: Vec<SkippedEntry>) {
+        self.skipped_entries = skipped_entries;
+    }
+
+    /// The guid directories the archive contained more than once and how
+    /// they have been resolved, see `UnpackConfig::duplicate_guids`.
+    pub fn get_duplicate_guids(&self) -> &[DuplicateGuid] {
+        &self.duplicate_guids
+    }
+
+    pub(crate) fn set_duplicate_guids(&mut self, duplicate_guids: Vec<DuplicateGuid>) {
+        self.duplicate_guids = duplicate_guids;
+    }
+
+    /// How many bytes the last unpack read, wrote and deleted.
+    pub fn get_stats(&self) -> &UnpackStats {
+        &self.stats
+    }
+
+    pub fn get_file_by_guid(&self, guid: Guid) -> Option<&UnityAssetFile> {
+        self.files.get(&guid)
+    }
+
+    /// Look up an asset by the text form of its guid. Strings that are not a
+    /// valid guid find nothing.
+    pub fn get_file(&self, guid: &str) -> Option<&UnityAssetFile> {
+        guid.parse().ok().and_then(|g| self.get_file_by_guid(g))
+    }
+
+    /// Look up an asset by its relative path, e.g. `Assets/Textures/a.png`.
+    /// Backslashes are treated as separators, so Windows style paths match too.
+    pub fn get_file_by_path(&self, path: impl AsRef<Path>) -> Option<&UnityAssetFile> {
+        let key = normalize_path_key(&path.as_ref().to_string_lossy());
+        self.paths.get(&key).and_then(|guid| self.files.get(guid))
+    }
+
+    /// Every asset indexed by the last unpack, ordered by relative path.
+    pub fn files(&self) -> impl Iterator<Item = &UnityAssetFile> + '_ {
+        let mut files: Vec<&UnityAssetFile> = self.files.values().collect();
+        files.sort_by(|a, b| {
+            a.get_relative_asset_path()
+                .cmp(b.get_relative_asset_path())
+                .then_with(|| a.get_guid().cmp(&b.get_guid()))
+        });
+
+        files.into_iter()
+    }
+
+    /// The indexed assets whose relative path matches the regular expression
+    /// pattern, ordered by relative path. Paths use forward slashes. Fails
+    /// with `InvalidPattern` if pattern cannot be compiled. Needs an unpack
+    /// (or `analyze_package`), see `find_entries` to search the archive.
+    pub fn find(&self, pattern: &str) -> Result<Vec<&UnityAssetFile>, UnityPackageReaderError> {
+        self.find_with(pattern, false)
+    }
+
+    /// Same as `find`, but ignores the case with ignore_case.
+    pub fn find_with(
+        &self,
+        pattern: &str,
+        ignore_case: bool,
+    ) -> Result<Vec<&UnityAssetFile>, UnityPackageReaderError> {
+        let search = PathSearch::new(pattern, ignore_case)?;
+        Ok(self
+            .files()
+            .filter(|f| search.matches(f.get_relative_asset_path()))
+            .collect())
+    }
+
+    /// The number of indexed assets per importer, folders included.
+    pub fn count_by_importer(&self) -> BTreeMap<Importer, usize> {
+        let mut counts = BTreeMap::new();
+        for file in self.files.values() {
+            *counts.entry(file.importer()).or_insert(0) += 1;
+        }
+
+        counts
+    }
+
+    /// The number of indexed assets.
+    pub fn len(&self) -> usize {
+        self.files.len()
     }
 
-    pub fn get_file(&self, guid: &String) -> Option<&UnityAssetFile> {
-        self.files.get(guid)
+    /// True, if no asset has been indexed (yet).
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// A few lines with the counts of the last unpack, for printing: the
+    /// indexed assets and folders, what was skipped, overwritten and filtered,
+    /// the warnings and the bytes written into the target.
+    pub fn summary(&self) -> String {
+        let count = |f: fn(&UnityAssetFile) -> bool| self.files().filter(|a| f(a)).count();
+        let package = self
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        format!(
+            "package: {}\nassets: {}\nfolders: {}\nskipped: {}\noverwritten: {}\nfiltered: {}\nwarnings: {}\nbytes written: {}\n",
+            package,
+            self.len(),
+            count(UnityAssetFile::is_folder),
+            count(UnityAssetFile::is_skipped),
+            count(UnityAssetFile::is_overwritten),
+            self.filtered.len(),
+            self.warnings.len(),
+            self.stats.target_bytes_written,
+        )
     }
 
-    /// The default tmp directory is always the current [working directory]/tmp
+    /// The directory the package is extracted into, the tmp directory passed
+    /// when the package was created if there is one. The default is
+    /// [OS temp dir]/`TMP_SUBFOLDER`/[package name]-[random] (see
+    /// `std::env::temp_dir`), which is different for every instance, so
+    /// unpacks running side by side do not collide. Earlier versions used
+    /// [working directory]/tmp, pass that explicitly to keep the old location.
     pub fn get_tmp_dir(&self) -> Result<PathBuf, UnityPackageReaderError> {
+        file_system::require_file_system("The tmp directory")?;
         match &self.temp_directory {
-            Some(s) => Ok(PathBuf::from(s)),
-            None => {
-                if let Ok(mut working_dir) = std::env::current_dir() {
-                    working_dir.push("tmp");
-                    Ok(working_dir)
-                } else {
-                    Err(UnityPackageReaderError::WorkingDirectoryError(
-                        ErrorInformation::new(None, file!(), line!()),
-                    ))
-                }
-            }
+            Some(s) => Ok(s.clone()),
+            None => Ok(std::env::temp_dir()
+                .join(TMP_SUBFOLDER)
+                .join(&self.default_tmp_name)),
         }
     }
 
     /// Return the file name of the package without extension.
     fn get_package_file_name(&self) -> Result<String, UnityPackageReaderError> {
-        let p = PathBuf::from(&self.path);
-
-        match p.file_stem() {
+        match self.path.file_stem() {
             Some(s) => {
                 if let Some(file_stem) = s.to_str() {
                     Ok(String::from(file_stem))
@@ -111,339 +567,6196 @@ impl UnityPackage {
     /// Get the target directory. If the target has been set by the user
     /// then this directory is beeing return.
     /// Otherwise we use the current working directory and append the file name
-    /// of the package.
+    /// of the package. A relative target is resolved against the current
+    /// working directory, so the result is always absolute.
     pub fn get_target_dir(&self) -> Result<PathBuf, UnityPackageReaderError> {
-        match &self.target_path {
-            Some(s) => Ok(PathBuf::from(s)),
+        file_system::require_file_system("The target directory")?;
+        let relative = match &self.target_path {
+            Some(s) if s.is_absolute() => return Ok(s.clone()),
+            Some(s) => s.clone(),
 
             None => match self.get_package_file_name() {
-                Ok(s) => match std::env::current_dir() {
-                    Ok(mut r) => {
-                        r.push(s);
-                        Ok(r)
-                    }
-                    Err(e) => Err(UnityPackageReaderError::WorkingDirectoryError(
+                Ok(s) => PathBuf::from(s),
+                Err(e) => {
+                    return Err(UnityPackageReaderError::NotAPackageFile(
                         ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
-                    )),
-                },
-                Err(e) => Err(UnityPackageReaderError::NotAPackageFile(
-                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
-                )),
+                    ));
+                }
             },
+        };
+
+        match std::env::current_dir() {
+            Ok(r) => Ok(r.join(relative)),
+            Err(e) => Err(UnityPackageReaderError::WorkingDirectoryError(
+                ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+            )),
         }
     }
 
+    /// Extract the package into the tmp directory, index its assets and (unless
+    /// `UnpackConfig::install` is false) copy them into the target directory,
+    /// using the configuration of this package. With delete_tmp, the tmp
+    /// directory is removed even if the unpack fails or panics.
     pub fn unpack_package(&mut self, delete_tmp: bool) -> Result<(), UnityPackageReaderError> {
-        let tmp = get_file_as_byte_vec(Path::new(self.path.clone().as_str()));
-        match tmp {
-            Ok(bytes) => {
-                let tar = GzDecoder::new(&bytes[..]);
-                let mut archive = Archive::new(tar);
-
-                let tmp_path = match self.get_tmp_dir() {
-                    Ok(e) => e,
-                    Err(e) => {
-                        return Err(UnityPackageReaderError::TmpDirectoryCouldNotBeCreated(
-                            ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
-                        ));
-                    }
-                };
+        let options = UnpackOptions {
+            delete_tmp,
+            config: None,
+            path_rewrite: None,
+            observer: None,
+            filter: None,
+            buffer_sizes: self.buffer_sizes,
+        };
 
-                match std::fs::create_dir_all(tmp_path.clone()) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        return Err(UnityPackageReaderError::TmpDirectoryCouldNotBeCreated(
-                            ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
-                        ));
-                    }
-                }
+        self.unpack_package_with(&options)
+    }
 
-                match archive.unpack(tmp_path.clone()) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        return Err(UnityPackageReaderError::CorruptPackage(
-                            ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
-                        ));
-                    }
-                }
+    /// Same as `unpack_package`, but all settings are taken from options. The
+    /// configuration of the options, if set, replaces the configuration of
+    /// this package; without one the configuration of this package is used.
+    /// In analyze-only mode the tmp directory is always kept, since the index
+    /// refers to the files inside it.
+    pub fn unpack_package_with(
+        &mut self,
+        options: &UnpackOptions,
+    ) -> Result<(), UnityPackageReaderError> {
+        UnpackSession::new(self, options).run().map(|_| ())
+    }
 
-                match self.copy_files_to_target() {
-                    Ok(_) => {}
-                    Err(e) => {
-                        return Err(e);
-                    }
-                }
+    /// Run only the copy phase, using the tmp directory an earlier unpack left
+    /// behind (e.g. because copying into the target failed and delete_tmp was
+    /// off), so the package is
+    /// not decompressed again. Assets that are in the target already are
+    /// handled by the overwrite policy, `OverwritePolicy::Skip` keeps them.
+    /// Fails with `InvalidTmpDirectory` if tmp does not look like an
+    /// extracted package. The archive order is not known anymore, so
+    /// `ExtractOrdering::ArchiveOrder` installs by path.
+    pub fn unpack_from_tmp(&mut self, delete_tmp: bool) -> Result<(), UnityPackageReaderError> {
+        let options = UnpackOptions {
+            delete_tmp,
+            config: None,
+            path_rewrite: None,
+            observer: None,
+            filter: None,
+            buffer_sizes: self.buffer_sizes,
+        };
 
-                if delete_tmp {
-                    match std::fs::remove_dir_all(tmp_path) {
-                        Ok(_) => Ok(()),
-                        Err(e) => Err(UnityPackageReaderError::CouldNotDeleteTmp(
-                            ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
-                        )),
-                    }
+        UnpackSession::from_tmp(self, &options).run().map(|_| ())
+    }
+
+    /// Same as `unpack_package_with`, but returns what happened to every
+    /// asset of the package, together with the counts and the time it took.
+    pub fn unpack_with_report(
+        &mut self,
+        options: &UnpackOptions,
+    ) -> Result<ExtractionReport, UnityPackageReaderError> {
+        let start = Instant::now();
+        self.unpack_package_with(options)?;
+        let elapsed = start.elapsed();
+        let target = self.get_target_dir()?;
+
+        // The index may still hold an asset a filter left out this time.
+        let filtered: HashSet<Guid> = self
+            .filtered
+            .iter()
+            .map(|(g, _)| *g)
+            .chain(self.failures.iter().map(|f| f.guid))
+            .collect();
+        let mut assets: Vec<AssetReport> = self
+            .files
+            .values()
+            .filter(|f| !filtered.contains(&f.get_guid()))
+            .map(|f| {
+                let status = if f.is_skipped() {
+                    AssetStatus::Skipped
+                } else if f.is_unchanged() {
+                    AssetStatus::Unchanged
+                } else if !self.config.install || !f.is_installed() {
+                    AssetStatus::NotInstalled
+                } else if f.is_overwritten() {
+                    AssetStatus::Overwritten
                 } else {
-                    Ok(())
+                    AssetStatus::Extracted
+                };
+
+                AssetReport {
+                    guid: f.get_guid(),
+                    relative_path: f.get_relative_asset_path().clone(),
+                    package_path: f.get_package_path().clone(),
+                    absolute_path: target.join(f.get_relative_asset_path()),
+                    status,
+                    root_rule: self.root_rules.get(&f.get_guid()).copied(),
+                    sha256: f.get_stored_sha256().map(|h| checksums::to_hex(&h)),
+                    backups: f.get_backups().to_vec(),
                 }
-            }
+            })
+            .collect();
 
-            Err(e) => match e {
-                FileErrors::FileNotFound => Err(UnityPackageReaderError::PackageNotFound(
-                    ErrorInformation::new(None, file!(), line!()),
-                )),
-                FileErrors::CorruptFile => Err(UnityPackageReaderError::CorruptPackage(
-                    ErrorInformation::new(None, file!(), line!()),
-                )),
-            },
-        }
+        assets.extend(self.failures.iter().map(|f| AssetReport {
+            guid: f.guid,
+            relative_path: f.path.clone(),
+            package_path: f.path.clone(),
+            absolute_path: target.join(&f.path),
+            status: AssetStatus::Failed,
+            root_rule: None,
+            sha256: None,
+            backups: Vec::new(),
+        }));
+        assets.extend(self.filtered.iter().map(|(guid, path)| AssetReport {
+            guid: *guid,
+            relative_path: path.clone(),
+            package_path: path.clone(),
+            absolute_path: target.join(path),
+            status: AssetStatus::Filtered,
+            root_rule: None,
+            sha256: None,
+            backups: Vec::new(),
+        }));
+
+        Ok(ExtractionReport {
+            warnings: self.warnings.clone(),
+            duplicate_guids: self.duplicate_guids.clone(),
+            ..ExtractionReport::new(
+                target,
+                self.get_tmp_dir()?,
+                self.stats.target_bytes_written,
+                elapsed,
+                assets,
+                self.skipped_entries.clone(),
+                self.failures.clone(),
+            )
+        })
     }
 
-    fn copy_files_to_target(&mut self) -> Result<(), UnityPackageReaderError> {
-        let p = self.get_tmp_dir();
-        let t = self.get_target_dir();
+    /// Start an unpack that is driven by calling `UnpackSession::step`, so the
+    /// work can be spread over several calls on a single thread.
+    pub fn start_unpack(&mut self, options: &UnpackOptions) -> UnpackSession<'_> {
+        UnpackSession::new(self, options)
+    }
 
-        let target = match t {
-            Ok(f) => f,
-            Err(e) => return Err(e),
-        };
+    /// Index the extracted package after the extraction has produced stats.
+    /// Returns the target directory and the assets to install, in the order
+    /// given by `UnpackConfig::ordering`. When resuming, assets an earlier
+    /// unpack has installed already are indexed from the target.
+    pub(crate) fn begin_install(
+        &mut self,
+        tmp_path: &Path,
+        stats: UnpackStats,
+        archive_order: &[Guid],
+        hashes: &HashMap<Guid, [u8; 32]>,
+        resuming: bool,
+    ) -> Result<(PathBuf, Vec<UnityAssetFile>), UnityPackageReaderError> {
+        self.stats = stats;
+        let target = self.get_target_dir()?;
 
-        let origin = match p {
-            Ok(f) => f,
-            Err(e) => return Err(e),
+        let installed = if resuming {
+            Some(target.as_path())
+        } else {
+            None
         };
+        let mut failures = Vec::new();
+        let collect = self.config.continue_on_error.then_some(&mut failures);
+        let (assets, warnings) = self.read_tmp_assets(tmp_path, installed, collect)?;
+        self.failures = failures;
+        self.warnings = warnings;
+        self.installed = None;
+        if self.config.incremental && self.config.install {
+            let installed = InstalledManifest::read(&target)?;
+            let guids: HashSet<Guid> = assets.iter().map(|a| a.get_guid()).collect();
+            for (guid, entry) in installed.entries().filter(|(g, _)| !guids.contains(g)) {
+                self.warnings.push(Warning::RemovedFromPackage {
+                    guid: *guid,
+                    path: entry.path.clone(),
+                });
+            }
+            self.installed = Some(installed);
+        }
+        let mut resolved = self.resolve_paths(assets, archive_order)?;
+        if self.config.hash_assets || self.config.incremental {
+            self.hash_assets(&mut resolved, hashes)?;
+        }
+        self.excluded = resolved.excluded;
+        self.root_rules = resolved.root_rules;
+        self.filtered = resolved.filtered;
+        self.warnings.extend(resolved.warnings);
+        self.present = self.scan_present(&target, tmp_path)?;
 
-        let files = match fs::read_dir(origin.clone()) {
-            Ok(f) => f,
-            Err(e) => {
-                return Err(UnityPackageReaderError::TmpDirectoryCouldNotBeCreated(
-                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
-                ));
+        Ok((target, resolved.assets))
+    }
+
+    /// Set the sha256 of the files the unpack installs. Payloads that have
+    /// not been hashed while extracting (e.g. when resuming) are read once
+    /// more. With `UnpackConfig::continue_on_error`, an asset that cannot be
+    /// read is recorded as failed and left out.
+    fn hash_assets(
+        &mut self,
+        resolved: &mut ResolvedPaths,
+        hashes: &HashMap<Guid, [u8; 32]>,
+    ) -> Result<(), UnityPackageReaderError> {
+        for mut asset in std::mem::take(&mut resolved.assets) {
+            if asset.is_folder() || !resolved.includes(&asset) {
+                resolved.assets.push(asset);
+                continue;
             }
-        };
 
-        for entry in files {
-            let entry = match entry {
-                Ok(f) => f,
-                Err(e) => {
-                    return Err(UnityPackageReaderError::CorruptPackage(
-                        ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
-                    ))
-                }
+            let hash = match hashes.get(&asset.get_guid()).copied().map(Ok) {
+                Some(h) => h,
+                None => asset.sha256(),
             };
-
-            let p = entry.path();
-            let asset_file = UnityAssetFile::from(p);
-            match asset_file {
-                Ok(mut a) => {
-                    match a.copy_asset(&target) {
-                        Ok(()) => {}
-                        Err(e) => {
-                            return Err(e);
-                        }
-                    }
-                    self.files.insert(a.get_guid().clone(), a);
+            match hash {
+                Ok(h) => {
+                    asset.set_sha256(h);
+                    resolved.assets.push(asset);
                 }
-                Err(e) => {
-                    return Err(e);
+                Err(error) if self.config.continue_on_error && !error.is_catastrophic() => {
+                    self.failures.push(AssetFailure {
+                        guid: asset.get_guid(),
+                        path: asset.get_package_path().clone(),
+                        error,
+                    });
                 }
+                Err(e) => return Err(e),
             }
         }
 
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use serial_test::serial;
-    use super::*;
+    /// Decide which assets the filters keep, sort them into install order
+    /// and move them to the paths they are installed at: `strip_prefix` and
+    /// `root_remap`, the path rewrite, reserved names, case collisions and
+    /// `flatten`, in that order. Fails if a final path is not inside the
+    /// target. Shared by `begin_install` and `plan_unpack`, so a plan shows
+    /// the paths a real unpack uses.
+    fn resolve_paths(
+        &self,
+        assets: Vec<UnityAssetFile>,
+        archive_order: &[Guid],
+    ) -> Result<ResolvedPaths, UnityPackageReaderError> {
+        let filter = self.asset_filter();
+        let excluded = assets
+            .iter()
+            .filter(|a| !filter.keep(a))
+            .map(|a| a.get_guid())
+            .collect();
+        let mut resolved = ResolvedPaths {
+            assets,
+            excluded,
+            root_rules: HashMap::new(),
+            filtered: Vec::new(),
+            warnings: Vec::new(),
+        };
 
-    fn get_test_base_path() -> PathBuf {
-        let mut r = std::env::current_dir().unwrap();
-        if r.ends_with("unity_unpacker_lib") {
-            r = r.parent().unwrap().to_path_buf();
-        }
+        self.config
+            .ordering
+            .sort(&mut resolved.assets, archive_order);
+        self.configure_paths(&mut resolved)?;
+        self.rewrite_paths(&mut resolved)?;
+        self.resolve_reserved_names(&mut resolved)?;
+        self.resolve_case_collisions(&mut resolved)?;
+        self.flatten_paths(&mut resolved);
+        check_paths_inside_target(resolved.assets.iter().filter(|a| resolved.includes(a)))?;
 
-        r
+        Ok(resolved)
     }
 
-    #[test]
-    fn test_default_tmp_dir() {
-        let mut p = std::env::current_dir().unwrap();
-        p.push("tmp");
-
-        let mut t2 = std::env::current_dir().unwrap();
-        t2.push("file");
-
-        let item = UnityPackage::new("file.unitypackage", None, None).unwrap();
+    /// The filters of the configuration and `UnpackOptions::filter` (while
+    /// an unpack runs) as one.
+    fn asset_filter(&self) -> UnpackFilter<'_> {
+        UnpackFilter {
+            config: &self.config,
+            filter: self.filter.as_ref(),
+        }
+    }
 
-        assert_eq!(p, item.get_tmp_dir().unwrap());
-        assert_eq!(item.get_target_dir().unwrap(), t2);
+    /// True, if asset is installed by the running unpack. The filters are
+    /// asked once per asset in `begin_install`, the filtered assets stay in
+    /// the index and are reported as `SkipReason::Filtered`.
+    fn includes_asset(&self, asset: &UnityAssetFile) -> bool {
+        !self.excluded.contains(&asset.get_guid())
     }
 
-    #[test]
-    fn test_new_function_with_file_name() {
-        let n = "file_name.unitypackage";
-        let mut p = std::env::current_dir().unwrap();
-        p.push(n);
+    /// Move the assets to install according to `UnpackConfig::strip_prefix`
+    /// and `root_remap`, see `configured_path`. Assets the configuration
+    /// leaves out are reported as filtered.
+    fn configure_paths(&self, resolved: &mut ResolvedPaths) -> Result<(), UnityPackageReaderError> {
+        if self.config.strip_prefix.is_none() && self.config.root_remap.is_empty() {
+            return Ok(());
+        }
 
-        let mut t2 = std::env::current_dir().unwrap();
-        t2.push("file_name");
+        for mut asset in std::mem::take(&mut resolved.assets) {
+            if !resolved.includes(&asset) {
+                resolved.assets.push(asset);
+                continue;
+            }
 
-        let package = UnityPackage::new(n, None, None).unwrap();
+            let path = asset.get_relative_asset_path().clone();
+            match self.configured_path(&path)? {
+                Some((configured, rule)) => {
+                    if let Some(rule) = rule {
+                        resolved.root_rules.insert(asset.get_guid(), rule);
+                    }
+                    asset.set_relative_asset_path(configured);
+                    resolved.assets.push(asset);
+                }
+                None => resolved.filtered.push((asset.get_guid(), path)),
+            }
+        }
 
-        assert_eq!(p.into_os_string().into_string().unwrap(), package.path);
-        assert_eq!(package.get_target_dir().unwrap(), t2);
+        Ok(())
     }
 
-    #[test]
-    fn test_new_function_with_path() {
-        let mut p = std::env::current_dir().unwrap();
-        let parent = match p.parent() {
-            Some(i) => i,
-            None => {
-                panic!("Could not determine path")
+    /// The path an asset at path is moved to by `UnpackConfig::strip_prefix`
+    /// and then `root_remap`, with the index of the remap rule that matched.
+    /// None if the configuration leaves the asset out: it lacks the prefix
+    /// with `skip_unprefixed`, or no rule matches with `drop_unmapped`.
+    fn configured_path(
+        &self,
+        path: &Path,
+    ) -> Result<Option<(PathBuf, Option<usize>)>, UnityPackageReaderError> {
+        let path = match self
+            .config
+            .strip_prefix
+            .as_ref()
+            .map(|p| (p, p.strip(path)))
+        {
+            Some((prefix, Some(stripped))) if stripped.as_os_str().is_empty() => {
+                return Err(UnityPackageReaderError::PathError(ErrorInformation::new(
+                    Some(format!(
+                        "Stripping {:?} from '{}' leaves nothing",
+                        prefix,
+                        path.display()
+                    )),
+                    file!(),
+                    line!(),
+                )));
             }
+            Some((_, Some(stripped))) => stripped,
+            Some((_, None)) if self.config.skip_unprefixed => return Ok(None),
+            _ => path.to_path_buf(),
         };
 
-        p = parent.to_path_buf();
-        p.push("file_name.unitypackage");
-
-        let mut t2 = std::env::current_dir().unwrap();
-        t2.push("file_name");
+        if self.config.root_remap.is_empty() {
+            return Ok(Some((path, None)));
+        }
 
-        let subject = UnityPackage::new(
+        let matched = self
+            .config
+            .root_remap
+            .iter()
+            .enumerate()
+            .find_map(|(i, rule)| rule.apply(&path).map(|p| (i, p)));
+        match matched {
+            Some((_, remapped)) if !is_inside_target(&remapped) => {
+                Err(UnityPackageReaderError::PathError(ErrorInformation::new(
+                    Some(format!(
+                        "'{}' is remapped to '{}', which is not inside the target",
+                        path.display(),
+                        remapped.display()
+                    )),
+                    file!(),
+                    line!(),
+                )))
+            }
+            Some((rule, remapped)) => Ok(Some((remapped, Some(rule)))),
+            None if self.config.drop_unmapped => Ok(None),
+            None => Ok(Some((path, None))),
+        }
+    }
+
+    /// Ask `UnpackOptions::path_rewrite` for the path of every asset to install.
+    /// Assets it returns None for are left out.
+    fn rewrite_paths(&self, resolved: &mut ResolvedPaths) -> Result<(), UnityPackageReaderError> {
+        let rewrite = match &self.path_rewrite {
+            Some(r) => r,
+            None => return Ok(()),
+        };
+
+        for mut asset in std::mem::take(&mut resolved.assets) {
+            if !resolved.includes(&asset) {
+                resolved.assets.push(asset);
+                continue;
+            }
+
+            let guid = asset.get_guid();
+            let path = asset.get_relative_asset_path().clone();
+            let rewritten =
+                match panic::catch_unwind(AssertUnwindSafe(|| rewrite.apply(&guid, &path))) {
+                    Ok(r) => r,
+                    Err(_) => {
+                        return Err(UnityPackageReaderError::PathError(ErrorInformation::new(
+                            Some(format!(
+                                "The path rewrite panicked for '{}'",
+                                path.display()
+                            )),
+                            file!(),
+                            line!(),
+                        )));
+                    }
+                };
+
+            match rewritten {
+                Some(r) if !is_inside_target(&r) => {
+                    return Err(UnityPackageReaderError::PathError(ErrorInformation::new(
+                        Some(format!(
+                            "'{}' has been rewritten to '{}', which is not inside the target",
+                            path.display(),
+                            r.display()
+                        )),
+                        file!(),
+                        line!(),
+                    )));
+                }
+                Some(r) => {
+                    asset.set_relative_asset_path(r);
+                    resolved.assets.push(asset);
+                }
+                None => resolved.filtered.push((guid, path)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Look for assets to install whose paths contain names Windows reserves
+    /// for devices and handle them according to `UnpackConfig::reserved_names`.
+    fn resolve_reserved_names(
+        &self,
+        resolved: &mut ResolvedPaths,
+    ) -> Result<(), UnityPackageReaderError> {
+        let policy = self.config.reserved_names;
+        if policy == ReservedNames::Ignore {
+            return Ok(());
+        }
+
+        let mut offending = Vec::new();
+        for mut asset in std::mem::take(&mut resolved.assets) {
+            let path = asset.get_relative_asset_path().clone();
+            let renamed = match reserved_names::sanitize_path(&path) {
+                Some(r) if resolved.includes(&asset) => r,
+                _ => {
+                    resolved.assets.push(asset);
+                    continue;
+                }
+            };
+
+            match policy {
+                ReservedNames::Error | ReservedNames::Ignore => {
+                    offending.push(format!("'{}'", path.display()));
+                }
+                ReservedNames::RenameWithSuffix => {
+                    resolved.warnings.push(Warning::ReservedName {
+                        guid: asset.get_guid(),
+                        path,
+                        renamed: renamed.clone(),
+                    });
+                    asset.set_relative_asset_path(renamed);
+                }
+            }
+            resolved.assets.push(asset);
+        }
+
+        if !offending.is_empty() {
+            return Err(UnityPackageReaderError::ReservedName(
+                ErrorInformation::new(Some(offending.join(", ")), file!(), line!()),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Look for assets to install whose relative paths only differ in case and
+    /// handle them according to `UnpackConfig::case_collisions`. The first
+    /// asset in install order always keeps its path.
+    fn resolve_case_collisions(
+        &self,
+        resolved: &mut ResolvedPaths,
+    ) -> Result<(), UnityPackageReaderError> {
+        let policy = self.config.case_collisions;
+        if policy == CaseCollisions::Ignore {
+            return Ok(());
+        }
+
+        let key = |p: &Path| normalize_path_key(&p.to_string_lossy()).to_lowercase();
+        let mut used: HashSet<String> = resolved
+            .assets
+            .iter()
+            .map(|a| key(a.get_relative_asset_path()))
+            .collect();
+        // The path that has been kept for every key.
+        let mut kept: HashMap<String, PathBuf> = HashMap::new();
+        let mut collisions = Vec::new();
+
+        for mut asset in std::mem::take(&mut resolved.assets) {
+            let path = asset.get_relative_asset_path().clone();
+            if !resolved.includes(&asset) {
+                resolved.assets.push(asset);
+                continue;
+            }
+
+            let other = match kept.get(&key(&path)) {
+                Some(other) if *other != path => other.clone(),
+                Some(_) => {
+                    resolved.assets.push(asset);
+                    continue;
+                }
+                None => {
+                    kept.insert(key(&path), path);
+                    resolved.assets.push(asset);
+                    continue;
+                }
+            };
+
+            match policy {
+                CaseCollisions::Error | CaseCollisions::Ignore => {
+                    collisions.push(format!("'{}' and '{}'", other.display(), path.display()));
+                }
+                CaseCollisions::KeepFirst => {
+                    resolved.warnings.push(Warning::CaseCollision {
+                        guid: asset.get_guid(),
+                        path: path.clone(),
+                        renamed: None,
+                    });
+                    resolved.filtered.push((asset.get_guid(), path));
+                }
+                CaseCollisions::RenameWithSuffix => {
+                    let renamed = (1..)
+                        .map(|n| with_suffix(&path, n))
+                        .find(|p| !used.contains(&key(p)))
+                        .unwrap_or_default();
+                    used.insert(key(&renamed));
+                    kept.insert(key(&renamed), renamed.clone());
+                    resolved.warnings.push(Warning::CaseCollision {
+                        guid: asset.get_guid(),
+                        path,
+                        renamed: Some(renamed.clone()),
+                    });
+                    asset.set_relative_asset_path(renamed);
+                    resolved.assets.push(asset);
+                }
+            }
+        }
+
+        if !collisions.is_empty() {
+            return Err(UnityPackageReaderError::CaseCollision(
+                ErrorInformation::new(Some(collisions.join(", ")), file!(), line!()),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Move every file asset that is going to be installed directly into the
+    /// target root (`UnpackConfig::flatten`). A name that is taken already
+    /// gets a suffix according to `Flatten::collisions`.
+    fn flatten_paths(&self, resolved: &mut ResolvedPaths) {
+        let flatten = match self.config.flatten {
+            Some(f) => f,
+            None => return,
+        };
+
+        let key = |p: &Path| normalize_path_key(&p.to_string_lossy()).to_lowercase();
+        let numbered = |p: &Path, used: &HashSet<String>| {
+            (1..)
+                .map(|n| with_suffix(p, n))
+                .find(|p| !used.contains(&key(p)))
+                .unwrap_or_default()
+        };
+        let mut used = HashSet::new();
+        for asset in resolved.assets.iter_mut() {
+            if asset.is_folder() || resolved.excluded.contains(&asset.get_guid()) {
+                continue;
+            }
+
+            let name = match asset.get_relative_asset_path().file_name() {
+                Some(n) => PathBuf::from(n),
+                None => continue,
+            };
+            let flattened = match flatten.collisions {
+                _ if !used.contains(&key(&name)) => name,
+                FlattenCollisions::NumericSuffix => numbered(&name, &used),
+                FlattenCollisions::GuidPrefix => {
+                    let prefixed = with_suffix(&name, &asset.get_guid().to_string()[..8]);
+                    // Two guids can start with the same digits.
+                    if used.contains(&key(&prefixed)) {
+                        numbered(&prefixed, &used)
+                    } else {
+                        prefixed
+                    }
+                }
+            };
+
+            used.insert(key(&flattened));
+            asset.set_relative_asset_path(flattened);
+        }
+    }
+
+    /// Install a single asset (unless in analyze-only mode) and add it to the
+    /// index. With a staging directory, the files are written there instead of
+    /// into target. Returns the number of bytes written. The observer of the
+    /// options is told about the start and the end. With
+    /// `UnpackConfig::continue_on_error`, a failed asset is recorded and left
+    /// out instead of failing the unpack.
+    pub(crate) fn install_asset(
+        &mut self,
+        target: &Path,
+        staging: Option<&Path>,
+        asset: UnityAssetFile,
+    ) -> Result<u64, UnityPackageReaderError> {
+        let guid = asset.get_guid();
+        let path = asset.get_relative_asset_path().clone();
+        match self.observe_install(target, staging, asset) {
+            Err(error) if self.config.continue_on_error && !error.is_catastrophic() => {
+                self.failures.push(AssetFailure { guid, path, error });
+                Ok(0)
+            }
+            result => result,
+        }
+    }
+
+    /// `install_asset` with the observer, but without continue_on_error.
+    fn observe_install(
+        &mut self,
+        target: &Path,
+        staging: Option<&Path>,
+        asset: UnityAssetFile,
+    ) -> Result<u64, UnityPackageReaderError> {
+        let observer = match self.observer.clone() {
+            Some(o) => o,
+            None => return self.install_single(target, staging, asset).map(|(n, _)| n),
+        };
+
+        observer.on_start(&asset);
+        let guid = asset.get_guid();
+        let before = asset.clone();
+        match self.install_single(target, staging, asset) {
+            Ok((installed, status)) => {
+                let asset = match status {
+                    FileStatus::Skipped(SkipReason::Filtered) => &before,
+                    _ => self.files.get(&guid).unwrap_or(&before),
+                };
+                observer.on_finished(asset, &status);
+                Ok(installed)
+            }
+            Err(e) => {
+                observer.on_finished(&before, &FileStatus::Failed(e.clone()));
+                Err(e)
+            }
+        }
+    }
+
+    /// `observe_install` without the observer. Returns what has happened as well.
+    fn install_single(
+        &mut self,
+        target: &Path,
+        staging: Option<&Path>,
+        mut asset: UnityAssetFile,
+    ) -> Result<(u64, FileStatus), UnityPackageReaderError> {
+        // The index is built from the tmp directory, installing is optional.
+        if !self.includes_asset(&asset) {
+            self.filtered
+                .push((asset.get_guid(), asset.get_relative_asset_path().clone()));
+            return Ok((0, FileStatus::Skipped(SkipReason::Filtered)));
+        }
+
+        // Installed by an earlier unpack, see `unpack_from_tmp`.
+        let resumed = asset.is_skipped();
+        if self.config.install && !resumed && !asset.remap_guids(&self.config)? {
+            self.warnings.push(Warning::ReferencesNotRewritten {
+                guid: asset.get_guid(),
+                path: asset.get_relative_asset_path().clone(),
+            });
+        }
+
+        let mut installed = 0;
+        if let Some(existing) = self.present.get(&asset.get_guid()) {
+            self.warnings.push(Warning::AlreadyPresent {
+                guid: asset.get_guid(),
+                path: asset.get_relative_asset_path().clone(),
+                existing: existing.clone(),
+            });
+            asset.mark_skipped();
+        } else if self.config.install && !resumed && self.is_unchanged(target, &asset)? {
+            let (installed_asset, installed_meta) =
+                asset.get_target_paths(target, &self.config.meta_naming)?;
+            asset.mark_unchanged(
+                installed_asset,
+                (self.config.installs_metas() && asset.has_meta()).then_some(installed_meta),
+            );
+        } else if self.config.install && !resumed {
+            let destination = staging.unwrap_or(target);
+            match asset.copy_asset_into(
+                target,
+                destination,
+                &self.config,
+                self.file_system(),
+                self.buffer_sizes.copy,
+            ) {
+                Ok(()) => {
+                    installed = asset.get_installed_bytes();
+                    if let Some(reason) = asset.take_mtime_error() {
+                        self.warnings.push(Warning::TimestampNotApplied {
+                            guid: asset.get_guid(),
+                            path: asset.get_relative_asset_path().clone(),
+                            reason,
+                        });
+                    }
+                }
+                Err(e) => {
+                    return Err(e);
+                }
+            }
+        }
+
+        let status = if resumed {
+            FileStatus::Skipped(SkipReason::Resumed)
+        } else if self.present.contains_key(&asset.get_guid()) {
+            FileStatus::Skipped(SkipReason::AlreadyPresent)
+        } else if asset.is_skipped() {
+            FileStatus::Skipped(SkipReason::AlreadyExists)
+        } else if asset.is_unchanged() {
+            FileStatus::Skipped(SkipReason::Unchanged)
+        } else if !self.config.install || !asset.is_installed() {
+            FileStatus::Skipped(SkipReason::NotInstalled)
+        } else {
+            FileStatus::Extracted
+        };
+
+        self.stats.target_bytes_written += installed;
+        self.paths.insert(
+            normalize_path_key(&asset.get_relative_asset_path().to_string_lossy()),
+            asset.get_guid(),
+        );
+        self.files.insert(asset.get_guid(), asset);
+        Ok((installed, status))
+    }
+
+    /// Incremental mode: true, if the last unpack installed the asset at the
+    /// same path with the same payload and the target still has it like that.
+    fn is_unchanged(
+        &self,
+        target: &Path,
+        asset: &UnityAssetFile,
+    ) -> Result<bool, UnityPackageReaderError> {
+        let entry = match self
+            .installed
+            .as_ref()
+            .and_then(|m| m.get(&asset.get_guid()))
+        {
+            Some(e) => e,
+            None => return Ok(false),
+        };
+        if entry.path != *asset.get_relative_asset_path()
+            || entry.sha256 != asset.get_stored_sha256()
+        {
+            return Ok(false);
+        }
+
+        let (installed_asset, installed_meta) =
+            asset.get_target_paths(target, &self.config.meta_naming)?;
+        if self.config.installs_metas() && asset.has_meta() {
+            match self.file_system.read(&installed_meta) {
+                Ok(meta) if meta == asset.read_meta()?.as_bytes() => {}
+                _ => return Ok(false),
+            }
+        }
+
+        if asset.is_folder() {
+            return Ok(self.file_system.is_dir(&installed_asset));
+        }
+        Ok(self.file_system.sha256(&installed_asset).ok() == entry.sha256)
+    }
+
+    /// Incremental mode: record what the last unpack has installed. Entries
+    /// of assets the package does not contain anymore are kept.
+    fn write_installed_manifest(&mut self) -> Result<(), UnityPackageReaderError> {
+        let mut installed = match self.installed.take() {
+            Some(m) => m,
+            None => return Ok(()),
+        };
+
+        let filtered: HashSet<Guid> = self.filtered.iter().map(|(g, _)| *g).collect();
+        for file in self.files.values() {
+            if file.is_installed() && !filtered.contains(&file.get_guid()) {
+                installed.insert(
+                    file.get_guid(),
+                    InstalledEntry {
+                        sha256: file.get_stored_sha256(),
+                        path: file.get_relative_asset_path().clone(),
+                    },
+                );
+            } else {
+                installed.remove(&file.get_guid());
+            }
+        }
+
+        installed.write(&self.get_target_dir()?)?;
+        self.installed = Some(installed);
+        Ok(())
+    }
+
+    /// Update the .gitignore and remove the tmp directory once every asset is installed.
+    pub(crate) fn finish_unpack(
+        &mut self,
+        tmp_path: &Path,
+        delete_tmp: bool,
+    ) -> Result<(), UnityPackageReaderError> {
+        self.write_installed_manifest()?;
+        if self.config.git_aware {
+            self.update_gitignore(tmp_path, delete_tmp)?;
+        }
+
+        if delete_tmp {
+            let size = counting::directory_size(tmp_path);
+            match self.file_system.remove_dir_all(tmp_path) {
+                Ok(_) => self.stats.cleanup_bytes_deleted = size,
+                Err(e) => {
+                    return Err(UnityPackageReaderError::CouldNotDeleteTmp(
+                        ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                    ))
+                }
+            }
+        }
+
+        if self.config.fail_on_asset_errors && !self.failures.is_empty() {
+            return Err(UnityPackageReaderError::PartialFailure(
+                self.failures.clone(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Write the auxiliary outputs left in the target into a .gitignore, if the
+    /// target is part of a git repository. A block an earlier unpack wrote is
+    /// removed again once there is nothing left to ignore, e.g. because the
+    /// tmp directory is deleted this time.
+    fn update_gitignore(
+        &mut self,
+        tmp_path: &Path,
+        delete_tmp: bool,
+    ) -> Result<(), UnityPackageReaderError> {
+        let target = self.get_target_dir()?;
+        if git_ignore::find_git_root(&target).is_none() {
+            return Ok(());
+        }
+
+        let tmp = (!delete_tmp).then_some(tmp_path);
+        let patterns = git_ignore::auxiliary_patterns(&target, tmp, &self.config);
+        self.gitignore_edit = git_ignore::update_gitignore(&target, &patterns)?;
+
+        Ok(())
+    }
+
+    /// Decompress the package into the tmp directory and return the tmp directory
+    /// together with the guids in archive order.
+    fn extract_to_tmp(&self) -> Result<(TempDirGuard, Vec<Guid>), UnityPackageReaderError> {
+        Extraction::start(self, true)?
+            .run()
+            .map(|(tmp, _, archive_order)| (tmp, archive_order))
+    }
+
+    /// Create an UnityAssetFile for every guid directory inside the tmp directory
+    /// and resolve contradictions according to the configuration. With
+    /// installed, a guid directory without a meta file is looked up there.
+    /// With failures, a guid directory that cannot be read is recorded there
+    /// instead of failing.
+    fn read_tmp_assets(
+        &self,
+        origin: &Path,
+        installed: Option<&Path>,
+        mut failures: Option<&mut Vec<AssetFailure>>,
+    ) -> Result<(Vec<UnityAssetFile>, Vec<Warning>), UnityPackageReaderError> {
+        let files = match self.file_system.read_dir(origin) {
+            Ok(f) => f,
+            Err(e) => {
+                return Err(UnityPackageReaderError::TmpDirectoryCouldNotBeCreated(
+                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                ));
+            }
+        };
+
+        let mut directories = Vec::new();
+        for entry in files {
+            match entry {
+                Ok(f) => directories.push(f),
+                Err(e) => {
+                    return Err(UnityPackageReaderError::CorruptPackage(
+                        ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                    ))
+                }
+            };
+        }
+        // The order of read_dir differs between file systems, sorted the
+        // warnings and failures come out the same on every run.
+        directories.sort();
+
+        let mut result = Vec::new();
+        let mut warnings = Vec::new();
+        for path in directories {
+            // Extracting never creates links, so a link in tmp could only be
+            // used to write somewhere else.
+            for file in [
+                path.clone(),
+                path.join("asset"),
+                path.join("asset.meta"),
+                path.join(LEGACY_META_FILE),
+            ] {
+                if self.file_system.is_symlink(&file) {
+                    return Err(UnityPackageReaderError::UnsafeEntry(ErrorInformation::new(
+                        Some(format!("{:?} is a link", file)),
+                        file!(),
+                        line!(),
+                    )));
+                }
+            }
+
+            if let Some(warning) = self.missing_pathname(&path) {
+                warnings.push(warning);
+                continue;
+            }
+
+            let mut asset = match (self.read_tmp_asset(&path, installed), failures.as_mut()) {
+                (Ok(a), _) => a,
+                (Err(e), Some(failures)) if !e.is_catastrophic() => {
+                    failures.push(self.failed_tmp_asset(&path, e));
+                    continue;
+                }
+                (Err(e), _) => return Err(e),
+            };
+            warnings.extend(self.entry_warnings(&path, &asset));
+            if asset.is_folder_with_payload() {
+                warnings.push(Warning::FolderAssetWithPayload {
+                    guid: asset.get_guid(),
+                    path: asset.get_relative_asset_path().clone(),
+                });
+
+                if self.config.folder_payload == FolderPayloadResolution::InstallAsFile {
+                    asset.install_payload_as_file();
+                }
+            }
+
+            result.push(asset);
+        }
+
+        Ok((result, warnings))
+    }
+
+    /// Index a single guid directory of the tmp directory.
+    fn read_tmp_asset(
+        &self,
+        path: &Path,
+        installed: Option<&Path>,
+    ) -> Result<UnityAssetFile, UnityPackageReaderError> {
+        let asset = match installed {
+            Some(target) if unity_asset_file::meta_file(path, self.file_system()).is_none() => {
+                match UnityAssetFile::from_installed(
+                    path.to_path_buf(),
+                    target,
+                    &self.config.meta_naming,
+                    self.file_system(),
+                )? {
+                    Some(a) => a,
+                    None if self.config.lenient => UnityAssetFile::from_file_system_without_meta(
+                        path.to_path_buf(),
+                        self.file_system(),
+                    )?,
+                    None => {
+                        return Err(UnityPackageReaderError::InvalidTmpDirectory(
+                            ErrorInformation::new(
+                                Some(format!(
+                                    "{:?} has no asset.meta and has not been installed",
+                                    path
+                                )),
+                                file!(),
+                                line!(),
+                            ),
+                        ))
+                    }
+                }
+            }
+            _ if self.config.lenient
+                && unity_asset_file::meta_file(path, self.file_system()).is_none() =>
+            {
+                UnityAssetFile::from_file_system_without_meta(
+                    path.to_path_buf(),
+                    self.file_system(),
+                )?
+            }
+            _ => UnityAssetFile::from_file_system(path.to_path_buf(), self.file_system())?,
+        };
+        if self.config.strict_pathnames && asset.has_backslash_pathname() {
+            return Err(UnityPackageReaderError::PathError(ErrorInformation::new(
+                Some(format!(
+                    "The pathname of {} uses backslashes: '{}'",
+                    asset.get_guid(),
+                    asset.get_relative_asset_path().display()
+                )),
+                file!(),
+                line!(),
+            )));
+        }
+
+        Ok(asset)
+    }
+
+    /// `UnpackConfig::lenient`: the warning for a guid directory without a
+    /// pathname, which is skipped. None if it is indexed as usual.
+    fn missing_pathname(&self, path: &Path) -> Option<Warning> {
+        if !self.config.lenient || self.file_system.exists(&path.join("pathname")) {
+            return None;
+        }
+
+        let directory = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut entries: Vec<String> = self
+            .file_system
+            .read_dir(path)
+            .unwrap_or_default()
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .collect();
+        entries.sort();
+
+        Some(Warning::MissingPathname { directory, entries })
+    }
+
+    /// What is odd about a guid directory that could be indexed: a missing
+    /// meta, extra lines in the pathname, unknown files and a preview that is
+    /// no PNG.
+    fn entry_warnings(&self, path: &Path, asset: &UnityAssetFile) -> Vec<Warning> {
+        let guid = asset.get_guid();
+        let relative = asset.get_relative_asset_path();
+        let mut warnings = Vec::new();
+        if !asset.has_meta() {
+            warnings.push(Warning::MissingMeta {
+                guid,
+                path: relative.clone(),
+            });
+        }
+        if asset.has_unknown_pathname_lines() {
+            warnings.push(Warning::PathnameExtraLines {
+                guid,
+                path: relative.clone(),
+                lines: asset.get_pathname_extra_lines().to_vec(),
+            });
+        }
+
+        let entries = self.file_system.read_dir(path).unwrap_or_default();
+        for name in entries
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.file_name().map(|n| n.to_string_lossy().into_owned()))
+        {
+            if !ASSET_ENTRIES.contains(&name.as_str()) {
+                warnings.push(Warning::UnknownEntry {
+                    guid,
+                    path: relative.clone(),
+                    name,
+                });
+            }
+        }
+
+        let preview = path.join("preview.png");
+        if self.file_system.exists(&preview) {
+            let reason = match self.file_system.read_prefix(&preview, PNG_SIGNATURE.len()) {
+                Ok(c) if c == PNG_SIGNATURE => None,
+                Ok(_) => Some(String::from("no PNG signature")),
+                Err(e) => Some(format!("{}", e)),
+            };
+            if let Some(reason) = reason {
+                warnings.push(Warning::UnreadablePreview {
+                    guid,
+                    path: relative.clone(),
+                    reason,
+                });
+            }
+        }
+
+        warnings
+    }
+
+    /// The failure of a guid directory that could not be indexed. The path is
+    /// the one of its pathname file if that can be read, otherwise the
+    /// directory inside tmp.
+    fn failed_tmp_asset(&self, path: &Path, error: UnityPackageReaderError) -> AssetFailure {
+        let guid = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.parse::<Guid>().ok())
+            .unwrap_or_default();
+        let relative = match self.file_system.read_to_string(&path.join("pathname")) {
+            Ok(p) if !unity_asset_file::trim_pathname(&p).is_empty() => PathBuf::from(
+                unity_asset_file::normalize_pathname(unity_asset_file::trim_pathname(&p)),
+            ),
+            _ => path.to_path_buf(),
+        };
+
+        AssetFailure {
+            guid,
+            path: relative,
+            error,
+        }
+    }
+
+    /// Extract the package into the tmp directory and index it without writing
+    /// anything to the target. The tmp directory is kept, so the absolute paths
+    /// of the indexed assets stay valid.
+    pub fn analyze_package(&mut self) -> Result<(), UnityPackageReaderError> {
+        let install = self.config.install;
+        self.config.install = false;
+        let result = self.unpack_package(false);
+        self.config.install = install;
+
+        result
+    }
+
+    /// Open the package file for streaming, read through a buffer of
+    /// `BufferSizes::read` bytes or mapped (see `with_memory_map`).
+    pub(crate) fn open_package_file(&self) -> Result<PackageSource, UnityPackageReaderError> {
+        #[cfg(feature = "mmap")]
+        let memory_map = self.memory_map;
+        #[cfg(not(feature = "mmap"))]
+        let memory_map = false;
+        match &self.bytes {
+            Some(b) => Ok(PackageSource::Memory(std::io::Cursor::new(b.clone()))),
+            None => PackageSource::open(&self.path, self.buffer_sizes.read, memory_map),
+        }
+    }
+
+    /// Read every asset of the package into memory (see `MemoryAsset`),
+    /// sorted by relative path. Nothing is written, so this also works on
+    /// targets without a file system. `UnpackConfig::limits` applies.
+    pub fn unpack_to_memory(&self) -> Result<Vec<MemoryAsset>, UnityPackageReaderError> {
+        memory_unpack::unpack_to_memory(self.open_package_file()?, self.config.limits)
+    }
+
+    /// Stream through the package without extracting anything and call f for
+    /// every asset. Returning `ControlFlow::Break` stops decompressing right away,
+    /// which makes queries like "find the first scene" cheap.
+    pub fn visit_entries(
+        &self,
+        f: impl FnMut(&EntrySummary) -> ControlFlow<()>,
+    ) -> Result<(), UnityPackageReaderError> {
+        package_listing::visit_entries(self.open_package_file()?, f)
+    }
+
+    /// Write the `preview.png` of every asset that has one to `<dir>/<guid>.png`,
+    /// without extracting the assets themselves. Returns the number of previews
+    /// written. `EntrySummary::has_preview` tells which assets have one.
+    /// `UnpackConfig::limits` applies.
+    pub fn extract_previews_to(&self, dir: &Path) -> Result<usize, UnityPackageReaderError> {
+        file_system::require_file_system("Extracting previews")?;
+        package_listing::extract_previews(self.open_package_file()?, dir, self.config.limits)
+    }
+
+    /// Write only the payload of the asset guid to destination, e.g. a single
+    /// texture of a large package. Nothing else is extracted and reading stops
+    /// once the asset is complete. With with_meta, the meta file is written
+    /// next to destination, named according to `UnpackConfig::meta_naming`.
+    /// Fails with `AssetNotFound` if the package does not contain guid. A file
+    /// at destination is only replaced once the asset has been written
+    /// completely, a failure leaves it untouched.
+    pub fn extract_asset(
+        &self,
+        guid: &str,
+        destination: &Path,
+        with_meta: bool,
+    ) -> Result<ExtractedAsset, UnityPackageReaderError> {
+        file_system::require_file_system("Extracting an asset to a file")?;
+        let guid: Guid = guid.parse()?;
+        self.config.meta_naming.check()?;
+        let suffix = with_meta.then(|| self.config.meta_naming.suffix());
+
+        single_asset::extract_asset(self.open_package_file()?, guid, destination, suffix)
+    }
+
+    /// Copy the payload of the asset guid into writer without touching the
+    /// disk, e.g. to serve it over the network. The archive is only read up
+    /// to the end of the payload. Returns the number of bytes written.
+    pub fn extract_asset_to_writer(
+        &self,
+        guid: &str,
+        writer: &mut impl Write,
+    ) -> Result<u64, UnityPackageReaderError> {
+        let guid: Guid = guid.parse()?;
+        single_asset::copy_asset(self.open_package_file()?, guid, writer)
+    }
+
+    /// Write a new package that only contains the assets whose relative path
+    /// passes filter. Guid directories are copied as they are (pathname, meta,
+    /// payload and preview). Folder assets are dropped if filtering removed
+    /// everything below them. output_path is only replaced once the new
+    /// package is complete. Returns the number of assets written.
+    pub fn repack(
+        &self,
+        filter: impl Fn(&Path) -> bool,
+        output_path: &Path,
+    ) -> Result<usize, UnityPackageReaderError> {
+        file_system::require_file_system("Writing a package")?;
+        let entries = self.list_entries()?;
+
+        let mut occupied = HashSet::new();
+        let mut still_occupied = HashSet::new();
+        for entry in entries.iter().filter(|e| !e.is_folder) {
+            let kept = filter(&entry.relative_path);
+            for ancestor in entry.relative_path.ancestors().skip(1) {
+                occupied.insert(ancestor.to_path_buf());
+                if kept {
+                    still_occupied.insert(ancestor.to_path_buf());
+                }
+            }
+        }
+
+        let keep: HashSet<String> = entries
+            .iter()
+            .filter(|e| filter(&e.relative_path))
+            .filter(|e| {
+                !e.is_folder
+                    || still_occupied.contains(&e.relative_path)
+                    || !occupied.contains(&e.relative_path)
+            })
+            .map(|e| e.guid.clone())
+            .collect();
+
+        let source = fs::canonicalize(&self.path).ok();
+        if source.is_some() && source == fs::canonicalize(output_path).ok() {
+            return Err(UnityPackageReaderError::CouldNotWritePackage(
+                ErrorInformation::new(
+                    Some(format!("{:?} is the package being read", output_path)),
+                    file!(),
+                    line!(),
+                ),
+            ));
+        }
+
+        // Written next to output_path and renamed once complete, a failure
+        // leaves no partial package behind.
+        let file = match AtomicFile::create(output_path) {
+            Ok(f) => f,
+            Err(e) => {
+                return Err(UnityPackageReaderError::CouldNotWritePackage(
+                    ErrorInformation::new(
+                        Some(format!("{:?}: {}", output_path, e)),
+                        file!(),
+                        line!(),
+                    ),
+                ));
+            }
+        };
+
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        package_listing::copy_entries(self.open_package_file()?, &keep, &mut builder)?;
+
+        let finished = builder
+            .into_inner()
+            .and_then(|encoder| encoder.finish())
+            .and_then(|file| file.commit());
+        match finished {
+            Ok(_) => Ok(keep.len()),
+            Err(e) => Err(UnityPackageReaderError::CouldNotWritePackage(
+                ErrorInformation::new(Some(format!("{:?}: {}", output_path, e)), file!(), line!()),
+            )),
+        }
+    }
+
+    /// A UPM `package.json` for the package as it would be installed with the
+    /// current configuration: the files below the package root (see
+    /// `UpmManifestOptions::write`) after `strip_prefix` and `root_remap`.
+    /// Read from the archive without extracting it. With
+    /// `UpmManifestOptions::write` the manifest is also written into the
+    /// package root in the target.
+    pub fn generate_upm_manifest(
+        &self,
+        options: &UpmManifestOptions,
+    ) -> Result<String, UnityPackageReaderError> {
+        let root = self
+            .config
+            .root_remap
+            .first()
+            .map(|r| PathBuf::from(&r.to))
+            .unwrap_or_default();
+
+        let mut files = Vec::new();
+        for entry in self.list_entries()?.into_iter().filter(|e| !e.is_folder) {
+            let path = match self.installed_path(&entry.relative_path)? {
+                Some(p) => p,
+                None => continue,
+            };
+            match path.strip_prefix(&root) {
+                Ok(p) if !p.as_os_str().is_empty() => files.push(p.to_path_buf()),
+                _ => {}
+            }
+        }
+        files.sort();
+
+        let json = upm_manifest::build_manifest(&self.get_package_file_name()?, options, &files)?;
+        if !options.write {
+            return Ok(json);
+        }
+
+        let directory = self.get_target_dir()?.join(&root);
+        match fs::create_dir_all(&directory)
+            .and_then(|_| fs::write(directory.join(UPM_MANIFEST_FILE), &json))
+        {
+            Ok(_) => Ok(json),
+            Err(e) => Err(UnityPackageReaderError::CouldNotWriteReport(
+                ErrorInformation::new(Some(format!("{:?}: {}", directory, e)), file!(), line!()),
+            )),
+        }
+    }
+
+    /// Write the assets of the package into a zip or tar file at path, under
+    /// the paths they would be installed at (see `generate_upm_manifest`)
+    /// instead of their guid directories. With include_metas every asset is
+    /// followed by its meta, named per `UnpackConfig::meta_naming`. The
+    /// package is streamed, only the files of one guid directory at a time
+    /// are spooled to the system temp directory. `UnpackConfig::limits`
+    /// applies. An existing file at path is only replaced by a complete
+    /// archive. Zip files need the `zip` feature.
+    pub fn convert_to(
+        &self,
+        path: &Path,
+        format: ArchiveFormat,
+        include_metas: bool,
+    ) -> Result<(), UnityPackageReaderError> {
+        self.config.meta_naming.check()?;
+        let meta_suffix = include_metas.then(|| self.config.meta_naming.suffix());
+
+        archive_convert::convert(
+            self.open_package_file()?,
+            path,
+            format,
+            meta_suffix,
+            self.config.limits,
+            |p| self.installed_path(p),
+        )
+    }
+
+    /// The path relative to the target an asset at path ends up at, None if
+    /// it is left out. Follows `begin_install` as far as a single path
+    /// allows: `configured_path`, then `UnpackConfig::reserved_names` and
+    /// the check that the path stays inside the target. Case collisions and
+    /// `flatten` need every path of the package and are not applied.
+    fn installed_path(&self, path: &Path) -> Result<Option<PathBuf>, UnityPackageReaderError> {
+        let path = match self.configured_path(path)? {
+            Some((p, _)) => p,
+            None => return Ok(None),
+        };
+
+        let path = match (
+            self.config.reserved_names,
+            reserved_names::sanitize_path(&path),
+        ) {
+            (ReservedNames::Error, Some(_)) => {
+                return Err(UnityPackageReaderError::ReservedName(
+                    ErrorInformation::new(Some(format!("'{}'", path.display())), file!(), line!()),
+                ));
+            }
+            (ReservedNames::RenameWithSuffix, Some(renamed)) => renamed,
+            _ => path,
+        };
+
+        if !is_inside_target(&path) {
+            return Err(UnityPackageReaderError::PathError(ErrorInformation::new(
+                Some(format!("Not inside the target: '{}'", path.display())),
+                file!(),
+                line!(),
+            )));
+        }
+
+        Ok(Some(path))
+    }
+
+    /// An inventory of the package (see `PackageManifest`), read from the
+    /// archive without extracting it.
+    pub fn manifest(&self) -> Result<PackageManifest, UnityPackageReaderError> {
+        let path = self.path.as_path();
+        let package_size = match &self.bytes {
+            Some(b) => b.len() as u64,
+            None => match fs::metadata(path) {
+                Ok(m) => m.len(),
+                Err(e) => {
+                    return Err(UnityPackageReaderError::PackageNotFound(
+                        ErrorInformation::new(Some(format!("{:?}: {}", path, e)), file!(), line!()),
+                    ));
+                }
+            },
+        };
+
+        let source_file = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        Ok(PackageManifest::new(
+            source_file,
+            package_size,
+            self.list_entries()?,
+        ))
+    }
+
+    /// Write `manifest()` as pretty printed JSON.
+    #[cfg(feature = "serde")]
+    pub fn write_manifest_json(&self, path: &Path) -> Result<(), UnityPackageReaderError> {
+        file_system::require_file_system("Writing a manifest")?;
+        let manifest = self.manifest()?;
+        let written = fs::File::create(path)
+            .map_err(serde_json::Error::io)
+            .and_then(|f| {
+                let mut writer = std::io::BufWriter::new(f);
+                serde_json::to_writer_pretty(&mut writer, &manifest)?;
+                writer.flush().map_err(serde_json::Error::io)
+            });
+
+        match written {
+            Ok(_) => Ok(()),
+            Err(e) => Err(UnityPackageReaderError::CouldNotWriteReport(
+                ErrorInformation::new(Some(format!("{:?}: {}", path, e)), file!(), line!()),
+            )),
+        }
+    }
+
+    /// Write `manifest()` as CSV, see `PackageManifest::to_csv`.
+    pub fn write_manifest_csv(
+        &self,
+        path: &Path,
+        include_folders: bool,
+    ) -> Result<(), UnityPackageReaderError> {
+        file_system::require_file_system("Writing a manifest")?;
+        let csv = self.manifest()?.to_csv(include_folders);
+        match fs::write(path, csv) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(UnityPackageReaderError::CouldNotWriteReport(
+                ErrorInformation::new(Some(format!("{:?}: {}", path, e)), file!(), line!()),
+            )),
+        }
+    }
+
+    /// Every guid of the package and its pathname, in both directions, read
+    /// from the archive without extracting it. Folders are included.
+    pub fn guid_map(&self) -> Result<GuidMap, UnityPackageReaderError> {
+        let mut entries = Vec::new();
+        for entry in self.list_entries()? {
+            entries.push((entry.guid.parse::<Guid>()?, entry.relative_path));
+        }
+
+        Ok(GuidMap::new(entries))
+    }
+
+    /// Same as `guid_map`, but for the assets indexed by the last unpack,
+    /// with the paths they have (or would have) in the target.
+    pub fn indexed_guid_map(&self) -> GuidMap {
+        GuidMap::new(
+            self.files()
+                .map(|f| (f.get_guid(), f.get_relative_asset_path().clone())),
+        )
+    }
+
+    /// Compare this (older) package with other (newer) by guid: added, removed
+    /// and moved assets, and assets whose payload changed. The payloads are
+    /// hashed while reading the archives, nothing is extracted. Fails with
+    /// `InvalidGuid` if a directory of either archive is not named by a guid.
+    pub fn diff(&self, other: &UnityPackage) -> Result<PackageDiff, UnityPackageReaderError> {
+        let old = package_listing::hash_entries(self.open_package_file()?)?;
+        let new = package_listing::hash_entries(other.open_package_file()?)?;
+
+        package_diff::build_diff(old, new)
+    }
+
+    /// Check the structure of the package without extracting it: guid
+    /// directory names, missing `pathname` or `asset.meta` files, unreadable
+    /// metas, pathnames used by more than one asset and truncated entries.
+    /// All findings are collected; only a file that is no package is an error.
+    pub fn validate(&self) -> Result<ValidationReport, UnityPackageReaderError> {
+        validation::validate(self.open_package_file()?)
+    }
+
+    /// Classify every guid directory of the archive by whether it contains a
+    /// payload and a meta, ordered by name. Same as the `guid_folders` of
+    /// `validate`, use `GuidFolder::is_orphan` to find the broken ones.
+    pub fn orphans(&self) -> Result<Vec<GuidFolder>, UnityPackageReaderError> {
+        Ok(self.validate()?.guid_folders)
+    }
+
+    /// Stream through the package and collect the guids each text serialized
+    /// asset references (`guid: ...` in its YAML). Nothing is extracted.
+    pub fn scan_dependencies(&self) -> Result<DependencyGraph, UnityPackageReaderError> {
+        dependencies::scan_dependencies(self.open_package_file()?)
+    }
+
+    /// The assembly definitions, assembly definition references, scripts and
+    /// native plugins of the package, with the platform settings of the
+    /// plugins' `PluginImporter`. Only pathnames and metas are read, nothing
+    /// is extracted. `UnpackConfig::limits` applies.
+    pub fn code_report(&self) -> Result<CodeReport, UnityPackageReaderError> {
+        code_report::scan_code(self.open_package_file()?, self.config.limits)
+    }
+
+    /// The guids the assets of the package reference, but which neither the
+    /// package nor known contains. References to Unity's built-in resources are
+    /// reported apart from the others.
+    pub fn missing_dependencies(
+        &self,
+        known: &HashSet<Guid>,
+    ) -> Result<MissingDependencies, UnityPackageReaderError> {
+        Ok(self.scan_dependencies()?.missing(known))
+    }
+
+    /// All assets of the package, without extracting it.
+    pub fn list_entries(&self) -> Result<Vec<EntrySummary>, UnityPackageReaderError> {
+        let mut result = Vec::new();
+        self.visit_entries(|e| {
+            result.push(e.clone());
+            ControlFlow::Continue(())
+        })?;
+
+        Ok(result)
+    }
+
+    /// The entries of the archive whose pathname matches the regular
+    /// expression pattern, in archive order, without extracting anything.
+    /// The pattern is compiled before the package is opened.
+    pub fn find_entries(
+        &self,
+        pattern: &str,
+        ignore_case: bool,
+    ) -> Result<Vec<EntrySummary>, UnityPackageReaderError> {
+        let search = PathSearch::new(pattern, ignore_case)?;
+        let mut result = Vec::new();
+        self.visit_entries(|e| {
+            if search.matches(&e.relative_path) {
+                result.push(e.clone());
+            }
+            ControlFlow::Continue(())
+        })?;
+
+        Ok(result)
+    }
+
+    /// The uncompressed size of the package by kind of payload, read from the
+    /// tar headers without writing anything to disk. Entries that are not
+    /// part of a guid directory are counted as `SizeEstimate::other`.
+    pub fn estimated_size(&self) -> Result<SizeEstimate, UnityPackageReaderError> {
+        size_estimate::estimate_size(self.open_package_file()?)
+    }
+
+    /// The number of assets in the package, without extracting it.
+    pub fn count_entries(&self) -> Result<usize, UnityPackageReaderError> {
+        let mut result = 0;
+        self.visit_entries(|_| {
+            result += 1;
+            ControlFlow::Continue(())
+        })?;
+
+        Ok(result)
+    }
+
+    /// Describe what `unpack_package` would do without writing anything to the
+    /// target directory. The package is decompressed into the tmp directory,
+    /// which is removed afterwards regardless of the outcome.
+    pub fn plan_unpack(&self) -> Result<Vec<PlannedOperation>, UnityPackageReaderError> {
+        self.with_extracted_tmp(|tmp, archive_order| self.plan_from_tmp(tmp, archive_order))
+    }
+
+    /// Check whether the package fits the Unity project at project_root: the
+    /// estimated exporter version, assembly definition references that the
+    /// package and the project do not define, and native plugins that are not
+    /// enabled for any platform the project builds for. Nothing is written to
+    /// the target or the project; the tmp directory is removed afterwards.
+    pub fn compatibility_report(
+        &self,
+        project_root: &Path,
+    ) -> Result<CompatibilityReport, UnityPackageReaderError> {
+        let project = ProjectInfo::read(project_root)?;
+
+        self.with_extracted_tmp(|tmp, _| {
+            let (assets, _) = self.read_tmp_assets(tmp, None, None)?;
+            Ok(compatibility::build_report(&project, &assets))
+        })
+    }
+
+    /// Compare the package with an installed tree: assets whose content or
+    /// only whose meta file differs, assets the target has under another path
+    /// (same guid), and assets that only exist on one side. Assets are found
+    /// in the target through their meta files (see `UnpackConfig::meta_naming`).
+    /// Nothing is written to the target; the tmp directory is removed afterwards.
+    pub fn diff_against_target(
+        &self,
+        target: &Path,
+    ) -> Result<TargetDiff, UnityPackageReaderError> {
+        self.diff_target(target, |diff, _| Ok(diff))
+    }
+
+    /// Classify every asset of the package the configuration installs against
+    /// target: missing, identical, modified or only its meta file present
+    /// (see `TargetStatus`). Built on `diff_against_target`, so the package
+    /// is extracted to the tmp directory, which is removed afterwards.
+    /// Nothing is written to the target.
+    pub fn compare_with_target(
+        &self,
+        target: &Path,
+    ) -> Result<Vec<AssetComparison>, UnityPackageReaderError> {
+        file_system::require_file_system("Comparing with a target directory")?;
+        self.config.meta_naming.check()?;
+
+        self.diff_target(target, |diff, assets| {
+            target_diff::compare_assets(target, diff, assets, &self.config.meta_naming)
+        })
+    }
+
+    /// Diff the assets the configuration installs against target and pass
+    /// the diff and the compared assets (sorted by relative path) to f
+    /// while the tmp directory still exists.
+    fn diff_target<T>(
+        &self,
+        target: &Path,
+        f: impl FnOnce(TargetDiff, &[UnityAssetFile]) -> Result<T, UnityPackageReaderError>,
+    ) -> Result<T, UnityPackageReaderError> {
+        let tree = TargetTree::read(target, &self.config, &self.get_tmp_dir()?)?;
+
+        self.with_extracted_tmp(|tmp, _| {
+            let (assets, _) = self.read_tmp_assets(tmp, None, None)?;
+            let mut compared: Vec<UnityAssetFile> = assets
+                .into_iter()
+                .filter(|a| {
+                    (self.config.installs_folders() || !a.is_folder())
+                        && self.asset_filter().keep(a)
+                })
+                .collect();
+            compared.sort_by(|a, b| a.get_relative_asset_path().cmp(b.get_relative_asset_path()));
+            check_paths_inside_target(&compared)?;
+            let diff = target_diff::build_diff(target, tree, &compared, &self.config)?;
+            f(diff, &compared)
+        })
+    }
+
+    /// Decompress the package into the tmp directory, run f on it and the
+    /// guids in archive order and remove the tmp directory again, regardless
+    /// of the outcome.
+    fn with_extracted_tmp<T>(
+        &self,
+        f: impl FnOnce(&Path, &[Guid]) -> Result<T, UnityPackageReaderError>,
+    ) -> Result<T, UnityPackageReaderError> {
+        // The guard cleans up after a failed extraction or a panic of f.
+        let (tmp, archive_order) = self.extract_to_tmp()?;
+        let result = f(tmp.path(), &archive_order);
+
+        match self.file_system.remove_dir_all(&tmp.keep()) {
+            Ok(_) => result,
+            Err(e) => {
+                if result.is_ok() {
+                    return Err(UnityPackageReaderError::CouldNotDeleteTmp(
+                        ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                    ));
+                }
+                result
+            }
+        }
+    }
+
+    /// Additive mode: the guids the target contains already. Empty otherwise.
+    fn scan_present(
+        &self,
+        target: &Path,
+        tmp: &Path,
+    ) -> Result<HashMap<Guid, PathBuf>, UnityPackageReaderError> {
+        if !self.config.additive {
+            return Ok(HashMap::new());
+        }
+
+        Ok(TargetTree::read(target, &self.config, tmp)?.guids())
+    }
+
+    /// The plan of `plan_unpack`, sorted by the relative paths the assets
+    /// are installed at. The paths are resolved in install order like
+    /// `begin_install` does.
+    fn plan_from_tmp(
+        &self,
+        tmp: &Path,
+        archive_order: &[Guid],
+    ) -> Result<Vec<PlannedOperation>, UnityPackageReaderError> {
+        let target = self.get_target_dir()?;
+        let present = self.scan_present(&target, tmp)?;
+
+        let (assets, _) = self.read_tmp_assets(tmp, None, None)?;
+        let resolved = self.resolve_paths(assets, archive_order)?;
+        let mut installed: Vec<&UnityAssetFile> = resolved
+            .assets
+            .iter()
+            .filter(|a| (self.config.installs_folders() || !a.is_folder()) && resolved.includes(a))
+            .collect();
+        installed.sort_by(|a, b| a.get_relative_asset_path().cmp(b.get_relative_asset_path()));
+
+        let mut created_directories = HashSet::new();
+        let mut result = Vec::new();
+        for asset in installed {
+            let (destination, meta) = asset.get_target_paths(&target, &self.config.meta_naming)?;
+            let exists = (!asset.is_folder() && destination.exists()) || meta.exists();
+
+            let action = match (exists, self.config.overwrite_policy) {
+                _ if present.contains_key(&asset.get_guid()) => PlannedAction::AlreadyPresent,
+                (false, _) | (true, OverwritePolicy::Overwrite) => PlannedAction::Install,
+                (true, OverwritePolicy::Skip) => PlannedAction::Skip,
+                (true, OverwritePolicy::Error) => PlannedAction::Fail,
+                (true, OverwritePolicy::IfChanged) => {
+                    if asset.is_identical_in(&target, &self.config, self.file_system())? {
+                        PlannedAction::Skip
+                    } else {
+                        PlannedAction::Install
+                    }
+                }
+            };
+
+            let directory = if asset.is_folder() {
+                Some(destination.as_path())
+            } else {
+                destination.parent()
+            };
+
+            let creates_directory = match directory {
+                Some(directory) => {
+                    action == PlannedAction::Install
+                        && !directory.exists()
+                        && created_directories.insert(directory.to_path_buf())
+                }
+                None => false,
+            };
+
+            result.push(PlannedOperation {
+                guid: asset.get_guid(),
+                relative_path: asset.get_relative_asset_path().clone(),
+                overwrites_existing: exists && action == PlannedAction::Install,
+                destination,
+                creates_directory,
+                action,
+            });
+        }
+
+        Ok(result)
+    }
+
+    /// Write a `sha256sum` compatible file listing every installed asset and
+    /// its meta file (`<hex>  <relative path>`, forward slashes, sorted by path).
+    /// Must be called after the package has been unpacked.
+    pub fn write_checksums(&self, path: &Path) -> Result<(), UnityPackageReaderError> {
+        let target = self.get_target_dir()?;
+
+        let files = self
+            .files()
+            .filter(|f| !f.is_skipped() && (self.config.installs_folders() || !f.is_folder()));
+
+        let mut content = String::new();
+        for file in files {
+            let asset = checksums::to_forward_slashes(file.get_relative_asset_path());
+            let meta = format!("{}{}", asset, self.config.meta_naming.suffix());
+
+            let mut installed = Vec::new();
+            if !file.is_folder() {
+                installed.push(asset);
+            }
+            if self.config.installs_metas() && file.has_meta() {
+                installed.push(meta);
+            }
+
+            for relative in installed {
+                let hash = match checksums::sha256_file(&target.join(&relative)) {
+                    Ok(h) => h,
+                    Err(e) => {
+                        return Err(UnityPackageReaderError::ChecksumFileError(
+                            ErrorInformation::new(
+                                Some(format!("{}: {}", relative, e)),
+                                file!(),
+                                line!(),
+                            ),
+                        ));
+                    }
+                };
+
+                content.push_str(&checksums::format_checksum_line(
+                    &checksums::to_hex(&hash),
+                    &relative,
+                ));
+            }
+        }
+
+        let written = fs::File::create(path).and_then(|mut f| f.write_all(content.as_bytes()));
+        match written {
+            Ok(_) => Ok(()),
+            Err(e) => Err(UnityPackageReaderError::ChecksumFileError(
+                ErrorInformation::new(Some(format!("{:?}: {}", path, e)), file!(), line!()),
+            )),
+        }
+    }
+
+    /// Validate an installed tree against a `sha256sum` style checksum file,
+    /// e.g. one written by `write_checksums`.
+    pub fn verify_checksums(
+        target: &Path,
+        checksum_file: &Path,
+    ) -> Result<VerifyReport, UnityPackageReaderError> {
+        file_system::require_file_system("Verifying checksums")?;
+        checksums::verify_checksums(target, checksum_file)
+    }
+}
+
+/// True, if path is relative, not empty and does not leave the directory it
+/// is joined onto.
+pub(crate) fn is_inside_target(path: &Path) -> bool {
+    path.components().any(|c| matches!(c, Component::Normal(_)))
+        && path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// Fail with `PathError` if an asset to install would end up outside the
+/// target, e.g. for a pathname like `..\..\evil.txt`. Runs on the final
+/// paths, after every step that changes them.
+fn check_paths_inside_target<'a>(
+    assets: impl IntoIterator<Item = &'a UnityAssetFile>,
+) -> Result<(), UnityPackageReaderError> {
+    let outside: Vec<String> = assets
+        .into_iter()
+        .filter(|a| !is_inside_target(a.get_relative_asset_path()))
+        .map(|a| format!("'{}'", a.get_relative_asset_path().display()))
+        .collect();
+    if outside.is_empty() {
+        return Ok(());
+    }
+
+    Err(UnityPackageReaderError::PathError(ErrorInformation::new(
+        Some(format!("Not inside the target: {}", outside.join(", "))),
+        file!(),
+        line!(),
+    )))
+}
+
+/// path with `_<n>` appended to the file stem, e.g. `icon_1.png`.
+fn with_suffix(path: &Path, n: impl std::fmt::Display) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(e) => format!("{}_{}.{}", stem, n, e.to_string_lossy()),
+        None => format!("{}_{}", stem, n),
+    };
+
+    path.with_file_name(name)
+}
+
+/// [package name]-[16 random hex digits].
+pub(crate) fn unique_tmp_name(package: &Path) -> String {
+    let mut random = RandomState::new().build_hasher();
+    random.write_u32(std::process::id());
+    if let Ok(elapsed) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        random.write_u128(elapsed.as_nanos());
+    }
+
+    let name = package
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    format!("{}-{:016x}", name, random.finish())
+}
+
+/// The key of the path index: components joined with forward slashes, with
+/// empty and `.` components dropped.
+pub(crate) fn normalize_path_key(path: &str) -> String {
+    path.split(['/', '\\'])
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_system::MemoryFileSystem;
+    use crate::prelude::{
+        All, Any, AssetCategory, AssetDependencies, AssetStatus, Backup, CaseCollisions, CodeAsset,
+        CompatibilityCheck, DiffEntry, DuplicateGuid, DuplicateGuids, DuplicateResolution,
+        ExporterEstimate, ExtractOrdering, ExtractionObserver, FileModes, Flatten,
+        FlattenCollisions, FlattenMetas, MetaData, MetaFormat, MetaNaming, Not, ReservedNames,
+        RootRemap, Severity, SpecialEntries, StepBudget, StepResult, StripPrefix, TargetStatus,
+        UnpackLimits, UpmManifestOptions, MAX_BUFFER_SIZE,
+    };
+    use crate::test_utils::*;
+    use serial_test::serial;
+    use sha2::Digest;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    fn get_test_base_path() -> PathBuf {
+        let mut r = std::env::current_dir().unwrap();
+        if r.ends_with("unity_unpacker_lib") {
+            r = r.parent().unwrap().to_path_buf();
+        }
+
+        r
+    }
+
+    #[test]
+    fn test_default_tmp_dir() {
+        let p = std::env::temp_dir().join(TMP_SUBFOLDER);
+
+        let mut t2 = std::env::current_dir().unwrap();
+        t2.push("file");
+
+        let item = UnityPackage::from_path("file.unitypackage", None, None).unwrap();
+        let tmp = item.get_tmp_dir().unwrap();
+
+        assert_eq!(tmp.parent(), Some(p.as_path()));
+        assert!(tmp
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("file-"));
+        assert_eq!(tmp, item.get_tmp_dir().unwrap());
+        assert_eq!(item.get_target_dir().unwrap(), t2);
+
+        let other = UnityPackage::from_path("file.unitypackage", None, None).unwrap();
+        assert_ne!(tmp, other.get_tmp_dir().unwrap());
+        assert!(tmp.starts_with(std::env::temp_dir()));
+
+        // An explicit tmp directory is used as it is.
+        let explicit =
+            UnityPackage::from_path("file.unitypackage", None, Some(PathBuf::from("tmp"))).unwrap();
+        assert_eq!(explicit.get_tmp_dir().unwrap(), PathBuf::from("tmp"));
+    }
+
+    #[test]
+    fn test_from_path() {
+        let fixture = Fixture::sample();
+        let as_str = fixture.package.to_str().unwrap();
+        let target = Some(fixture.target.clone());
+
+        for subject in [
+            UnityPackage::from_path(as_str, target.clone(), None).unwrap(),
+            UnityPackage::from_path(fixture.package.as_path(), target.clone(), None).unwrap(),
+            UnityPackage::from_path(fixture.package.clone(), target.clone(), None).unwrap(),
+        ] {
+            assert_eq!(subject.get_package_path(), fixture.package);
+            assert_eq!(subject.get_target_dir().unwrap(), fixture.target);
+        }
+
+        #[allow(deprecated)]
+        let subject = UnityPackage::new(as_str, None, None).unwrap();
+        #[allow(deprecated)]
+        let path = subject.get_path();
+        assert_eq!(path, as_str);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_new_function_with_file_name() {
+        let n = "file_name.unitypackage";
+        let mut p = std::env::current_dir().unwrap();
+        p.push(n);
+
+        let mut t2 = std::env::current_dir().unwrap();
+        t2.push("file_name");
+
+        let package = UnityPackage::new(n, None, None).unwrap();
+
+        assert_eq!(p, package.path);
+        assert_eq!(package.get_target_dir().unwrap(), t2);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_new_function_with_path() {
+        let mut p = std::env::current_dir().unwrap();
+        let parent = match p.parent() {
+            Some(i) => i,
+            None => {
+                panic!("Could not determine path")
+            }
+        };
+
+        p = parent.to_path_buf();
+        p.push("file_name.unitypackage");
+
+        let mut t2 = std::env::current_dir().unwrap();
+        t2.push("file_name");
+
+        let subject = UnityPackage::new(
             p.clone().into_os_string().into_string().unwrap().as_str(),
             None,
             None,
         )
         .unwrap();
-        assert_eq!(p.into_os_string().into_string().unwrap(), subject.path);
-        assert_eq!(subject.get_target_dir().unwrap(), t2);
+        assert_eq!(p, subject.path);
+        assert_eq!(subject.get_target_dir().unwrap(), t2);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_new_function_with_tmp_path() {
+        let p = String::from("./test/test/test");
+        let mut t2 = std::env::current_dir().unwrap();
+        t2.push("test");
+
+        let subject = UnityPackage::new("test.unitypackage", None, Some(p.clone())).unwrap();
+
+        assert_eq!(subject.get_tmp_dir().unwrap(), PathBuf::from(p));
+        assert_eq!(subject.get_target_dir().unwrap(), t2);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_new_function_with_target_path() {
+        let path = std::env::current_dir().unwrap();
+        let mut origin = path.clone();
+        origin.push("origin/file.unitypackage");
+
+        let mut target = path.clone();
+        target.push("target");
+
+        let t = target.clone().into_os_string().into_string().unwrap();
+        let o = origin.clone().into_os_string().into_string().unwrap();
+
+        let subject = UnityPackage::new(&o, Some(t), None).unwrap();
+
+        assert_eq!(subject.get_target_dir().unwrap(), target);
+        assert_eq!(subject.get_package_file_name().unwrap(), "file");
+        assert_eq!(
+            subject.get_path(),
+            origin.into_os_string().into_string().unwrap()
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_asset_file_internals() {
+        let base = get_test_base_path();
+        println!("{:?}", base);
+        let mut tmp = base.clone();
+        tmp.push("assets/tmp");
+
+        let mut target = base.clone();
+        target.push("assets/target");
+
+        let mut absolute_path = base.clone();
+        absolute_path.push("assets/test.unitypackage");
+
+        let mut subject = match UnityPackage::from_path(
+            &absolute_path,
+            Some(target.clone()),
+            Some(tmp.clone()),
+        ) {
+            Ok(s) => s,
+            Err(_) => panic!("Could not unpack package"),
+        };
+
+        match subject.unpack_package(true) {
+            Ok(e) => e,
+            Err(e) => {
+                panic!("{}", e)
+            }
+        };
+
+        let file = match subject.get_file("1af567ac160bb164fb19b8cb9b55b34b") {
+            Some(f) => f,
+            None => {
+                panic!("The file does not exist in this package.")
+            }
+        };
+
+        let working_dir = get_test_base_path();
+
+        let mut absolute_meta = working_dir.clone();
+        absolute_meta.push("assets/Assets/Textures/Ground/IMGP1287.jpg.unitymeta");
+
+        let mut absolute_target = working_dir.clone();
+        absolute_target.push("assets/Assets/Textures/Ground/IMGP1287.jpg");
+
+        assert_eq!(file.get_guid(), "1af567ac160bb164fb19b8cb9b55b34b");
+
+        assert_eq!(
+            file.get_relative_asset_path().to_str().unwrap(),
+            "Assets/Textures/Ground/IMGP1287.jpg"
+        );
+
+        std::fs::remove_dir_all(target.clone()).unwrap();
+
+        assert!(!target.exists());
+        assert!(!tmp.exists());
+    }
+
+    // Check if tmp directory still exists after unpacking.
+    #[test]
+    #[serial]
+    fn test_deleting_tmp_files() {
+        let base = get_test_base_path();
+        println!("{:?}", base);
+        let mut tmp = base.clone();
+        tmp.push("assets/tmp");
+
+        let mut target = base.clone();
+        target.push("assets/target");
+
+        let mut absolute_path = base.clone();
+        absolute_path.push("assets/test.unitypackage");
+
+        let mut subject =
+            UnityPackage::from_path(&absolute_path, Some(target.clone()), Some(tmp.clone()))
+                .unwrap();
+
+        subject.unpack_package(false).unwrap();
+
+        assert!(tmp.exists());
+
+        // Clean up
+        std::fs::remove_dir_all(target.clone()).unwrap();
+        std::fs::remove_dir_all(tmp.clone()).unwrap();
+
+        assert!(!target.exists());
+        assert!(!tmp.exists());
+    }
+
+    #[test]
+    fn test_write_and_verify_checksums() {
+        let fixture = Fixture::sample();
+        let mut subject = fixture.open();
+        subject.unpack_package(true).unwrap();
+
+        let sums = fixture.dir.path().join("SHA256SUMS");
+        subject.write_checksums(&sums).unwrap();
+
+        let content = std::fs::read_to_string(&sums).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].ends_with("  Assets/Scripts/Player Controller.cs"));
+        assert!(lines[1].ends_with("  Assets/Scripts/Player Controller.cs.unitymeta"));
+        assert!(lines[2].ends_with(&format!("  {}.unitymeta", FOLDER_PATH)));
+        assert!(lines[3].ends_with(&format!("  {}", TEXTURE_PATH)));
+
+        let report = UnityPackage::verify_checksums(&fixture.target, &sums).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.verified.len(), 5);
+
+        std::fs::write(fixture.target.join(SCRIPT_PATH), "changed").unwrap();
+        std::fs::remove_file(fixture.target.join(TEXTURE_PATH)).unwrap();
+
+        let report = UnityPackage::verify_checksums(&fixture.target, &sums).unwrap();
+        assert_eq!(report.modified, vec![PathBuf::from(SCRIPT_PATH)]);
+        assert_eq!(report.missing, vec![PathBuf::from(TEXTURE_PATH)]);
+    }
+
+    #[test]
+    fn test_unpack_with_skip_policy_records_skipped_asset() {
+        let fixture = Fixture::sample();
+        let existing = fixture.target.join(TEXTURE_PATH);
+        std::fs::create_dir_all(existing.parent().unwrap()).unwrap();
+        std::fs::write(&existing, "local change").unwrap();
+
+        let mut subject = fixture.open();
+        subject.set_overwrite_policy(OverwritePolicy::Skip);
+        subject.unpack_package(true).unwrap();
+
+        assert!(subject.get_file(TEXTURE_GUID).unwrap().is_skipped());
+        assert!(!subject.get_file(SCRIPT_GUID).unwrap().is_skipped());
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "local change");
+        assert!(fixture.target.join(SCRIPT_PATH).exists());
+    }
+
+    #[test]
+    fn test_plan_unpack_writes_nothing() {
+        let fixture = Fixture::sample();
+        let subject = fixture.open();
+
+        let plan = subject.plan_unpack().unwrap();
+
+        assert!(!fixture.target.exists());
+        assert!(!fixture.tmp.exists());
+
+        // The folder and the two files, sorted by path.
+        assert_eq!(plan.len(), 3);
+        assert_eq!(plan[0].guid, SCRIPT_GUID);
+        assert_eq!(plan[1].guid, FOLDER_GUID);
+        assert_eq!(plan[2].guid, TEXTURE_GUID);
+        assert_eq!(plan[2].relative_path, PathBuf::from(TEXTURE_PATH));
+        assert_eq!(plan[2].destination, fixture.target.join(TEXTURE_PATH));
+        assert!(plan.iter().all(|p| p.creates_directory));
+        assert!(plan.iter().all(|p| !p.overwrites_existing));
+        assert!(plan.iter().all(|p| p.action == PlannedAction::Install));
+    }
+
+    #[test]
+    fn test_plan_unpack_honors_overwrite_policy() {
+        let fixture = Fixture::sample();
+        let existing = fixture.target.join(TEXTURE_PATH);
+        std::fs::create_dir_all(existing.parent().unwrap()).unwrap();
+        std::fs::write(&existing, "local change").unwrap();
+
+        let mut subject = fixture.open();
+        let plan = subject.plan_unpack().unwrap();
+        assert!(plan[2].overwrites_existing);
+        assert!(!plan[2].creates_directory);
+        assert_eq!(plan[2].action, PlannedAction::Install);
+
+        subject.set_overwrite_policy(OverwritePolicy::Skip);
+        let plan = subject.plan_unpack().unwrap();
+        assert!(!plan[2].overwrites_existing);
+        assert_eq!(plan[2].action, PlannedAction::Skip);
+
+        subject.set_overwrite_policy(OverwritePolicy::Error);
+        let plan = subject.plan_unpack().unwrap();
+        assert_eq!(plan[2].action, PlannedAction::Fail);
+
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "local change");
+        assert!(!fixture.tmp.exists());
+    }
+
+    #[test]
+    fn test_plan_unpack_uses_install_paths() {
+        let first = "1af567ac160bb164fb19b8cb9b55b34b";
+        let second = "2bf567ac160bb164fb19b8cb9b55b34b";
+        let builder = PackageBuilder::new()
+            .asset(first, "Assets/A/x.txt", b"a", &script_meta(first))
+            .asset(second, "Assets/B/x.txt", b"b", &script_meta(second));
+        let fixture = Fixture::new(&builder);
+        let config = UnpackConfig {
+            strip_prefix: Some(StripPrefix::Literal(String::from("Assets"))),
+            flatten: Some(Flatten::default()),
+            ..Default::default()
+        };
+        let mut subject = fixture.open().with_config(config);
+
+        let plan = subject.plan_unpack().unwrap();
+        let planned: Vec<(String, PathBuf)> = plan
+            .iter()
+            .map(|p| (p.guid.to_string(), p.destination.clone()))
+            .collect();
+        assert_eq!(
+            planned,
+            [
+                (first.to_string(), fixture.target.join("x.txt")),
+                (second.to_string(), fixture.target.join("x_1.txt")),
+            ]
+        );
+        assert_eq!(plan[1].relative_path, Path::new("x_1.txt"));
+        assert!(!fixture.target.exists());
+
+        subject.unpack_package(true).unwrap();
+        for operation in &plan {
+            assert!(operation.destination.is_file());
+        }
+        assert_eq!(fs::read(fixture.target.join("x_1.txt")).unwrap(), b"b");
+    }
+
+    #[test]
+    fn test_plan_unpack_cleans_tmp_on_error() {
+        let fixture = Fixture::new(&PackageBuilder::new().file("0123/asset", "no pathname"));
+        let subject = fixture.open();
+
+        assert!(subject.plan_unpack().is_err());
+        assert!(!fixture.tmp.exists());
+    }
+
+    #[test]
+    fn test_list_and_count_entries() {
+        let fixture = Fixture::sample();
+        let subject = fixture.open();
+
+        assert_eq!(subject.count_entries().unwrap(), 3);
+
+        let entries = subject.list_entries().unwrap();
+        assert!(entries
+            .iter()
+            .any(|e| e.guid == TEXTURE_GUID && e.relative_path == Path::new(TEXTURE_PATH)));
+
+        let mut first = None;
+        subject
+            .visit_entries(|e| {
+                first = Some(e.guid.clone());
+                ControlFlow::Break(())
+            })
+            .unwrap();
+        assert!(first.is_some());
+        assert!(!fixture.tmp.exists());
+        assert!(!fixture.target.exists());
+    }
+
+    #[test]
+    fn test_list_entries_missing_package() {
+        let subject = UnityPackage::from_path("/does/not/exist.unitypackage", None, None).unwrap();
+        assert!(matches!(
+            subject.list_entries(),
+            Err(UnityPackageReaderError::PackageNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_overlapping_directories() {
+        let fixture = Fixture::sample();
+        std::fs::create_dir_all(fixture.dir.path().join(".git")).unwrap();
+        let cases = [
+            (
+                fixture.tmp.clone(),
+                fixture.tmp.join("target"),
+                "the target is inside tmp",
+            ),
+            // The same directory, spelled differently.
+            (
+                fixture.target.join("Assets/.."),
+                fixture.target.clone(),
+                "are the same directory",
+            ),
+        ];
+
+        for (tmp, target, problem) in cases {
+            let mut subject =
+                UnityPackage::from_path(&fixture.package, Some(target), Some(tmp)).unwrap();
+            subject.set_config(UnpackConfig {
+                git_aware: true,
+                ..Default::default()
+            });
+            for result in [
+                subject.unpack_package(false),
+                subject.unpack_from_tmp(false),
+            ] {
+                match result {
+                    Err(UnityPackageReaderError::InvalidConfiguration(e)) => {
+                        let message = e.message.unwrap();
+                        assert!(message.contains(problem), "{}", message);
+                        assert!(message.starts_with("tmp '") && message.contains("' and target '"));
+                    }
+                    other => panic!("unexpected result {:?}", other),
+                }
+            }
+            assert!(!fixture.target.exists());
+            assert!(!fixture.tmp.exists());
+        }
+    }
+
+    #[test]
+    fn test_git_aware_writes_gitignore_for_tmp_inside_target() {
+        let fixture = Fixture::sample();
+        std::fs::create_dir_all(fixture.dir.path().join(".git")).unwrap();
+        let tmp = fixture.target.join("tmp");
+
+        let mut subject = UnityPackage::from_path(
+            &fixture.package,
+            Some(fixture.target.clone()),
+            Some(tmp.clone()),
+        )
+        .unwrap()
+        .with_config(UnpackConfig {
+            git_aware: true,
+            ..Default::default()
+        });
+
+        subject.unpack_package(false).unwrap();
+        subject.unpack_package(false).unwrap();
+
+        let gitignore = fixture.target.join(".gitignore");
+        let content = std::fs::read_to_string(&gitignore).unwrap();
+        assert_eq!(content.matches("/tmp/").count(), 1);
+        assert!(!content.contains("Assets"));
+
+        let edit = subject.get_gitignore_edit().unwrap().clone();
+        assert_eq!(edit.path, gitignore);
+        edit.revert().unwrap();
+        assert!(!gitignore.exists());
+    }
+
+    #[test]
+    fn test_git_aware_outside_repository() {
+        let fixture = Fixture::sample();
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            git_aware: true,
+            ..Default::default()
+        });
+
+        subject.unpack_package(false).unwrap();
+        assert!(subject.get_gitignore_edit().is_none());
+        assert!(!fixture.target.join(".gitignore").exists());
+    }
+
+    fn folder_with_payload_package() -> PackageBuilder {
+        sample_package()
+            .file("0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f/asset", "payload")
+            .file(
+                "0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f/asset.meta",
+                folder_meta("0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f"),
+            )
+            .file("0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f/pathname", "Assets/Readme")
+    }
+
+    #[test]
+    fn test_folder_asset_with_payload_installs_file() {
+        let fixture = Fixture::new(&folder_with_payload_package());
+        let mut subject = fixture.open();
+        subject.unpack_package(true).unwrap();
+
+        let installed = fixture.target.join("Assets/Readme");
+        assert_eq!(std::fs::read_to_string(installed).unwrap(), "payload");
+        assert!(!subject
+            .get_file("0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f")
+            .unwrap()
+            .is_folder());
+        assert_eq!(
+            subject.get_warnings(),
+            &[Warning::FolderAssetWithPayload {
+                guid: "0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f".parse().unwrap(),
+                path: PathBuf::from("Assets/Readme"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_folder_asset_with_payload_installs_folder() {
+        let fixture = Fixture::new(&folder_with_payload_package());
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            create_folders: true,
+            folder_payload: FolderPayloadResolution::InstallAsFolder,
+            ..Default::default()
+        });
+        subject.unpack_package(true).unwrap();
+
+        assert!(fixture.target.join("Assets/Readme").is_dir());
+        assert_eq!(subject.get_warnings().len(), 1);
+    }
+
+    #[test]
+    fn test_unpack_stats() {
+        let builder = sample_package();
+        let fixture = Fixture::new(&builder);
+        let mut subject = fixture.open();
+        subject.unpack_package(true).unwrap();
+
+        let stats = *subject.get_stats();
+        let texture_meta = texture_meta(TEXTURE_GUID).len() as u64;
+        let script_meta = script_meta(SCRIPT_GUID).len() as u64;
+        let folder_meta = folder_meta(FOLDER_GUID).len() as u64;
+        let installed = (TEXTURE_CONTENT.len() + SCRIPT_CONTENT.len()) as u64
+            + texture_meta
+            + script_meta
+            + folder_meta;
+        let pathnames = (TEXTURE_PATH.len() + SCRIPT_PATH.len() + FOLDER_PATH.len()) as u64;
+
+        assert_eq!(
+            stats.compressed_bytes_read,
+            std::fs::metadata(&fixture.package).unwrap().len()
+        );
+        assert!(stats.uncompressed_bytes > 0);
+        assert!(stats.uncompressed_bytes <= builder.tar_bytes().len() as u64);
+        assert_eq!(stats.tmp_bytes_written, installed + pathnames);
+        assert_eq!(stats.target_bytes_written, installed);
+        assert_eq!(stats.cleanup_bytes_deleted, pathnames);
+    }
+
+    #[test]
+    fn test_unpack_uncompressed_tar() {
+        let fixture = Fixture::sample();
+        std::fs::write(&fixture.package, sample_package().tar_bytes()).unwrap();
+
+        let mut subject = fixture.open();
+        subject.unpack_package(true).unwrap();
+        assert_eq!(
+            std::fs::read(fixture.target.join(TEXTURE_PATH)).unwrap(),
+            TEXTURE_CONTENT
+        );
+        assert_eq!(subject.list_entries().unwrap().len(), subject.len());
+    }
+
+    #[test]
+    fn test_unknown_package_format() {
+        let fixture = Fixture::sample();
+        std::fs::write(&fixture.package, b"PK\x03\x04 not a package").unwrap();
+
+        match fixture.open().unpack_package(true) {
+            Err(UnityPackageReaderError::CorruptPackage(e)) => {
+                assert!(e.message.unwrap().contains("50 4b 03 04"))
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_diff_against_target() {
+        let fixture = Fixture::sample();
+        fixture.open().unpack_package(true).unwrap();
+        let subject = fixture.open();
+        assert!(subject
+            .diff_against_target(&fixture.target)
+            .unwrap()
+            .is_empty());
+
+        let target = &fixture.target;
+        let moved = PathBuf::from("Assets/Ground.jpg");
+        std::fs::rename(target.join(TEXTURE_PATH), target.join(&moved)).unwrap();
+        std::fs::rename(
+            target.join(format!("{}.unitymeta", TEXTURE_PATH)),
+            target.join("Assets/Ground.jpg.unitymeta"),
+        )
+        .unwrap();
+        std::fs::write(target.join(SCRIPT_PATH), b"changed").unwrap();
+        std::fs::write(target.join("Assets/Extra.txt"), b"extra").unwrap();
+        std::fs::write(
+            target.join("Assets/Extra.txt.unitymeta"),
+            script_meta("44444444444444444444444444444444"),
+        )
+        .unwrap();
+
+        let diff = subject.diff_against_target(target).unwrap();
+        assert_eq!(
+            diff.entries,
+            vec![
+                DiffEntry::ContentChanged {
+                    guid: SCRIPT_GUID.parse().unwrap(),
+                    path: PathBuf::from(SCRIPT_PATH),
+                },
+                DiffEntry::Moved {
+                    guid: TEXTURE_GUID.parse().unwrap(),
+                    from: moved,
+                    to: PathBuf::from(TEXTURE_PATH),
+                },
+                DiffEntry::OnlyInTarget {
+                    guid: "44444444444444444444444444444444".parse().ok(),
+                    path: PathBuf::from("Assets/Extra.txt"),
+                },
+            ]
+        );
+        assert!(!fixture.tmp.exists());
+    }
+
+    #[test]
+    fn test_diff_meta_only_and_missing_target() {
+        let fixture = Fixture::sample();
+        let subject = fixture.open();
+        let diff = subject.diff_against_target(&fixture.target).unwrap();
+        assert_eq!(diff.entries.len(), 3);
+        assert!(diff
+            .entries
+            .iter()
+            .all(|e| matches!(e, DiffEntry::OnlyInPackage { .. })));
+
+        fixture.open().unpack_package(true).unwrap();
+        let meta = fixture.target.join(format!("{}.unitymeta", SCRIPT_PATH));
+        std::fs::write(
+            &meta,
+            format!("{}labels:\n- Edited\n", script_meta(SCRIPT_GUID)),
+        )
+        .unwrap();
+        assert_eq!(
+            subject
+                .diff_against_target(&fixture.target)
+                .unwrap()
+                .entries,
+            vec![DiffEntry::MetaChanged {
+                guid: SCRIPT_GUID.parse().unwrap(),
+                path: PathBuf::from(SCRIPT_PATH),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_additive_skips_present_guids() {
+        let fixture = Fixture::sample();
+        let existing = PathBuf::from("Assets/Old/ground.jpg");
+        std::fs::create_dir_all(fixture.target.join("Assets/Old")).unwrap();
+        std::fs::write(fixture.target.join(&existing), b"old").unwrap();
+        std::fs::write(
+            fixture.target.join("Assets/Old/ground.jpg.unitymeta"),
+            texture_meta(TEXTURE_GUID),
+        )
+        .unwrap();
+
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            additive: true,
+            ..Default::default()
+        });
+        let plan = subject.plan_unpack().unwrap();
+        assert_eq!(plan[2].action, PlannedAction::AlreadyPresent);
+        assert_eq!(plan[0].action, PlannedAction::Install);
+
+        subject.unpack_package(true).unwrap();
+        assert!(!fixture.target.join(TEXTURE_PATH).exists());
+        assert!(fixture.target.join(SCRIPT_PATH).exists());
+        assert!(subject.get_file(TEXTURE_GUID).unwrap().is_skipped());
+        assert_eq!(
+            subject.get_warnings(),
+            &[Warning::AlreadyPresent {
+                guid: TEXTURE_GUID.parse().unwrap(),
+                path: PathBuf::from(TEXTURE_PATH),
+                existing,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_repack_subset() {
+        let demo_folder = "77777777777777777777777777777777";
+        let demo_scene = "88888888888888888888888888888888";
+        let fixture = Fixture::new(
+            &PackageBuilder::new()
+                .folder(FOLDER_GUID, FOLDER_PATH)
+                .asset(
+                    TEXTURE_GUID,
+                    TEXTURE_PATH,
+                    TEXTURE_CONTENT,
+                    &texture_meta(TEXTURE_GUID),
+                )
+                .file(&format!("{}/preview.png", TEXTURE_GUID), b"\x89PNG")
+                .folder(demo_folder, "Assets/Demo")
+                .asset(
+                    demo_scene,
+                    "Assets/Demo/Demo.unity",
+                    b"%YAML 1.1",
+                    &script_meta(demo_scene),
+                ),
+        );
+
+        let output = fixture.dir.path().join("subset.unitypackage");
+        let subject = fixture.open();
+        let written = subject
+            .repack(|p| p.extension().is_none_or(|e| e != "unity"), &output)
+            .unwrap();
+        assert_eq!(written, 2);
+
+        let mut repacked = UnityPackage::from_path(
+            &output,
+            Some(fixture.dir.path().join("out")),
+            Some(fixture.tmp.clone()),
+        )
+        .unwrap();
+        let entries = repacked.list_entries().unwrap();
+        let paths: HashSet<&Path> = entries.iter().map(|e| e.relative_path.as_path()).collect();
+        assert_eq!(
+            paths,
+            HashSet::from([Path::new(FOLDER_PATH), Path::new(TEXTURE_PATH)])
+        );
+        assert!(entries.iter().any(|e| e.has_preview));
+
+        repacked.unpack_package(true).unwrap();
+        let texture = repacked.get_file(TEXTURE_GUID).unwrap();
+        assert_eq!(texture.meta_data().guid, TEXTURE_GUID);
+        assert_eq!(
+            std::fs::read(fixture.dir.path().join("out").join(TEXTURE_PATH)).unwrap(),
+            TEXTURE_CONTENT
+        );
+
+        assert!(matches!(
+            subject.repack(|_| true, &fixture.package),
+            Err(UnityPackageReaderError::CouldNotWritePackage(_))
+        ));
+    }
+
+    #[test]
+    fn test_repack_keeps_extended_headers() {
+        let long_name = format!("{}/{}.txt", TEXTURE_GUID, "long".repeat(40));
+        let pax_name = format!("{}/{}.txt", SCRIPT_GUID, "pax".repeat(40));
+        let fixture = Fixture::new(
+            &sample_package()
+                .file(&long_name, b"long")
+                .pax_file(&pax_name, b"pax"),
+        );
+
+        let output = fixture.dir.path().join("subset.unitypackage");
+        fixture.open().repack(|_| true, &output).unwrap();
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(
+            fs::File::open(&output).unwrap(),
+        ));
+        let mut names = BTreeMap::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let mut content = Vec::new();
+            std::io::Read::read_to_end(&mut entry, &mut content).unwrap();
+            names.insert(
+                entry.path().unwrap().to_string_lossy().into_owned(),
+                content,
+            );
+        }
+        assert_eq!(names[&long_name], b"long");
+        assert_eq!(names[&pax_name], b"pax");
+        assert!(!names.contains_key("placeholder"));
+
+        // A failed write leaves no partial package behind.
+        let occupied = fixture.dir.path().join("occupied");
+        fs::create_dir(&occupied).unwrap();
+        assert!(matches!(
+            fixture.open().repack(|_| true, &occupied),
+            Err(UnityPackageReaderError::CouldNotWritePackage(_))
+        ));
+        assert!(fs::read_dir(fixture.dir.path()).unwrap().all(|e| !e
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .ends_with(".part")));
+    }
+
+    #[test]
+    fn test_remap_rewrites_prefab_references() {
+        let new_guid = "0123456789abcdef0123456789abcdef";
+        let prefab_guid = "77777777777777777777777777777777";
+        let binary_guid = "88888888888888888888888888888888";
+        let prefab = format!(
+            "%YAML 1.1\n%TAG !u! tag:unity3d.com,2011:\n--- !u!23 &2300000\nMeshRenderer:\n  m_Materials:\n  - {{fileID: 2100000, guid: {}, type: 2}}\n",
+            TEXTURE_GUID
+        );
+        let binary = [
+            b"\x00\x00\x00\x10guid: ".as_slice(),
+            TEXTURE_GUID.as_bytes(),
+        ]
+        .concat();
+        let fixture = Fixture::new(
+            &sample_package()
+                .asset(
+                    prefab_guid,
+                    "Assets/Player.prefab",
+                    prefab.as_bytes(),
+                    &script_meta(prefab_guid),
+                )
+                .asset(
+                    binary_guid,
+                    "Assets/Binary.asset",
+                    &binary,
+                    &script_meta(binary_guid),
+                ),
+        );
+
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            guid_remap: [(TEXTURE_GUID.parse().unwrap(), new_guid.parse().unwrap())].into(),
+            rewrite_references: true,
+            ..Default::default()
+        });
+        subject.unpack_package(true).unwrap();
+
+        let target = &fixture.target;
+        let meta =
+            std::fs::read_to_string(target.join(format!("{}.unitymeta", TEXTURE_PATH))).unwrap();
+        assert_eq!(MetaData::parse(&meta).unwrap().guid, new_guid);
+        assert_eq!(
+            std::fs::read_to_string(target.join("Assets/Player.prefab")).unwrap(),
+            prefab.replace(TEXTURE_GUID, new_guid)
+        );
+        assert_eq!(
+            std::fs::read(target.join("Assets/Binary.asset")).unwrap(),
+            binary
+        );
+        assert_eq!(
+            subject.get_warnings(),
+            &[Warning::ReferencesNotRewritten {
+                guid: binary_guid.parse().unwrap(),
+                path: PathBuf::from("Assets/Binary.asset"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_manifest() {
+        let fixture = Fixture::sample();
+        let manifest = fixture.open().manifest().unwrap();
+        assert_eq!(manifest.source_file, "sample.unitypackage");
+        assert_eq!(
+            manifest.package_size,
+            std::fs::metadata(&fixture.package).unwrap().len()
+        );
+        assert_eq!(
+            manifest.total_size,
+            (TEXTURE_CONTENT.len() + SCRIPT_CONTENT.len()) as u64
+        );
+
+        let paths: Vec<&str> = manifest
+            .assets
+            .iter()
+            .map(|a| a.relative_path.as_str())
+            .collect();
+        assert_eq!(paths, vec![SCRIPT_PATH, FOLDER_PATH, TEXTURE_PATH]);
+        assert!(manifest.assets[1].is_folder);
+
+        let csv = fixture.dir.path().join("manifest.csv");
+        fixture.open().write_manifest_csv(&csv, false).unwrap();
+        let content = std::fs::read_to_string(&csv).unwrap();
+        assert!(content.starts_with("guid,relative_path,extension,size,is_folder\r\n"));
+        assert!(content.contains(&format!("{},{},jpg,", TEXTURE_GUID, TEXTURE_PATH)));
+        assert_eq!(content.lines().count(), 3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_write_manifest_json() {
+        let fixture = Fixture::sample();
+        let path = fixture.dir.path().join("manifest.json");
+        fixture.open().write_manifest_json(&path).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["source_file"], "sample.unitypackage");
+        let texture = &json["assets"][2];
+        assert_eq!(texture["guid"], TEXTURE_GUID);
+        assert_eq!(texture["relative_path"], TEXTURE_PATH);
+        assert_eq!(texture["is_folder"], false);
+        assert_eq!(texture["size"], TEXTURE_CONTENT.len());
+        assert_eq!(texture["has_preview"], false);
+    }
+
+    #[test]
+    fn test_diff_packages() {
+        let added = "77777777777777777777777777777777";
+        let dir = tempfile::tempdir().unwrap();
+        let old_path = dir.path().join("v2.0.unitypackage");
+        let new_path = dir.path().join("v2.1.unitypackage");
+        sample_package().write(&old_path);
+        PackageBuilder::new()
+            .folder(FOLDER_GUID, FOLDER_PATH)
+            .asset(
+                TEXTURE_GUID,
+                TEXTURE_PATH,
+                b"a better texture",
+                &texture_meta(TEXTURE_GUID),
+            )
+            .asset(
+                SCRIPT_GUID,
+                "Assets/Scripts/Player.cs",
+                b"a better script",
+                &script_meta(SCRIPT_GUID),
+            )
+            .asset(added, "Assets/New.txt", b"new", &script_meta(added))
+            .write(&new_path);
+
+        let open = |p: &Path| UnityPackage::from_path(p, None, None).unwrap();
+        let old = open(&old_path);
+        let new = open(&new_path);
+        assert!(old.diff(&old).unwrap().is_empty());
+
+        let diff = old.diff(&new).unwrap();
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].guid, Guid::parse(added).unwrap());
+        // The script moved and changed, it is part of both lists.
+        let modified: Vec<&Path> = diff.modified.iter().map(|a| a.path.as_path()).collect();
+        assert_eq!(
+            modified,
+            [
+                Path::new("Assets/Scripts/Player.cs"),
+                Path::new(TEXTURE_PATH)
+            ]
+        );
+        assert_eq!(diff.moved.len(), 1);
+        assert_eq!(diff.moved[0].guid, Guid::parse(SCRIPT_GUID).unwrap());
+        assert_eq!(diff.moved[0].from, Path::new(SCRIPT_PATH));
+        assert_eq!(diff.moved[0].to, Path::new("Assets/Scripts/Player.cs"));
+        assert!(diff.removed.is_empty());
+
+        let reverse = new.diff(&old).unwrap();
+        assert_eq!(reverse.removed.len(), 1);
+        assert_eq!(reverse.removed[0].guid, Guid::parse(added).unwrap());
+    }
+
+    #[test]
+    fn test_validate_healthy_package() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("healthy.unitypackage");
+        sample_package().write(&path);
+
+        let package = UnityPackage::from_path(&path, None, None).unwrap();
+        let report = package.validate().unwrap();
+        assert!(report.is_valid());
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+        let orphans = package.orphans().unwrap();
+        assert_eq!(orphans, report.guid_folders);
+        assert_eq!(orphans.len(), 3);
+        assert!(!orphans.iter().any(|f| f.is_orphan()));
+    }
+
+    #[test]
+    fn test_meta_naming() {
+        for naming in [
+            MetaNaming::UnityMeta,
+            MetaNaming::Meta,
+            MetaNaming::Custom(String::from(".assetmeta")),
+        ] {
+            let fixture = Fixture::sample();
+            let mut subject = fixture.open();
+            subject.set_meta_naming(naming.clone());
+            subject.unpack_package(true).unwrap();
+
+            let expected = fixture
+                .target
+                .join(format!("{}{}", TEXTURE_PATH, naming.suffix()));
+            assert!(expected.is_file(), "{:?}", expected);
+            assert_eq!(
+                expected.parent(),
+                fixture.target.join(TEXTURE_PATH).parent()
+            );
+            let texture = subject.get_file(TEXTURE_GUID).unwrap();
+            assert_eq!(texture.get_installed_meta_path(), Some(&expected));
+            assert_eq!(
+                std::fs::read_dir(expected.parent().unwrap())
+                    .unwrap()
+                    .count(),
+                2
+            );
+        }
+
+        let fixture = Fixture::sample();
+        let mut subject = fixture.open();
+        subject.set_meta_naming(MetaNaming::Custom(String::from("/../meta")));
+        assert!(matches!(
+            subject.unpack_package(true),
+            Err(UnityPackageReaderError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_newline_terminated_pathnames() {
+        let fixture = Fixture::new(
+            &PackageBuilder::new()
+                .folder(FOLDER_GUID, &format!("{}\n", FOLDER_PATH))
+                .asset(
+                    TEXTURE_GUID,
+                    &format!("{}\r\n", TEXTURE_PATH),
+                    TEXTURE_CONTENT,
+                    &texture_meta(TEXTURE_GUID),
+                )
+                .asset(
+                    SCRIPT_GUID,
+                    &format!(" {} \n", SCRIPT_PATH),
+                    SCRIPT_CONTENT,
+                    &script_meta(SCRIPT_GUID),
+                ),
+        );
+        let mut subject = fixture.open();
+
+        let mut listed: Vec<PathBuf> = subject
+            .list_entries()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.relative_path)
+            .collect();
+        listed.sort();
+        assert_eq!(
+            listed,
+            [SCRIPT_PATH, FOLDER_PATH, TEXTURE_PATH].map(PathBuf::from)
+        );
+        assert!(subject.validate().unwrap().issues.is_empty());
+
+        subject.unpack_package(true).unwrap();
+        assert!(fixture.target.join(TEXTURE_PATH).is_file());
+        assert!(fixture.target.join(SCRIPT_PATH).is_file());
+        assert_eq!(
+            subject
+                .get_file(TEXTURE_GUID)
+                .unwrap()
+                .get_relative_asset_path(),
+            &PathBuf::from(TEXTURE_PATH)
+        );
+
+        let empty = Fixture::new(&PackageBuilder::new().asset(
+            TEXTURE_GUID,
+            "\r\n",
+            TEXTURE_CONTENT,
+            &texture_meta(TEXTURE_GUID),
+        ));
+        assert!(matches!(
+            empty.open().unpack_package(true),
+            Err(UnityPackageReaderError::CorruptPackage(_))
+        ));
+    }
+
+    #[test]
+    fn test_two_line_pathnames() {
+        let fixture = Fixture::new(
+            &PackageBuilder::new()
+                .asset(
+                    TEXTURE_GUID,
+                    &format!("{}\n00", TEXTURE_PATH),
+                    TEXTURE_CONTENT,
+                    &texture_meta(TEXTURE_GUID),
+                )
+                .asset(
+                    SCRIPT_GUID,
+                    &format!("{}\r\n00\r\n", SCRIPT_PATH),
+                    SCRIPT_CONTENT,
+                    &script_meta(SCRIPT_GUID),
+                )
+                .asset(
+                    FOLDER_GUID,
+                    "ProjectSettings/Tags.asset\n00\n",
+                    b"tags",
+                    &script_meta(FOLDER_GUID),
+                ),
+        );
+        let mut subject = fixture.open();
+
+        let listed: HashSet<PathBuf> = subject
+            .list_entries()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.relative_path)
+            .collect();
+        assert_eq!(
+            listed,
+            HashSet::from(
+                [TEXTURE_PATH, SCRIPT_PATH, "ProjectSettings/Tags.asset"].map(PathBuf::from)
+            )
+        );
+
+        subject.set_config(UnpackConfig {
+            allowed_roots: vec![String::from("Assets")],
+            ..Default::default()
+        });
+        subject.unpack_package(true).unwrap();
+        assert!(fixture.target.join(TEXTURE_PATH).is_file());
+        assert!(fixture.target.join(SCRIPT_PATH).is_file());
+        assert!(!fixture.target.join("ProjectSettings").exists());
+
+        let texture = subject.get_file(TEXTURE_GUID).unwrap();
+        assert_eq!(texture.get_relative_asset_path(), Path::new(TEXTURE_PATH));
+        assert_eq!(texture.get_pathname_extra_lines(), ["00"]);
+    }
+
+    #[test]
+    fn test_empty_folder_assets_are_created() {
+        let empty = "66666666666666666666666666666666";
+        let fixture = Fixture::new(&sample_package().folder(empty, "Assets/Placeholder/Empty"));
+        let mut subject = fixture.open();
+        subject.unpack_package(true).unwrap();
+
+        assert!(fixture.target.join("Assets/Placeholder/Empty").is_dir());
+        assert!(fixture.target.join(FOLDER_PATH).is_dir());
+        let folder = subject.get_file(empty).unwrap();
+        assert!(folder.is_folder());
+        assert_eq!(
+            folder.get_relative_asset_path(),
+            Path::new("Assets/Placeholder/Empty")
+        );
+    }
+
+    #[test]
+    fn test_preserve_mtimes() {
+        // The time PackageBuilder stores in every header.
+        let archived = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+        let modified = |p: PathBuf| std::fs::metadata(p).unwrap().modified().unwrap();
+
+        let fixture = Fixture::sample();
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            preserve_mtimes: true,
+            ..Default::default()
+        });
+        subject.unpack_package(true).unwrap();
+        assert_eq!(modified(fixture.target.join(TEXTURE_PATH)), archived);
+        assert_eq!(
+            modified(fixture.target.join(format!("{}.unitymeta", TEXTURE_PATH))),
+            archived
+        );
+
+        let fixture = Fixture::sample();
+        fixture.open().unpack_package(true).unwrap();
+        assert!(modified(fixture.target.join(TEXTURE_PATH)) > archived);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tool_guid = "66666666666666666666666666666666";
+        let tool = "Assets/Tools/build.sh";
+        let builder = sample_package()
+            .file_with_mode(&format!("{}/asset", tool_guid), b"#!/bin/sh\n", 0o4755)
+            .file(&format!("{}/asset.meta", tool_guid), script_meta(tool_guid))
+            .file(&format!("{}/pathname", tool_guid), tool);
+        let mode = |p: PathBuf| std::fs::metadata(p).unwrap().permissions().mode() & 0o7777;
+
+        let fixture = Fixture::new(&builder);
+        fixture.open().unpack_package(true).unwrap();
+        assert_eq!(mode(fixture.target.join(tool)), 0o755);
+        assert_eq!(mode(fixture.target.join(TEXTURE_PATH)), 0o644);
+
+        let fixture = Fixture::new(&builder);
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            file_modes: FileModes::Sanitized,
+            ..Default::default()
+        });
+        subject.unpack_package(true).unwrap();
+        assert_eq!(mode(fixture.target.join(tool)), 0o644);
+    }
+
+    #[test]
+    fn test_limits_stop_decompression_bombs() {
+        let bomb_guid = "66666666666666666666666666666666";
+        let builder = sample_package().asset(
+            bomb_guid,
+            "Assets/Bomb.bin",
+            &vec![0u8; 4 << 20],
+            &script_meta(bomb_guid),
+        );
+        let fixture = Fixture::new(&builder);
+        assert!(std::fs::metadata(&fixture.package).unwrap().len() < 64 << 10);
+
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            limits: UnpackLimits {
+                max_total_bytes: 1 << 20,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        match subject.unpack_package(true) {
+            Err(UnityPackageReaderError::LimitExceeded(e)) => {
+                assert!(format!("{}", e).contains("max_total_bytes is 1048576"))
+            }
+            other => panic!("{:?}", other.err()),
+        }
+        assert!(!fixture.tmp.exists());
+        assert!(!fixture.target.exists());
+
+        let mut subject = fixture.open();
+        subject.unpack_package(true).unwrap();
+        assert!(fixture.target.join("Assets/Bomb.bin").is_file());
+    }
+
+    /// The names of the staging and backup directories next to the target.
+    fn leftover_staging(fixture: &Fixture) -> Vec<String> {
+        std::fs::read_dir(fixture.dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|n| n.starts_with(".target."))
+            .collect()
+    }
+
+    #[test]
+    fn test_atomic_unpack() {
+        let fixture = Fixture::sample();
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            atomic: true,
+            ..Default::default()
+        });
+        subject.unpack_package(true).unwrap();
+
+        assert_eq!(
+            std::fs::read(fixture.target.join(TEXTURE_PATH)).unwrap(),
+            TEXTURE_CONTENT
+        );
+        assert!(fixture
+            .target
+            .join(format!("{}.unitymeta", SCRIPT_PATH))
+            .is_file());
+        assert!(subject
+            .get_file(TEXTURE_GUID)
+            .unwrap()
+            .get_installed_meta_path()
+            .unwrap()
+            .starts_with(&fixture.target));
+        assert!(leftover_staging(&fixture).is_empty());
+    }
+
+    #[test]
+    fn test_atomic_unpack_rolls_back() {
+        let fixture = Fixture::sample();
+        let script = fixture.target.join(SCRIPT_PATH);
+        std::fs::create_dir_all(script.parent().unwrap()).unwrap();
+        std::fs::write(&script, "local").unwrap();
+        // A directory in place of the texture makes moving it into the target fail.
+        let blocker = fixture.target.join(TEXTURE_PATH);
+        std::fs::create_dir_all(&blocker).unwrap();
+        std::fs::write(blocker.join("keep"), "").unwrap();
+
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            overwrite_policy: OverwritePolicy::Overwrite,
+            atomic: true,
+            ..Default::default()
+        });
+        assert!(matches!(
+            subject.unpack_package(true),
+            Err(UnityPackageReaderError::CopyFailed(_))
+        ));
+
+        assert_eq!(std::fs::read_to_string(&script).unwrap(), "local");
+        assert!(!fixture
+            .target
+            .join(format!("{}.unitymeta", SCRIPT_PATH))
+            .exists());
+        assert!(!fixture
+            .target
+            .join(format!("{}.unitymeta", FOLDER_PATH))
+            .exists());
+        assert!(blocker.join("keep").is_file());
+        assert!(leftover_staging(&fixture).is_empty());
+    }
+
+    #[test]
+    fn test_unpack_from_tmp_after_failed_copy() {
+        let fixture = Fixture::sample();
+        let script = fixture.target.join(SCRIPT_PATH);
+        std::fs::create_dir_all(script.parent().unwrap()).unwrap();
+        std::fs::write(&script, "local").unwrap();
+
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            overwrite_policy: OverwritePolicy::Error,
+            ..Default::default()
+        });
+        assert!(matches!(
+            subject.unpack_package(false),
+            Err(UnityPackageReaderError::TargetFileExists(_))
+        ));
+        assert!(fixture.tmp.is_dir());
+
+        // Without the package file, only the tmp directory is left to install from.
+        std::fs::remove_file(&fixture.package).unwrap();
+        subject.set_overwrite_policy(OverwritePolicy::Skip);
+        subject.unpack_from_tmp(true).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&script).unwrap(), "local");
+        assert!(subject.get_file(SCRIPT_GUID).unwrap().is_skipped());
+        assert_eq!(
+            std::fs::read(fixture.target.join(TEXTURE_PATH)).unwrap(),
+            TEXTURE_CONTENT
+        );
+        // Moved into the target by the failed unpack already.
+        assert!(subject
+            .get_file(TEXTURE_GUID)
+            .unwrap()
+            .get_installed_meta_path()
+            .is_some());
+        assert_eq!(subject.len(), 3);
+        assert!(!fixture.tmp.exists());
+    }
+
+    #[test]
+    fn test_unpack_from_invalid_tmp() {
+        let fixture = Fixture::sample();
+        let mut subject = fixture.open();
+        let is_invalid = |r: Result<(), UnityPackageReaderError>| {
+            matches!(r, Err(UnityPackageReaderError::InvalidTmpDirectory(_)))
+        };
+
+        assert!(is_invalid(subject.unpack_from_tmp(false)));
+
+        std::fs::create_dir_all(&fixture.tmp).unwrap();
+        assert!(is_invalid(subject.unpack_from_tmp(false)));
+
+        let asset = fixture.tmp.join(TEXTURE_GUID);
+        std::fs::create_dir_all(&asset).unwrap();
+        std::fs::write(asset.join("asset"), TEXTURE_CONTENT).unwrap();
+        assert!(is_invalid(subject.unpack_from_tmp(false)));
+
+        std::fs::write(asset.join("pathname"), TEXTURE_PATH).unwrap();
+        std::fs::write(fixture.tmp.join("notes.txt"), "").unwrap();
+        assert!(is_invalid(subject.unpack_from_tmp(false)));
+        assert!(!fixture.target.exists());
+    }
+
+    #[test]
+    fn test_symlink_entries_are_skipped() {
+        let outside = tempfile::tempdir().unwrap();
+        let secret = outside.path().join("secret");
+        std::fs::write(&secret, "secret").unwrap();
+        let package = PackageBuilder::new()
+            .folder(FOLDER_GUID, FOLDER_PATH)
+            .symlink(&format!("{}/asset", FOLDER_GUID), &secret.to_string_lossy());
+
+        let fixture = Fixture::new(&package);
+        let mut subject = fixture.open();
+        let report = subject
+            .unpack_with_report(&UnpackOptions::default())
+            .unwrap();
+        assert_eq!(report.skipped_entries.len(), 1);
+        assert_eq!(
+            report.skipped_entries[0].link.as_deref(),
+            Some(secret.as_path())
+        );
+        assert_eq!(subject.get_skipped_entries(), report.skipped_entries);
+        assert!(fixture.target.join(FOLDER_PATH).is_dir());
+        assert_eq!(std::fs::read(&secret).unwrap(), b"secret");
+
+        let fixture = Fixture::new(&package);
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            special_entries: SpecialEntries::Reject,
+            ..Default::default()
+        });
+        assert!(matches!(
+            subject.unpack_package(true),
+            Err(UnityPackageReaderError::UnsafeEntry(_))
+        ));
+        assert!(!fixture.target.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unpack_from_tmp_rejects_links() {
+        let fixture = Fixture::sample();
+        let outside = tempfile::tempdir().unwrap();
+        let guid_dir = outside.path().join(TEXTURE_GUID);
+        std::fs::create_dir_all(&guid_dir).unwrap();
+        std::fs::write(guid_dir.join("asset"), TEXTURE_CONTENT).unwrap();
+        std::fs::write(guid_dir.join("asset.meta"), texture_meta(TEXTURE_GUID)).unwrap();
+        std::fs::write(guid_dir.join("pathname"), TEXTURE_PATH).unwrap();
+        std::fs::create_dir_all(&fixture.tmp).unwrap();
+        std::os::unix::fs::symlink(&guid_dir, fixture.tmp.join(TEXTURE_GUID)).unwrap();
+
+        let mut subject = fixture.open();
+        assert!(matches!(
+            subject.unpack_from_tmp(false),
+            Err(UnityPackageReaderError::UnsafeEntry(_))
+        ));
+        assert!(guid_dir.join("asset").is_file());
+    }
+
+    #[test]
+    fn test_category_filters() {
+        let fixture = Fixture::sample();
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            include_categories: vec![AssetCategory::Script],
+            ..Default::default()
+        });
+        subject.unpack_package(true).unwrap();
+        assert!(fixture.target.join(SCRIPT_PATH).is_file());
+        assert!(fixture.target.join(FOLDER_PATH).is_dir());
+        assert!(!fixture.target.join(TEXTURE_PATH).exists());
+        assert_eq!(
+            subject.get_file(SCRIPT_GUID).unwrap().category(),
+            AssetCategory::Script
+        );
+        // Filtered assets are not indexed.
+        assert!(subject.get_file(TEXTURE_GUID).is_none());
+
+        let fixture = Fixture::sample();
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            exclude_categories: vec![AssetCategory::Texture, AssetCategory::Audio],
+            ..Default::default()
+        });
+        subject.unpack_package(true).unwrap();
+        assert!(fixture.target.join(SCRIPT_PATH).is_file());
+        assert!(!fixture.target.join(TEXTURE_PATH).exists());
+    }
+
+    #[test]
+    fn test_unpack_with_report() {
+        let fixture = Fixture::sample();
+        let script = fixture.target.join(SCRIPT_PATH);
+        std::fs::create_dir_all(script.parent().unwrap()).unwrap();
+        std::fs::write(&script, "local").unwrap();
+
+        let mut subject = fixture.open();
+        let report = subject
+            .unpack_with_report(&UnpackOptions::from(UnpackConfig {
+                exclude_categories: vec![AssetCategory::Texture],
+                ..Default::default()
+            }))
+            .unwrap();
+
+        assert_eq!(report.target_root, fixture.target);
+        assert_eq!(
+            (report.extracted, report.overwritten, report.skipped),
+            (1, 1, 0)
+        );
+        assert_eq!(
+            report.bytes_written,
+            subject.get_stats().target_bytes_written
+        );
+        let status: Vec<(&str, AssetStatus)> = report
+            .assets
+            .iter()
+            .map(|a| (a.relative_path.to_str().unwrap(), a.status))
+            .collect();
+        assert_eq!(
+            status,
+            vec![
+                (SCRIPT_PATH, AssetStatus::Overwritten),
+                (FOLDER_PATH, AssetStatus::Extracted),
+                (TEXTURE_PATH, AssetStatus::Filtered),
+            ]
+        );
+        assert_eq!(report.assets[0].absolute_path, script);
+
+        // A relative target is resolved against the working directory.
+        let relative = UnityPackage::from_path(
+            &fixture.package,
+            Some(PathBuf::from("relative/target")),
+            None,
+        )
+        .unwrap();
+        let target = relative.get_target_dir().unwrap();
+        assert!(target.is_absolute());
+        assert_eq!(
+            target,
+            std::env::current_dir().unwrap().join("relative/target")
+        );
+
+        let report = subject
+            .unpack_with_report(&UnpackOptions::from(UnpackConfig {
+                overwrite_policy: OverwritePolicy::Skip,
+                ..Default::default()
+            }))
+            .unwrap();
+        assert_eq!(
+            (report.extracted, report.overwritten, report.skipped),
+            (1, 0, 2)
+        );
+        assert_eq!(report.assets[2].status, AssetStatus::Extracted);
+    }
+
+    #[test]
+    fn test_scan_dependencies() {
+        let material_guid = "33333333333333333333333333333333";
+        let prefab_guid = "44444444444444444444444444444444";
+        let material = format!(
+            "%YAML 1.1\n%TAG !u! tag:unity3d.com,2011:\n--- !u!21 &2100000\nMaterial:\n  m_SavedProperties:\n    m_TexEnvs:\n    - _MainTex:\n        m_Texture: {{fileID: 2800000, guid: {}, type: 3}}\n",
+            TEXTURE_GUID
+        );
+        let prefab = format!(
+            "%YAML 1.1\n--- !u!1001 &100100000\nPrefabInstance:\n  m_Material: {{fileID: 2100000, guid: {}, type: 2}}\n  m_Script: {{fileID: 11500000, guid: {}, type: 3}}\n",
+            material_guid, SCRIPT_GUID
+        );
+        let binary_guid = "55555555555555555555555555555555";
+        let binary = [
+            b"\x00\x00\x00\x10guid: ".as_slice(),
+            TEXTURE_GUID.as_bytes(),
+        ]
+        .concat();
+        let fixture = Fixture::new(
+            &sample_package()
+                .asset(
+                    material_guid,
+                    "Assets/Materials/Ground.mat",
+                    material.as_bytes(),
+                    &script_meta(material_guid),
+                )
+                .asset(
+                    prefab_guid,
+                    "Assets/Ground.prefab",
+                    prefab.as_bytes(),
+                    &script_meta(prefab_guid),
+                )
+                .asset(
+                    binary_guid,
+                    "Assets/Binary.asset",
+                    &binary,
+                    &script_meta(binary_guid),
+                ),
+        );
+
+        let graph = fixture.open().scan_dependencies().unwrap();
+        let guid = |g: &str| g.parse::<Guid>().unwrap();
+        assert_eq!(graph.assets().len(), 6);
+        assert_eq!(
+            graph.dependencies_of(guid(material_guid)),
+            Some(&AssetDependencies::Scanned([guid(TEXTURE_GUID)].into()))
+        );
+        assert_eq!(
+            graph.dependencies_of(guid(TEXTURE_GUID)),
+            Some(&AssetDependencies::Scanned(Default::default()))
+        );
+        assert_eq!(
+            graph.unscanned().collect::<Vec<_>>(),
+            vec![&guid(binary_guid)]
+        );
+        assert_eq!(
+            graph.closure(guid(prefab_guid)),
+            [guid(material_guid), guid(TEXTURE_GUID), guid(SCRIPT_GUID)].into()
+        );
+        assert!(!fixture.tmp.exists());
+    }
+
+    #[test]
+    fn test_missing_dependencies() {
+        let material_guid = "33333333333333333333333333333333";
+        let gone = "abcdefabcdefabcdefabcdefabcdef01";
+        let project = "abcdefabcdefabcdefabcdefabcdef02";
+        let builtin = "0000000000000000f000000000000000";
+        let material = format!(
+            "%YAML 1.1\nMaterial:\n  m_Shader: {{fileID: 46, guid: {}, type: 0}}\n  - _MainTex: {{fileID: 2800000, guid: {}, type: 3}}\n  - _BumpMap: {{fileID: 2800000, guid: {}, type: 3}}\n  - _Mask: {{fileID: 2800000, guid: {}, type: 3}}\n",
+            builtin, TEXTURE_GUID, gone, project
+        );
+        let fixture = Fixture::new(&sample_package().asset(
+            material_guid,
+            "Assets/Materials/Ground.mat",
+            material.as_bytes(),
+            &script_meta(material_guid),
+        ));
+        let guid = |g: &str| g.parse::<Guid>().unwrap();
+
+        let missing = fixture
+            .open()
+            .missing_dependencies(&[guid(project)].into())
+            .unwrap();
+        assert_eq!(
+            missing.external,
+            [(guid(gone), [guid(material_guid)].into())].into()
+        );
+        assert_eq!(
+            missing.builtin.keys().collect::<Vec<_>>(),
+            vec![&guid(builtin)]
+        );
+
+        let missing = fixture
+            .open()
+            .missing_dependencies(&HashSet::new())
+            .unwrap();
+        assert_eq!(missing.external.len(), 2);
+        assert!(!fixture
+            .open()
+            .missing_dependencies(&HashSet::new())
+            .unwrap()
+            .is_empty());
+        assert!(Fixture::sample()
+            .open()
+            .missing_dependencies(&HashSet::new())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_extract_asset() {
+        let fixture = Fixture::sample();
+        let subject = fixture.open();
+        let destination = fixture.dir.path().join("single").join("ground.jpg");
+
+        let extracted = subject
+            .extract_asset(TEXTURE_GUID, &destination, true)
+            .unwrap();
+        assert_eq!(extracted.path, destination);
+        assert_eq!(extracted.size, TEXTURE_CONTENT.len() as u64);
+        assert!(!fixture.tmp.exists());
+
+        fixture.open().unpack_package(true).unwrap();
+        assert_eq!(
+            std::fs::read(&destination).unwrap(),
+            std::fs::read(fixture.target.join(TEXTURE_PATH)).unwrap()
+        );
+        assert_eq!(
+            std::fs::read(extracted.meta.unwrap()).unwrap(),
+            std::fs::read(fixture.target.join(format!("{}.unitymeta", TEXTURE_PATH))).unwrap()
+        );
+
+        let missing = fixture.dir.path().join("missing.jpg");
+        assert!(matches!(
+            subject.extract_asset("00000000000000000000000000000042", &missing, false),
+            Err(UnityPackageReaderError::AssetNotFound(_))
+        ));
+        assert!(matches!(
+            subject.extract_asset(FOLDER_GUID, &missing, false),
+            Err(UnityPackageReaderError::AssetNotFound(_))
+        ));
+        assert!(!missing.exists());
+    }
+
+    #[test]
+    fn test_extract_asset_with_unsafe_pathname() {
+        let evil = "66666666666666666666666666666666";
+        let fixture = Fixture::new(&sample_package().asset(
+            evil,
+            "Assets/../../evil.txt",
+            b"evil",
+            &script_meta(evil),
+        ));
+        let destination = fixture.dir.path().join("evil.txt");
+
+        assert!(matches!(
+            fixture.open().extract_asset(evil, &destination, false),
+            Err(UnityPackageReaderError::PathError(_))
+        ));
+        assert!(!destination.exists());
+
+        // A file that was there before is kept, without a partial file next to it.
+        std::fs::write(&destination, "before").unwrap();
+        assert!(fixture
+            .open()
+            .extract_asset(evil, &destination, false)
+            .is_err());
+        assert_eq!(std::fs::read_to_string(&destination).unwrap(), "before");
+        let names: Vec<_> = std::fs::read_dir(fixture.dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .filter(|n| n.to_string_lossy().ends_with(".part"))
+            .collect();
+        assert!(names.is_empty());
+    }
+
+    #[test]
+    fn test_unpack_with_backslash_traversal() {
+        let evil = "66666666666666666666666666666666";
+        let fixture = Fixture::new(&sample_package().asset(
+            evil,
+            "Assets\\..\\..\\evil.txt",
+            b"evil",
+            &script_meta(evil),
+        ));
+
+        match fixture.open().unpack_package(true) {
+            Err(UnityPackageReaderError::PathError(e)) => {
+                assert!(e.message.unwrap().contains("evil.txt"));
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(!fixture.dir.path().join("evil.txt").exists());
+        assert!(!fixture.target.join(TEXTURE_PATH).exists());
+
+        // Comparing does not look outside the target either.
+        match fixture.open().compare_with_target(&fixture.target) {
+            Err(UnityPackageReaderError::PathError(_)) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_extract_asset_to_writer() {
+        let fixture = Fixture::sample();
+        let mut content = Vec::new();
+        let written = fixture
+            .open()
+            .extract_asset_to_writer(SCRIPT_GUID, &mut content)
+            .unwrap();
+        assert_eq!(written, SCRIPT_CONTENT.len() as u64);
+        assert_eq!(content, SCRIPT_CONTENT);
+        assert!(!fixture.tmp.exists());
+
+        // Everything after the texture payload is garbage, so reading on would fail.
+        let tar = sample_package().tar_bytes();
+        let end = tar
+            .windows(TEXTURE_CONTENT.len())
+            .position(|w| w == TEXTURE_CONTENT)
+            .unwrap()
+            + 512;
+        let mut broken = tar[..end].to_vec();
+        broken.extend_from_slice(&[0x5a; 2048]);
+        std::fs::write(&fixture.package, &broken).unwrap();
+
+        let mut content = Vec::new();
+        fixture
+            .open()
+            .extract_asset_to_writer(TEXTURE_GUID, &mut content)
+            .unwrap();
+        assert_eq!(content, TEXTURE_CONTENT);
+        assert!(fixture.open().list_entries().is_err());
+    }
+
+    #[test]
+    fn test_extract_asset_to_failing_writer() {
+        struct Full(usize);
+        impl Write for Full {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                match self.0.min(buf.len()) {
+                    0 => Err(std::io::Error::other("no space left")),
+                    n => {
+                        self.0 -= n;
+                        Ok(n)
+                    }
+                }
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let fixture = Fixture::sample();
+        match fixture
+            .open()
+            .extract_asset_to_writer(TEXTURE_GUID, &mut Full(4))
+        {
+            Err(UnityPackageReaderError::CopyFailed(e)) => {
+                let message = e.to_string();
+                assert!(message.contains(TEXTURE_GUID));
+                assert!(message.contains("no space left"));
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_backslash_pathnames() {
+        let package = PackageBuilder::new().asset(
+            TEXTURE_GUID,
+            &TEXTURE_PATH.replace('/', "\\"),
+            TEXTURE_CONTENT,
+            &texture_meta(TEXTURE_GUID),
+        );
+
+        let fixture = Fixture::new(&package);
+        let mut subject = fixture.open();
+        subject.unpack_package(true).unwrap();
+        let asset = subject.get_file(TEXTURE_GUID).unwrap();
+        assert!(asset.has_backslash_pathname());
+        assert_eq!(asset.get_relative_asset_path(), Path::new(TEXTURE_PATH));
+        assert_eq!(
+            std::fs::read(fixture.target.join(TEXTURE_PATH)).unwrap(),
+            TEXTURE_CONTENT
+        );
+
+        let fixture = Fixture::new(&package);
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            strict_pathnames: true,
+            ..Default::default()
+        });
+        assert!(matches!(
+            subject.unpack_package(true),
+            Err(UnityPackageReaderError::PathError(_))
+        ));
+        assert!(!fixture.target.join("Assets").exists());
+    }
+
+    #[test]
+    fn test_case_collisions() {
+        let other_guid = "0f1e2d3c4b5a69788796a5b4c3d2e1f0";
+        let package = PackageBuilder::new()
+            .asset(
+                TEXTURE_GUID,
+                "Assets/Foo/icon.png",
+                TEXTURE_CONTENT,
+                &texture_meta(TEXTURE_GUID),
+            )
+            .asset(
+                other_guid,
+                "Assets/Foo/Icon.png",
+                b"second",
+                &texture_meta(other_guid),
+            );
+        let config = |case_collisions| UnpackConfig {
+            case_collisions,
+            ..Default::default()
+        };
+
+        let fixture = Fixture::new(&package);
+        let mut subject = fixture.open().with_config(config(CaseCollisions::Error));
+        match subject.unpack_package(true) {
+            Err(UnityPackageReaderError::CaseCollision(e)) => {
+                let message = e.message.unwrap();
+                assert!(message.contains("Assets/Foo/icon.png"));
+                assert!(message.contains("Assets/Foo/Icon.png"));
+            }
+            other => panic!("{:?}", other.err()),
+        }
+        assert!(!fixture.target.join("Assets").exists());
+
+        let fixture = Fixture::new(&package);
+        let mut subject = fixture
+            .open()
+            .with_config(config(CaseCollisions::KeepFirst));
+        subject.unpack_package(true).unwrap();
+        let files: Vec<_> = std::fs::read_dir(fixture.target.join("Assets/Foo"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .filter(|n| n.ends_with(".png"))
+            .collect();
+        assert_eq!(files.len(), 1);
+        assert!(matches!(
+            subject.get_warnings(),
+            [Warning::CaseCollision { renamed: None, .. }]
+        ));
+
+        let fixture = Fixture::new(&package);
+        let mut subject = fixture
+            .open()
+            .with_config(config(CaseCollisions::RenameWithSuffix));
+        subject.unpack_package(true).unwrap();
+        let (guid, renamed) = match subject.get_warnings() {
+            [Warning::CaseCollision {
+                guid,
+                renamed: Some(renamed),
+                ..
+            }] => (*guid, renamed.clone()),
+            other => panic!("{:?}", other),
+        };
+        assert!(renamed.ends_with("icon_1.png") || renamed.ends_with("Icon_1.png"));
+        assert!(fixture.target.join(&renamed).is_file());
+        assert_eq!(
+            subject
+                .get_file(&guid.to_string())
+                .unwrap()
+                .get_relative_asset_path(),
+            &renamed
+        );
+        assert_eq!(
+            std::fs::read_dir(fixture.target.join("Assets/Foo"))
+                .unwrap()
+                .count(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_open() {
+        let fixture = Fixture::sample();
+        let target = Some(fixture.target.clone());
+        let mut subject = UnityPackage::open(&fixture.package, target, None).unwrap();
+        assert_eq!(subject.get_package_path(), fixture.package);
+        subject.unpack_package(true).unwrap();
+        assert!(fixture.target.join(TEXTURE_PATH).is_file());
+
+        assert!(matches!(
+            UnityPackage::open(fixture.dir.path().join("missing.unitypackage"), None, None),
+            Err(UnityPackageReaderError::PackageNotFound(_))
+        ));
+        assert!(matches!(
+            UnityPackage::open(fixture.dir.path(), None, None),
+            Err(UnityPackageReaderError::NotAPackageFile(_))
+        ));
+
+        let text = fixture.dir.path().join("readme.unitypackage");
+        std::fs::write(&text, "PK not a package").unwrap();
+        match UnityPackage::open(&text, None, None) {
+            Err(UnityPackageReaderError::NotAPackageFile(e)) => {
+                assert!(e.message.unwrap().contains("50 4b 20 6e"))
+            }
+            other => panic!("{:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn test_parallel_unpacks_use_their_own_tmp() {
+        let fixture = Fixture::sample();
+        let targets = [fixture.dir.path().join("a"), fixture.dir.path().join("b")];
+
+        let tmp_dirs: Vec<PathBuf> = std::thread::scope(|scope| {
+            let handles: Vec<_> = targets
+                .iter()
+                .map(|target| {
+                    let package = &fixture.package;
+                    scope.spawn(move || {
+                        let mut subject =
+                            UnityPackage::from_path(package, Some(target.clone()), None).unwrap();
+                        let report = subject
+                            .unpack_with_report(&UnpackOptions::default())
+                            .unwrap();
+                        assert_eq!(report.extracted, 3);
+                        report.tmp_dir
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        assert_ne!(tmp_dirs[0], tmp_dirs[1]);
+        for (target, tmp) in targets.iter().zip(&tmp_dirs) {
+            assert!(target.join(TEXTURE_PATH).is_file());
+            assert!(target.join(SCRIPT_PATH).is_file());
+            assert!(!tmp.exists());
+        }
+        let _ = std::fs::remove_dir(tmp_dirs[0].parent().unwrap());
+    }
+
+    #[test]
+    fn test_failed_unpack_removes_tmp() {
+        let content = vec![7u8; 200_000];
+        let builder = sample_package().asset(
+            "0123456789abcdef0123456789abcdef",
+            "Assets/large.bin",
+            &content,
+            &texture_meta("0123456789abcdef0123456789abcdef"),
+        );
+        let fixture = Fixture::new(&builder);
+
+        // Cut off in the middle of the large asset.
+        let bytes = builder.bytes();
+        std::fs::write(&fixture.package, &bytes[..bytes.len() - 100]).unwrap();
+        let mut subject = fixture.open();
+        assert!(subject.unpack_package(true).is_err());
+        assert!(!fixture.tmp.exists());
+
+        assert!(subject.unpack_package(false).is_err());
+        assert!(fixture.tmp.is_dir());
+        std::fs::remove_dir_all(&fixture.tmp).unwrap();
+
+        // A failed install.
+        builder.write(&fixture.package);
+        let script = fixture.target.join(SCRIPT_PATH);
+        std::fs::create_dir_all(script.parent().unwrap()).unwrap();
+        std::fs::write(&script, "local").unwrap();
+        subject.set_overwrite_policy(OverwritePolicy::Error);
+        assert!(matches!(
+            subject.unpack_package(true),
+            Err(UnityPackageReaderError::TargetFileExists(_))
+        ));
+        assert!(!fixture.tmp.exists());
+
+        // A session that is abandoned half way.
+        subject.set_overwrite_policy(OverwritePolicy::Skip);
+        let mut session = UnpackSession::new(&mut subject, &UnpackOptions::default());
+        assert_eq!(session.step(StepBudget::Entries(2)), StepResult::Continue);
+        assert!(fixture.tmp.is_dir());
+        drop(session);
+        assert!(!fixture.tmp.exists());
+    }
+
+    #[test]
+    fn test_unpack_from_tmp_file_system_failures() {
+        let tmp = PathBuf::from("/memory/tmp");
+        let target = PathBuf::from("/memory/target");
+        let setup = || {
+            let fs = MemoryFileSystem::default();
+            for (guid, path, meta) in [
+                (TEXTURE_GUID, TEXTURE_PATH, texture_meta(TEXTURE_GUID)),
+                (FOLDER_GUID, FOLDER_PATH, folder_meta(FOLDER_GUID)),
+            ] {
+                fs.write(tmp.join(guid).join("asset.meta"), meta);
+                fs.write(tmp.join(guid).join("pathname"), path);
+            }
+            fs.write(tmp.join(TEXTURE_GUID).join("asset"), TEXTURE_CONTENT);
+            Arc::new(fs)
+        };
+        let open = |fs: &Arc<MemoryFileSystem>| {
+            UnityPackage::from_path(
+                "/memory/p.unitypackage",
+                Some(target.clone()),
+                Some(tmp.clone()),
+            )
+            .unwrap()
+            .with_file_system(fs.clone())
+        };
+
+        let fs = setup();
+        fs.fail("read_dir_entry", &tmp, std::io::ErrorKind::PermissionDenied);
+        assert!(matches!(
+            open(&fs).unpack_from_tmp(false),
+            Err(UnityPackageReaderError::InvalidTmpDirectory(_))
+        ));
+        assert!(matches!(
+            open(&fs).read_tmp_assets(&tmp, None, None),
+            Err(UnityPackageReaderError::CorruptPackage(_))
+        ));
+
+        let fs = setup();
+        fs.fail("read_dir", &tmp, std::io::ErrorKind::PermissionDenied);
+        assert!(matches!(
+            open(&fs).read_tmp_assets(&tmp, None, None),
+            Err(UnityPackageReaderError::TmpDirectoryCouldNotBeCreated(_))
+        ));
+
+        let fs = setup();
+        fs.fail("remove_dir_all", &tmp, std::io::ErrorKind::PermissionDenied);
+        let mut subject = open(&fs);
+        assert!(matches!(
+            subject.unpack_from_tmp(true),
+            Err(UnityPackageReaderError::CouldNotDeleteTmp(_))
+        ));
+        // Everything has been installed before.
+        assert_eq!(fs.read(target.join(TEXTURE_PATH)).unwrap(), TEXTURE_CONTENT);
+
+        let fs = setup();
+        let mut subject = open(&fs);
+        subject.unpack_from_tmp(true).unwrap();
+        assert_eq!(subject.len(), 2);
+        assert!(fs.exists(&target.join(format!("{}.unitymeta", FOLDER_PATH))));
+        assert!(!fs.exists(&tmp));
+    }
+
+    #[test]
+    fn test_from_bytes_lists_and_unpacks_to_memory() {
+        let builder = sample_package()
+            .symlink(&format!("{}/asset", FOLDER_GUID), "/etc/passwd")
+            .file("not-a-guid/pathname", "Assets/Stray.txt");
+        let bytes = builder.bytes();
+        let package = UnityPackage::from_bytes("upload.unitypackage", bytes.clone()).unwrap();
+
+        let fixture = Fixture::new(&builder);
+        assert_eq!(
+            package.list_entries().unwrap(),
+            fixture.open().list_entries().unwrap()
+        );
+        let manifest = package.manifest().unwrap();
+        assert_eq!(manifest.source_file, "upload.unitypackage");
+        assert_eq!(manifest.package_size, bytes.len() as u64);
+
+        let assets = package.unpack_to_memory().unwrap();
+        let paths: Vec<&Path> = assets.iter().map(|a| a.relative_path.as_path()).collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new(SCRIPT_PATH),
+                Path::new(FOLDER_PATH),
+                Path::new(TEXTURE_PATH)
+            ]
+        );
+        assert_eq!(assets[2].guid, TEXTURE_GUID.parse::<Guid>().unwrap());
+        assert_eq!(assets[2].content, TEXTURE_CONTENT);
+        assert_eq!(assets[2].meta, texture_meta(TEXTURE_GUID).as_bytes());
+        // The link is left out, the folder keeps an empty payload.
+        assert!(assets[1].is_folder);
+        assert!(assets[1].content.is_empty());
+
+        let mut limited = UnityPackage::from_bytes("upload.unitypackage", bytes).unwrap();
+        limited.config.limits.max_entries = 2;
+        assert!(matches!(
+            limited.unpack_to_memory(),
+            Err(UnityPackageReaderError::LimitExceeded(_))
+        ));
+
+        assert!(matches!(
+            UnityPackage::from_bytes("upload.unitypackage", &b"not a package"[..]),
+            Err(UnityPackageReaderError::NotAPackageFile(_))
+        ));
+    }
+
+    #[test]
+    fn test_summary() {
+        let fixture = Fixture::sample();
+        let mut package = fixture.open();
+        assert_eq!(
+            package.summary(),
+            "package: sample.unitypackage\nassets: 0\nfolders: 0\nskipped: 0\n\
+             overwritten: 0\nfiltered: 0\nwarnings: 0\nbytes written: 0\n"
+        );
+
+        package.unpack_package(true).unwrap();
+        package.set_overwrite_policy(OverwritePolicy::Skip);
+        package.unpack_package(true).unwrap();
+        assert_eq!(
+            package.summary(),
+            "package: sample.unitypackage\nassets: 3\nfolders: 1\nskipped: 3\n\
+             overwritten: 0\nfiltered: 0\nwarnings: 0\nbytes written: 0\n"
+        );
+    }
+
+    #[test]
+    fn test_hash_assets_while_unpacking() {
+        let material_guid = "6f1e2d3c4b5a69788796a5b4c3d2e1f0";
+        let new_guid = "0123456789abcdef0123456789abcdef";
+        let material = format!(
+            "%YAML 1.1\n--- !u!21 &2100000\nm_Texture: {{fileID: 2800000, guid: {}, type: 3}}\n",
+            TEXTURE_GUID
+        );
+        let fixture = Fixture::new(&sample_package().asset(
+            material_guid,
+            "Assets/Ground.mat",
+            material.as_bytes(),
+            &script_meta(material_guid),
+        ));
+
+        let config = UnpackConfig {
+            hash_assets: true,
+            guid_remap: [(TEXTURE_GUID.parse().unwrap(), new_guid.parse().unwrap())].into(),
+            rewrite_references: true,
+            ..Default::default()
+        };
+        let mut subject = fixture.open();
+        let report = subject
+            .unpack_with_report(&UnpackOptions::from(config))
+            .unwrap();
+
+        let hash = |path: &str| {
+            report
+                .assets
+                .iter()
+                .find(|a| a.relative_path == Path::new(path))
+                .unwrap()
+                .sha256
+                .clone()
+        };
+        let texture = "5368e7b92b51f4d19d0319af90a37a3795fb9f0ac3c0a64edd06eb7792bd4f78";
+        assert_eq!(hash(TEXTURE_PATH).as_deref(), Some(texture));
+        assert_eq!(hash(FOLDER_PATH), None);
+        // The hash is the one of the installed, rewritten material.
+        let installed = checksums::sha256_file(&fixture.target.join("Assets/Ground.mat")).unwrap();
+        assert_eq!(
+            hash("Assets/Ground.mat"),
+            Some(checksums::to_hex(&installed))
+        );
+
+        let file = subject.get_file(TEXTURE_GUID).unwrap();
+        assert_eq!(checksums::to_hex(&file.sha256().unwrap()), texture);
+
+        // Without the option nothing is hashed.
+        let report = fixture
+            .open()
+            .unpack_with_report(&UnpackOptions::default())
+            .unwrap();
+        assert!(report.assets.iter().all(|a| a.sha256.is_none()));
+    }
+
+    #[test]
+    fn test_compare_with_target() {
+        let readme = "11111111111111111111111111111111";
+        let notes = "22222222222222222222222222222222";
+        let fixture = Fixture::new(
+            &sample_package()
+                .asset(
+                    readme,
+                    "Assets/Readme.txt",
+                    b"read me",
+                    &script_meta(readme),
+                )
+                .asset(notes, "Assets/Notes.txt", b"notes", &script_meta(notes)),
+        );
+        let config = UnpackConfig {
+            meta_naming: MetaNaming::Meta,
+            ..Default::default()
+        };
+        let mut package = fixture.open().with_config(config.clone());
+        package
+            .unpack_package_with(&UnpackOptions::from(config))
+            .unwrap();
+
+        let target = &fixture.target;
+        std::fs::write(target.join(TEXTURE_PATH), "changed").unwrap();
+        std::fs::remove_file(target.join(SCRIPT_PATH)).unwrap();
+        std::fs::remove_file(target.join("Assets/Notes.txt")).unwrap();
+        std::fs::remove_file(target.join("Assets/Notes.txt.meta")).unwrap();
+
+        let status = |package: &UnityPackage| -> Vec<(String, TargetStatus)> {
+            package
+                .compare_with_target(target)
+                .unwrap()
+                .into_iter()
+                .map(|c| (c.relative_path.to_string_lossy().into_owned(), c.status))
+                .collect()
+        };
+        let expected = |readme: TargetStatus| {
+            vec![
+                (String::from("Assets/Notes.txt"), TargetStatus::Missing),
+                (String::from("Assets/Readme.txt"), readme),
+                (String::from(SCRIPT_PATH), TargetStatus::MetaOnly),
+                (String::from(FOLDER_PATH), TargetStatus::Identical),
+                (String::from(TEXTURE_PATH), TargetStatus::Modified),
+            ]
+        };
+        assert_eq!(status(&package), expected(TargetStatus::Identical));
+        assert!(!fixture.tmp.exists());
+        let readme_meta = target.join("Assets/Readme.txt.meta");
+        let meta = std::fs::read_to_string(&readme_meta).unwrap();
+        std::fs::write(&readme_meta, format!("{}userData: changed\n", meta)).unwrap();
+        assert_eq!(status(&package), expected(TargetStatus::Modified));
+        std::fs::write(&readme_meta, meta).unwrap();
+
+        // With `.unitymeta` the meta files of the target are not found.
+        let unity_meta = fixture.open();
+        let statuses = status(&unity_meta);
+        assert_eq!(statuses[1].1, TargetStatus::Modified);
+        assert_eq!(statuses[2].1, TargetStatus::Missing);
+    }
+
+    #[test]
+    fn test_incremental_unpack() {
+        let fixture = Fixture::sample();
+        let options = UnpackOptions::from(UnpackConfig {
+            incremental: true,
+            ..Default::default()
+        });
+
+        let report = fixture.open().unpack_with_report(&options).unwrap();
+        assert_eq!((report.extracted, report.unchanged), (3, 0));
+        let manifest = fs::read_to_string(fixture.target.join(".unpacked-assets")).unwrap();
+        assert!(manifest.contains(&format!(
+            "{}\t5368e7b92b51f4d19d0319af90a37a3795fb9f0ac3c0a64edd06eb7792bd4f78\t{}\n",
+            TEXTURE_GUID, TEXTURE_PATH
+        )));
+
+        // Nothing has changed, so nothing is written.
+        let report = fixture.open().unpack_with_report(&options).unwrap();
+        assert_eq!((report.extracted, report.unchanged), (0, 3));
+        assert_eq!(report.bytes_written, 0);
+        assert!(report
+            .assets
+            .iter()
+            .all(|a| a.status == AssetStatus::Unchanged));
+
+        // A file edited in the target is written again.
+        fs::write(fixture.target.join(TEXTURE_PATH), "edited").unwrap();
+        let report = fixture.open().unpack_with_report(&options).unwrap();
+        assert_eq!((report.overwritten, report.unchanged), (1, 2));
+        assert_eq!(
+            fs::read(fixture.target.join(TEXTURE_PATH)).unwrap(),
+            TEXTURE_CONTENT
+        );
+
+        // An asset the package has lost is reported and kept.
+        PackageBuilder::new()
+            .folder(FOLDER_GUID, FOLDER_PATH)
+            .asset(
+                TEXTURE_GUID,
+                TEXTURE_PATH,
+                TEXTURE_CONTENT,
+                &texture_meta(TEXTURE_GUID),
+            )
+            .write(&fixture.package);
+        let mut subject = fixture.open();
+        let report = subject.unpack_with_report(&options).unwrap();
+        assert_eq!(report.unchanged, 2);
+        assert_eq!(
+            subject.get_warnings(),
+            [Warning::RemovedFromPackage {
+                guid: SCRIPT_GUID.parse().unwrap(),
+                path: PathBuf::from(SCRIPT_PATH),
+            }]
+        );
+        assert!(fixture.target.join(SCRIPT_PATH).is_file());
+        let manifest = fs::read_to_string(fixture.target.join(".unpacked-assets")).unwrap();
+        assert!(manifest.contains(SCRIPT_GUID));
+    }
+
+    #[test]
+    fn test_flatten() {
+        let other_guid = "2bf567ac160bb164fb19b8cb9b55b34b";
+        let builder = sample_package().asset(
+            other_guid,
+            "Assets/Other/IMGP1287.jpg",
+            b"other",
+            &texture_meta(other_guid),
+        );
+        let meta_naming = MetaNaming::default();
+
+        let fixture = Fixture::new(&builder);
+        let mut subject = fixture.open();
+        let config = UnpackConfig {
+            flatten: Some(Flatten::default()),
+            ..Default::default()
+        };
+        let report = subject
+            .unpack_with_report(&UnpackOptions::from(config))
+            .unwrap();
+
+        let target = &fixture.target;
+        assert_eq!(
+            fs::read(target.join("IMGP1287.jpg")).unwrap(),
+            TEXTURE_CONTENT
+        );
+        assert_eq!(fs::read(target.join("IMGP1287_1.jpg")).unwrap(), b"other");
+        assert!(target
+            .join(format!("IMGP1287_1.jpg{}", meta_naming.suffix()))
+            .is_file());
+        assert!(target.join("Player Controller.cs").is_file());
+        assert!(!target.join("Assets").exists());
+
+        let other = subject.get_file(other_guid).unwrap();
+        assert_eq!(other.get_relative_asset_path(), Path::new("IMGP1287_1.jpg"));
+        assert_eq!(
+            other.get_package_path(),
+            Path::new("Assets/Other/IMGP1287.jpg")
+        );
+        let folder = report
+            .assets
+            .iter()
+            .find(|a| a.package_path == Path::new(FOLDER_PATH))
+            .unwrap();
+        assert_eq!(folder.status, AssetStatus::NotInstalled);
+
+        let fixture = Fixture::new(&builder);
+        let config = UnpackConfig {
+            flatten: Some(Flatten {
+                collisions: FlattenCollisions::GuidPrefix,
+                metas: FlattenMetas::Skip,
+            }),
+            ..Default::default()
+        };
+        let report = fixture
+            .open()
+            .unpack_with_report(&UnpackOptions::from(config))
+            .unwrap();
+
+        let mut names: Vec<String> = fs::read_dir(&fixture.target)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "IMGP1287.jpg",
+                "IMGP1287_2bf567ac.jpg",
+                "Player Controller.cs"
+            ]
+        );
+        assert_eq!(report.extracted, 3);
+    }
+
+    #[test]
+    fn test_strip_prefix() {
+        let readme_guid = "3cf567ac160bb164fb19b8cb9b55b34b";
+        let builder = sample_package().asset(
+            readme_guid,
+            "Other/readme.txt",
+            b"hi",
+            &script_meta(readme_guid),
+        );
+        let unpack = |strip_prefix: StripPrefix, skip_unprefixed: bool| {
+            let fixture = Fixture::new(&builder);
+            let config = UnpackConfig {
+                strip_prefix: Some(strip_prefix),
+                skip_unprefixed,
+                ..Default::default()
+            };
+            let report = fixture
+                .open()
+                .unpack_with_report(&UnpackOptions::from(config));
+            (fixture, report)
+        };
+
+        let (fixture, report) = unpack(StripPrefix::Literal(String::from("Assets/")), false);
+        let report = report.unwrap();
+        assert!(fixture
+            .target
+            .join("Textures/Ground/IMGP1287.jpg")
+            .is_file());
+        assert!(fixture
+            .target
+            .join("Scripts/Player Controller.cs")
+            .is_file());
+        assert!(fixture.target.join("Other/readme.txt").is_file());
+        assert!(!fixture.target.join("Assets").exists());
+        let texture = report
+            .assets
+            .iter()
+            .find(|a| a.guid == TEXTURE_GUID.parse::<Guid>().unwrap())
+            .unwrap();
+        assert_eq!(texture.package_path, Path::new(TEXTURE_PATH));
+
+        let (fixture, report) = unpack(StripPrefix::Literal(String::from("Assets")), true);
+        let report = report.unwrap();
+        assert!(!fixture.target.join("Other").exists());
+        let readme = report
+            .assets
+            .iter()
+            .find(|a| a.relative_path == Path::new("Other/readme.txt"))
+            .unwrap();
+        assert_eq!(readme.status, AssetStatus::Filtered);
+
+        let (fixture, report) = unpack(StripPrefix::Components(1), false);
+        report.unwrap();
+        assert!(fixture
+            .target
+            .join("Textures/Ground/IMGP1287.jpg")
+            .is_file());
+        assert!(fixture.target.join("readme.txt").is_file());
+
+        // The folder asset Assets/Textures would become the target itself.
+        let (fixture, report) = unpack(StripPrefix::Components(2), false);
+        assert!(matches!(report, Err(UnityPackageReaderError::PathError(_))));
+        assert!(!fixture.target.join("Ground").exists());
+    }
+
+    #[test]
+    fn test_root_remap() {
+        let script_guid = "4df567ac160bb164fb19b8cb9b55b34b";
+        let thing_guid = "5ef567ac160bb164fb19b8cb9b55b34b";
+        let builder = sample_package()
+            .folder(thing_guid, "Assets/Vendor/Thing")
+            .asset(
+                script_guid,
+                "Assets/Vendor/Thing/Scripts/Thing.cs",
+                b"class Thing {}",
+                &script_meta(script_guid),
+            );
+        let unpack = |drop_unmapped: bool| {
+            let fixture = Fixture::new(&builder);
+            let config = UnpackConfig {
+                meta_naming: MetaNaming::Meta,
+                root_remap: vec![
+                    RootRemap::new(
+                        "Assets/Vendor/Thing/Scripts",
+                        "Packages/com.vendor.thing/Runtime",
+                    ),
+                    RootRemap::new("Assets/Vendor/Thing", "Packages/com.vendor.thing"),
+                ],
+                drop_unmapped,
+                ..Default::default()
+            };
+            let report = fixture
+                .open()
+                .unpack_with_report(&UnpackOptions::from(config))
+                .unwrap();
+            (fixture, report)
+        };
+        let find = |report: &ExtractionReport, guid: &str| {
+            let guid: Guid = guid.parse().unwrap();
+            report
+                .assets
+                .iter()
+                .find(|a| a.guid == guid)
+                .cloned()
+                .unwrap()
+        };
+
+        let (fixture, report) = unpack(false);
+        let script = find(&report, script_guid);
+        assert_eq!(
+            script.relative_path,
+            Path::new("Packages/com.vendor.thing/Runtime/Thing.cs")
+        );
+        assert_eq!(script.root_rule, Some(0));
+        let thing = find(&report, thing_guid);
+        assert_eq!(thing.relative_path, Path::new("Packages/com.vendor.thing"));
+        assert_eq!(thing.root_rule, Some(1));
+        assert!(fixture
+            .target
+            .join("Packages/com.vendor.thing/Runtime/Thing.cs.meta")
+            .is_file());
+        assert!(fixture
+            .target
+            .join("Packages/com.vendor.thing.meta")
+            .is_file());
+        let texture = find(&report, TEXTURE_GUID);
+        assert_eq!(
+            (texture.relative_path.as_path(), texture.root_rule),
+            (Path::new(TEXTURE_PATH), None)
+        );
+        assert!(fixture.target.join(TEXTURE_PATH).is_file());
+
+        let (fixture, report) = unpack(true);
+        assert_eq!(find(&report, TEXTURE_GUID).status, AssetStatus::Filtered);
+        assert!(!fixture.target.join("Assets").exists());
+        assert_eq!(report.extracted, 2);
+
+        let outside = fixture.dir.path().join("outside");
+        for to in [String::from("../outside"), outside.display().to_string()] {
+            let fixture = Fixture::new(&builder);
+            let config = UnpackConfig {
+                root_remap: vec![RootRemap::new("Assets/Vendor", to.as_str())],
+                ..Default::default()
+            };
+            match fixture
+                .open()
+                .unpack_with_report(&UnpackOptions::from(config))
+            {
+                Err(UnityPackageReaderError::PathError(e)) => {
+                    assert!(e.message.unwrap().contains("not inside the target"));
+                }
+                other => panic!("{:?}", other.err()),
+            }
+            assert!(!fixture.dir.path().join("outside").exists() && !outside.exists());
+            assert!(!fixture.target.join(TEXTURE_PATH).exists());
+        }
+    }
+
+    #[test]
+    fn test_path_rewrite() {
+        let fixture = Fixture::sample();
+        let options = |rewrite: PathRewrite| UnpackOptions {
+            path_rewrite: Some(rewrite),
+            ..Default::default()
+        };
+
+        // Shard by the first digits of the guid and leave the scripts out.
+        let shard = PathRewrite::new(|guid: &Guid, path: &Path| {
+            if path.extension().is_some_and(|e| e == "cs") {
+                return None;
+            }
+            Some(Path::new(&guid.to_string()[..2]).join(path))
+        });
+        let report = fixture.open().unpack_with_report(&options(shard)).unwrap();
+        assert!(fixture.target.join("1a").join(TEXTURE_PATH).is_file());
+        assert!(!fixture.target.join(SCRIPT_PATH).exists());
+        let script = report
+            .assets
+            .iter()
+            .find(|a| a.relative_path == Path::new(SCRIPT_PATH))
+            .unwrap();
+        assert_eq!(script.status, AssetStatus::Filtered);
+
+        let fixture = Fixture::sample();
+        let escape = PathRewrite::new(|_: &Guid, path: &Path| Some(Path::new("..").join(path)));
+        let result = fixture.open().unpack_package_with(&options(escape));
+        assert!(matches!(result, Err(UnityPackageReaderError::PathError(_))));
+        assert!(!fixture.tmp.exists());
+        assert!(!fixture.dir.path().join(TEXTURE_PATH).exists());
+
+        let panicking = PathRewrite::new(|_: &Guid, _: &Path| panic!("no rule"));
+        let result = fixture.open().unpack_package_with(&options(panicking));
+        assert!(matches!(result, Err(UnityPackageReaderError::PathError(_))));
+        assert!(!fixture.tmp.exists());
+    }
+
+    /// Records (path, None) for every start and (path, Some(status)) for every end.
+    #[derive(Default)]
+    struct Recorder(Vec<(PathBuf, Option<FileStatus>)>);
+
+    impl ExtractionObserver for Recorder {
+        fn on_start(&mut self, asset: &UnityAssetFile) {
+            self.0.push((asset.get_relative_asset_path().clone(), None));
+        }
+
+        fn on_finished(&mut self, asset: &UnityAssetFile, status: &FileStatus) {
+            self.0.push((
+                asset.get_relative_asset_path().clone(),
+                Some(status.clone()),
+            ));
+        }
+    }
+
+    #[test]
+    fn test_extraction_observer() {
+        let fixture = Fixture::sample();
+        let unpack = |overwrite_policy: OverwritePolicy| {
+            let recorder = Arc::new(Mutex::new(Recorder::default()));
+            let options = UnpackOptions {
+                config: Some(UnpackConfig {
+                    ordering: ExtractOrdering::Alphabetical,
+                    overwrite_policy,
+                    ..Default::default()
+                }),
+                observer: Some(SharedObserver::new(recorder.clone())),
+                ..Default::default()
+            };
+            let result = fixture.open().unpack_package_with(&options);
+            let events = std::mem::take(&mut recorder.lock().unwrap().0);
+            (result, events)
+        };
+
+        let (result, events) = unpack(OverwritePolicy::Overwrite);
+        result.unwrap();
+        let extracted = Some(FileStatus::Extracted);
+        assert_eq!(
+            events,
+            [
+                (PathBuf::from(SCRIPT_PATH), None),
+                (PathBuf::from(SCRIPT_PATH), extracted.clone()),
+                (PathBuf::from(FOLDER_PATH), None),
+                (PathBuf::from(FOLDER_PATH), extracted.clone()),
+                (PathBuf::from(TEXTURE_PATH), None),
+                (PathBuf::from(TEXTURE_PATH), extracted),
+            ]
+        );
+
+        let (result, events) = unpack(OverwritePolicy::Skip);
+        result.unwrap();
+        assert_eq!(
+            events[1],
+            (
+                PathBuf::from(SCRIPT_PATH),
+                Some(FileStatus::Skipped(SkipReason::AlreadyExists))
+            )
+        );
+
+        let (result, events) = unpack(OverwritePolicy::Error);
+        assert!(result.is_err());
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            events[1].1,
+            Some(FileStatus::Failed(
+                UnityPackageReaderError::TargetFileExists(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_panicking_observer_removes_tmp() {
+        struct Panicking;
+        impl ExtractionObserver for Panicking {
+            fn on_start(&mut self, _asset: &UnityAssetFile) {
+                panic!("telemetry is down");
+            }
+        }
+
+        let fixture = Fixture::sample();
+        let options = UnpackOptions {
+            observer: Some(SharedObserver::new(Arc::new(Mutex::new(Panicking)))),
+            ..Default::default()
+        };
+        let mut subject = fixture.open();
+        let result =
+            panic::catch_unwind(AssertUnwindSafe(|| subject.unpack_package_with(&options)));
+        assert!(result.is_err());
+        assert!(!fixture.tmp.exists());
+    }
+
+    #[test]
+    fn test_continue_on_error() {
+        let broken = "6af567ac160bb164fb19b8cb9b55b34b";
+        let fixture = Fixture::new(
+            &sample_package()
+                .file(&format!("{}/pathname", broken), "Assets/Broken.png")
+                .file(&format!("{}/asset", broken), "png")
+                .file(&format!("{}/asset.meta", broken), b"\xff\xfe"),
+        );
+        let options = |continue_on_error: bool, fail_on_asset_errors: bool| UnpackOptions {
+            config: Some(UnpackConfig {
+                continue_on_error,
+                fail_on_asset_errors,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert!(fixture
+            .open()
+            .unpack_with_report(&options(false, false))
+            .is_err());
+        assert!(!fixture.tmp.exists());
+
+        let mut package = fixture.open();
+        let report = package.unpack_with_report(&options(true, false)).unwrap();
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].guid, broken.parse::<Guid>().unwrap());
+        assert_eq!(report.failures[0].path, Path::new("Assets/Broken.png"));
+        assert_eq!(package.get_failures(), &report.failures[..]);
+        assert!(fixture.target.join(TEXTURE_PATH).is_file());
+        assert!(fixture.target.join(SCRIPT_PATH).is_file());
+        assert!(!fixture.target.join("Assets/Broken.png").exists());
+        assert!(!fixture.tmp.exists());
+
+        let fixture = Fixture::new(
+            &sample_package()
+                .file(&format!("{}/pathname", broken), "Assets/Broken.png")
+                .file(&format!("{}/asset.meta", broken), b"\xff\xfe"),
+        );
+        let result = fixture.open().unpack_package_with(&options(true, true));
+        match result {
+            Err(UnityPackageReaderError::PartialFailure(failures)) => {
+                assert_eq!(failures.len(), 1);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(fixture.target.join(TEXTURE_PATH).is_file());
+        assert!(!fixture.tmp.exists());
+    }
+
+    #[test]
+    fn test_continue_on_hash_error() {
+        let fixture = Fixture::sample();
+        fixture.open().analyze_package().unwrap();
+        // Payloads that cannot be hashed when installing from tmp.
+        for guid in [TEXTURE_GUID, SCRIPT_GUID] {
+            let asset = fixture.tmp.join(guid).join("asset");
+            fs::remove_file(&asset).unwrap();
+            fs::create_dir(&asset).unwrap();
+        }
+
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            hash_assets: true,
+            exclude_categories: vec![AssetCategory::Script],
+            ..Default::default()
+        });
+        assert!(matches!(
+            subject.unpack_from_tmp(false),
+            Err(UnityPackageReaderError::CouldNotReadAsset(_))
+        ));
+
+        subject.set_config(UnpackConfig {
+            hash_assets: true,
+            continue_on_error: true,
+            exclude_categories: vec![AssetCategory::Script],
+            ..Default::default()
+        });
+        subject.unpack_from_tmp(true).unwrap();
+        // The excluded script is not read at all.
+        let failures = subject.get_failures();
+        assert_eq!(failures.len(), 1, "{:?}", failures);
+        assert_eq!(failures[0].guid, TEXTURE_GUID.parse::<Guid>().unwrap());
+        assert_eq!(failures[0].path, Path::new(TEXTURE_PATH));
+        assert!(!fixture.target.join(TEXTURE_PATH).exists());
+        assert!(fixture.target.join(FOLDER_PATH).is_dir());
+    }
+
+    #[test]
+    fn test_extraction_warnings() {
+        let odd = "7af567ac160bb164fb19b8cb9b55b34b";
+        let fixture = Fixture::new(
+            &sample_package()
+                .file(
+                    &format!("{}/preview.png", TEXTURE_GUID),
+                    b"\x89PNG\r\n\x1a\n",
+                )
+                .asset(
+                    odd,
+                    "Assets/Odd.png\n00\nexported by hand\n",
+                    b"png",
+                    &texture_meta(odd),
+                )
+                .file(&format!("{}/Thumbs.db", odd), b"thumbs")
+                .file(&format!("{}/preview.png", odd), b"GIF89a"),
+        );
+
+        let report = fixture
+            .open()
+            .unpack_with_report(&UnpackOptions::default())
+            .unwrap();
+        let guid = odd.parse::<Guid>().unwrap();
+        let path = PathBuf::from("Assets/Odd.png");
+        assert_eq!(
+            report.warnings,
+            vec![
+                Warning::PathnameExtraLines {
+                    guid,
+                    path: path.clone(),
+                    lines: vec![String::from("00"), String::from("exported by hand")],
+                },
+                Warning::UnknownEntry {
+                    guid,
+                    path: path.clone(),
+                    name: String::from("Thumbs.db"),
+                },
+                Warning::UnreadablePreview {
+                    guid,
+                    path: path.clone(),
+                    reason: String::from("no PNG signature"),
+                },
+            ]
+        );
+        assert!(fixture.target.join(&path).is_file());
+        assert!(!fixture.target.join("Assets/Thumbs.db").exists());
+
+        // Unity's own second line is no reason for a warning.
+        let fixture = Fixture::new(&PackageBuilder::new().asset(
+            odd,
+            "Assets/Odd.png\n00\n",
+            b"png",
+            &texture_meta(odd),
+        ));
+        let report = fixture
+            .open()
+            .unpack_with_report(&UnpackOptions::default())
+            .unwrap();
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_missing_pathname() {
+        let stray = "8af567ac160bb164fb19b8cb9b55b34b";
+        let fixture = Fixture::new(
+            &sample_package()
+                .file(&format!("{}/asset.meta", stray), folder_meta(stray))
+                .file("not-a-guid/asset", "stray"),
+        );
+        let options = |lenient: bool| UnpackOptions {
+            config: Some(UnpackConfig {
+                lenient,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let result = fixture.open().unpack_package_with(&options(false));
+        assert!(matches!(
+            result,
+            Err(UnityPackageReaderError::CorruptPackage(_))
+        ));
+        assert!(!fixture.tmp.exists());
+
+        let mut package = fixture.open();
+        package.unpack_package_with(&options(true)).unwrap();
+        assert_eq!(
+            package.get_warnings(),
+            &[
+                Warning::MissingPathname {
+                    directory: String::from(stray),
+                    entries: vec![String::from("asset.meta")],
+                },
+                Warning::MissingPathname {
+                    directory: String::from("not-a-guid"),
+                    entries: vec![String::from("asset")],
+                },
+            ]
+        );
+        assert_eq!(package.len(), 3);
+        assert!(fixture.target.join(TEXTURE_PATH).is_file());
+        assert!(fixture.target.join(SCRIPT_PATH).is_file());
+        assert!(!fixture.tmp.exists());
+    }
+
+    #[test]
+    fn test_missing_meta() {
+        let metaless = "9af567ac160bb164fb19b8cb9b55b34b";
+        let fixture = Fixture::new(
+            &sample_package()
+                .file(&format!("{}/pathname", metaless), "Assets/Metaless.txt")
+                .file(&format!("{}/asset", metaless), "text"),
+        );
+        let options = |lenient: bool| UnpackOptions {
+            config: Some(UnpackConfig {
+                lenient,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let result = fixture.open().unpack_package_with(&options(false));
+        assert!(matches!(
+            result,
+            Err(UnityPackageReaderError::CouldReadMetaFile(_))
+        ));
+        assert!(!fixture.tmp.exists());
+
+        let mut package = fixture.open();
+        package.unpack_package_with(&options(true)).unwrap();
+        let guid = metaless.parse::<Guid>().unwrap();
+        assert_eq!(
+            package.get_warnings(),
+            &[Warning::MissingMeta {
+                guid,
+                path: PathBuf::from("Assets/Metaless.txt"),
+            }]
+        );
+        let asset = package.files().find(|f| f.get_guid() == guid).unwrap();
+        assert!(!asset.has_meta());
+        assert!(!asset.is_folder());
+        assert_eq!(asset.get_installed_meta_path(), None);
+        assert!(package
+            .files()
+            .filter(|f| f.get_guid() != guid)
+            .all(|f| f.has_meta()));
+        assert_eq!(
+            fs::read(fixture.target.join("Assets/Metaless.txt")).unwrap(),
+            b"text"
+        );
+        assert!(!fixture
+            .target
+            .join("Assets/Metaless.txt.unitymeta")
+            .exists());
+        assert!(fixture.target.join(TEXTURE_PATH).is_file());
+
+        let manifest = package.manifest().unwrap();
+        let entry = manifest
+            .assets
+            .iter()
+            .find(|a| a.relative_path == "Assets/Metaless.txt")
+            .unwrap();
+        assert!(!entry.has_meta);
+    }
+
+    #[test]
+    fn test_duplicate_guids() {
+        let twice = |pathname: &str, content: &[u8]| {
+            sample_package().asset(TEXTURE_GUID, pathname, content, &texture_meta(TEXTURE_GUID))
+        };
+        let options = |duplicate_guids: DuplicateGuids| UnpackOptions {
+            config: Some(UnpackConfig {
+                duplicate_guids,
+                hash_assets: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let texture = TEXTURE_GUID.parse::<Guid>().unwrap();
+
+        let fixture = Fixture::new(&twice(TEXTURE_PATH, TEXTURE_CONTENT));
+        let report = fixture
+            .open()
+            .unpack_with_report(&options(DuplicateGuids::Error))
+            .unwrap();
+        assert_eq!(
+            report.duplicate_guids,
+            vec![DuplicateGuid {
+                guid: texture,
+                resolution: DuplicateResolution::Identical,
+            }]
+        );
+
+        let fixture = Fixture::new(&twice("Assets/Renamed.jpg", b"newer"));
+        let result = fixture
+            .open()
+            .unpack_package_with(&options(DuplicateGuids::Error));
+        match result {
+            Err(UnityPackageReaderError::DuplicateGuid(e)) => {
+                assert_eq!(e.message, Some(texture.to_string()));
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(!fixture.tmp.exists());
+        assert!(!fixture.target.exists());
+
+        let mut package = fixture.open();
+        package
+            .unpack_package_with(&options(DuplicateGuids::KeepFirst))
+            .unwrap();
+        assert_eq!(
+            package.get_duplicate_guids()[0].resolution,
+            DuplicateResolution::KeptFirst
+        );
+        assert_eq!(
+            fs::read(fixture.target.join(TEXTURE_PATH)).unwrap(),
+            TEXTURE_CONTENT
+        );
+        assert!(!fixture.target.join("Assets/Renamed.jpg").exists());
+
+        let fixture = Fixture::new(&twice("Assets/Renamed.jpg", b"newer"));
+        let report = fixture
+            .open()
+            .unpack_with_report(&options(DuplicateGuids::KeepLast))
+            .unwrap();
+        assert_eq!(
+            report.duplicate_guids[0].resolution,
+            DuplicateResolution::KeptLast
+        );
+        assert_eq!(
+            fs::read(fixture.target.join("Assets/Renamed.jpg")).unwrap(),
+            b"newer"
+        );
+        assert!(!fixture.target.join(TEXTURE_PATH).exists());
+        let renamed = report.assets.iter().find(|a| a.guid == texture).unwrap();
+        let expected: [u8; 32] = sha2::Sha256::digest(b"newer").into();
+        assert_eq!(renamed.sha256, Some(checksums::to_hex(&expected)));
+    }
+
+    #[test]
+    fn test_legacy_meta_data() {
+        let folder = "aaf567ac160bb164fb19b8cb9b55b34b";
+        let texture = "baf567ac160bb164fb19b8cb9b55b34b";
+        let legacy_texture = "fileFormatVersion: 1\nTextureImporter:\n  mipmaps: 1\n";
+        let fixture = Fixture::new(
+            &sample_package()
+                .file(&format!("{}/pathname", folder), "Assets/Legacy")
+                .file(
+                    &format!("{}/metaData", folder),
+                    "fileFormatVersion: 1\nisFolder: 1\n",
+                )
+                .file(&format!("{}/pathname", texture), "Assets/Legacy/Old.png")
+                .file(&format!("{}/asset", texture), b"png")
+                .file(&format!("{}/metaData", texture), legacy_texture),
+        );
+
+        let mut package = fixture.open();
+        package.unpack_package(true).unwrap();
+        assert!(package.get_warnings().is_empty());
+        let by_guid = |guid: &str| {
+            let guid = guid.parse::<Guid>().unwrap();
+            package.files().find(|f| f.get_guid() == guid).unwrap()
+        };
+        assert_eq!(
+            by_guid(folder).get_meta_format(),
+            MetaFormat::LegacyMetaData
+        );
+        assert!(by_guid(folder).is_folder());
+        assert_eq!(
+            by_guid(texture).get_meta_format(),
+            MetaFormat::LegacyMetaData
+        );
+        assert!(!by_guid(texture).is_folder());
+        assert_eq!(by_guid(texture).meta_data().guid, texture);
+        assert_eq!(
+            by_guid(TEXTURE_GUID).get_meta_format(),
+            MetaFormat::AssetMeta
+        );
+
+        assert_eq!(
+            fs::read(fixture.target.join("Assets/Legacy/Old.png")).unwrap(),
+            b"png"
+        );
+        assert_eq!(
+            fs::read_to_string(fixture.target.join("Assets/Legacy/Old.png.unitymeta")).unwrap(),
+            legacy_texture
+        );
+        assert!(fixture.target.join("Assets/Legacy.unitymeta").is_file());
+
+        let manifest = package.manifest().unwrap();
+        let legacy = manifest
+            .assets
+            .iter()
+            .find(|a| a.relative_path == "Assets/Legacy")
+            .unwrap();
+        assert!(legacy.is_folder && legacy.has_meta);
+        assert!(package.validate().unwrap().issues.is_empty());
+    }
+
+    #[test]
+    fn test_require_unity_project() {
+        let fixture = Fixture::sample();
+        let options = UnpackOptions {
+            config: Some(UnpackConfig {
+                require_unity_project: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        fs::create_dir_all(&fixture.target).unwrap();
+        let result = fixture.open().unpack_package_with(&options);
+        match result {
+            Err(UnityPackageReaderError::NotAUnityProject(e)) => {
+                let message = e.message.unwrap();
+                assert!(message.contains("missing Assets and ProjectSettings/ProjectVersion.txt"));
+                assert!(message.contains("the directory is empty"));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(!fixture.tmp.exists());
+        assert_eq!(fs::read_dir(&fixture.target).unwrap().count(), 0);
+
+        fs::create_dir_all(fixture.target.join("Assets")).unwrap();
+        fs::create_dir_all(fixture.target.join("ProjectSettings")).unwrap();
+        fs::write(
+            fixture.target.join("ProjectSettings/ProjectVersion.txt"),
+            "m_EditorVersion: 2021.3.5f1\n",
+        )
+        .unwrap();
+        fixture.open().unpack_package_with(&options).unwrap();
+        assert!(fixture.target.join(TEXTURE_PATH).is_file());
+
+        let mut package = UnityPackage::from_path(
+            &fixture.package,
+            Some(fixture.target.join("Assets")),
+            Some(fixture.tmp.clone()),
+        )
+        .unwrap();
+        package
+            .unpack_package_with(&UnpackOptions {
+                config: Some(UnpackConfig {
+                    strip_prefix: Some(StripPrefix::Literal(String::from("Assets"))),
+                    require_unity_project: true,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(fixture.target.join(TEXTURE_PATH).is_file());
+
+        fs::remove_file(fixture.target.join("ProjectSettings/ProjectVersion.txt")).unwrap();
+        assert!(matches!(
+            package.unpack_package_with(&options),
+            Err(UnityPackageReaderError::NotAUnityProject(_))
+        ));
+    }
+
+    #[test]
+    fn test_generate_upm_manifest() {
+        let fixture = Fixture::sample();
+        let json = fixture
+            .open()
+            .generate_upm_manifest(&UpmManifestOptions::default())
+            .unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(manifest["name"], "com.unitypackage.sample");
+        assert_eq!(manifest["version"], "1.0.0");
+        assert_eq!(manifest["displayName"], "sample");
+        assert!(manifest.get("unity").is_none());
+        assert_eq!(
+            manifest["files"],
+            serde_json::json!([SCRIPT_PATH, TEXTURE_PATH])
+        );
+        assert!(!fixture.target.exists());
+
+        let mut package = fixture.open();
+        package.set_config(UnpackConfig {
+            root_remap: vec![RootRemap::new(
+                "Assets/Textures",
+                "Packages/com.vendor.textures",
+            )],
+            drop_unmapped: true,
+            ..Default::default()
+        });
+        let options = UpmManifestOptions {
+            name: Some(String::from("com.vendor.textures")),
+            display_name: Some(String::from("Vendor Textures")),
+            unity: Some(String::from("2021.3")),
+            write: true,
+            ..Default::default()
+        };
+        let json = package.generate_upm_manifest(&options).unwrap();
+        let written = fixture
+            .target
+            .join("Packages/com.vendor.textures/package.json");
+        assert_eq!(fs::read_to_string(written).unwrap(), json);
+        let manifest: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(manifest["name"], "com.vendor.textures");
+        assert_eq!(manifest["displayName"], "Vendor Textures");
+        assert_eq!(manifest["unity"], "2021.3");
+        assert_eq!(
+            manifest["files"],
+            serde_json::json!(["Ground/IMGP1287.jpg"])
+        );
+
+        let invalid = UpmManifestOptions {
+            name: Some(String::from("Vendor Textures")),
+            ..Default::default()
+        };
+        assert!(matches!(
+            package.generate_upm_manifest(&invalid),
+            Err(UnityPackageReaderError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_convert_to() {
+        let fixture = Fixture::sample();
+        fixture.open().unpack_package(true).unwrap();
+        let files = |entries: BTreeMap<String, Vec<u8>>| -> BTreeMap<String, Vec<u8>> {
+            entries
+                .into_iter()
+                .filter(|(name, _)| !name.ends_with('/'))
+                .collect()
+        };
+        let extracted = files(read_tree(&fixture.target));
+        assert!(
+            extracted.contains_key(TEXTURE_PATH),
+            "{:?}",
+            extracted.keys()
+        );
+
+        let tar = fixture.dir.path().join("sample.tar");
+        fixture
+            .open()
+            .convert_to(&tar, ArchiveFormat::Tar, true)
+            .unwrap();
+        let archived = read_tar(&tar);
+        assert!(archived.contains_key(&format!("{}/", FOLDER_PATH)));
+        assert_eq!(files(archived), extracted);
+        let mut archive = tar::Archive::new(fs::File::open(&tar).unwrap());
+        for entry in archive.entries().unwrap() {
+            let header = entry.unwrap().header().clone();
+            if header.entry_type().is_file() {
+                assert_eq!(header.mtime().unwrap(), 1_600_000_000);
+            }
+        }
+        assert!(!fixture.tmp.exists());
+
+        let mut package = fixture.open();
+        package.set_config(UnpackConfig {
+            strip_prefix: Some(StripPrefix::Literal(String::from("Assets"))),
+            ..Default::default()
+        });
+        package.convert_to(&tar, ArchiveFormat::Tar, false).unwrap();
+        assert_eq!(
+            files(read_tar(&tar)).into_keys().collect::<Vec<_>>(),
+            vec![
+                "Scripts/Player Controller.cs",
+                "Textures/Ground/IMGP1287.jpg"
+            ]
+        );
+
+        let mut package = fixture.open();
+        package.set_config(UnpackConfig {
+            limits: UnpackLimits {
+                max_entry_bytes: 16,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let before = fs::read(&tar).unwrap();
+        match package.convert_to(&tar, ArchiveFormat::Tar, true) {
+            Err(UnityPackageReaderError::LimitExceeded(_)) => {}
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(fs::read(&tar).unwrap(), before);
+        let partial = fixture.dir.path().join("partial.tar");
+        assert!(package
+            .convert_to(&partial, ArchiveFormat::Tar, true)
+            .is_err());
+        let mut names: Vec<_> = fs::read_dir(fixture.dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["sample.tar", "sample.unitypackage", "target"]);
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn test_convert_to_zip() {
+        let fixture = Fixture::sample();
+        fixture.open().unpack_package(true).unwrap();
+        let extracted = read_tree(&fixture.target);
+
+        let zip = fixture.dir.path().join("sample.zip");
+        fixture
+            .open()
+            .convert_to(&zip, ArchiveFormat::Zip, true)
+            .unwrap();
+        let zipped = read_zip(&zip);
+        assert!(zipped.contains_key(&format!("{}/", FOLDER_PATH)));
+        assert_eq!(
+            zipped
+                .into_iter()
+                .filter(|(name, _)| !name.ends_with('/'))
+                .collect::<BTreeMap<_, _>>(),
+            extracted
+                .into_iter()
+                .filter(|(name, _)| !name.ends_with('/'))
+                .collect::<BTreeMap<_, _>>()
+        );
+
+        let mut archive = zip::ZipArchive::new(fs::File::open(&zip).unwrap()).unwrap();
+        let file = archive.by_name(TEXTURE_PATH).unwrap();
+        assert_eq!(file.last_modified().unwrap().year(), 2020);
+        assert!(file.unix_mode().unwrap() & 0o100_000 != 0);
     }
 
     #[test]
-    fn test_new_function_with_tmp_path() {
-        let p = String::from("./test/test/test");
-        let mut t2 = std::env::current_dir().unwrap();
-        t2.push("test");
+    fn test_convert_uses_install_paths() {
+        let device = "77777777777777777777777777777777";
+        let fixture = Fixture::new(&sample_package().asset(
+            device,
+            "Assets/con.txt",
+            b"console",
+            &script_meta(device),
+        ));
+        let config = UnpackConfig {
+            reserved_names: ReservedNames::RenameWithSuffix,
+            ..Default::default()
+        };
+        let tar = fixture.dir.path().join("sample.tar");
+        fixture
+            .open()
+            .with_config(config.clone())
+            .convert_to(&tar, ArchiveFormat::Tar, false)
+            .unwrap();
+        assert_eq!(read_tar(&tar)["Assets/con_.txt"], b"console");
+        fixture
+            .open()
+            .with_config(config)
+            .unpack_package(true)
+            .unwrap();
+        assert!(fixture.target.join("Assets/con_.txt").is_file());
 
-        let subject = UnityPackage::new("test.unitypackage", None, Some(p.clone())).unwrap();
+        let mut config = UnpackConfig {
+            reserved_names: ReservedNames::Error,
+            ..Default::default()
+        };
+        match fixture
+            .open()
+            .with_config(config.clone())
+            .convert_to(&tar, ArchiveFormat::Tar, false)
+        {
+            Err(UnityPackageReaderError::ReservedName(_)) => {}
+            other => panic!("{:?}", other),
+        }
 
-        assert_eq!(subject.get_tmp_dir().unwrap(), PathBuf::from(p));
-        assert_eq!(subject.get_target_dir().unwrap(), t2);
+        config.reserved_names = ReservedNames::Ignore;
+        config.root_remap = vec![RootRemap::new("Assets", "../outside")];
+        match fixture
+            .open()
+            .with_config(config)
+            .convert_to(&tar, ArchiveFormat::Tar, false)
+        {
+            Err(UnityPackageReaderError::PathError(_)) => {}
+            other => panic!("{:?}", other),
+        }
     }
 
     #[test]
-    fn test_new_function_with_target_path() {
-        let path = std::env::current_dir().unwrap();
-        let mut origin = path.clone();
-        origin.push("origin/file.unitypackage");
+    fn test_asset_filter() {
+        let big = "5c0a8a3a2b7e4d1f9e6b0c2d4f6a8b0c";
+        let fixture = Fixture::new(&sample_package().asset(
+            big,
+            "Assets/Big.bytes",
+            &[0u8; 4096],
+            &texture_meta(big),
+        ));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let small = move |asset: &UnityAssetFile| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            asset.get_size() <= 1024
+        };
+        let script = |asset: &UnityAssetFile| {
+            asset
+                .get_relative_asset_path()
+                .extension()
+                .is_some_and(|e| e == "cs")
+        };
+        let filter = All(vec![
+            Box::new(small),
+            Box::new(Not(Box::new(Any(vec![Box::new(script)])))),
+        ]);
 
-        let mut target = path.clone();
-        target.push("target");
+        let report = fixture
+            .open()
+            .unpack_with_report(&UnpackOptions {
+                filter: Some(SharedFilter::new(filter)),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        assert!(fixture.target.join(TEXTURE_PATH).is_file());
+        assert!(!fixture.target.join("Assets/Big.bytes").exists());
+        assert!(!fixture.target.join(SCRIPT_PATH).exists());
+        let status = |guid: &str| {
+            let guid: Guid = guid.parse().unwrap();
+            report
+                .assets
+                .iter()
+                .find(|a| a.guid == guid)
+                .map(|a| a.status)
+        };
+        assert_eq!(status(big), Some(AssetStatus::Filtered));
+        assert_eq!(status(SCRIPT_GUID), Some(AssetStatus::Filtered));
+        assert_eq!(status(TEXTURE_GUID), Some(AssetStatus::Extracted));
 
-        let t = target.clone().into_os_string().into_string().unwrap();
-        let o = origin.clone().into_os_string().into_string().unwrap();
+        // The built-in filters go through the same trait.
+        let config = UnpackConfig {
+            exclude_categories: vec![AssetCategory::Script],
+            ..Default::default()
+        };
+        let fixture = Fixture::sample();
+        let mut subject = fixture.open();
+        subject
+            .unpack_package_with(&UnpackOptions {
+                filter: Some(SharedFilter::new(config)),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(fixture.target.join(TEXTURE_PATH).is_file());
+        assert!(!fixture.target.join(SCRIPT_PATH).exists());
 
-        let subject = UnityPackage::new(&o, Some(t), None).unwrap();
+        // The filter of the options ends with the unpack.
+        let plan = subject.plan_unpack().unwrap();
+        assert!(plan.iter().any(|p| p.guid == SCRIPT_GUID));
+    }
 
-        assert_eq!(subject.get_target_dir().unwrap(), target);
-        assert_eq!(subject.get_package_file_name().unwrap(), "file");
+    #[test]
+    fn test_stable_order() {
+        // The same assets, stored in the opposite order.
+        let reversed = PackageBuilder::new()
+            .asset(
+                SCRIPT_GUID,
+                SCRIPT_PATH,
+                SCRIPT_CONTENT,
+                &script_meta(SCRIPT_GUID),
+            )
+            .asset(
+                TEXTURE_GUID,
+                TEXTURE_PATH,
+                TEXTURE_CONTENT,
+                &texture_meta(TEXTURE_GUID),
+            )
+            .folder(FOLDER_GUID, FOLDER_PATH);
+        let fixtures = [Fixture::sample(), Fixture::new(&reversed)];
+
+        let manifests: Vec<Vec<u8>> = fixtures
+            .iter()
+            .map(|fixture| {
+                let path = fixture.dir.path().join("manifest.csv");
+                fixture.open().write_manifest_csv(&path, true).unwrap();
+                fs::read(path).unwrap()
+            })
+            .collect();
+        assert_eq!(manifests[0], manifests[1]);
+
+        let reports: Vec<Vec<(PathBuf, Guid, AssetStatus)>> = fixtures
+            .iter()
+            .map(|fixture| {
+                let report = fixture
+                    .open()
+                    .unpack_with_report(&UnpackOptions::default())
+                    .unwrap();
+                report
+                    .assets
+                    .into_iter()
+                    .map(|a| (a.relative_path, a.guid, a.status))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(reports[0], reports[1]);
+        let paths: Vec<&Path> = reports[0].iter().map(|r| r.0.as_path()).collect();
+        let mut sorted = paths.clone();
+        sorted.sort();
+        assert_eq!(paths, sorted);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_stable_manifest_json() {
+        let fixture = Fixture::sample();
+        let write = |name: &str| {
+            let path = fixture.dir.path().join(name);
+            fixture.open().write_manifest_json(&path).unwrap();
+            fs::read(path).unwrap()
+        };
+
+        assert_eq!(write("first.json"), write("second.json"));
+    }
+
+    #[test]
+    fn test_find() {
+        let fixture = Fixture::sample();
+        let mut package = fixture.open();
+        let paths = |found: Vec<&UnityAssetFile>| -> Vec<PathBuf> {
+            found
+                .into_iter()
+                .map(|f| f.get_relative_asset_path().clone())
+                .collect()
+        };
+
+        // Nothing is indexed before an unpack, the listing works anyway.
+        assert!(package.find("Textures").unwrap().is_empty());
+        let entries = package.find_entries("^Assets/Textures", false).unwrap();
+        let mut listed: Vec<&Path> = entries.iter().map(|e| e.relative_path.as_path()).collect();
+        listed.sort();
+        assert_eq!(listed, [Path::new(FOLDER_PATH), Path::new(TEXTURE_PATH)]);
+        assert!(package.find_entries("^textures", true).unwrap().is_empty());
+
+        package.unpack_package(true).unwrap();
         assert_eq!(
-            subject.get_path(),
-            origin.into_os_string().into_string().unwrap()
+            paths(package.find("Textures").unwrap()),
+            [PathBuf::from(FOLDER_PATH), PathBuf::from(TEXTURE_PATH)]
+        );
+        assert_eq!(
+            paths(package.find(r"^Assets/Textures$").unwrap()),
+            [PathBuf::from(FOLDER_PATH)]
+        );
+        assert_eq!(
+            paths(package.find(r"\.(jpg|cs)$").unwrap()),
+            [PathBuf::from(SCRIPT_PATH), PathBuf::from(TEXTURE_PATH)]
+        );
+        assert!(package.find("player controller").unwrap().is_empty());
+        assert_eq!(
+            paths(package.find_with("player controller", true).unwrap()),
+            [PathBuf::from(SCRIPT_PATH)]
         );
+
+        for result in [
+            package.find("Assets/[").map(|_| ()),
+            package.find_entries("(", false).map(|_| ()),
+        ] {
+            assert!(matches!(
+                result,
+                Err(UnityPackageReaderError::InvalidPattern(_))
+            ));
+        }
     }
 
     #[test]
-    #[serial]
-    fn test_asset_file_internals() {
-        let base = get_test_base_path();
-        println!("{:?}", base);
-        let mut tmp = base.clone();
-        tmp.push("assets/tmp");
+    fn test_guid_map() {
+        let fixture = Fixture::sample();
+        let mut package = fixture.open();
+        let texture: Guid = TEXTURE_GUID.parse().unwrap();
 
-        let mut target = base.clone();
-        target.push("assets/target");
+        let listed = package.guid_map().unwrap();
+        assert_eq!(listed.by_guid.len(), 3);
+        assert_eq!(listed.by_guid[&texture], Path::new(TEXTURE_PATH));
+        assert_eq!(listed.by_path[Path::new(TEXTURE_PATH)], texture);
+        assert!(listed.is_unique());
 
-        let mut absolute_path = base.clone();
-        absolute_path.push("assets/test.unitypackage");
+        // After the unpack the map follows the target paths.
+        package
+            .unpack_package_with(&UnpackOptions {
+                config: Some(UnpackConfig {
+                    strip_prefix: Some(StripPrefix::Literal(String::from("Assets"))),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .unwrap();
+        let indexed = package.indexed_guid_map();
+        assert_eq!(
+            indexed.by_guid[&texture],
+            Path::new("Textures/Ground/IMGP1287.jpg")
+        );
+        assert_eq!(
+            indexed.by_path[Path::new("Textures/Ground/IMGP1287.jpg")],
+            texture
+        );
 
-        let mut subject = match UnityPackage::new(
-            absolute_path.to_str().unwrap(),
-            Some(target.to_str().unwrap().to_string()),
-            Some(tmp.to_str().unwrap().to_string()),
-        ) {
-            Ok(s) => s,
-            Err(_) => panic!("Could not unpack package"),
+        let path = fixture.dir.path().join("guids.tsv");
+        listed.write_tsv(&path).unwrap();
+        let tsv = fs::read_to_string(&path).unwrap();
+        assert!(tsv.starts_with("guid\tpath\n"));
+        assert!(tsv.contains(&format!("{}\t{}\n", TEXTURE_GUID, TEXTURE_PATH)));
+        assert_eq!(tsv.lines().count(), 4);
+
+        let duplicate = "3c4d5e6f708192a3b4c5d6e7f8091a2b";
+        let fixture = Fixture::new(&sample_package().asset(
+            duplicate,
+            TEXTURE_PATH,
+            b"copy",
+            &texture_meta(duplicate),
+        ));
+        let map = fixture.open().guid_map().unwrap();
+        assert_eq!(map.by_guid.len(), 4);
+        assert_eq!(
+            map.duplicate_paths[Path::new(TEXTURE_PATH)],
+            [texture, duplicate.parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_overwrite_if_changed() {
+        let fixture = Fixture::sample();
+        let texture = fixture.target.join(TEXTURE_PATH);
+        let meta = fixture.target.join(format!("{}.unitymeta", TEXTURE_PATH));
+        let config = UnpackConfig {
+            overwrite_policy: OverwritePolicy::IfChanged,
+            ..Default::default()
+        };
+        let options = UnpackOptions::from(config.clone());
+        let unpack = || fixture.open().unpack_with_report(&options).unwrap();
+        let status = |report: &ExtractionReport, guid: &str| {
+            let guid: Guid = guid.parse().unwrap();
+            report
+                .assets
+                .iter()
+                .find(|a| a.guid == guid)
+                .unwrap()
+                .status
+        };
+        let mtime = |path: &Path| fs::metadata(path).unwrap().modified().unwrap();
+        let age = |path: &Path| {
+            let old = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(old)
+                .unwrap();
+            old
         };
 
-        match subject.unpack_package(true) {
-            Ok(e) => e,
-            Err(e) => {
-                panic!("{}", e)
+        assert_eq!(status(&unpack(), TEXTURE_GUID), AssetStatus::Extracted);
+        let (texture_time, meta_time) = (age(&texture), age(&meta));
+
+        // Nothing differs, nothing is written.
+        let report = unpack();
+        assert_eq!(report.unchanged, 3);
+        assert_eq!(status(&report, TEXTURE_GUID), AssetStatus::Unchanged);
+        assert_eq!((mtime(&texture), mtime(&meta)), (texture_time, meta_time));
+
+        // Only the meta differs, the asset keeps its time.
+        fs::write(&meta, "edited").unwrap();
+        let report = unpack();
+        assert_eq!(status(&report, TEXTURE_GUID), AssetStatus::Overwritten);
+        assert_eq!(status(&report, SCRIPT_GUID), AssetStatus::Unchanged);
+        assert_eq!(mtime(&texture), texture_time);
+        assert_eq!(
+            fs::read_to_string(&meta).unwrap(),
+            texture_meta(TEXTURE_GUID)
+        );
+
+        fs::write(&texture, "edited").unwrap();
+        let plan = fixture
+            .open()
+            .with_config(config.clone())
+            .plan_unpack()
+            .unwrap();
+        let actions: Vec<(&Path, PlannedAction)> = plan
+            .iter()
+            .map(|p| (p.relative_path.as_path(), p.action))
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                (Path::new(SCRIPT_PATH), PlannedAction::Skip),
+                (Path::new(FOLDER_PATH), PlannedAction::Skip),
+                (Path::new(TEXTURE_PATH), PlannedAction::Install),
+            ]
+        );
+        let report = unpack();
+        assert_eq!(status(&report, TEXTURE_GUID), AssetStatus::Overwritten);
+        assert_eq!(fs::read(&texture).unwrap(), TEXTURE_CONTENT);
+    }
+
+    #[test]
+    fn test_backup() {
+        let fixture = Fixture::sample();
+        let texture = fixture.target.join(TEXTURE_PATH);
+        let meta = fixture.target.join(format!("{}.unitymeta", TEXTURE_PATH));
+        let backup_of = |path: &Path, suffix: &str| {
+            let mut name = path.as_os_str().to_os_string();
+            name.push(suffix);
+            PathBuf::from(name)
+        };
+        let unpack = |backup: Backup, atomic: bool| {
+            let report = fixture
+                .open()
+                .unpack_with_report(&UnpackOptions {
+                    config: Some(UnpackConfig {
+                        backup: Some(backup),
+                        atomic,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .unwrap();
+            let guid: Guid = TEXTURE_GUID.parse().unwrap();
+            report
+                .assets
+                .into_iter()
+                .find(|a| a.guid == guid)
+                .unwrap()
+                .backups
+        };
+
+        // Nothing is overwritten, nothing is backed up.
+        assert!(unpack(Backup::Suffix, false).is_empty());
+        fs::write(&texture, "edited").unwrap();
+        assert_eq!(
+            unpack(Backup::Suffix, false),
+            [backup_of(&texture, ".bak"), backup_of(&meta, ".bak")]
+        );
+        assert_eq!(fs::read(backup_of(&texture, ".bak")).unwrap(), b"edited");
+        assert_eq!(
+            fs::read_to_string(backup_of(&meta, ".bak")).unwrap(),
+            texture_meta(TEXTURE_GUID)
+        );
+        assert_eq!(fs::read(&texture).unwrap(), TEXTURE_CONTENT);
+
+        // An earlier backup is not replaced.
+        fs::write(&texture, "edited again").unwrap();
+        assert_eq!(
+            unpack(Backup::Suffix, false),
+            [backup_of(&texture, ".bak.1"), backup_of(&meta, ".bak.1")]
+        );
+        assert_eq!(fs::read(backup_of(&texture, ".bak")).unwrap(), b"edited");
+        assert_eq!(
+            fs::read(backup_of(&texture, ".bak.1")).unwrap(),
+            b"edited again"
+        );
+
+        // Into a directory, also when staged.
+        let directory = fixture.dir.path().join("backups");
+        fs::write(&texture, "edited once more").unwrap();
+        let backups = unpack(Backup::Directory(directory.clone()), true);
+        let backed_up = backup_of(&directory.join(TEXTURE_PATH), ".bak");
+        assert_eq!(backups[0], backed_up);
+        assert_eq!(fs::read(&backed_up).unwrap(), b"edited once more");
+        assert_eq!(fs::read(&texture).unwrap(), TEXTURE_CONTENT);
+    }
+
+    #[test]
+    fn test_check_disk_space() {
+        let fixture = Fixture::sample();
+        assert!(fixture.open().estimated_size().unwrap().total() > 0);
+
+        let options = UnpackOptions {
+            config: Some(UnpackConfig {
+                check_disk_space: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        fixture.open().unpack_package_with(&options).unwrap();
+        assert!(fixture.target.join(TEXTURE_PATH).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_readonly_target() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let fixture = Fixture::sample();
+        let texture = fixture.target.join(TEXTURE_PATH);
+        let unpack = |clear_readonly: bool, restore_readonly: bool| {
+            let options = UnpackOptions {
+                config: Some(UnpackConfig {
+                    clear_readonly,
+                    restore_readonly,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            fixture.open().unpack_package_with(&options)
+        };
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        fixture.open().unpack_package(true).unwrap();
+        fs::write(&texture, b"checked out").unwrap();
+        fs::set_permissions(&texture, fs::Permissions::from_mode(0o444)).unwrap();
+
+        match unpack(false, false) {
+            Err(UnityPackageReaderError::ReadOnlyTarget(e)) => {
+                assert!(e.message.unwrap().contains(&texture.display().to_string()));
+            }
+            other => panic!("Expected ReadOnlyTarget, got {:?}", other),
+        }
+        assert_eq!(fs::read(&texture).unwrap(), b"checked out");
+
+        unpack(true, true).unwrap();
+        assert_eq!(fs::read(&texture).unwrap(), TEXTURE_CONTENT);
+        assert_eq!(mode(&texture) & 0o222, 0);
+
+        unpack(true, false).unwrap();
+        assert_eq!(fs::read(&texture).unwrap(), TEXTURE_CONTENT);
+        assert_ne!(mode(&texture) & 0o200, 0);
+    }
+
+    #[test]
+    fn test_estimated_size() {
+        fn sizes(dir: &Path, result: &mut (u64, u64)) {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    sizes(&path, result);
+                } else if path.extension().is_some_and(|e| e == "unitymeta") {
+                    result.1 += fs::metadata(&path).unwrap().len();
+                } else {
+                    result.0 += fs::metadata(&path).unwrap().len();
+                }
             }
+        }
+
+        let fixture = Fixture::sample();
+        let mut package = fixture.open();
+        let estimate = package.estimated_size().unwrap();
+        assert!(!fixture.tmp.exists());
+        assert!(estimate.entries >= 2 * package.count_entries().unwrap());
+
+        package.unpack_package(true).unwrap();
+        let mut extracted = (0, 0);
+        sizes(&fixture.target, &mut extracted);
+        assert_eq!((estimate.assets, estimate.metas), extracted);
+        assert_eq!(estimate.installed(), extracted.0 + extracted.1);
+        assert!(estimate.other > 0);
+        assert!(estimate.total() >= estimate.installed() + estimate.other);
+    }
+
+    #[test]
+    fn test_buffer_sizes() {
+        let fixture = Fixture::sample();
+        let mut package = fixture.open();
+        for invalid in [
+            BufferSizes {
+                read: 0,
+                ..Default::default()
+            },
+            BufferSizes {
+                chunk: MAX_BUFFER_SIZE + 1,
+                ..Default::default()
+            },
+            BufferSizes {
+                copy: Some(0),
+                ..Default::default()
+            },
+        ] {
+            let options = UnpackOptions {
+                buffer_sizes: invalid,
+                ..Default::default()
+            };
+            assert!(matches!(
+                package.unpack_package_with(&options),
+                Err(UnityPackageReaderError::InvalidConfig(_))
+            ));
+        }
+        assert!(!fixture.tmp.exists());
+
+        let options = UnpackOptions {
+            buffer_sizes: BufferSizes {
+                read: 1024,
+                chunk: 512,
+                copy: Some(4096),
+            },
+            ..Default::default()
         };
+        package.unpack_package_with(&options).unwrap();
+        assert!(fixture.target.join(TEXTURE_PATH).exists());
+        #[cfg(not(feature = "mmap"))]
+        match package.open_package_file().unwrap() {
+            PackageSource::File(reader) => assert_eq!(reader.capacity(), 1024),
+            _ => panic!("expected a buffered file"),
+        }
 
-        let file = match subject.get_file(&"1af567ac160bb164fb19b8cb9b55b34b".to_string()) {
-            Some(f) => f,
-            None => {
-                panic!("The file does not exist in this package.")
+        // Tmp and target on different volumes: the assets are copied.
+        let tmp = PathBuf::from("/memory/tmp");
+        let fs = Arc::new(MemoryFileSystem::default());
+        fs.write(
+            tmp.join(TEXTURE_GUID).join("asset.meta"),
+            texture_meta(TEXTURE_GUID),
+        );
+        fs.write(tmp.join(TEXTURE_GUID).join("pathname"), TEXTURE_PATH);
+        fs.write(tmp.join(TEXTURE_GUID).join("asset"), TEXTURE_CONTENT);
+        for file in ["asset", "asset.meta"] {
+            fs.fail(
+                "rename",
+                tmp.join(TEXTURE_GUID).join(file),
+                std::io::ErrorKind::CrossesDevices,
+            );
+        }
+        let mut package = UnityPackage::from_path(
+            "/memory/p.unitypackage",
+            Some(PathBuf::from("/memory/target")),
+            Some(tmp),
+        )
+        .unwrap()
+        .with_file_system(fs.clone());
+        UnpackSession::from_tmp(&mut package, &options)
+            .run()
+            .unwrap();
+        assert_eq!(fs.copy_buffers(), [4096, 4096]);
+    }
+
+    #[test]
+    fn test_reserved_names() {
+        let folder_guid = "0f1e2d3c4b5a69788796a5b4c3d2e1f0";
+        let package = PackageBuilder::new()
+            .folder(folder_guid, "Assets/Con")
+            .asset(
+                TEXTURE_GUID,
+                "Assets/Con/aux.png",
+                TEXTURE_CONTENT,
+                &texture_meta(TEXTURE_GUID),
+            );
+        let config = |reserved_names| UnpackConfig {
+            reserved_names,
+            ..Default::default()
+        };
+
+        let fixture = Fixture::new(&package);
+        let mut subject = fixture.open().with_config(config(ReservedNames::Error));
+        match subject.unpack_package(true) {
+            Err(UnityPackageReaderError::ReservedName(e)) => {
+                let message = e.message.unwrap();
+                assert!(message.contains("'Assets/Con'"));
+                assert!(message.contains("'Assets/Con/aux.png'"));
             }
+            other => panic!("{:?}", other.err()),
+        }
+        assert!(!fixture.target.join("Assets").exists());
+
+        let fixture = Fixture::new(&package);
+        let options = UnpackOptions {
+            config: Some(config(ReservedNames::RenameWithSuffix)),
+            ..Default::default()
         };
+        let mut subject = fixture.open();
+        let report = subject.unpack_with_report(&options).unwrap();
+        let renamed = Path::new("Assets/Con_/aux_.png");
+        assert_eq!(
+            fs::read(fixture.target.join(renamed)).unwrap(),
+            TEXTURE_CONTENT
+        );
+        assert!(fixture
+            .target
+            .join("Assets/Con_/aux_.png.unitymeta")
+            .is_file());
+        assert!(fixture.target.join("Assets/Con_.unitymeta").is_file());
+        assert!(!fixture.target.join("Assets/Con").exists());
+        assert!(subject.get_warnings().contains(&Warning::ReservedName {
+            guid: TEXTURE_GUID.parse().unwrap(),
+            path: PathBuf::from("Assets/Con/aux.png"),
+            renamed: renamed.to_path_buf(),
+        }));
+        let texture = report
+            .assets
+            .iter()
+            .find(|a| a.relative_path == renamed)
+            .unwrap();
+        assert_eq!(texture.package_path, Path::new("Assets/Con/aux.png"));
+    }
 
-        let working_dir = get_test_base_path();
+    #[test]
+    fn test_count_by_importer() {
+        let metaless = "9af567ac160bb164fb19b8cb9b55b34b";
+        let fixture = Fixture::new(
+            &sample_package()
+                .file(&format!("{}/pathname", metaless), "Assets/Metaless.txt")
+                .file(&format!("{}/asset", metaless), "text"),
+        );
+        let mut package = fixture.open();
+        package
+            .unpack_package_with(&UnpackOptions {
+                config: Some(UnpackConfig {
+                    lenient: true,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .unwrap();
 
-        let mut absolute_meta = working_dir.clone();
-        absolute_meta.push("assets/Assets/Textures/Ground/IMGP1287.jpg.unitymeta");
+        let importer = |guid: &str| package.get_file(guid).unwrap().importer();
+        assert_eq!(importer(TEXTURE_GUID), Importer::Texture);
+        assert_eq!(importer(FOLDER_GUID), Importer::Default);
+        assert_eq!(importer(metaless), Importer::Unknown);
+        assert_eq!(
+            package.count_by_importer(),
+            BTreeMap::from([
+                (Importer::Texture, 1),
+                (Importer::Mono, 1),
+                (Importer::Default, 1),
+                (Importer::Unknown, 1),
+            ])
+        );
+    }
 
-        let mut absolute_target = working_dir.clone();
-        absolute_target.push("assets/Assets/Textures/Ground/IMGP1287.jpg");
+    #[test]
+    fn test_code_report() {
+        let plugin_guid = "55555555555555555555555555555555";
+        let asmdef_guid = "66666666666666666666666666666666";
+        let plugin_meta = format!(
+            "fileFormatVersion: 2\nguid: {}\nPluginImporter:\n  serializedVersion: 2\n  isPreloaded: 0\n  platformData:\n  - first:\n      Any: \n    second:\n      enabled: 0\n      settings: {{}}\n  - first:\n      Android: Android\n    second:\n      enabled: 1\n      settings:\n        CPU: ARMv7\n",
+            plugin_guid
+        );
+        let fixture = Fixture::new(
+            &sample_package()
+                .asset(
+                    plugin_guid,
+                    "Assets/Plugins/Android/Vendor.DLL",
+                    b"MZ",
+                    &plugin_meta,
+                )
+                .asset(
+                    asmdef_guid,
+                    "Assets/Vendor/Vendor.asmdef",
+                    b"{\"name\": \"Vendor\"}",
+                    &script_meta(asmdef_guid),
+                ),
+        );
 
-        assert_eq!(file.get_guid(), "1af567ac160bb164fb19b8cb9b55b34b");
+        let report = fixture.open().code_report().unwrap();
+        assert!(report.ships_code());
+        assert!(!fixture.tmp.exists());
+        assert_eq!(
+            report.assembly_definitions,
+            vec![CodeAsset {
+                guid: asmdef_guid.parse().unwrap(),
+                path: PathBuf::from("Assets/Vendor/Vendor.asmdef"),
+            }]
+        );
+        assert!(report.assembly_references.is_empty());
+        assert_eq!(
+            report.scripts,
+            vec![CodeAsset {
+                guid: SCRIPT_GUID.parse().unwrap(),
+                path: PathBuf::from(SCRIPT_PATH),
+            }]
+        );
+        assert_eq!(report.plugins.len(), 1);
+        let plugin = &report.plugins[0];
+        assert_eq!(
+            plugin.path,
+            PathBuf::from("Assets/Plugins/Android/Vendor.DLL")
+        );
+        assert_eq!(
+            plugin.enabled_platforms().collect::<Vec<_>>(),
+            vec!["Android"]
+        );
+        assert_eq!(plugin.platforms[1].cpu.as_deref(), Some("ARMv7"));
 
+        let limited = fixture.open().with_config(UnpackConfig {
+            limits: UnpackLimits {
+                max_entries: 4,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        assert!(matches!(
+            limited.code_report(),
+            Err(UnityPackageReaderError::LimitExceeded(_))
+        ));
+
+        let huge = "77777777777777777777777777777777";
+        let fixture = Fixture::new(&sample_package().asset(
+            huge,
+            "Assets/Huge.cs",
+            b"",
+            &format!("{}\n{}", script_meta(huge), "#".repeat(5 << 20)),
+        ));
+        assert!(matches!(
+            fixture.open().code_report(),
+            Err(UnityPackageReaderError::CorruptPackage(_))
+        ));
+    }
+
+    #[test]
+    fn test_analyze_only_builds_index_without_target() {
+        let fixture = Fixture::sample();
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            install: false,
+            ..Default::default()
+        });
+
+        // delete_tmp is ignored in analyze-only mode.
+        subject.unpack_package(true).unwrap();
+
+        assert!(!fixture.target.exists());
+        assert!(fixture.tmp.exists());
+        let texture = subject.get_file(TEXTURE_GUID).unwrap();
+        assert_eq!(texture.get_size(), TEXTURE_CONTENT.len() as u64);
         assert_eq!(
-            file.get_relative_asset_path().to_str().unwrap(),
-            "Assets/Textures/Ground/IMGP1287.jpg"
+            std::fs::read(texture.get_absolute_asset_path()).unwrap(),
+            TEXTURE_CONTENT
         );
+        assert!(texture.get_absolute_meta_file_path().exists());
+        assert_eq!(subject.get_stats().target_bytes_written, 0);
+    }
 
-        std::fs::remove_dir_all(target.clone()).unwrap();
+    #[test]
+    fn test_analyze_package() {
+        let fixture = Fixture::sample();
+        let mut subject = fixture.open();
+        subject.analyze_package().unwrap();
 
-        assert!(!target.exists());
-        assert!(!tmp.exists());
+        assert!(!fixture.target.exists());
+        assert!(subject.get_file(SCRIPT_GUID).is_some());
+        assert!(subject.get_config().install);
     }
 
-    // Check if tmp directory still exists after unpacking.
     #[test]
-    #[serial]
-    fn test_deleting_tmp_files() {
-        let base = get_test_base_path();
-        println!("{:?}", base);
-        let mut tmp = base.clone();
-        tmp.push("assets/tmp");
+    fn test_default_options_match_unpack_package() {
+        let options = UnpackOptions::default();
+        assert!(options.delete_tmp);
+        assert_eq!(options.config, None);
 
-        let mut target = base.clone();
-        target.push("assets/target");
+        let with_options = Fixture::sample();
+        let mut subject = with_options.open();
+        subject.unpack_package_with(&options).unwrap();
 
-        let mut absolute_path = base.clone();
-        absolute_path.push("assets/test.unitypackage");
+        let classic = Fixture::sample();
+        let mut reference = classic.open();
+        reference.unpack_package(true).unwrap();
+
+        for fixture in [&with_options, &classic] {
+            assert!(!fixture.tmp.exists());
+            assert!(fixture.target.join(TEXTURE_PATH).exists());
+            assert!(fixture
+                .target
+                .join(format!("{}.unitymeta", TEXTURE_PATH))
+                .exists());
+            // Folder assets are created by default.
+            assert!(fixture
+                .target
+                .join(format!("{}.unitymeta", FOLDER_PATH))
+                .exists());
+        }
+        assert_eq!(subject.get_stats(), reference.get_stats());
+    }
+
+    #[test]
+    fn test_options_are_reusable() {
+        let options = UnpackOptions {
+            delete_tmp: false,
+            config: Some(UnpackConfig {
+                meta_naming: MetaNaming::Meta,
+                ..Default::default()
+            }),
+            path_rewrite: None,
+            observer: None,
+            filter: None,
+            buffer_sizes: BufferSizes::default(),
+        };
+
+        for _ in 0..2 {
+            let fixture = Fixture::sample();
+            let mut subject = fixture.open();
+            subject.unpack_package_with(&options.clone()).unwrap();
+
+            assert!(fixture.tmp.exists());
+            assert!(fixture
+                .target
+                .join(format!("{}.meta", TEXTURE_PATH))
+                .exists());
+            assert_eq!(Some(subject.get_config()), options.config.as_ref());
+        }
+
+        // Without a configuration, the one of the package is kept.
+        let fixture = Fixture::sample();
+        let mut subject = fixture.open().with_config(UnpackConfig {
+            meta_naming: MetaNaming::Meta,
+            ..Default::default()
+        });
+        subject
+            .unpack_package_with(&UnpackOptions::default())
+            .unwrap();
+        assert!(fixture
+            .target
+            .join(format!("{}.meta", TEXTURE_PATH))
+            .exists());
+        assert_eq!(subject.get_config().meta_naming, MetaNaming::Meta);
+    }
+
+    #[test]
+    fn test_files_iterator() {
+        let fixture = Fixture::sample();
+        let mut subject = fixture.open();
+        assert!(subject.is_empty());
+        subject.unpack_package(true).unwrap();
+
+        let files: Vec<&UnityAssetFile> = subject.files().collect();
+        assert_eq!(files.len(), subject.len());
+        assert_eq!(subject.len(), 3);
+        assert!(files.iter().any(|f| f.get_guid() == TEXTURE_GUID));
+
+        let paths: Vec<&PathBuf> = files.iter().map(|f| f.get_relative_asset_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                &PathBuf::from(SCRIPT_PATH),
+                &PathBuf::from(FOLDER_PATH),
+                &PathBuf::from(TEXTURE_PATH)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lookup_by_guid_and_path() {
+        let fixture = Fixture::sample();
+        let mut subject = fixture.open();
+        subject.unpack_package(true).unwrap();
+
+        let by_guid = subject.get_file(TEXTURE_GUID).unwrap();
+        let by_path = subject.get_file_by_path(TEXTURE_PATH).unwrap();
+        assert_eq!(by_guid.get_guid(), by_path.get_guid());
+        assert!(subject
+            .get_file_by_guid(TEXTURE_GUID.parse().unwrap())
+            .is_some());
+        assert!(subject.get_file(&TEXTURE_GUID.to_uppercase()).is_none());
+        assert!(subject.get_file(TEXTURE_PATH).is_none());
+
+        let windows = TEXTURE_PATH.replace('/', "\\");
+        assert_eq!(
+            subject.get_file_by_path(&windows).unwrap().get_guid(),
+            TEXTURE_GUID
+        );
+        assert_eq!(
+            subject
+                .get_file_by_path(Path::new(SCRIPT_PATH))
+                .unwrap()
+                .get_guid(),
+            SCRIPT_GUID
+        );
+        assert!(subject.get_file_by_path("Assets/Missing.png").is_none());
+    }
 
-        let mut subject = UnityPackage::new(
-            absolute_path.to_str().unwrap(),
-            Some(target.to_str().unwrap().to_string()),
-            Some(tmp.to_str().unwrap().to_string()),
+    #[test]
+    fn test_compatibility_report() {
+        let project = tempfile::tempdir().unwrap();
+        let root = project.path();
+        std::fs::create_dir_all(root.join("ProjectSettings")).unwrap();
+        std::fs::create_dir_all(root.join("Packages")).unwrap();
+        let ui = root.join("Library/PackageCache/com.unity.ugui@1.0.0/Runtime");
+        std::fs::create_dir_all(&ui).unwrap();
+        std::fs::write(
+            root.join("ProjectSettings/ProjectVersion.txt"),
+            "m_EditorVersion: 2018.4.36f1\nm_EditorVersionWithRevision: 2018.4.36f1 (6cd387d23174)\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("ProjectSettings/ProjectSettings.asset"),
+            "PlayerSettings:\n  m_BuildTargetBatching:\n  - m_BuildTarget: Standalone\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("Packages/manifest.json"),
+            "{\"dependencies\": {\"com.unity.ugui\": \"1.0.0\"}}",
+        )
+        .unwrap();
+        std::fs::write(
+            ui.join("UnityEngine.UI.asmdef"),
+            "{\"name\": \"UnityEngine.UI\"}",
         )
         .unwrap();
 
-        subject.unpack_package(false).unwrap();
+        let asmdef_guid = "a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1";
+        let plugin_guid = "b2b2b2b2b2b2b2b2b2b2b2b2b2b2b2b2";
+        let plugin_meta = format!(
+            "fileFormatVersion: 2\nguid: {}\nPluginImporter:\n  platformData:\n  - first:\n      Android: Android\n    second:\n      enabled: 1\n",
+            plugin_guid
+        );
+        let builder = sample_package()
+            .asset(
+                asmdef_guid,
+                "Assets/Vendor/Vendor.asmdef",
+                b"{\"name\": \"Vendor\", \"references\": [\"UnityEngine.UI\", \"Missing.Assembly\"]}",
+                &script_meta(asmdef_guid),
+            )
+            .asset(
+                plugin_guid,
+                "Assets/Plugins/Android/libvendor.so",
+                b"\x7fELF",
+                &plugin_meta,
+            );
 
-        assert!(tmp.exists());
+        let fixture = Fixture::new(&builder);
+        let report = fixture.open().compatibility_report(root).unwrap();
 
-        // Clean up
-        std::fs::remove_dir_all(target.clone()).unwrap();
-        std::fs::remove_dir_all(tmp.clone()).unwrap();
+        assert_eq!(report.project_version, "2018.4.36f1");
+        assert_eq!(report.exporter_estimate, ExporterEstimate::Since2019);
+        assert!(!report.is_compatible());
+        assert!(!fixture.tmp.exists());
+        assert!(!fixture.target.exists());
 
-        assert!(!target.exists());
-        assert!(!tmp.exists());
+        let issues: Vec<(Severity, CompatibilityCheck)> = report
+            .issues
+            .iter()
+            .map(|i| (i.severity, i.check))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                (Severity::Error, CompatibilityCheck::AssemblyReference),
+                (Severity::Warning, CompatibilityCheck::ExporterVersion),
+                (Severity::Warning, CompatibilityCheck::PluginPlatform),
+            ]
+        );
+        assert!(report.issues[0].message.contains("Missing.Assembly"));
+    }
+
+    #[test]
+    fn test_compatibility_report_requires_project() {
+        let project = tempfile::tempdir().unwrap();
+        let fixture = Fixture::sample();
+        assert!(matches!(
+            fixture.open().compatibility_report(project.path()),
+            Err(UnityPackageReaderError::CouldNotReadProjectFile(_))
+        ));
     }
 }
@@ -0,0 +1,894 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Component, Path, PathBuf},
+};
+
+#[cfg(feature = "serde")]
+use crate::prelude::Warning;
+use crate::prelude::{
+    AssetCategory, ErrorInformation, Guid, OverwritePolicy, UnityAssetFile,
+    UnityPackageReaderError, UnpackLimits,
+};
+
+/// How the meta file of an asset is named in the target directory.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum MetaNaming {
+    /// `Foo.png.unitymeta`, which keeps the meta files apart from other meta files.
+    #[default]
+    UnityMeta,
+    /// `Foo.png.meta`, which is what the Unity editor expects.
+    Meta,
+    /// Any other suffix, e.g. `.assetmeta`. It has to start with a dot and
+    /// must not contain path separators.
+    Custom(String),
+}
+
+impl MetaNaming {
+    /// The extension (including the leading dot) appended to the asset's file name.
+    pub fn suffix(&self) -> &str {
+        match self {
+            MetaNaming::UnityMeta => ".unitymeta",
+            MetaNaming::Meta => ".meta",
+            MetaNaming::Custom(s) => s,
+        }
+    }
+
+    /// Fails for a custom suffix that would not result in a file next to the asset.
+    pub(crate) fn check(&self) -> Result<(), UnityPackageReaderError> {
+        let suffix = self.suffix();
+        if suffix.len() > 1 && suffix.starts_with('.') && !suffix.contains(['/', '\\']) {
+            return Ok(());
+        }
+
+        Err(UnityPackageReaderError::InvalidConfig(
+            ErrorInformation::new(
+                Some(format!("'{}' is not a valid meta file suffix", suffix)),
+                file!(),
+                line!(),
+            ),
+        ))
+    }
+}
+
+/// What to install for an asset whose meta says `folderAsset: yes` while the
+/// package also contains a non-empty payload for it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum FolderPayloadResolution {
+    /// Install the payload as a regular file.
+    #[default]
+    InstallAsFile,
+    /// Treat the asset as folder and drop the payload.
+    InstallAsFolder,
+}
+
+/// What to do if two assets of a package have relative paths that only
+/// differ in case (`Icon.png` and `icon.png`). On a case-insensitive file
+/// system the second would silently replace the first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum CaseCollisions {
+    /// Don't look for collisions. The default on case-sensitive platforms.
+    Ignore,
+    /// Fail with `CaseCollision` before anything is written, listing every
+    /// colliding pair. The default on Windows and macOS.
+    Error,
+    /// Install the asset that comes first in the install order and leave the
+    /// others out.
+    KeepFirst,
+    /// Install the later assets with a numeric suffix (`icon_1.png`).
+    RenameWithSuffix,
+}
+
+impl Default for CaseCollisions {
+    fn default() -> Self {
+        if cfg!(any(target_os = "windows", target_os = "macos")) {
+            CaseCollisions::Error
+        } else {
+            CaseCollisions::Ignore
+        }
+    }
+}
+
+/// What to do with paths Windows reserves for devices (`con.txt`,
+/// `aux.shader`, a folder `prn`), see `is_reserved_name`. Windows cannot
+/// create them, other systems create files Windows cannot delete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ReservedNames {
+    /// Install the paths as they are. The default outside of Windows.
+    Ignore,
+    /// Fail with `ReservedName` before anything is written, listing every
+    /// offending path. The default on Windows.
+    Error,
+    /// Append an underscore to every reserved name in the path
+    /// (`con.txt` becomes `con_.txt`). The meta follows the asset.
+    RenameWithSuffix,
+}
+
+impl Default for ReservedNames {
+    fn default() -> Self {
+        if cfg!(target_os = "windows") {
+            ReservedNames::Error
+        } else {
+            ReservedNames::Ignore
+        }
+    }
+}
+
+/// What to do with archive entries that are neither files nor directories:
+/// symbolic links, hard links, devices and fifos. They are never created as
+/// such, so nothing can be written through them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SpecialEntries {
+    /// Leave them out and list them in `UnityPackage::get_skipped_entries`.
+    #[default]
+    Skip,
+    /// Fail with `UnsafeEntry`.
+    Reject,
+    /// Symbolic links whose target is a file of the package are replaced by
+    /// a copy of that file. Everything else is skipped.
+    CopyInternalSymlinks,
+}
+
+/// What to do if the archive contains a guid directory more than once, e.g.
+/// because a tool concatenated two packages. Copies whose files do not differ
+/// are always collapsed, see `UnityPackage::get_duplicate_guids`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum DuplicateGuids {
+    /// Fail with `DuplicateGuid`, listing every guid whose copies differ.
+    Error,
+    /// Keep the files that come first in the archive.
+    KeepFirst,
+    /// Keep the files that come last in the archive, like extracting the
+    /// archive with tar does.
+    #[default]
+    KeepLast,
+}
+
+/// Which unix permissions the installed files get. Ignored on other platforms.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum FileModes {
+    /// The permission bits stored in the archive, so executables stay
+    /// executable. Setuid, setgid and sticky bits are dropped and the owner
+    /// can always read and write.
+    #[default]
+    Archive,
+    /// Always `0o644`, whatever the archive says.
+    Sanitized,
+}
+
+impl FileModes {
+    /// The mode to give a file whose header has archive_mode.
+    pub(crate) fn apply(&self, archive_mode: u32) -> u32 {
+        match self {
+            FileModes::Archive => archive_mode & 0o777 | 0o600,
+            FileModes::Sanitized => 0o644,
+        }
+    }
+}
+
+/// Leading components removed from every relative path before it is joined
+/// onto the target (`UnpackConfig::strip_prefix`), e.g. when the target is the
+/// `Assets` folder of a project already.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum StripPrefix {
+    /// A literal prefix like `Assets/`, compared component by component.
+    Literal(String),
+    /// The first N components.
+    Components(usize),
+}
+
+impl StripPrefix {
+    /// The path without the prefix, None if path does not carry it.
+    pub(crate) fn strip(&self, path: &Path) -> Option<PathBuf> {
+        match self {
+            StripPrefix::Literal(prefix) => path.strip_prefix(prefix).ok().map(PathBuf::from),
+            StripPrefix::Components(n) => {
+                if path.components().count() < *n {
+                    return None;
+                }
+                Some(path.components().skip(*n).collect())
+            }
+        }
+    }
+}
+
+/// Where `UnpackConfig::backup` keeps the files an unpack overwrites. A file
+/// that has a backup already gets a numbered one (`.bak.1`, `.bak.2`, ...),
+/// an asset and its meta always share the number.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Backup {
+    /// Next to the file, as `<name>.bak`.
+    Suffix,
+    /// Below this directory, at the path of the file relative to the target,
+    /// as `<name>.bak`.
+    Directory(PathBuf),
+}
+
+/// Moves the assets below from to below to (`UnpackConfig::root_remap`), e.g.
+/// `Assets/Vendor/Thing` to `Packages/com.vendor.thing` to turn asset store
+/// content into an embedded package.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RootRemap {
+    /// A path prefix, compared component by component.
+    pub from: String,
+    /// Replaces from.
+    pub to: String,
+}
+
+impl RootRemap {
+    pub fn new(from: impl Into<String>, to: impl Into<String>) -> Self {
+        RootRemap {
+            from: from.into(),
+            to: to.into(),
+        }
+    }
+
+    /// The rewritten path, None if path is not below from.
+    pub(crate) fn apply(&self, path: &Path) -> Option<PathBuf> {
+        let rest = path.strip_prefix(&self.from).ok()?;
+        Some(Path::new(&self.to).join(rest))
+    }
+}
+
+/// Where a flattened asset goes if its file name is taken already.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum FlattenCollisions {
+    /// `Ground_1.jpg`, `Ground_2.jpg`, ...
+    #[default]
+    NumericSuffix,
+    /// The first 8 digits of the guid, `Ground_1af567ac.jpg`.
+    GuidPrefix,
+}
+
+/// What happens to the meta files of flattened assets.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum FlattenMetas {
+    /// Installed next to the flattened file, named after it.
+    #[default]
+    Keep,
+    /// Only the payloads are installed.
+    Skip,
+}
+
+/// Install every file directly into the target directory, using only its
+/// file name (`UnpackConfig::flatten`). Folder assets are not created.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct Flatten {
+    pub collisions: FlattenCollisions,
+    pub metas: FlattenMetas,
+}
+
+/// The order in which the assets are installed into the target. The tmp
+/// directory is always filled in archive order. The result is the same for
+/// every ordering, only the sequence (and so the progress) differs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ExtractOrdering {
+    /// The order of the guid directories inside the package.
+    #[default]
+    ArchiveOrder,
+    /// Small assets first, so the file counter moves early.
+    SmallestFirst,
+    /// Large assets first, which fails early if the disk runs full.
+    LargestFirst,
+    /// Sorted by relative path.
+    Alphabetical,
+}
+
+impl ExtractOrdering {
+    /// Sort assets according to this ordering. Ties are broken by relative path,
+    /// then by guid.
+    pub(crate) fn sort(&self, assets: &mut [UnityAssetFile], archive_order: &[Guid]) {
+        let position: HashMap<Guid, usize> = archive_order
+            .iter()
+            .enumerate()
+            .map(|(i, g)| (*g, i))
+            .collect();
+
+        assets.sort_by(|a, b| {
+            let by_path = a
+                .get_relative_asset_path()
+                .cmp(b.get_relative_asset_path())
+                .then_with(|| a.get_guid().cmp(&b.get_guid()));
+
+            match self {
+                ExtractOrdering::ArchiveOrder => {
+                    let a = position.get(&a.get_guid()).unwrap_or(&usize::MAX);
+                    let b = position.get(&b.get_guid()).unwrap_or(&usize::MAX);
+                    a.cmp(b).then(by_path)
+                }
+                ExtractOrdering::SmallestFirst => a.get_size().cmp(&b.get_size()).then(by_path),
+                ExtractOrdering::LargestFirst => b.get_size().cmp(&a.get_size()).then(by_path),
+                ExtractOrdering::Alphabetical => by_path,
+            }
+        });
+    }
+}
+
+/// The version of the serialized `UnpackConfig` this crate writes and reads.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// The names of the serialized fields, see `UnpackConfig::from_reader`.
+#[cfg(feature = "serde")]
+const CONFIG_FIELDS: &[&str] = &[
+    "config_version",
+    "install",
+    "overwrite_policy",
+    "meta_naming",
+    "create_folders",
+    "unity_ignore_rules",
+    "allowed_roots",
+    "include_categories",
+    "exclude_categories",
+    "git_aware",
+    "folder_payload",
+    "ordering",
+    "additive",
+    "guid_remap",
+    "rewrite_references",
+    "preserve_mtimes",
+    "file_modes",
+    "limits",
+    "atomic",
+    "strict_pathnames",
+    "case_collisions",
+    "special_entries",
+    "hash_assets",
+    "incremental",
+    "flatten",
+    "strip_prefix",
+    "skip_unprefixed",
+    "root_remap",
+    "drop_unmapped",
+    "continue_on_error",
+    "fail_on_asset_errors",
+    "lenient",
+    "duplicate_guids",
+    "require_unity_project",
+    "backup",
+    "check_disk_space",
+    "clear_readonly",
+    "restore_readonly",
+    "reserved_names",
+];
+
+/// Everything that controls how the assets of a package end up in the target
+/// directory. The default installs every asset of the package under its
+/// pathname, with the default limits and safety checks; everything else is
+/// opt-in. `unity_project_import` reproduces the import of the Unity editor.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct UnpackConfig {
+    /// The schema version of a serialized config. Configs written by a newer
+    /// version of this crate are rejected.
+    pub config_version: u32,
+    /// Copy the assets into the target directory. If false, the package is only
+    /// extracted into tmp and indexed (analyze-only); tmp is kept in that case,
+    /// because the index points into it.
+    pub install: bool,
+    /// What to do with files that already exist in the target directory.
+    pub overwrite_policy: OverwritePolicy,
+    /// How meta files are named in the target directory.
+    pub meta_naming: MetaNaming,
+    /// Create directories (and their meta files) for folder assets, so empty
+    /// folders of the package show up in the target as well. On by default.
+    pub create_folders: bool,
+    /// Skip assets the Unity editor would ignore: hidden files and folders,
+    /// names ending with '~', folders named 'cvs' and files ending with '.tmp'.
+    pub unity_ignore_rules: bool,
+    /// Only install assets whose relative path starts with one of these
+    /// top level folders. An empty list installs everything.
+    pub allowed_roots: Vec<String>,
+    /// Only install assets of these categories (see `AssetCategory::of`).
+    /// An empty list installs every category. Folders are not filtered.
+    pub include_categories: Vec<AssetCategory>,
+    /// Never install assets of these categories.
+    pub exclude_categories: Vec<AssetCategory>,
+    /// If the target is inside a git repository, keep the auxiliary outputs of
+    /// this crate (e.g. a tmp directory inside the target) out of it by writing
+    /// a block to `<target>/.gitignore`. Assets are never ignored.
+    pub git_aware: bool,
+    /// How folder assets that carry a payload are installed. A
+    /// `Warning::FolderAssetWithPayload` is reported either way.
+    pub folder_payload: FolderPayloadResolution,
+    /// The order in which assets are installed.
+    pub ordering: ExtractOrdering,
+    /// Only install assets whose guid does not exist anywhere in the target
+    /// yet (found through the meta files). Assets that are already present are
+    /// skipped and reported as `Warning::AlreadyPresent`.
+    pub additive: bool,
+    /// Guids (from the package) to replace by another guid when installing.
+    /// The installed meta file gets the new guid.
+    pub guid_remap: BTreeMap<Guid, Guid>,
+    /// Also replace the remapped guids where text serialized assets (scenes,
+    /// prefabs, materials, ...) reference them. Binary serialized assets are
+    /// installed unchanged and reported as `Warning::ReferencesNotRewritten`.
+    pub rewrite_references: bool,
+    /// Give the installed assets and meta files the modification time stored
+    /// in the archive instead of the time of the unpack. Entries without a
+    /// valid time keep the time of the unpack.
+    pub preserve_mtimes: bool,
+    /// The unix permissions of the installed files.
+    pub file_modes: FileModes,
+    /// Stop unpacking packages that would extract more than this.
+    pub limits: UnpackLimits,
+    /// Install into a staging directory next to the target and move the
+    /// result into the target at the end. If the unpack fails, the target is
+    /// left as it was.
+    pub atomic: bool,
+    /// Reject packages whose `pathname` files use backslashes as separators
+    /// (`PathError`). By default they are read as forward slashes.
+    pub strict_pathnames: bool,
+    /// How assets whose relative paths only differ in case are handled.
+    /// `Warning::CaseCollision` reports what has been left out or renamed.
+    pub case_collisions: CaseCollisions,
+    /// How links and other special entries of the archive are handled.
+    pub special_entries: SpecialEntries,
+    /// Compute the sha256 of every payload while it is extracted, see
+    /// `UnityAssetFile::sha256` and `AssetReport::sha256`.
+    pub hash_assets: bool,
+    /// Leave assets alone whose content, path and meta are unchanged since
+    /// the last incremental unpack into the same target. What has been
+    /// installed is recorded in `.unpacked-assets` in the target. Implies
+    /// `hash_assets`.
+    pub incremental: bool,
+    /// Dump every file into the target directory without its directories.
+    /// The path inside the package stays available as
+    /// `UnityAssetFile::get_package_path`.
+    pub flatten: Option<Flatten>,
+    /// Remove a prefix from the relative paths before installing. A path
+    /// that would become empty fails the unpack with `PathError`.
+    pub strip_prefix: Option<StripPrefix>,
+    /// Leave assets out whose path does not carry `strip_prefix`, instead of
+    /// installing them at their path.
+    pub skip_unprefixed: bool,
+    /// Rules that move assets to another root, applied after `strip_prefix`.
+    /// The first rule whose from matches is used, see `AssetReport::root_rule`.
+    pub root_remap: Vec<RootRemap>,
+    /// Leave assets out that no rule of `root_remap` matches, instead of
+    /// installing them at their path. Ignored without rules.
+    pub drop_unmapped: bool,
+    /// Skip assets that cannot be indexed or installed and go on with the
+    /// rest, see `UnityPackage::get_failures`. Errors that concern the whole
+    /// unpack, like a full disk, still stop it.
+    pub continue_on_error: bool,
+    /// With `continue_on_error`, fail the unpack with `PartialFailure` once
+    /// the other assets are installed.
+    pub fail_on_asset_errors: bool,
+    /// Skip guid directories without a pathname, as Unity does, instead of
+    /// failing with `CorruptPackage`. They are reported as
+    /// `Warning::MissingPathname`. Assets without a meta file are installed
+    /// without one and reported as `Warning::MissingMeta`.
+    pub lenient: bool,
+    /// What happens to guid directories the archive contains more than once.
+    pub duplicate_guids: DuplicateGuids,
+    /// Refuse with `NotAUnityProject` before anything is written unless the
+    /// target is the root of a Unity project (`Assets` and
+    /// `ProjectSettings/ProjectVersion.txt`) or the `Assets` folder of one.
+    pub require_unity_project: bool,
+    /// Keep a copy of every file an unpack overwrites, see `Backup`. The
+    /// locations are reported in `AssetReport::backups`.
+    pub backup: Option<Backup>,
+    /// Before extracting, compare the uncompressed size of the package with
+    /// the free space of the tmp and target volumes and fail with
+    /// `InsufficientDiskSpace` if it does not fit. Costs an extra pass over
+    /// the archive. Leave it off on file systems that misreport their free
+    /// space. Only unix and Windows tell the free space, elsewhere nothing
+    /// is checked.
+    pub check_disk_space: bool,
+    /// Clear the read-only flag (the write bits on unix) of a target file
+    /// that gets replaced. Without it, replacing a read-only file fails with
+    /// `ReadOnlyTarget` before anything of the asset is written.
+    pub clear_readonly: bool,
+    /// Mark a file read-only again after it replaced a read-only one, e.g. to
+    /// keep files checked out from Perforce locked.
+    pub restore_readonly: bool,
+    /// How paths with Windows device names in them are handled.
+    /// `Warning::ReservedName` reports every rename.
+    pub reserved_names: ReservedNames,
+}
+
+impl Default for UnpackConfig {
+    fn default() -> Self {
+        UnpackConfig {
+            config_version: CONFIG_VERSION,
+            install: true,
+            overwrite_policy: OverwritePolicy::default(),
+            meta_naming: MetaNaming::default(),
+            create_folders: true,
+            unity_ignore_rules: false,
+            allowed_roots: Vec::new(),
+            include_categories: Vec::new(),
+            exclude_categories: Vec::new(),
+            git_aware: false,
+            folder_payload: FolderPayloadResolution::default(),
+            ordering: ExtractOrdering::default(),
+            additive: false,
+            guid_remap: BTreeMap::new(),
+            rewrite_references: false,
+            preserve_mtimes: false,
+            file_modes: FileModes::default(),
+            limits: UnpackLimits::default(),
+            atomic: false,
+            strict_pathnames: false,
+            case_collisions: CaseCollisions::default(),
+            special_entries: SpecialEntries::default(),
+            hash_assets: false,
+            incremental: false,
+            flatten: None,
+            strip_prefix: None,
+            skip_unprefixed: false,
+            root_remap: Vec::new(),
+            drop_unmapped: false,
+            continue_on_error: false,
+            fail_on_asset_errors: false,
+            lenient: false,
+            duplicate_guids: DuplicateGuids::KeepLast,
+            require_unity_project: false,
+            backup: None,
+            check_disk_space: false,
+            clear_readonly: true,
+            restore_readonly: false,
+            reserved_names: ReservedNames::default(),
+        }
+    }
+}
+
+impl UnpackConfig {
+    /// The settings that reproduce what the Unity editor's own import does:
+    /// meta files keep their `.meta` extension, ignored files are dropped,
+    /// folder assets are created together with their metas, existing files are
+    /// overwritten, assets are installed in the order of the archive and only
+    /// content below `Assets/` and `Packages/` is installed.
+    /// Paths that only differ in case or use Windows device names are
+    /// rejected on every platform, because projects are shared between them.
+    pub fn unity_project_import() -> Self {
+        UnpackConfig {
+            config_version: CONFIG_VERSION,
+            install: true,
+            overwrite_policy: OverwritePolicy::Overwrite,
+            meta_naming: MetaNaming::Meta,
+            create_folders: true,
+            unity_ignore_rules: true,
+            allowed_roots: vec![String::from("Assets"), String::from("Packages")],
+            include_categories: Vec::new(),
+            exclude_categories: Vec::new(),
+            git_aware: false,
+            folder_payload: FolderPayloadResolution::InstallAsFile,
+            ordering: ExtractOrdering::ArchiveOrder,
+            additive: false,
+            guid_remap: BTreeMap::new(),
+            rewrite_references: false,
+            preserve_mtimes: false,
+            file_modes: FileModes::Archive,
+            limits: UnpackLimits::default(),
+            atomic: false,
+            strict_pathnames: false,
+            case_collisions: CaseCollisions::Error,
+            special_entries: SpecialEntries::Skip,
+            hash_assets: false,
+            incremental: false,
+            flatten: None,
+            strip_prefix: None,
+            skip_unprefixed: false,
+            root_remap: Vec::new(),
+            drop_unmapped: false,
+            continue_on_error: false,
+            fail_on_asset_errors: false,
+            lenient: false,
+            duplicate_guids: DuplicateGuids::KeepLast,
+            require_unity_project: false,
+            backup: None,
+            check_disk_space: false,
+            clear_readonly: true,
+            restore_readonly: false,
+            reserved_names: ReservedNames::Error,
+        }
+    }
+
+    /// Read a config (e.g. a per-vendor policy file) written as YAML or JSON.
+    /// Missing fields keep their default. Unknown fields are reported as
+    /// `Warning::UnknownConfigField` instead of failing, so older versions of
+    /// this crate can read newer policies as long as the version allows it.
+    #[cfg(feature = "serde")]
+    pub fn from_reader(
+        reader: impl std::io::Read,
+    ) -> Result<(Self, Vec<Warning>), UnityPackageReaderError> {
+        let value: serde_yaml::Value = match serde_yaml::from_reader(reader) {
+            Ok(v) => v,
+            Err(e) => return Err(config_error(format!("{}", e))),
+        };
+
+        let mut map = match value {
+            serde_yaml::Value::Mapping(m) => m,
+            serde_yaml::Value::Null => serde_yaml::Mapping::new(),
+            _ => return Err(config_error(String::from("expected a mapping"))),
+        };
+
+        let mut warnings = Vec::new();
+        map.retain(|key, _| {
+            let known = key.as_str().is_some_and(|k| CONFIG_FIELDS.contains(&k));
+            if !known {
+                let field = match key.as_str() {
+                    Some(k) => String::from(k),
+                    None => format!("{:?}", key),
+                };
+                warnings.push(Warning::UnknownConfigField { field });
+            }
+            known
+        });
+
+        let config: UnpackConfig = match serde_yaml::from_value(serde_yaml::Value::Mapping(map)) {
+            Ok(c) => c,
+            Err(e) => return Err(config_error(format!("{}", e))),
+        };
+
+        if config.config_version > CONFIG_VERSION {
+            return Err(config_error(format!(
+                "config_version {} is newer than the supported version {}",
+                config.config_version, CONFIG_VERSION
+            )));
+        }
+
+        Ok((config, warnings))
+    }
+
+    /// True, if an asset with the given relative path is installed.
+    pub fn includes(&self, relative_path: &Path) -> bool {
+        if !self.allowed_roots.is_empty() {
+            let root = match relative_path.components().next() {
+                Some(Component::Normal(r)) => r.to_string_lossy(),
+                _ => return false,
+            };
+
+            if !self.allowed_roots.iter().any(|a| *a == root) {
+                return false;
+            }
+        }
+
+        !(self.unity_ignore_rules && is_ignored_by_unity(relative_path))
+    }
+
+    /// True, if the asset is installed: its path passes `includes` and its
+    /// category passes the category filters.
+    pub fn includes_asset(&self, asset: &UnityAssetFile) -> bool {
+        if !self.includes(asset.get_package_path()) {
+            return false;
+        }
+
+        if asset.is_folder() {
+            return true;
+        }
+
+        let category = asset.category();
+        (self.include_categories.is_empty() || self.include_categories.contains(&category))
+            && !self.exclude_categories.contains(&category)
+    }
+
+    /// True, if folder assets are created in the target.
+    pub(crate) fn installs_folders(&self) -> bool {
+        self.create_folders && self.flatten.is_none()
+    }
+
+    /// False, if flattening leaves the meta files out.
+    pub(crate) fn installs_metas(&self) -> bool {
+        !matches!(
+            self.flatten,
+            Some(Flatten {
+                metas: FlattenMetas::Skip,
+                ..
+            })
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+fn config_error(message: String) -> UnityPackageReaderError {
+    UnityPackageReaderError::InvalidConfig(ErrorInformation::new(Some(message), file!(), line!()))
+}
+
+/// The rules the Unity editor uses to decide which files in the Assets folder
+/// it does not import.
+pub(crate) fn is_ignored_by_unity(relative_path: &Path) -> bool {
+    relative_path.components().any(|c| {
+        let name = c.as_os_str().to_string_lossy();
+        name.starts_with('.')
+            || name.ends_with('~')
+            || name.eq_ignore_ascii_case("cvs")
+            || name.to_ascii_lowercase().ends_with(".tmp")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_includes_everything() {
+        let config = UnpackConfig::default();
+        assert!(config.includes(Path::new("Assets/.hidden")));
+        assert!(config.includes(Path::new("ProjectSettings/Tags.asset")));
+    }
+
+    #[test]
+    fn test_unity_ignore_rules() {
+        let config = UnpackConfig::unity_project_import();
+        assert!(config.includes(Path::new("Assets/Textures/a.png")));
+        assert!(config.includes(Path::new("Packages/com.vendor.tool/package.json")));
+        assert!(!config.includes(Path::new("Assets/.DS_Store")));
+        assert!(!config.includes(Path::new("Assets/Docs~/readme.txt")));
+        assert!(!config.includes(Path::new("Assets/CVS/Entries")));
+        assert!(!config.includes(Path::new("Assets/build.TMP")));
+        assert!(!config.includes(Path::new("ProjectSettings/Tags.asset")));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialized_schema() {
+        // Renaming a field or variant breaks stored policies; update with care.
+        let expected = "config_version: 1
+install: true
+overwrite_policy: overwrite
+meta_naming: meta
+create_folders: true
+unity_ignore_rules: true
+allowed_roots:
+- Assets
+- Packages
+include_categories: []
+exclude_categories: []
+git_aware: false
+folder_payload: install_as_file
+ordering: archive_order
+additive: false
+guid_remap: {}
+rewrite_references: false
+preserve_mtimes: false
+file_modes: archive
+limits:
+  max_total_bytes: 68719476736
+  max_entry_bytes: 17179869184
+  max_entries: 1000000
+atomic: false
+strict_pathnames: false
+case_collisions: error
+special_entries: skip
+hash_assets: false
+incremental: false
+flatten: null
+strip_prefix: null
+skip_unprefixed: false
+root_remap: []
+drop_unmapped: false
+continue_on_error: false
+fail_on_asset_errors: false
+lenient: false
+duplicate_guids: keep_last
+require_unity_project: false
+backup: null
+check_disk_space: false
+clear_readonly: true
+restore_readonly: false
+reserved_names: error
+";
+        let config = UnpackConfig::unity_project_import();
+        assert_eq!(serde_yaml::to_string(&config).unwrap(), expected);
+
+        let (read, warnings) = UnpackConfig::from_reader(expected.as_bytes()).unwrap();
+        assert_eq!(read, config);
+        assert!(warnings.is_empty());
+
+        let keys: Vec<String> = match serde_yaml::to_value(&config).unwrap() {
+            serde_yaml::Value::Mapping(m) => {
+                m.keys().map(|k| k.as_str().unwrap().to_string()).collect()
+            }
+            _ => unreachable!(),
+        };
+        assert_eq!(keys, CONFIG_FIELDS);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_from_reader() {
+        let policy = "overwrite_policy: skip\nallowed_roots: [Assets]\nvendor_note: legacy\n";
+        let (config, warnings) = UnpackConfig::from_reader(policy.as_bytes()).unwrap();
+        assert_eq!(config.overwrite_policy, OverwritePolicy::Skip);
+        assert_eq!(config.allowed_roots, vec!["Assets"]);
+        assert!(config.install);
+        assert_eq!(
+            warnings,
+            vec![Warning::UnknownConfigField {
+                field: String::from("vendor_note")
+            }]
+        );
+
+        let custom = "meta_naming: !custom .assetmeta\n";
+        let (config, _) = UnpackConfig::from_reader(custom.as_bytes()).unwrap();
+        assert_eq!(
+            config.meta_naming,
+            MetaNaming::Custom(String::from(".assetmeta"))
+        );
+
+        let (json, _) = UnpackConfig::from_reader(&b"{\"additive\": true}"[..]).unwrap();
+        assert!(json.additive);
+        assert_eq!(
+            UnpackConfig::from_reader(&b""[..]).unwrap().0,
+            UnpackConfig::default()
+        );
+
+        for invalid in ["config_version: 99\n", "install: maybe\n", "- a\n"] {
+            assert!(matches!(
+                UnpackConfig::from_reader(invalid.as_bytes()),
+                Err(UnityPackageReaderError::InvalidConfig(_))
+            ));
+        }
+    }
+}
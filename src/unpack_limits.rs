@@ -0,0 +1,142 @@
+use std::io::{self, Read};
+
+use crate::unpacker_error::{ErrorInformation, UnityPackageReaderError};
+
+/// Text entries (pathnames, metas, asmdefs) that are read into memory are
+/// cut off at this size, no matter the limits.
+pub(crate) const MAX_TEXT_BYTES: u64 = 4 << 20;
+
+/// Read a text entry into memory, failing with `InvalidData` if it is larger
+/// than `MAX_TEXT_BYTES` or no UTF-8.
+pub(crate) fn read_text(reader: impl Read) -> io::Result<String> {
+    let mut content = Vec::new();
+    reader.take(MAX_TEXT_BYTES + 1).read_to_end(&mut content)?;
+    if content.len() as u64 > MAX_TEXT_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("text entry larger than {} bytes", MAX_TEXT_BYTES),
+        ));
+    }
+
+    String::from_utf8(content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Upper bounds for what an unpack may extract, so a hostile or corrupt
+/// package cannot fill the disk. Checked against the sizes announced by the
+/// archive headers before anything of an entry is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct UnpackLimits {
+    /// The sum of the sizes of all entries (64 GiB by default).
+    pub max_total_bytes: u64,
+    /// The size of a single entry (16 GiB by default).
+    pub max_entry_bytes: u64,
+    /// The number of entries in the archive (one million by default).
+    pub max_entries: u64,
+}
+
+impl Default for UnpackLimits {
+    fn default() -> Self {
+        UnpackLimits {
+            max_total_bytes: 64 << 30,
+            max_entry_bytes: 16 << 30,
+            max_entries: 1_000_000,
+        }
+    }
+}
+
+/// Keeps track of what has been extracted so far.
+#[derive(Debug, Default)]
+pub(crate) struct LimitTracker {
+    limits: UnpackLimits,
+    total_bytes: u64,
+    entries: u64,
+}
+
+impl LimitTracker {
+    pub(crate) fn new(limits: UnpackLimits) -> Self {
+        LimitTracker {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    /// Account for the next entry of the archive with the given size.
+    pub(crate) fn add_entry(&mut self, size: u64) -> Result<(), UnityPackageReaderError> {
+        self.entries += 1;
+        self.total_bytes = self.total_bytes.saturating_add(size);
+
+        check("max_entry_bytes", self.limits.max_entry_bytes, size)?;
+        check("max_entries", self.limits.max_entries, self.entries)?;
+        check(
+            "max_total_bytes",
+            self.limits.max_total_bytes,
+            self.total_bytes,
+        )
+    }
+
+    /// Account for size bytes that an entry which has already been counted
+    /// writes, e.g. the copy of the file a symlink points to.
+    pub(crate) fn add_bytes(&mut self, size: u64) -> Result<(), UnityPackageReaderError> {
+        self.total_bytes = self.total_bytes.saturating_add(size);
+
+        check("max_entry_bytes", self.limits.max_entry_bytes, size)?;
+        check(
+            "max_total_bytes",
+            self.limits.max_total_bytes,
+            self.total_bytes,
+        )
+    }
+}
+
+fn check(name: &str, limit: u64, observed: u64) -> Result<(), UnityPackageReaderError> {
+    if observed <= limit {
+        return Ok(());
+    }
+
+    Err(UnityPackageReaderError::LimitExceeded(
+        ErrorInformation::new(
+            Some(format!(
+                "{} is {}, but {} has been reached",
+                name, limit, observed
+            )),
+            file!(),
+            line!(),
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits() {
+        let mut tracker = LimitTracker::new(UnpackLimits {
+            max_total_bytes: 100,
+            max_entry_bytes: 60,
+            max_entries: 3,
+        });
+
+        assert!(tracker.add_entry(60).is_ok());
+        assert!(tracker.add_entry(0).is_ok());
+        let error = tracker.add_entry(50).unwrap_err();
+        assert!(matches!(error, UnityPackageReaderError::LimitExceeded(_)));
+        assert!(format!("{}", error).contains("max_total_bytes is 100, but 110"));
+
+        let mut tracker = LimitTracker::new(UnpackLimits {
+            max_entries: 1,
+            ..Default::default()
+        });
+        assert!(tracker.add_entry(1).is_ok());
+        assert!(tracker.add_bytes(1).is_ok());
+        assert!(format!("{}", tracker.add_entry(1).unwrap_err()).contains("max_entries"));
+
+        let mut tracker = LimitTracker::new(UnpackLimits::default());
+        assert!(format!("{}", tracker.add_entry(u64::MAX).unwrap_err()).contains("max_entry_bytes"));
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    prelude::{Guid, Shared, SharedFilter, SharedObserver, UnpackConfig},
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+type RewriteFn = dyn Fn(&Guid, &Path) -> Option<PathBuf> + Send + Sync;
+
+/// A caller supplied mapping of relative target paths, see
+/// `UnpackOptions::path_rewrite`.
+pub type PathRewrite = Shared<RewriteFn>;
+
+impl PathRewrite {
+    /// f gets the guid and the relative path of an asset and returns the path
+    /// to install it at, or None to leave it out.
+    pub fn new(f: impl Fn(&Guid, &Path) -> Option<PathBuf> + Send + Sync + 'static) -> Self {
+        Shared::from_arc(Arc::new(f))
+    }
+
+    pub(crate) fn apply(&self, guid: &Guid, path: &Path) -> Option<PathBuf> {
+        (**self)(guid, path)
+    }
+}
+
+/// The largest buffer `BufferSizes` accepts, 64 MiB.
+pub const MAX_BUFFER_SIZE: usize = 64 * 1024 * 1024;
+
+/// The sizes of the I/O buffers of an unpack, see `UnpackOptions::buffer_sizes`.
+/// Larger buffers mean fewer system calls, which helps on network file
+/// systems and spinning disks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferSizes {
+    /// The capacity of the buffer the package file is read through. Not used
+    /// for packages created with `UnityPackage::from_bytes` or mapped with
+    /// `UnityPackage::with_memory_map`.
+    pub read: usize,
+    /// The most bytes of an entry read and written into its file at once
+    /// while extracting.
+    pub chunk: usize,
+    /// The buffer used when an asset has to be copied instead of renamed
+    /// (tmp and target on different volumes, backups of staged files). None
+    /// leaves the copy to the operating system.
+    pub copy: Option<usize>,
+}
+
+impl Default for BufferSizes {
+    fn default() -> Self {
+        BufferSizes {
+            read: 8 * 1024,
+            chunk: 64 * 1024,
+            copy: None,
+        }
+    }
+}
+
+impl BufferSizes {
+    /// Fail with `InvalidConfig` if a size is 0 or larger than `MAX_BUFFER_SIZE`.
+    pub(crate) fn check(&self) -> Result<(), UnityPackageReaderError> {
+        let sizes = [
+            ("read", Some(self.read)),
+            ("chunk", Some(self.chunk)),
+            ("copy", self.copy),
+        ];
+        for (name, size) in sizes {
+            match size {
+                Some(size) if size == 0 || size > MAX_BUFFER_SIZE => {
+                    return Err(UnityPackageReaderError::InvalidConfig(
+                        ErrorInformation::new(
+                            Some(format!(
+                                "the {} buffer size {} is not between 1 and {}",
+                                name, size, MAX_BUFFER_SIZE
+                            )),
+                            file!(),
+                            line!(),
+                        ),
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Options of a single `UnityPackage::unpack_package_with` call. Cloning it
+/// allows to reuse one configuration for many packages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnpackOptions {
+    /// Remove the tmp directory after the assets have been copied, or as soon
+    /// as the unpack fails. Turn it off to keep tmp for
+    /// `UnityPackage::unpack_from_tmp`. Ignored in analyze-only mode
+    /// (`UnpackConfig::install == false`).
+    pub delete_tmp: bool,
+    /// How the assets end up in the target directory. Replaces the
+    /// configuration of the package (`UnityPackage::with_config`) if set,
+    /// None keeps it.
+    pub config: Option<UnpackConfig>,
+    /// Decides the relative target path of every asset the filters of the
+    /// configuration let through. It sees the path after `strip_prefix` and
+    /// `root_remap`; case collisions and flattening are resolved afterwards.
+    /// A path leaving the target or a panic fails the unpack with `PathError`.
+    pub path_rewrite: Option<PathRewrite>,
+    /// Told about the start and the end of every asset that is installed.
+    pub observer: Option<SharedObserver>,
+    /// Asked once for every asset before anything is written, after the
+    /// filters of the configuration. Assets it rejects are not installed and
+    /// reported as `AssetStatus::Filtered`.
+    pub filter: Option<SharedFilter>,
+    /// The I/O buffers of the unpack. An invalid size fails the unpack with
+    /// `InvalidConfig` before anything is written.
+    pub buffer_sizes: BufferSizes,
+}
+
+impl Default for UnpackOptions {
+    fn default() -> Self {
+        UnpackOptions {
+            delete_tmp: true,
+            config: None,
+            path_rewrite: None,
+            observer: None,
+            filter: None,
+            buffer_sizes: BufferSizes::default(),
+        }
+    }
+}
+
+impl From<UnpackConfig> for UnpackOptions {
+    fn from(config: UnpackConfig) -> Self {
+        UnpackOptions {
+            config: Some(config),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use crate::guid::Guid;
+
+/// What `unpack_package` would do with a single asset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlannedAction {
+    /// The asset and its meta file would be written to the target.
+    Install,
+    /// The target already exists and the overwrite policy keeps it, or has
+    /// the same content (`OverwritePolicy::IfChanged`).
+    Skip,
+    /// The target already exists and the overwrite policy aborts the unpack.
+    Fail,
+    /// Additive mode: the guid already exists somewhere in the target.
+    AlreadyPresent,
+}
+
+/// A single step of a dry run, see `UnityPackage::plan_unpack`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedOperation {
+    /// The guid of the asset.
+    pub guid: Guid,
+    /// Path of the asset relative to the target directory.
+    pub relative_path: PathBuf,
+    /// Absolute path the asset would be written to.
+    pub destination: PathBuf,
+    /// True, if the parent directory of the destination would be created.
+    pub creates_directory: bool,
+    /// True, if an existing file would be replaced.
+    pub overwrites_existing: bool,
+    pub action: PlannedAction,
+}
@@ -0,0 +1,900 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    compatibility,
+    counting::CountingReader,
+    disk_space,
+    extraction_report::{DuplicateGuid, DuplicateResolution, SkippedEntry},
+    file_system::{self, FileSystem},
+    guid::Guid,
+    package_format::{self, Decompressed},
+    package_source::PackageSource,
+    prelude::{DuplicateGuids, UnityAssetFile, UnityPackage, UnpackOptions, UnpackStats},
+    size_estimate,
+    staging::Staging,
+    tar_stream::{TarStream, DUPLICATES_DIR},
+    tmp_guard::TempDirGuard,
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// How much work a single call of `UnpackSession::step` may do. Every call
+/// makes some progress, even with a budget of zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepBudget {
+    /// At most this many archive entries while extracting, or assets while installing.
+    Entries(usize),
+    /// At most this many bytes: uncompressed archive bytes while extracting,
+    /// asset bytes while installing. Large files are split across calls.
+    Bytes(u64),
+    /// Run until the unpack has finished.
+    Unlimited,
+}
+
+/// The state of an `UnpackSession` after a call of `step`.
+#[derive(Debug, PartialEq)]
+pub enum StepResult {
+    /// The budget has been used up, call `step` again.
+    Continue,
+    /// The unpack is complete. The package holds the index, warnings and stats.
+    Finished(UnpackStats),
+    /// The unpack has been aborted.
+    Failed(UnityPackageReaderError),
+}
+
+/// What is left of a `StepBudget` during a single step.
+pub(crate) struct Allowance {
+    budget: StepBudget,
+    entries: usize,
+    bytes: u64,
+}
+
+impl Allowance {
+    pub(crate) fn new(budget: StepBudget) -> Self {
+        Allowance {
+            budget,
+            entries: 0,
+            bytes: 0,
+        }
+    }
+
+    pub(crate) fn is_exhausted(&self) -> bool {
+        match self.budget {
+            StepBudget::Entries(n) => self.entries >= n.max(1),
+            StepBudget::Bytes(n) => self.bytes >= n.max(1),
+            StepBudget::Unlimited => false,
+        }
+    }
+
+    /// The number of bytes that may still be processed in one go.
+    pub(crate) fn max_bytes(&self) -> u64 {
+        match self.budget {
+            StepBudget::Bytes(n) => n.max(1).saturating_sub(self.bytes),
+            _ => u64::MAX,
+        }
+    }
+
+    pub(crate) fn spend(&mut self, entries: usize, bytes: u64) {
+        self.entries += entries;
+        self.bytes += bytes;
+    }
+}
+
+type PackageReader = CountingReader<Decompressed<CountingReader<PackageSource>>>;
+
+/// Decompresses a package into the tmp directory, a few entries at a time.
+pub(crate) struct Extraction {
+    stream: TarStream<PackageReader>,
+    tmp: TempDirGuard,
+    file_system: Arc<dyn FileSystem>,
+    written: u64,
+    /// The guid directories in the order they appear in the archive.
+    archive_order: Vec<Guid>,
+    seen: HashSet<Guid>,
+    duplicate_guids: DuplicateGuids,
+    /// The duplicates, once the archive is complete and they are resolved.
+    resolved: Option<Vec<DuplicateGuid>>,
+}
+
+impl Extraction {
+    /// Open the package and create the tmp directory. With delete_tmp, the
+    /// tmp directory is removed again if the extraction is dropped before
+    /// it has finished.
+    pub(crate) fn start(
+        package: &UnityPackage,
+        delete_tmp: bool,
+    ) -> Result<Self, UnityPackageReaderError> {
+        file_system::require_file_system("Unpacking into a directory")?;
+        let mut file = package.open_package_file()?;
+        let format = package_format::sniff(&mut file)?;
+
+        let tmp_path = match package.get_tmp_dir() {
+            Ok(e) => e,
+            Err(e) => {
+                return Err(UnityPackageReaderError::TmpDirectoryCouldNotBeCreated(
+                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                ));
+            }
+        };
+
+        match package.file_system().create_dir_all(&tmp_path) {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(UnityPackageReaderError::TmpDirectoryCouldNotBeCreated(
+                    ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+                ));
+            }
+        }
+
+        let compressed = CountingReader::new(file);
+        let tar = CountingReader::new(Decompressed::new(format, compressed));
+
+        Ok(Extraction {
+            stream: TarStream::new(tar)
+                .with_mtimes(package.get_config().preserve_mtimes)
+                .with_modes(package.get_config().file_modes)
+                .with_limits(package.get_config().limits)
+                .with_special_entries(package.get_config().special_entries)
+                .with_hashes(package.get_config().hash_assets || package.get_config().incremental)
+                .with_chunk_size(package.get_buffer_sizes().chunk as u64),
+            tmp: TempDirGuard::new(tmp_path, delete_tmp),
+            file_system: package.shared_file_system(),
+            written: 0,
+            archive_order: Vec::new(),
+            seen: HashSet::new(),
+            duplicate_guids: package.get_config().duplicate_guids,
+            resolved: None,
+        })
+    }
+
+    /// Extract until the allowance is used up. Returns true once the whole
+    /// archive has been extracted.
+    pub(crate) fn step(
+        &mut self,
+        allowance: &mut Allowance,
+    ) -> Result<bool, UnityPackageReaderError> {
+        loop {
+            if self.stream.is_finished() {
+                if self.resolved.is_none() {
+                    self.resolved = Some(self.resolve_duplicates()?);
+                }
+                return Ok(true);
+            }
+
+            if allowance.is_exhausted() {
+                return Ok(false);
+            }
+
+            let advance = self
+                .stream
+                .advance(self.tmp.path(), allowance.max_bytes())?;
+            self.written += advance.written;
+            allowance.spend(advance.completed_entry as usize, advance.consumed);
+
+            if let Some(Component::Normal(guid)) = advance
+                .started_entry
+                .as_ref()
+                .and_then(|p| p.components().next())
+            {
+                // Entries outside a guid directory are ignored when installing.
+                if let Ok(guid) = guid.to_string_lossy().parse::<Guid>() {
+                    if self.seen.insert(guid) {
+                        self.archive_order.push(guid);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The links and special files left out so far.
+    pub(crate) fn skipped_entries(&self) -> &[SkippedEntry] {
+        self.stream.skipped_entries()
+    }
+
+    /// The guid directories the archive contains more than once. Empty
+    /// until the extraction has finished.
+    pub(crate) fn duplicate_guids(&self) -> &[DuplicateGuid] {
+        self.resolved.as_deref().unwrap_or_default()
+    }
+
+    /// Decide for every guid directory the archive contains more than once
+    /// which copy stays in tmp, according to `UnpackConfig::duplicate_guids`,
+    /// and remove the other copies.
+    fn resolve_duplicates(&self) -> Result<Vec<DuplicateGuid>, UnityPackageReaderError> {
+        let dir = self.tmp.path();
+        let side = dir.join(DUPLICATES_DIR);
+        let mut by_guid: Vec<(Guid, Vec<&PathBuf>)> = Vec::new();
+        for relative in self.stream.duplicates() {
+            // Entries outside a guid directory are ignored when installing.
+            let guid = match relative.components().next() {
+                Some(Component::Normal(g)) => match g.to_string_lossy().parse::<Guid>() {
+                    Ok(g) => g,
+                    Err(_) => continue,
+                },
+                _ => continue,
+            };
+            match by_guid.iter_mut().find(|(g, _)| *g == guid) {
+                Some((_, files)) => files.push(relative),
+                None => by_guid.push((guid, vec![relative])),
+            }
+        }
+
+        let mut resolved = Vec::new();
+        let mut differing = Vec::new();
+        for (guid, files) in by_guid {
+            // Hashed in chunks, the copies may be large.
+            let identical = files.iter().all(|f| {
+                matches!(
+                    (self.file_system.sha256(&dir.join(f)), self.file_system.sha256(&side.join(f))),
+                    (Ok(a), Ok(b)) if a == b
+                )
+            });
+            let resolution = match (identical, self.duplicate_guids) {
+                (true, _) => DuplicateResolution::Identical,
+                (false, DuplicateGuids::Error) => {
+                    differing.push(guid.to_string());
+                    continue;
+                }
+                (false, DuplicateGuids::KeepFirst) => DuplicateResolution::KeptFirst,
+                (false, DuplicateGuids::KeepLast) => {
+                    for file in files {
+                        if let Err(e) = self.file_system.rename(&side.join(file), &dir.join(file)) {
+                            return Err(UnityPackageReaderError::CorruptPackage(
+                                ErrorInformation::new(
+                                    Some(format!("{:?}: {}", file, e)),
+                                    file!(),
+                                    line!(),
+                                ),
+                            ));
+                        }
+                    }
+                    DuplicateResolution::KeptLast
+                }
+            };
+            resolved.push(DuplicateGuid { guid, resolution });
+        }
+
+        if !differing.is_empty() {
+            return Err(UnityPackageReaderError::DuplicateGuid(
+                ErrorInformation::new(Some(differing.join(", ")), file!(), line!()),
+            ));
+        }
+
+        match self.file_system.remove_dir_all(&side) {
+            Ok(_) => Ok(resolved),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(resolved),
+            Err(e) => Err(UnityPackageReaderError::CouldNotDeleteTmp(
+                ErrorInformation::new(Some(format!("{:?}: {}", side, e)), file!(), line!()),
+            )),
+        }
+    }
+
+    /// The sha256 of the payloads extracted so far, by guid. Empty unless
+    /// `UnpackConfig::hash_assets` is set.
+    pub(crate) fn asset_hashes(&self) -> HashMap<Guid, [u8; 32]> {
+        self.stream
+            .hashes()
+            .iter()
+            .filter_map(|(path, hash)| {
+                // The later copy of a duplicate only counts if it has been kept.
+                let (path, duplicate) = match path.strip_prefix(DUPLICATES_DIR) {
+                    Ok(p) => (p, true),
+                    Err(_) => (path.as_path(), false),
+                };
+                let mut components = path.components();
+                let guid = match (components.next(), components.next(), components.next()) {
+                    (Some(Component::Normal(guid)), Some(Component::Normal(name)), None)
+                        if name == "asset" =>
+                    {
+                        guid.to_string_lossy().parse::<Guid>().ok()?
+                    }
+                    _ => return None,
+                };
+                let kept = self
+                    .duplicate_guids()
+                    .iter()
+                    .any(|d| d.guid == guid && d.resolution == DuplicateResolution::KeptLast);
+                (!duplicate || kept).then_some((guid, *hash))
+            })
+            .collect()
+    }
+
+    /// Extract everything at once.
+    pub(crate) fn run(
+        mut self,
+    ) -> Result<(TempDirGuard, UnpackStats, Vec<Guid>), UnityPackageReaderError> {
+        while !self.step(&mut Allowance::new(StepBudget::Unlimited))? {}
+        Ok(self.finish())
+    }
+
+    /// Remove everything extracted so far.
+    pub(crate) fn discard(self) {
+        let _ = self.file_system.remove_dir_all(self.tmp.path());
+    }
+
+    /// The tmp directory together with the byte counts of the extraction and
+    /// the guids in archive order.
+    pub(crate) fn finish(self) -> (TempDirGuard, UnpackStats, Vec<Guid>) {
+        let tar = self.stream.into_inner();
+        let stats = UnpackStats {
+            uncompressed_bytes: tar.count(),
+            compressed_bytes_read: tar.into_inner().into_inner().count(),
+            tmp_bytes_written: self.written,
+            ..Default::default()
+        };
+
+        (self.tmp, stats, self.archive_order)
+    }
+}
+
+enum Phase {
+    Starting,
+    /// Install from an existing tmp directory, see `UnityPackage::unpack_from_tmp`.
+    Resuming,
+    Extracting(Box<Extraction>),
+    Installing {
+        /// Removes tmp if the install fails, unless `delete_tmp` is off.
+        tmp: TempDirGuard,
+        target: PathBuf,
+        /// Only set for an atomic unpack. Dropping it (e.g. on an error)
+        /// removes everything installed so far.
+        staging: Option<Staging>,
+        assets: std::vec::IntoIter<UnityAssetFile>,
+    },
+    Finished(UnpackStats),
+    Failed(UnityPackageReaderError),
+}
+
+/// An unpack that is driven in small slices by calling `step` repeatedly,
+/// for embedders that cannot block or spawn threads.
+/// `UnityPackage::unpack_package_with` runs the same session to completion.
+pub struct UnpackSession<'a> {
+    package: &'a mut UnityPackage,
+    delete_tmp: bool,
+    phase: Phase,
+}
+
+impl<'a> UnpackSession<'a> {
+    /// Start an unpack of package with the given options. The configuration of
+    /// the options, if any, replaces the configuration of the package. Nothing
+    /// is read or written before the first call of `step`.
+    pub fn new(package: &'a mut UnityPackage, options: &UnpackOptions) -> Self {
+        if let Some(config) = &options.config {
+            package.set_config(config.clone());
+        }
+        package.set_path_rewrite(options.path_rewrite.clone());
+        package.set_observer(options.observer.clone());
+        package.set_filter(options.filter.clone());
+        package.set_buffer_sizes(options.buffer_sizes);
+        let delete_tmp = options.delete_tmp && package.get_config().install;
+
+        UnpackSession {
+            package,
+            delete_tmp,
+            phase: Phase::Starting,
+        }
+    }
+
+    /// Same as `new`, but the session skips the extraction and installs
+    /// what the tmp directory of package contains.
+    pub(crate) fn from_tmp(package: &'a mut UnityPackage, options: &UnpackOptions) -> Self {
+        UnpackSession {
+            phase: Phase::Resuming,
+            ..UnpackSession::new(package, options)
+        }
+    }
+
+    /// Do at most budget worth of work. Once the session has finished or
+    /// failed, every further call returns the same result again.
+    pub fn step(&mut self, budget: StepBudget) -> StepResult {
+        match &self.phase {
+            Phase::Finished(stats) => return StepResult::Finished(*stats),
+            Phase::Failed(e) => return StepResult::Failed(e.clone()),
+            _ => {}
+        }
+
+        let mut allowance = Allowance::new(budget);
+        loop {
+            match self.advance(&mut allowance) {
+                Ok(Some(stats)) => {
+                    self.release_options();
+                    self.phase = Phase::Finished(stats);
+                    return StepResult::Finished(stats);
+                }
+                Ok(None) => {
+                    if allowance.is_exhausted() {
+                        return StepResult::Continue;
+                    }
+                }
+                Err(e) => {
+                    self.release_options();
+                    self.phase = Phase::Failed(e.clone());
+                    return StepResult::Failed(e);
+                }
+            }
+        }
+    }
+
+    /// Remove the path rewrite, observer and filter of the options from the
+    /// package once the session is over, they only apply while it runs.
+    fn release_options(&mut self) {
+        self.package.set_path_rewrite(None);
+        self.package.set_observer(None);
+        self.package.set_filter(None);
+    }
+
+    /// Step until the unpack has finished.
+    pub fn run(mut self) -> Result<UnpackStats, UnityPackageReaderError> {
+        loop {
+            match self.step(StepBudget::Unlimited) {
+                StepResult::Continue => {}
+                StepResult::Finished(stats) => return Ok(stats),
+                StepResult::Failed(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Work on the current phase until it is done or the allowance is used up.
+    /// Returns the stats once the whole unpack is complete.
+    fn advance(
+        &mut self,
+        allowance: &mut Allowance,
+    ) -> Result<Option<UnpackStats>, UnityPackageReaderError> {
+        let phase = std::mem::replace(&mut self.phase, Phase::Starting);
+        match phase {
+            Phase::Starting => {
+                self.package.get_buffer_sizes().check()?;
+                self.check_directories()?;
+                self.check_target()?;
+                self.check_disk_space()?;
+                self.phase =
+                    Phase::Extracting(Box::new(Extraction::start(self.package, self.delete_tmp)?));
+                Ok(None)
+            }
+            Phase::Resuming => {
+                self.package.get_buffer_sizes().check()?;
+                self.check_directories()?;
+                self.check_target()?;
+                let tmp_path = self.package.get_tmp_dir()?;
+                check_extracted_tmp(&tmp_path, self.package.file_system())?;
+                self.package.set_skipped_entries(Vec::new());
+                self.package.set_duplicate_guids(Vec::new());
+                let tmp = TempDirGuard::new(tmp_path, self.delete_tmp);
+                self.begin_installing(tmp, UnpackStats::default(), &[], &HashMap::new(), true)?;
+                Ok(None)
+            }
+            Phase::Extracting(mut extraction) => {
+                let done = match extraction.step(allowance) {
+                    Ok(d) => d,
+                    Err(e @ UnityPackageReaderError::LimitExceeded(_)) => {
+                        // Nothing has been installed yet, only tmp needs to go.
+                        extraction.discard();
+                        return Err(e);
+                    }
+                    Err(e) => return Err(e),
+                };
+
+                if !done {
+                    self.phase = Phase::Extracting(extraction);
+                    return Ok(None);
+                }
+
+                self.package
+                    .set_skipped_entries(extraction.skipped_entries().to_vec());
+                self.package
+                    .set_duplicate_guids(extraction.duplicate_guids().to_vec());
+                let hashes = extraction.asset_hashes();
+                let (tmp, stats, archive_order) = extraction.finish();
+                self.begin_installing(tmp, stats, &archive_order, &hashes, false)?;
+                Ok(None)
+            }
+            Phase::Installing {
+                tmp,
+                target,
+                staging,
+                mut assets,
+            } => {
+                while !allowance.is_exhausted() {
+                    match assets.next() {
+                        Some(asset) => {
+                            let root = staging.as_ref().map(|s| s.root());
+                            let bytes = self.package.install_asset(&target, root, asset)?;
+                            allowance.spend(1, bytes);
+                        }
+                        None => {
+                            if let Some(staging) = staging {
+                                staging.commit()?;
+                            }
+                            self.package.finish_unpack(tmp.path(), self.delete_tmp)?;
+                            tmp.keep();
+                            return Ok(Some(*self.package.get_stats()));
+                        }
+                    }
+                }
+
+                self.phase = Phase::Installing {
+                    tmp,
+                    target,
+                    staging,
+                    assets,
+                };
+                Ok(None)
+            }
+            Phase::Finished(stats) => Ok(Some(stats)),
+            Phase::Failed(e) => Err(e),
+        }
+    }
+
+    /// Fail with `InvalidConfiguration` if the tmp and the target directory
+    /// are the same or the target is inside tmp. tmp inside the target is
+    /// allowed, `UnpackConfig::git_aware` keeps it out of the repository.
+    /// Directories that do not exist yet are compared by the paths they
+    /// would get.
+    fn check_directories(&self) -> Result<(), UnityPackageReaderError> {
+        if !self.package.get_config().install {
+            return Ok(());
+        }
+
+        let tmp = would_be_path(&self.package.get_tmp_dir()?);
+        let target = would_be_path(&self.package.get_target_dir()?);
+        let problem = if tmp == target {
+            "are the same directory"
+        } else if target.starts_with(&tmp) {
+            "overlap: the target is inside tmp"
+        } else {
+            return Ok(());
+        };
+
+        Err(UnityPackageReaderError::InvalidConfiguration(
+            ErrorInformation::new(
+                Some(format!(
+                    "tmp '{}' and target '{}' {}",
+                    tmp.display(),
+                    target.display(),
+                    problem
+                )),
+                file!(),
+                line!(),
+            ),
+        ))
+    }
+
+    /// With `UnpackConfig::require_unity_project`, fail before anything is
+    /// written unless the target is a Unity project.
+    fn check_target(&self) -> Result<(), UnityPackageReaderError> {
+        let config = self.package.get_config();
+        if !config.require_unity_project || !config.install {
+            return Ok(());
+        }
+
+        compatibility::check_unity_project(&self.package.get_target_dir()?)
+    }
+
+    /// With `UnpackConfig::check_disk_space`, fail before anything is
+    /// extracted if the package does not fit on the tmp and target volumes.
+    fn check_disk_space(&self) -> Result<(), UnityPackageReaderError> {
+        let config = self.package.get_config();
+        if !config.check_disk_space {
+            return Ok(());
+        }
+
+        let size = size_estimate::estimate_size(self.package.open_package_file()?)?.total();
+        let target = match config.install {
+            true => Some(self.package.get_target_dir()?),
+            false => None,
+        };
+        disk_space::check_disk_space(
+            size,
+            &self.package.get_tmp_dir()?,
+            target.as_deref(),
+            disk_space::available_space,
+            disk_space::volume_id,
+        )
+    }
+
+    /// Index the tmp directory and switch to the installing phase.
+    fn begin_installing(
+        &mut self,
+        tmp: TempDirGuard,
+        stats: UnpackStats,
+        archive_order: &[Guid],
+        hashes: &HashMap<Guid, [u8; 32]>,
+        resuming: bool,
+    ) -> Result<(), UnityPackageReaderError> {
+        let (target, assets) =
+            self.package
+                .begin_install(tmp.path(), stats, archive_order, hashes, resuming)?;
+        let staging = if self.package.get_config().atomic {
+            Some(Staging::new(&target)?)
+        } else {
+            None
+        };
+
+        self.phase = Phase::Installing {
+            tmp,
+            target,
+            staging,
+            assets: assets.into_iter(),
+        };
+        Ok(())
+    }
+}
+
+/// The absolute path path has, or would have once it is created: the part
+/// that exists is canonicalized (resolving symlinks), the rest is appended
+/// with `.` and `..` resolved.
+fn would_be_path(path: &Path) -> PathBuf {
+    let absolute = match std::env::current_dir() {
+        Ok(dir) => dir.join(path),
+        Err(_) => path.to_path_buf(),
+    };
+
+    for ancestor in absolute.ancestors() {
+        let mut result = match fs::canonicalize(ancestor) {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
+        for component in absolute
+            .strip_prefix(ancestor)
+            .unwrap_or(Path::new(""))
+            .components()
+        {
+            match component {
+                Component::ParentDir => {
+                    result.pop();
+                }
+                Component::Normal(n) => result.push(n),
+                _ => {}
+            }
+        }
+        return result;
+    }
+
+    absolute
+}
+
+/// Make sure dir looks like an extracted package: nothing but guid
+/// directories, each of them with a pathname file.
+fn check_extracted_tmp(dir: &Path, fs: &dyn FileSystem) -> Result<(), UnityPackageReaderError> {
+    let invalid = |message: String| {
+        UnityPackageReaderError::InvalidTmpDirectory(ErrorInformation::new(
+            Some(message),
+            file!(),
+            line!(),
+        ))
+    };
+
+    let entries = match fs.read_dir(dir) {
+        Ok(e) => e,
+        Err(e) => return Err(invalid(format!("{:?}: {}", dir, e))),
+    };
+
+    let mut count = 0;
+    for entry in entries {
+        let path = match entry {
+            Ok(e) => e,
+            Err(e) => return Err(invalid(format!("{:?}: {}", dir, e))),
+        };
+
+        let is_guid = path
+            .file_name()
+            .map(|n| n.to_string_lossy().parse::<Guid>().is_ok())
+            .unwrap_or(false);
+        let is_dir = fs.exists(&path) && fs.file_size(&path).is_none();
+        if !is_guid || !is_dir {
+            return Err(invalid(format!("{:?} is not a guid directory", path)));
+        }
+
+        if fs.file_size(&path.join("pathname")).is_none() {
+            return Err(invalid(format!("{:?} has no pathname file", path)));
+        }
+        count += 1;
+    }
+
+    if count == 0 {
+        return Err(invalid(format!("{:?} is empty", dir)));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{BufferSizes, ExtractOrdering, UnpackConfig};
+    use crate::test_utils::*;
+
+    fn steps_until_finished(package: &mut UnityPackage, budget: StepBudget) -> usize {
+        let mut session = UnpackSession::new(package, &UnpackOptions::default());
+        let mut steps = 1;
+        loop {
+            match session.step(budget) {
+                StepResult::Continue => steps += 1,
+                StepResult::Finished(_) => return steps,
+                StepResult::Failed(e) => panic!("{}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_stepping_matches_blocking_unpack() {
+        let blocking = Fixture::sample();
+        let mut reference = blocking.open();
+        reference.unpack_package(true).unwrap();
+
+        for budget in [StepBudget::Entries(1), StepBudget::Bytes(0)] {
+            let stepped = Fixture::sample();
+            let mut subject = stepped.open();
+            assert!(steps_until_finished(&mut subject, budget) > 8);
+
+            assert_eq!(subject.get_stats(), reference.get_stats());
+            assert!(!stepped.tmp.exists());
+            assert_eq!(
+                std::fs::read(stepped.target.join(TEXTURE_PATH)).unwrap(),
+                TEXTURE_CONTENT
+            );
+            assert!(subject.get_file(SCRIPT_GUID).is_some());
+        }
+    }
+
+    #[test]
+    fn test_byte_budget_splits_large_files() {
+        let content = vec![1u8; 100_000];
+        let guid = "0123456789abcdef0123456789abcdef";
+        let builder =
+            PackageBuilder::new().asset(guid, "Assets/large.bin", &content, &texture_meta(guid));
+        let fixture = Fixture::new(&builder);
+        let mut subject = fixture.open();
+
+        assert!(steps_until_finished(&mut subject, StepBudget::Bytes(10_000)) >= 10);
+        assert_eq!(
+            std::fs::read(fixture.target.join("Assets/large.bin")).unwrap(),
+            content
+        );
+        assert_eq!(
+            steps_until_finished(&mut fixture.open(), StepBudget::Unlimited),
+            1
+        );
+    }
+
+    #[test]
+    fn test_extraction_streams_the_package() {
+        // Poorly compressible, so the package file itself is large.
+        let mut state = 0x2545_f491u32;
+        let content: Vec<u8> = (0..4 << 20)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let guid = "0123456789abcdef0123456789abcdef";
+        let fixture = Fixture::new(&PackageBuilder::new().asset(
+            guid,
+            "Assets/large.bin",
+            &content,
+            &texture_meta(guid),
+        ));
+        let package_size = std::fs::metadata(&fixture.package).unwrap().len();
+
+        let package = fixture.open();
+        let mut extraction = Extraction::start(&package, true).unwrap();
+        let mut allowance = Allowance::new(StepBudget::Bytes(256 << 10));
+        assert!(!extraction.step(&mut allowance).unwrap());
+
+        // Only about as much as has been extracted has been read from disk.
+        let (_, stats, _) = extraction.finish();
+        assert!(stats.compressed_bytes_read < package_size / 4);
+        assert!(stats.tmp_bytes_written <= 256 << 10);
+    }
+
+    #[test]
+    fn test_failed_session_stays_failed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing.unitypackage");
+        let mut package = UnityPackage::from_path(&path, None, None).unwrap();
+        let mut session = UnpackSession::new(&mut package, &UnpackOptions::default());
+
+        let first = session.step(StepBudget::Entries(1));
+        assert!(matches!(
+            first,
+            StepResult::Failed(UnityPackageReaderError::PackageNotFound(_))
+        ));
+        assert_eq!(session.step(StepBudget::Entries(1)), first);
+    }
+
+    #[test]
+    fn test_allowance() {
+        let mut allowance = Allowance::new(StepBudget::Bytes(0));
+        assert!(!allowance.is_exhausted());
+        assert_eq!(allowance.max_bytes(), 1);
+        allowance.spend(0, 1);
+        assert!(allowance.is_exhausted());
+
+        let mut allowance = Allowance::new(StepBudget::Entries(2));
+        allowance.spend(1, 1000);
+        assert!(!allowance.is_exhausted());
+        allowance.spend(1, 0);
+        assert!(allowance.is_exhausted());
+    }
+
+    #[test]
+    fn test_install_ordering() {
+        let builder = PackageBuilder::new()
+            .asset(
+                SCRIPT_GUID,
+                SCRIPT_PATH,
+                SCRIPT_CONTENT,
+                &script_meta(SCRIPT_GUID),
+            )
+            .asset(
+                TEXTURE_GUID,
+                TEXTURE_PATH,
+                TEXTURE_CONTENT,
+                &texture_meta(TEXTURE_GUID),
+            )
+            .folder(FOLDER_GUID, FOLDER_PATH);
+
+        let expected = [
+            (
+                ExtractOrdering::ArchiveOrder,
+                [SCRIPT_GUID, TEXTURE_GUID, FOLDER_GUID],
+            ),
+            (
+                ExtractOrdering::SmallestFirst,
+                [FOLDER_GUID, TEXTURE_GUID, SCRIPT_GUID],
+            ),
+            (
+                ExtractOrdering::LargestFirst,
+                [SCRIPT_GUID, TEXTURE_GUID, FOLDER_GUID],
+            ),
+            (
+                ExtractOrdering::Alphabetical,
+                [SCRIPT_GUID, FOLDER_GUID, TEXTURE_GUID],
+            ),
+        ];
+
+        let mut reference: Option<Vec<PathBuf>> = None;
+        for (ordering, guids) in expected {
+            let fixture = Fixture::new(&builder);
+            let mut package = fixture.open().with_config(UnpackConfig {
+                ordering,
+                ..Default::default()
+            });
+
+            let (tmp, stats, archive_order) =
+                Extraction::start(&package, true).unwrap().run().unwrap();
+            let (_, assets) = package
+                .begin_install(tmp.path(), stats, &archive_order, &HashMap::new(), false)
+                .unwrap();
+            let order: Vec<String> = assets.iter().map(|a| a.get_guid().to_string()).collect();
+            assert_eq!(order, guids, "{:?}", ordering);
+
+            // The ordering does not change the result.
+            package
+                .unpack_package_with(&UnpackOptions {
+                    delete_tmp: true,
+                    config: None,
+                    path_rewrite: None,
+                    observer: None,
+                    filter: None,
+                    buffer_sizes: BufferSizes::default(),
+                })
+                .unwrap();
+            let installed: Vec<PathBuf> = package
+                .files()
+                .map(|f| f.get_relative_asset_path().clone())
+                .collect();
+            assert!(fixture.target.join(TEXTURE_PATH).exists());
+            match &reference {
+                Some(r) => assert_eq!(&installed, r),
+                None => reference = Some(installed),
+            }
+        }
+    }
+}
@@ -0,0 +1,14 @@
+/// Byte accounting of a single `unpack_package` run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UnpackStats {
+    /// Compressed bytes read from the package file.
+    pub compressed_bytes_read: u64,
+    /// Uncompressed bytes produced by the gzip decoder.
+    pub uncompressed_bytes: u64,
+    /// Bytes written into the tmp directory.
+    pub tmp_bytes_written: u64,
+    /// Bytes moved or copied from tmp into the target directory.
+    pub target_bytes_written: u64,
+    /// Bytes deleted when the tmp directory has been removed.
+    pub cleanup_bytes_deleted: u64,
+}
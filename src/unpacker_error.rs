@@ -1,6 +1,9 @@
-use std::fmt;
+use std::{fmt, path::PathBuf};
 
-#[derive(Debug, PartialEq, PartialOrd)]
+use crate::extraction_report::AssetFailure;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ErrorInformation {
     pub message: Option<String>,
     pub src_file: String,
@@ -28,7 +31,8 @@ impl fmt::Display for ErrorInformation {
     }
 }
 
-#[derive(Debug, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnityPackageReaderError {
     PackageNotFound(ErrorInformation),
     CorruptPackage(ErrorInformation),
@@ -39,6 +43,74 @@ pub enum UnityPackageReaderError {
     NotAPackageFile(ErrorInformation),
     CouldReadMetaFile(ErrorInformation),
     CouldNotDeleteTmp(ErrorInformation),
+    ChecksumFileError(ErrorInformation),
+    TargetFileExists(ErrorInformation),
+    CouldNotUpdateGitIgnore(ErrorInformation),
+    CopyFailed(ErrorInformation),
+    InvalidAssemblyDefinition(ErrorInformation),
+    CouldNotReadProjectFile(ErrorInformation),
+    CouldNotReadTarget(ErrorInformation),
+    InvalidGuid(ErrorInformation),
+    CouldNotWritePackage(ErrorInformation),
+    InvalidConfig(ErrorInformation),
+    CouldNotWriteReport(ErrorInformation),
+    LimitExceeded(ErrorInformation),
+    InvalidTmpDirectory(ErrorInformation),
+    AssetNotFound(ErrorInformation),
+    AssetIsFolder(ErrorInformation),
+    CouldNotReadAsset(ErrorInformation),
+    CaseCollision(ErrorInformation),
+    /// Paths of the package contain names Windows reserves for devices, see
+    /// `UnpackConfig::reserved_names`.
+    ReservedName(ErrorInformation),
+    UnsafeEntry(ErrorInformation),
+    UnsupportedOnThisTarget(ErrorInformation),
+    /// The target ran out of space.
+    DiskFull(ErrorInformation),
+    /// The archive contains a guid directory more than once and the copies
+    /// differ, see `UnpackConfig::duplicate_guids`.
+    DuplicateGuid(ErrorInformation),
+    /// The target is no Unity project, see `UnpackConfig::require_unity_project`.
+    NotAUnityProject(ErrorInformation),
+    /// A search pattern is no valid regular expression, see `UnityPackage::find`.
+    InvalidPattern(ErrorInformation),
+    /// The tmp and the target directory are the same or the target is inside
+    /// tmp, so installing would move files onto themselves or removing tmp
+    /// would delete installed assets. tmp inside the target is fine.
+    InvalidConfiguration(ErrorInformation),
+    /// A target file that gets replaced is read-only, see
+    /// `UnpackConfig::clear_readonly`.
+    ReadOnlyTarget(ErrorInformation),
+    /// An installed file could not be removed, see `CombinedIndex::uninstall`.
+    RemoveFailed(ErrorInformation),
+    /// The volume of path has less than required bytes available, see
+    /// `UnpackConfig::check_disk_space`.
+    InsufficientDiskSpace {
+        path: PathBuf,
+        required: u64,
+        available: u64,
+    },
+    /// Some assets could not be installed, see `UnpackConfig::fail_on_asset_errors`.
+    PartialFailure(Vec<AssetFailure>),
+}
+
+impl UnityPackageReaderError {
+    /// True, if the error concerns the whole unpack rather than a single
+    /// asset, so `UnpackConfig::continue_on_error` does not go on after it.
+    pub(crate) fn is_catastrophic(&self) -> bool {
+        matches!(
+            self,
+            UnityPackageReaderError::TmpDirectoryCouldNotBeCreated(_)
+                | UnityPackageReaderError::InvalidTmpDirectory(_)
+                | UnityPackageReaderError::WorkingDirectoryError(_)
+                | UnityPackageReaderError::LimitExceeded(_)
+                | UnityPackageReaderError::UnsafeEntry(_)
+                | UnityPackageReaderError::UnsupportedOnThisTarget(_)
+                | UnityPackageReaderError::DiskFull(_)
+                | UnityPackageReaderError::InsufficientDiskSpace { .. }
+                | UnityPackageReaderError::PartialFailure(_)
+        )
+    }
 }
 
 impl fmt::Display for UnityPackageReaderError {
@@ -50,9 +122,44 @@ impl fmt::Display for UnityPackageReaderError {
             UnityPackageReaderError::TmpDirectoryCouldNotBeCreated(s) => write!(f, "Could not create the temp dir.\n{}", s),
             UnityPackageReaderError::TargetDirectoryCouldNotBeCreated(s) => write!(f, "Could not create the target dir.\n{}", s),
             UnityPackageReaderError::WorkingDirectoryError(e) => write!(f, "Could not determine the current working directory. Consider passing an absolute path to the file to create a UnityPackage.{}", e),
-            UnityPackageReaderError::NotAPackageFile(e) => write!(f, "The given path seems to point to a directory.{}", e),
+            UnityPackageReaderError::NotAPackageFile(e) => write!(f, "The given path does not point to a package file.{}", e),
             UnityPackageReaderError::CouldReadMetaFile(e) => write!(f, "Could not interpret meta data.{}", e),
             UnityPackageReaderError::CouldNotDeleteTmp(e) => write!(f, "Could not delete tmp directory.{}", e),
+            UnityPackageReaderError::ChecksumFileError(e) => write!(f, "Could not read or write checksums.{}", e),
+            UnityPackageReaderError::TargetFileExists(e) => write!(f, "The target file already exists.{}", e),
+            UnityPackageReaderError::CouldNotUpdateGitIgnore(e) => write!(f, "Could not update the .gitignore file.{}", e),
+            UnityPackageReaderError::CopyFailed(e) => write!(f, "Could not copy a file into the target dir.{}", e),
+            UnityPackageReaderError::InvalidAssemblyDefinition(e) => write!(f, "Could not interpret an assembly definition.{}", e),
+            UnityPackageReaderError::CouldNotReadProjectFile(e) => write!(f, "Could not read a file of the Unity project.{}", e),
+            UnityPackageReaderError::CouldNotReadTarget(e) => write!(f, "Could not read the target dir.{}", e),
+            UnityPackageReaderError::InvalidGuid(e) => write!(f, "Not a valid guid.{}", e),
+            UnityPackageReaderError::CouldNotWritePackage(e) => write!(f, "Could not write the package.{}", e),
+            UnityPackageReaderError::InvalidConfig(e) => write!(f, "Could not read the unpack config.{}", e),
+            UnityPackageReaderError::CouldNotWriteReport(e) => write!(f, "Could not write a report file.{}", e),
+            UnityPackageReaderError::LimitExceeded(e) => write!(f, "The package exceeds an unpack limit.{}", e),
+            UnityPackageReaderError::InvalidTmpDirectory(e) => write!(f, "The tmp dir does not contain an extracted package.{}", e),
+            UnityPackageReaderError::AssetNotFound(e) => write!(f, "The asset is not part of the package.{}", e),
+            UnityPackageReaderError::UnsafeEntry(e) => write!(f, "The package contains a link or special file.{}", e),
+            UnityPackageReaderError::CaseCollision(e) => write!(f, "Some paths of the package only differ in case.{}", e),
+            UnityPackageReaderError::ReservedName(e) => write!(f, "Some paths of the package use names Windows reserves for devices.{}", e),
+            UnityPackageReaderError::AssetIsFolder(e) => write!(f, "The asset is a folder and has no content.{}", e),
+            UnityPackageReaderError::CouldNotReadAsset(e) => write!(f, "Could not read the content of an asset.{}", e),
+            UnityPackageReaderError::UnsupportedOnThisTarget(e) => write!(f, "The operation needs a file system, which this target does not have.{}", e),
+            UnityPackageReaderError::DiskFull(e) => write!(f, "The target is out of space.{}", e),
+            UnityPackageReaderError::DuplicateGuid(e) => write!(f, "The package contains differing copies of a guid.{}", e),
+            UnityPackageReaderError::NotAUnityProject(e) => write!(f, "The target is not a Unity project.{}", e),
+            UnityPackageReaderError::InvalidPattern(e) => write!(f, "The search pattern is not a valid regular expression.{}", e),
+            UnityPackageReaderError::InvalidConfiguration(e) => write!(f, "The tmp and the target directory overlap.{}", e),
+            UnityPackageReaderError::ReadOnlyTarget(e) => write!(f, "A target file is read-only.{}", e),
+            UnityPackageReaderError::RemoveFailed(e) => write!(f, "Could not remove a file from the target dir.{}", e),
+            UnityPackageReaderError::InsufficientDiskSpace { path, required, available } => write!(f, "Not enough disk space: {} bytes are needed on the volume of '{}', {} are available.", required, path.display(), available),
+            UnityPackageReaderError::PartialFailure(failures) => {
+                write!(f, "{} assets could not be installed.", failures.len())?;
+                for failure in failures {
+                    write!(f, "\n'{}': {}", failure.path.display(), failure.error)?;
+                }
+                Ok(())
+            }
         }
     }
 }
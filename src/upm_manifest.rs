@@ -0,0 +1,142 @@
+use std::path::PathBuf;
+
+use serde_json::{Map, Value};
+
+use crate::{
+    checksums,
+    unpacker_error::{ErrorInformation, UnityPackageReaderError},
+};
+
+/// The file name UPM expects in the root of a package.
+pub(crate) const UPM_MANIFEST_FILE: &str = "package.json";
+
+/// Prepended to names derived from a file name that is not in reverse-DNS form.
+const DEFAULT_NAME_PREFIX: &str = "com.unitypackage";
+
+/// The fields of the `package.json` written by
+/// `UnityPackage::generate_upm_manifest`. Every field left at None is derived
+/// from the package.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UpmManifestOptions {
+    /// Defaults to the file name of the package in reverse-DNS form, e.g.
+    /// `com.unitypackage.vendor-tool` for `Vendor Tool.unitypackage`.
+    pub name: Option<String>,
+    /// Defaults to `1.0.0`.
+    pub version: Option<String>,
+    /// Defaults to the file name of the package.
+    pub display_name: Option<String>,
+    /// The oldest Unity version the package supports, e.g. `2021.3`. Left
+    /// out of the manifest if None.
+    pub unity: Option<String>,
+    /// Also write the manifest into the package root in the target: the
+    /// destination of the first `UnpackConfig::root_remap` rule, or the
+    /// target itself without rules.
+    pub write: bool,
+}
+
+/// A UPM package name for a package file name: lower case, the dot separated
+/// parts reduced to `a-z`, `0-9` and `-`. Names with less than three parts
+/// get `DEFAULT_NAME_PREFIX`.
+pub(crate) fn package_name(file_name: &str) -> String {
+    let parts: Vec<String> = file_name
+        .split('.')
+        .map(|part| {
+            let mut sanitized = String::new();
+            for c in part.chars().map(|c| c.to_ascii_lowercase()) {
+                if c.is_ascii_lowercase() || c.is_ascii_digit() {
+                    sanitized.push(c);
+                } else if !sanitized.is_empty() && !sanitized.ends_with('-') {
+                    sanitized.push('-');
+                }
+            }
+            String::from(sanitized.trim_end_matches('-'))
+        })
+        .filter(|p| !p.is_empty())
+        .collect();
+
+    match parts.len() {
+        0 => format!("{}.package", DEFAULT_NAME_PREFIX),
+        1 | 2 => format!("{}.{}", DEFAULT_NAME_PREFIX, parts.join("-")),
+        _ => parts.join("."),
+    }
+}
+
+/// True, if name is a valid UPM package name: lower case letters, digits,
+/// `-`, `_` and `.`, not starting with a dot.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Build the JSON of the manifest. files are the paths relative to the
+/// package root.
+pub(crate) fn build_manifest(
+    file_name: &str,
+    options: &UpmManifestOptions,
+    files: &[PathBuf],
+) -> Result<String, UnityPackageReaderError> {
+    let name = options
+        .name
+        .clone()
+        .unwrap_or_else(|| package_name(file_name));
+    if !is_valid_name(&name) {
+        return Err(UnityPackageReaderError::InvalidConfig(
+            ErrorInformation::new(
+                Some(format!("'{}' is not a valid UPM package name", name)),
+                file!(),
+                line!(),
+            ),
+        ));
+    }
+
+    let mut manifest = Map::new();
+    manifest.insert(String::from("name"), Value::from(name));
+    manifest.insert(
+        String::from("version"),
+        Value::from(options.version.as_deref().unwrap_or("1.0.0")),
+    );
+    manifest.insert(
+        String::from("displayName"),
+        Value::from(options.display_name.as_deref().unwrap_or(file_name)),
+    );
+    if let Some(unity) = &options.unity {
+        manifest.insert(String::from("unity"), Value::from(unity.as_str()));
+    }
+    manifest.insert(
+        String::from("files"),
+        Value::from(
+            files
+                .iter()
+                .map(|f| checksums::to_forward_slashes(f))
+                .collect::<Vec<String>>(),
+        ),
+    );
+
+    match serde_json::to_string_pretty(&Value::Object(manifest)) {
+        Ok(json) => Ok(json),
+        Err(e) => Err(UnityPackageReaderError::CouldNotWriteReport(
+            ErrorInformation::new(Some(format!("{}", e)), file!(), line!()),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_name() {
+        assert_eq!(
+            package_name("Vendor Tool Kit"),
+            "com.unitypackage.vendor-tool-kit"
+        );
+        assert_eq!(package_name("Tool 1.2"), "com.unitypackage.tool-1-2");
+        assert_eq!(package_name("com.Vendor.Thing"), "com.vendor.thing");
+        assert_eq!(package_name("__"), "com.unitypackage.package");
+        assert!(is_valid_name(&package_name("Ünïcode Tööl")));
+        assert!(!is_valid_name("Com.Vendor"));
+    }
+}
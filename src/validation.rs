@@ -0,0 +1,457 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, BufRead},
+    path::{Component, Path, PathBuf},
+};
+use tar::Archive;
+
+use crate::{
+    checksums,
+    guid::Guid,
+    package_format,
+    prelude::{MetaData, MetaFormat, Severity},
+    unity_asset_file, unpack_limits,
+    unpacker_error::UnityPackageReaderError,
+};
+
+/// A single finding of `UnityPackage::validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    /// The guid directory the issue is about, None for problems of the archive itself.
+    pub guid: Option<String>,
+    pub message: String,
+}
+
+/// What a guid directory of the archive contains, see `GuidFolder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GuidFolderState {
+    /// Payload and meta, or a folder meta (folders have no payload).
+    Complete,
+    /// A payload without a meta.
+    MissingMeta,
+    /// A meta of an asset that is no folder, without a payload.
+    MissingAsset,
+    /// Neither a payload nor a meta, e.g. only a pathname.
+    Empty,
+}
+
+/// A guid directory of the archive, classified by what it contains.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuidFolder {
+    /// The name of the directory, which is not necessarily a valid guid.
+    pub guid: String,
+    /// The pathname, None if it is missing, empty or not UTF-8.
+    pub path: Option<PathBuf>,
+    pub state: GuidFolderState,
+}
+
+impl GuidFolder {
+    /// True, if the directory cannot be installed as it is.
+    pub fn is_orphan(&self) -> bool {
+        self.state != GuidFolderState::Complete
+    }
+}
+
+/// The result of checking the structure of a package without extracting it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+    /// Every guid directory of the archive, ordered by name.
+    pub guid_folders: Vec<GuidFolder>,
+}
+
+impl ValidationReport {
+    /// The severity of the worst issue, None if there are no issues.
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.issues.iter().map(|i| i.severity).max()
+    }
+
+    /// True, if no issue has `Severity::Error`.
+    pub fn is_valid(&self) -> bool {
+        self.max_severity() != Some(Severity::Error)
+    }
+
+    fn push(&mut self, severity: Severity, guid: Option<&str>, message: String) {
+        self.issues.push(ValidationIssue {
+            severity,
+            guid: guid.map(String::from),
+            message,
+        });
+    }
+}
+
+/// The files found in a guid directory.
+#[derive(Default)]
+struct GuidDirectory {
+    pathname: Option<String>,
+    has_meta: bool,
+    has_asset: bool,
+    /// `folderAsset` of the meta, None if the meta could not be parsed.
+    is_folder: Option<bool>,
+}
+
+impl GuidDirectory {
+    fn state(&self) -> GuidFolderState {
+        match (self.has_asset, self.has_meta) {
+            (true, true) => GuidFolderState::Complete,
+            (true, false) => GuidFolderState::MissingMeta,
+            (false, true) if self.is_folder == Some(true) => GuidFolderState::Complete,
+            (false, true) => GuidFolderState::MissingAsset,
+            (false, false) => GuidFolderState::Empty,
+        }
+    }
+}
+
+fn is_guid_name(name: &str) -> bool {
+    name.parse::<Guid>().is_ok()
+}
+
+/// Walk through the whole archive and collect every problem found instead of
+/// stopping at the first one. Only a file that is not a package at all is an error.
+pub(crate) fn validate<R: BufRead>(reader: R) -> Result<ValidationReport, UnityPackageReaderError> {
+    let mut archive = Archive::new(package_format::open(reader)?);
+    let mut report = ValidationReport::default();
+    let mut directories: BTreeMap<String, GuidDirectory> = BTreeMap::new();
+
+    let entries = match archive.entries() {
+        Ok(e) => e,
+        Err(e) => {
+            report.push(Severity::Error, None, format!("Unreadable archive: {}", e));
+            return Ok(report);
+        }
+    };
+
+    let mut current: Option<String> = None;
+    for entry in entries {
+        let mut entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                report.push(
+                    Severity::Error,
+                    current.as_deref(),
+                    format!("The archive is truncated or corrupt: {}", e),
+                );
+                break;
+            }
+        };
+
+        let path = match entry.path() {
+            Ok(p) => p.into_owned(),
+            Err(e) => {
+                report.push(Severity::Error, None, format!("Invalid entry path: {}", e));
+                continue;
+            }
+        };
+
+        let mut components = path.components().filter_map(|c| match c {
+            Component::Normal(n) => Some(n.to_string_lossy().into_owned()),
+            _ => None,
+        });
+        let guid = match components.next() {
+            Some(g) => g,
+            None => continue,
+        };
+        let name = components.next();
+        if components.next().is_some() {
+            report.push(
+                Severity::Warning,
+                Some(&guid),
+                format!("Unexpected nested entry {:?}", path),
+            );
+            continue;
+        }
+
+        if !directories.contains_key(&guid) && !is_guid_name(&guid) {
+            report.push(
+                Severity::Error,
+                Some(&guid),
+                String::from("The directory name is not a guid of 32 hex digits"),
+            );
+        }
+        current = Some(guid.clone());
+        let directory = directories.entry(guid.clone()).or_default();
+
+        let name = match name {
+            Some(n) => n,
+            None => continue,
+        };
+
+        match name.as_str() {
+            "asset" => {
+                directory.has_asset = true;
+                if let Err(e) = io::copy(&mut entry, &mut io::sink()) {
+                    report.push(
+                        Severity::Error,
+                        Some(&guid),
+                        format!("The asset is truncated: {}", e),
+                    );
+                    break;
+                }
+            }
+            "asset.meta" | "metaData" | "pathname" => {
+                // Too large or not UTF-8, the rest of the archive can still be read.
+                let content = match unpack_limits::read_text(&mut entry) {
+                    Ok(c) => c,
+                    Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                        report.push(
+                            Severity::Error,
+                            Some(&guid),
+                            format!("{} is unreadable: {}", name, e),
+                        );
+                        continue;
+                    }
+                    Err(e) => {
+                        report.push(
+                            Severity::Error,
+                            Some(&guid),
+                            format!("{} is truncated: {}", name, e),
+                        );
+                        break;
+                    }
+                };
+
+                if name == "pathname" {
+                    directory.pathname = Some(content);
+                    continue;
+                }
+
+                directory.has_meta = true;
+                let format = match name.as_str() {
+                    "metaData" => MetaFormat::LegacyMetaData,
+                    _ => MetaFormat::AssetMeta,
+                };
+                match MetaData::parse_format(&content, format, Some(&guid)) {
+                    Ok(meta) => {
+                        directory.is_folder = Some(meta.folder_asset);
+                        if meta.guid != guid {
+                            report.push(
+                                Severity::Warning,
+                                Some(&guid),
+                                format!("The meta file has the guid {}", meta.guid),
+                            );
+                        }
+                    }
+                    Err(e) => report.push(
+                        Severity::Error,
+                        Some(&guid),
+                        format!("Unreadable meta file: {}", e),
+                    ),
+                }
+            }
+            "preview.png" => {}
+            _ => report.push(
+                Severity::Warning,
+                Some(&guid),
+                format!("Unexpected file {:?}", name),
+            ),
+        }
+    }
+
+    let mut targets: HashMap<String, &str> = HashMap::new();
+    for (guid, directory) in &directories {
+        report.guid_folders.push(GuidFolder {
+            guid: guid.clone(),
+            path: directory
+                .pathname
+                .as_deref()
+                .map(unity_asset_file::trim_pathname)
+                .filter(|p| !p.is_empty())
+                .map(|p| PathBuf::from(unity_asset_file::normalize_pathname(p))),
+            state: directory.state(),
+        });
+
+        if !directory.has_meta {
+            report.push(
+                Severity::Error,
+                Some(guid),
+                String::from("asset.meta is missing"),
+            );
+        } else if directory.is_folder == Some(false) && !directory.has_asset {
+            report.push(
+                Severity::Warning,
+                Some(guid),
+                String::from("The asset payload is missing"),
+            );
+        }
+
+        let pathname = match directory
+            .pathname
+            .as_deref()
+            .map(unity_asset_file::trim_pathname)
+        {
+            Some(p) if !p.is_empty() => p,
+            Some(_) => {
+                report.push(
+                    Severity::Error,
+                    Some(guid),
+                    String::from("pathname is empty"),
+                );
+                continue;
+            }
+            None => {
+                report.push(
+                    Severity::Error,
+                    Some(guid),
+                    String::from("pathname is missing"),
+                );
+                continue;
+            }
+        };
+
+        let key = checksums::to_forward_slashes(Path::new(pathname));
+        match targets.get(&key) {
+            Some(other) => report.push(
+                Severity::Error,
+                Some(guid),
+                format!("{} is also the pathname of {}", key, other),
+            ),
+            None => {
+                targets.insert(key, guid);
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn issues_of<'a>(report: &'a ValidationReport, guid: &str) -> Vec<&'a ValidationIssue> {
+        report
+            .issues
+            .iter()
+            .filter(|i| i.guid.as_deref() == Some(guid))
+            .collect()
+    }
+
+    #[test]
+    fn test_truncated_package() {
+        let bytes = sample_package().tar_bytes();
+        let truncated = &bytes[..bytes.len() / 2 + 100];
+
+        let report = validate(truncated).unwrap();
+        assert!(!report.is_valid());
+        assert!(report
+            .issues
+            .iter()
+            .any(|i| i.message.contains("truncated")));
+    }
+
+    #[test]
+    fn test_broken_entries() {
+        let missing_meta = "00000000000000000000000000000001";
+        let collision = "ffffffffffffffffffffffffffffffff";
+        let bad_meta = "00000000000000000000000000000003";
+        let huge_pathname = "00000000000000000000000000000004";
+        let bytes = sample_package()
+            .file("not-a-guid/asset.meta", folder_meta("not-a-guid"))
+            .file("not-a-guid/pathname", "Assets/Odd")
+            .file(&format!("{}/asset", missing_meta), b"x")
+            .file(&format!("{}/pathname", missing_meta), "Assets/NoMeta.txt")
+            .asset(collision, TEXTURE_PATH, b"y", &texture_meta(collision))
+            .file(&format!("{}/asset.meta", bad_meta), b"  indented first\n")
+            .file(
+                &format!("{}/pathname", huge_pathname),
+                "a".repeat(unpack_limits::MAX_TEXT_BYTES as usize + 1),
+            )
+            .bytes();
+
+        let report = validate(&bytes[..]).unwrap();
+        assert!(!report.is_valid());
+        assert_eq!(issues_of(&report, "not-a-guid").len(), 1);
+        assert_eq!(
+            issues_of(&report, missing_meta)[0].message,
+            "asset.meta is missing"
+        );
+        assert!(issues_of(&report, collision)[0]
+            .message
+            .contains(TEXTURE_GUID));
+
+        let bad: Vec<&str> = issues_of(&report, bad_meta)
+            .iter()
+            .map(|i| i.message.as_str())
+            .collect();
+        assert_eq!(bad.len(), 2);
+        assert!(bad[0].starts_with("Unreadable meta file"));
+        assert_eq!(bad[1], "pathname is missing");
+        assert!(issues_of(&report, huge_pathname)[0]
+            .message
+            .starts_with("pathname is unreadable"));
+
+        assert!(issues_of(&report, TEXTURE_GUID).is_empty());
+        assert!(issues_of(&report, SCRIPT_GUID).is_empty());
+    }
+
+    #[test]
+    fn test_guid_folders() {
+        let missing_meta = "00000000000000000000000000000001";
+        let missing_asset = "00000000000000000000000000000002";
+        let legacy = "00000000000000000000000000000003";
+        let empty = "00000000000000000000000000000004";
+        let bytes = sample_package()
+            .file(&format!("{}/asset", missing_meta), b"x")
+            .file(&format!("{}/pathname", missing_meta), "Assets/NoMeta.txt")
+            .file(
+                &format!("{}/asset.meta", missing_asset),
+                texture_meta(missing_asset),
+            )
+            .file(
+                &format!("{}/pathname", missing_asset),
+                "Assets/Gone.png\n00",
+            )
+            .file(&format!("{}/asset", legacy), b"y")
+            .file(&format!("{}/metaData", legacy), texture_meta(legacy))
+            .file(&format!("{}/pathname", legacy), "Assets/Legacy.png")
+            .file(&format!("{}/preview.png", empty), b"png")
+            .bytes();
+
+        let report = validate(&bytes[..]).unwrap();
+        let folders: Vec<(&str, Option<&Path>, GuidFolderState)> = report
+            .guid_folders
+            .iter()
+            .map(|f| (f.guid.as_str(), f.path.as_deref(), f.state))
+            .collect();
+        assert_eq!(
+            folders,
+            [
+                (
+                    missing_meta,
+                    Some(Path::new("Assets/NoMeta.txt")),
+                    GuidFolderState::MissingMeta
+                ),
+                (
+                    missing_asset,
+                    Some(Path::new("Assets/Gone.png")),
+                    GuidFolderState::MissingAsset
+                ),
+                (
+                    legacy,
+                    Some(Path::new("Assets/Legacy.png")),
+                    GuidFolderState::Complete
+                ),
+                (empty, None, GuidFolderState::Empty),
+                (
+                    TEXTURE_GUID,
+                    Some(Path::new(TEXTURE_PATH)),
+                    GuidFolderState::Complete
+                ),
+                (
+                    FOLDER_GUID,
+                    Some(Path::new(FOLDER_PATH)),
+                    GuidFolderState::Complete
+                ),
+                (
+                    SCRIPT_GUID,
+                    Some(Path::new(SCRIPT_PATH)),
+                    GuidFolderState::Complete
+                ),
+            ]
+        );
+        let orphans = report.guid_folders.iter().filter(|f| f.is_orphan());
+        assert_eq!(orphans.count(), 3);
+    }
+}
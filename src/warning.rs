@@ -0,0 +1,189 @@
+use std::{fmt, path::PathBuf};
+
+use crate::guid::Guid;
+
+/// A condition that does not abort an unpack, but that the caller should know about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Warning {
+    /// The meta file marks the asset as folder, but there is a payload as well.
+    FolderAssetWithPayload { guid: Guid, path: PathBuf },
+    /// Additive mode: the target already contains the guid at existing, so the
+    /// asset has not been installed.
+    AlreadyPresent {
+        guid: Guid,
+        path: PathBuf,
+        existing: PathBuf,
+    },
+    /// The asset may reference remapped guids, but it is serialized in Unity's
+    /// binary format, so the references have not been rewritten.
+    ReferencesNotRewritten { guid: Guid, path: PathBuf },
+    /// path only differs in case from the path of an asset installed before
+    /// (`CaseCollisions`). The asset has been installed at renamed, or left
+    /// out if that is None.
+    CaseCollision {
+        guid: Guid,
+        path: PathBuf,
+        renamed: Option<PathBuf>,
+    },
+    /// path contains a name Windows reserves for devices
+    /// (`ReservedNames::RenameWithSuffix`). The asset has been installed at
+    /// renamed.
+    ReservedName {
+        guid: Guid,
+        path: PathBuf,
+        renamed: PathBuf,
+    },
+    /// Incremental mode: an earlier unpack installed the guid at path, but the
+    /// package does not contain it anymore. The files have been kept.
+    RemovedFromPackage { guid: Guid, path: PathBuf },
+    /// A serialized `UnpackConfig` contains a field this version does not know.
+    UnknownConfigField { field: String },
+    /// The pathname file has lines after the path that Unity does not write.
+    /// They have been ignored.
+    PathnameExtraLines {
+        guid: Guid,
+        path: PathBuf,
+        lines: Vec<String>,
+    },
+    /// The guid directory contains a file that is no part of an asset. It
+    /// has not been installed.
+    UnknownEntry {
+        guid: Guid,
+        path: PathBuf,
+        name: String,
+    },
+    /// The `preview.png` of the asset is no PNG or cannot be read.
+    UnreadablePreview {
+        guid: Guid,
+        path: PathBuf,
+        reason: String,
+    },
+    /// `UnpackConfig::preserve_mtimes`: the modification time of the
+    /// installed files could not be set.
+    TimestampNotApplied {
+        guid: Guid,
+        path: PathBuf,
+        reason: String,
+    },
+    /// `UnpackConfig::lenient`: the directory (named after a guid, unless the
+    /// package is broken) has no pathname, so it has been left out. entries
+    /// are the files it contains.
+    MissingPathname {
+        directory: String,
+        entries: Vec<String>,
+    },
+    /// `UnpackConfig::lenient`: the asset has no meta file. The payload has
+    /// been installed without one, Unity will create a meta with a new guid.
+    MissingMeta { guid: Guid, path: PathBuf },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::FolderAssetWithPayload { guid, path } => write!(
+                f,
+                "Asset {} ('{}') is marked as folder but has a payload.",
+                guid,
+                path.display()
+            ),
+            Warning::AlreadyPresent {
+                guid,
+                path,
+                existing,
+            } => write!(
+                f,
+                "Asset {} ('{}') is already present at '{}'.",
+                guid,
+                path.display(),
+                existing.display()
+            ),
+            Warning::ReferencesNotRewritten { guid, path } => write!(
+                f,
+                "Asset {} ('{}') is binary serialized, guid references have not been rewritten.",
+                guid,
+                path.display()
+            ),
+            Warning::CaseCollision {
+                guid,
+                path,
+                renamed: Some(renamed),
+            } => write!(
+                f,
+                "Asset {} ('{}') collides with another path and has been installed as '{}'.",
+                guid,
+                path.display(),
+                renamed.display()
+            ),
+            Warning::CaseCollision {
+                guid,
+                path,
+                renamed: None,
+            } => write!(
+                f,
+                "Asset {} ('{}') collides with another path and has been left out.",
+                guid,
+                path.display()
+            ),
+            Warning::ReservedName {
+                guid,
+                path,
+                renamed,
+            } => write!(
+                f,
+                "Asset {} ('{}') uses a reserved device name and has been installed as '{}'.",
+                guid,
+                path.display(),
+                renamed.display()
+            ),
+            Warning::RemovedFromPackage { guid, path } => write!(
+                f,
+                "Asset {} ('{}') is not part of the package anymore and has been kept.",
+                guid,
+                path.display()
+            ),
+            Warning::UnknownConfigField { field } => {
+                write!(f, "Unknown config field '{}' has been ignored.", field)
+            }
+            Warning::PathnameExtraLines { guid, path, lines } => write!(
+                f,
+                "The pathname of asset {} ('{}') has extra lines, which have been ignored: {:?}",
+                guid,
+                path.display(),
+                lines
+            ),
+            Warning::UnknownEntry { guid, path, name } => write!(
+                f,
+                "Asset {} ('{}') contains the unknown file '{}', which has been ignored.",
+                guid,
+                path.display(),
+                name
+            ),
+            Warning::UnreadablePreview { guid, path, reason } => write!(
+                f,
+                "The preview of asset {} ('{}') could not be read: {}",
+                guid,
+                path.display(),
+                reason
+            ),
+            Warning::TimestampNotApplied { guid, path, reason } => write!(
+                f,
+                "The modification time of asset {} ('{}') could not be set: {}",
+                guid,
+                path.display(),
+                reason
+            ),
+            Warning::MissingPathname { directory, entries } => write!(
+                f,
+                "The directory {} has no pathname and has been left out, it contains {:?}.",
+                directory, entries
+            ),
+            Warning::MissingMeta { guid, path } => write!(
+                f,
+                "Asset {} ('{}') has no meta file and has been installed without one.",
+                guid,
+                path.display()
+            ),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+#![cfg(feature = "cli")]
+#[allow(dead_code)]
+mod common;
+
+use common::{meta, PackageBuilder};
+use std::{path::Path, process::Command};
+
+const TEXTURE: &str = "TextureImporter:\n  serializedVersion: 11\n";
+
+fn unity_unpack(args: &[&str], dir: &Path) -> (Option<i32>, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_unity-unpack"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+
+    (
+        output.status.code(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn test_cli_commands_and_exit_codes() {
+    let dir = tempfile::tempdir().unwrap();
+    let guid = format!("{:032x}", 1);
+    PackageBuilder::new()
+        .asset(
+            &guid,
+            "Assets/Vendor/Ground.jpg",
+            b"jpg",
+            &meta(&guid, TEXTURE),
+        )
+        .write(&dir.path().join("vendor.unitypackage"));
+
+    let (code, out) = unity_unpack(&["list", "vendor.unitypackage"], dir.path());
+    assert_eq!(code, Some(0));
+    assert_eq!(out, format!("{}\t3\tAssets/Vendor/Ground.jpg\n", guid));
+
+    let (code, out) = unity_unpack(&["info", "--json", "vendor.unitypackage"], dir.path());
+    assert_eq!(code, Some(0));
+    let manifest: serde_json::Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(manifest["source_file"], "vendor.unitypackage");
+    assert_eq!(manifest["assets"][0]["guid"], guid.as_str());
+
+    let extract = [
+        "extract",
+        "vendor.unitypackage",
+        "--target",
+        "project",
+        "--tmp",
+        "tmp",
+    ];
+    let (code, out) = unity_unpack(&extract, dir.path());
+    assert_eq!(code, Some(0));
+    assert!(out.contains("\nassets: 1\nfolders: 0\n"));
+    assert!(dir
+        .path()
+        .join("project/Assets/Vendor/Ground.jpg")
+        .is_file());
+    assert!(!dir.path().join("tmp").exists());
+
+    let conflict = [&extract[..], &["--overwrite", "error"]].concat();
+    assert_eq!(unity_unpack(&conflict, dir.path()).0, Some(5));
+
+    assert_eq!(
+        unity_unpack(&["list", "missing.unitypackage"], dir.path()).0,
+        Some(3)
+    );
+    std::fs::write(dir.path().join("broken.unitypackage"), "not a package").unwrap();
+    assert_eq!(
+        unity_unpack(&["info", "broken.unitypackage"], dir.path()).0,
+        Some(4)
+    );
+    assert_eq!(unity_unpack(&["frobnicate"], dir.path()).0, Some(2));
+}
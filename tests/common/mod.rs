@@ -0,0 +1,42 @@
+//! Helpers shared by the integration tests.
+use std::path::{Path, PathBuf};
+
+// The builder of the unit tests, so both build packages the same way.
+#[allow(dead_code)]
+#[path = "../../src/test_utils/package_builder.rs"]
+mod package_builder;
+
+pub use package_builder::PackageBuilder;
+
+pub fn meta(guid: &str, body: &str) -> String {
+    format!("fileFormatVersion: 2\nguid: {}\n{}", guid, body)
+}
+
+/// All files and directories below root, relative, with forward slashes and
+/// directories marked by a trailing slash, sorted.
+pub fn list_tree(root: &Path) -> Vec<String> {
+    fn walk(root: &Path, dir: &Path, result: &mut Vec<String>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path: PathBuf = entry.unwrap().path();
+            let relative = path
+                .strip_prefix(root)
+                .unwrap()
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            if path.is_dir() {
+                result.push(format!("{}/", relative));
+                walk(root, &path, result);
+            } else {
+                result.push(relative);
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    walk(root, root, &mut result);
+    result.sort();
+    result
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use common::{list_tree, meta, PackageBuilder};
+use std::sync::{Arc, Mutex};
+use unity_unpacker_lib::prelude::*;
+
+const TEXTURE: &str = "TextureImporter:\n  serializedVersion: 11\n";
+const SCRIPT: &str = "MonoImporter:\n  serializedVersion: 2\n";
+const DEFAULT: &str = "DefaultImporter:\n  externalObjects: {}\n";
+
+/// The relative paths of the installed assets, in install order.
+#[derive(Default)]
+struct InstallOrder(Vec<String>);
+
+impl ExtractionObserver for InstallOrder {
+    fn on_finished(&mut self, asset: &UnityAssetFile, status: &FileStatus) {
+        if *status == FileStatus::Extracted {
+            let path = asset.get_relative_asset_path().to_string_lossy();
+            self.0.push(path.replace('\\', "/"));
+        }
+    }
+}
+
+/// Installs a package resembling a typical asset store package with the
+/// `unity_project_import` preset and compares the resulting tree against
+/// `tests/fixtures/unity_project_import.txt`. If a change of the preset or of
+/// an option default alters the result, this test fails. The install order
+/// is checked as well, since the preset keeps the order of the archive.
+#[test]
+fn test_unity_project_import_preset_tree() {
+    let dir = tempfile::tempdir().unwrap();
+    let package = dir.path().join("vendor.unitypackage");
+    let target = dir.path().join("project");
+    let tmp = dir.path().join("tmp");
+
+    let g = |n: u8| format!("{:032x}", n);
+    let assets: [(u8, &str, &[u8], &str); 7] = [
+        (3, "Assets/Vendor/Textures/Ground.jpg", b"jpg", TEXTURE),
+        (
+            4,
+            "Assets/Vendor/Scripts/Player.cs",
+            b"class Player {}",
+            SCRIPT,
+        ),
+        (5, "Assets/Vendor/.DS_Store", b"junk", DEFAULT),
+        (6, "Assets/Vendor/Docs~/readme.txt", b"docs", DEFAULT),
+        (7, "Assets/Vendor/cache.tmp", b"tmp", DEFAULT),
+        (8, "Packages/com.vendor.tool/package.json", b"{}", DEFAULT),
+        (9, "ProjectSettings/TagManager.asset", b"tags", DEFAULT),
+    ];
+    let folders = PackageBuilder::new()
+        .folder(&g(1), "Assets/Vendor")
+        .folder(&g(2), "Assets/Vendor/Empty");
+    assets
+        .iter()
+        .fold(folders, |builder, (n, pathname, content, body)| {
+            builder.asset(&g(*n), pathname, content, &meta(&g(*n), body))
+        })
+        .write(&package);
+
+    let mut subject = UnityPackage::from_path(&package, Some(target.clone()), Some(tmp.clone()))
+        .unwrap()
+        .with_config(UnpackConfig::unity_project_import());
+
+    let order = Arc::new(Mutex::new(InstallOrder::default()));
+    subject
+        .unpack_package_with(&UnpackOptions {
+            observer: Some(SharedObserver::new(order.clone())),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let expected: Vec<String> = include_str!("fixtures/unity_project_import.txt")
+        .lines()
+        .map(|l| l.trim_end_matches('\r').to_string())
+        .filter(|l| !l.is_empty())
+        .collect();
+
+    assert_eq!(list_tree(&target), expected);
+    assert!(!tmp.exists());
+
+    // The preset installs in archive order, not sorted by path.
+    assert_eq!(
+        order.lock().unwrap().0,
+        [
+            "Assets/Vendor",
+            "Assets/Vendor/Empty",
+            "Assets/Vendor/Textures/Ground.jpg",
+            "Assets/Vendor/Scripts/Player.cs",
+            "Packages/com.vendor.tool/package.json",
+        ]
+    );
+}
@@ -0,0 +1,40 @@
+//! Checks the in-memory backend on wasm32, where there is no file system.
+//! Build it with `cargo test --no-run --target wasm32-unknown-unknown`.
+#![cfg(target_arch = "wasm32")]
+#[allow(dead_code)]
+mod common;
+
+use common::{meta, PackageBuilder};
+use unity_unpacker_lib::prelude::*;
+
+const TEXTURE: &str = "TextureImporter:\n  serializedVersion: 11\n";
+
+#[test]
+fn test_list_and_unpack_in_memory() {
+    let guid = format!("{:032x}", 1);
+    let bytes = PackageBuilder::new()
+        .asset(
+            &guid,
+            "Assets/Vendor/Ground.jpg",
+            b"jpg",
+            &meta(&guid, TEXTURE),
+        )
+        .bytes();
+
+    let mut package = UnityPackage::from_bytes("vendor.unitypackage", bytes).unwrap();
+    let entries = package.list_entries().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].guid, guid);
+
+    let assets = package.unpack_to_memory().unwrap();
+    assert_eq!(assets[0].content, b"jpg");
+
+    assert!(matches!(
+        package.unpack_package(true),
+        Err(UnityPackageReaderError::UnsupportedOnThisTarget(_))
+    ));
+    assert!(matches!(
+        UnityPackage::from_path("vendor.unitypackage", None, None),
+        Err(UnityPackageReaderError::UnsupportedOnThisTarget(_))
+    ));
+}